@@ -1,6 +1,8 @@
 use crate::error::WebExcelError;
 use crate::util::cell_handle::*;
 use crate::{console_log, error};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 use wasm_bindgen::prelude::*;
 
@@ -39,15 +41,62 @@ pub struct Cell {
     pub fixed_column: bool,
 }
 
+/// Order of two sheet names, which are case insensitive like in Excel.
+/// Local (`None`) sheets come first.
+pub(crate) fn compare_sheets(a: &Option<String>, b: &Option<String>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => folded(a).cmp(folded(b)),
+        _ => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+pub(crate) fn same_sheet(a: &Option<String>, b: &Option<String>) -> bool {
+    compare_sheets(a, b) == Ordering::Equal
+}
+
+/// Sheet name folded the way sheets are compared, to key maps by sheet.
+pub(crate) fn sheet_key(sheet: &str) -> String {
+    folded(sheet).collect()
+}
+
+fn folded(sheet: &str) -> impl Iterator<Item = char> + '_ {
+    sheet.chars().flat_map(char::to_lowercase)
+}
+
+/// Sheet names are compared case insensitively, anchoring is ignored.
 impl PartialEq for Cell {
     fn eq(&self, other: &Self) -> bool {
-        let same_sheet = match (&self.sheet, &other.sheet) {
-            (Some(my), Some(oth)) => my == oth,
-            (None, None) => true,
-            _ => false,
-        };
+        same_sheet(&self.sheet, &other.sheet)
+            && (self.row == other.row && self.column == other.column)
+    }
+}
+
+impl Eq for Cell {}
+
+/// Consistent with `PartialEq`.
+impl Hash for Cell {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Some(sheet) = &self.sheet {
+            folded(sheet).for_each(|c| c.hash(state));
+        }
+        self.sheet.is_some().hash(state);
+        self.row.hash(state);
+        self.column.hash(state);
+    }
+}
+
+/// Cells are ordered by sheet, then row by row. Local cells come first.
+impl Ord for Cell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_sheets(&self.sheet, &other.sheet)
+            .then(self.row.cmp(&other.row))
+            .then(self.column.cmp(&other.column))
+    }
+}
 
-        same_sheet && (self.row == other.row && self.column == other.column)
+impl PartialOrd for Cell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -0,0 +1,836 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::func::{checked_formula, formula_text, MAX_TEXT_LENGTH};
+use crate::math::parser::Expr;
+use crate::range::Range;
+use crate::range_areas::RangeAreas;
+use crate::util::json::Json;
+use wasm_bindgen::prelude::*;
+
+/// Comparison of a cell value rule, named after Office JS `ConditionalCellValueOperator`.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CellValueOperator {
+    Between = "Between",
+    NotBetween = "NotBetween",
+    EqualTo = "EqualTo",
+    NotEqualTo = "NotEqualTo",
+    GreaterThan = "GreaterThan",
+    LessThan = "LessThan",
+    GreaterThanOrEqual = "GreaterThanOrEqual",
+    LessThanOrEqual = "LessThanOrEqual",
+}
+
+impl CellValueOperator {
+    fn as_str(&self) -> Result<&'static str, WebExcelError> {
+        match self {
+            CellValueOperator::Between => Ok("Between"),
+            CellValueOperator::NotBetween => Ok("NotBetween"),
+            CellValueOperator::EqualTo => Ok("EqualTo"),
+            CellValueOperator::NotEqualTo => Ok("NotEqualTo"),
+            CellValueOperator::GreaterThan => Ok("GreaterThan"),
+            CellValueOperator::LessThan => Ok("LessThan"),
+            CellValueOperator::GreaterThanOrEqual => Ok("GreaterThanOrEqual"),
+            CellValueOperator::LessThanOrEqual => Ok("LessThanOrEqual"),
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+
+    fn takes_two(&self) -> bool {
+        matches!(
+            self,
+            CellValueOperator::Between | CellValueOperator::NotBetween
+        )
+    }
+}
+
+/// How a point of a color scale, a data bar bound or an icon threshold is placed.
+/// `Automatic` only applies to data bars, `LowestValue` and `HighestValue` not to icon sets.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScaleKind {
+    Automatic = "Automatic",
+    LowestValue = "LowestValue",
+    HighestValue = "HighestValue",
+    Number = "Number",
+    Percent = "Percent",
+    Percentile = "Percentile",
+    Formula = "Formula",
+}
+
+impl ScaleKind {
+    fn as_str(&self) -> Result<&'static str, WebExcelError> {
+        match self {
+            ScaleKind::Automatic => Ok("Automatic"),
+            ScaleKind::LowestValue => Ok("LowestValue"),
+            ScaleKind::HighestValue => Ok("HighestValue"),
+            ScaleKind::Number => Ok("Number"),
+            ScaleKind::Percent => Ok("Percent"),
+            ScaleKind::Percentile => Ok("Percentile"),
+            ScaleKind::Formula => Ok("Formula"),
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+
+    /// Whether the point is placed by a value or formula rather than by the data.
+    fn needs_value(&self) -> bool {
+        !matches!(
+            self,
+            ScaleKind::Automatic | ScaleKind::LowestValue | ScaleKind::HighestValue
+        )
+    }
+}
+
+/// Ranking of a top/bottom rule, named after Office JS `ConditionalTopBottomCriterionType`.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TopBottomKind {
+    TopItems = "TopItems",
+    TopPercent = "TopPercent",
+    BottomItems = "BottomItems",
+    BottomPercent = "BottomPercent",
+}
+
+impl TopBottomKind {
+    fn as_str(&self) -> Result<&'static str, WebExcelError> {
+        match self {
+            TopBottomKind::TopItems => Ok("TopItems"),
+            TopBottomKind::TopPercent => Ok("TopPercent"),
+            TopBottomKind::BottomItems => Ok("BottomItems"),
+            TopBottomKind::BottomPercent => Ok("BottomPercent"),
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+
+    /// Largest rank Excel accepts.
+    fn max_rank(&self) -> u32 {
+        match self {
+            TopBottomKind::TopPercent | TopBottomKind::BottomPercent => 100,
+            _ => 1000,
+        }
+    }
+}
+
+/// Text comparison, named after Office JS `ConditionalTextOperator`.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextOperator {
+    Contains = "Contains",
+    NotContains = "NotContains",
+    BeginsWith = "BeginsWith",
+    EndsWith = "EndsWith",
+}
+
+impl TextOperator {
+    fn as_str(&self) -> Result<&'static str, WebExcelError> {
+        match self {
+            TextOperator::Contains => Ok("Contains"),
+            TextOperator::NotContains => Ok("NotContains"),
+            TextOperator::BeginsWith => Ok("BeginsWith"),
+            TextOperator::EndsWith => Ok("EndsWith"),
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+}
+
+/// Icon set styles of Office JS `IconSet`. The name tells the number of icons.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IconSetStyle {
+    ThreeArrows = "ThreeArrows",
+    ThreeArrowsGray = "ThreeArrowsGray",
+    ThreeFlags = "ThreeFlags",
+    ThreeTrafficLights1 = "ThreeTrafficLights1",
+    ThreeTrafficLights2 = "ThreeTrafficLights2",
+    ThreeSigns = "ThreeSigns",
+    ThreeSymbols = "ThreeSymbols",
+    ThreeSymbols2 = "ThreeSymbols2",
+    ThreeStars = "ThreeStars",
+    ThreeTriangles = "ThreeTriangles",
+    FourArrows = "FourArrows",
+    FourArrowsGray = "FourArrowsGray",
+    FourRedToBlack = "FourRedToBlack",
+    FourRating = "FourRating",
+    FourTrafficLights = "FourTrafficLights",
+    FiveArrows = "FiveArrows",
+    FiveArrowsGray = "FiveArrowsGray",
+    FiveRating = "FiveRating",
+    FiveQuarters = "FiveQuarters",
+    FiveBoxes = "FiveBoxes",
+}
+
+impl IconSetStyle {
+    fn as_str(&self) -> Result<&'static str, WebExcelError> {
+        match self {
+            IconSetStyle::ThreeArrows => Ok("ThreeArrows"),
+            IconSetStyle::ThreeArrowsGray => Ok("ThreeArrowsGray"),
+            IconSetStyle::ThreeFlags => Ok("ThreeFlags"),
+            IconSetStyle::ThreeTrafficLights1 => Ok("ThreeTrafficLights1"),
+            IconSetStyle::ThreeTrafficLights2 => Ok("ThreeTrafficLights2"),
+            IconSetStyle::ThreeSigns => Ok("ThreeSigns"),
+            IconSetStyle::ThreeSymbols => Ok("ThreeSymbols"),
+            IconSetStyle::ThreeSymbols2 => Ok("ThreeSymbols2"),
+            IconSetStyle::ThreeStars => Ok("ThreeStars"),
+            IconSetStyle::ThreeTriangles => Ok("ThreeTriangles"),
+            IconSetStyle::FourArrows => Ok("FourArrows"),
+            IconSetStyle::FourArrowsGray => Ok("FourArrowsGray"),
+            IconSetStyle::FourRedToBlack => Ok("FourRedToBlack"),
+            IconSetStyle::FourRating => Ok("FourRating"),
+            IconSetStyle::FourTrafficLights => Ok("FourTrafficLights"),
+            IconSetStyle::FiveArrows => Ok("FiveArrows"),
+            IconSetStyle::FiveArrowsGray => Ok("FiveArrowsGray"),
+            IconSetStyle::FiveRating => Ok("FiveRating"),
+            IconSetStyle::FiveQuarters => Ok("FiveQuarters"),
+            IconSetStyle::FiveBoxes => Ok("FiveBoxes"),
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+
+    /// Number of icons of the style.
+    pub fn icons(&self) -> Result<usize, WebExcelError> {
+        let name = self.as_str()?;
+        Ok(if name.starts_with("Three") {
+            3
+        } else if name.starts_with("Four") {
+            4
+        } else {
+            5
+        })
+    }
+}
+
+/// Point of a color scale, bound of a data bar or threshold of an icon set.
+/// `value` is a number or a formula, required unless the kind is `Automatic`,
+/// `LowestValue` or `HighestValue`. `color` is only used by color scales.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScalePoint {
+    pub kind: ScaleKind,
+    value: Option<Expr>,
+    color: Option<String>,
+}
+
+#[wasm_bindgen]
+impl ScalePoint {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        kind: ScaleKind,
+        value: Option<String>,
+        color: Option<String>,
+    ) -> Result<ScalePoint, WebExcelError> {
+        kind.as_str()?;
+        let value = match (kind.needs_value(), value) {
+            (true, Some(value)) => Some(checked_formula(&value)?),
+            (false, None) => None,
+            _ => return Err(WebExcelError::FormulaBuildError),
+        };
+        Ok(ScalePoint { kind, value, color })
+    }
+
+    /// Point placed by the data itself, e.g. `LowestValue`.
+    pub fn at(kind: ScaleKind, color: Option<String>) -> Result<ScalePoint, WebExcelError> {
+        ScalePoint::new(kind, None, color)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn color(&self) -> Option<String> {
+        self.color.clone()
+    }
+
+    /// Value or formula of the point, with a leading `=`.
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> Result<Option<String>, WebExcelError> {
+        self.value.as_ref().map(formula_text).transpose()
+    }
+}
+
+impl ScalePoint {
+    fn relocate(&self, rows: i64, columns: i64) -> Result<ScalePoint, WebExcelError> {
+        Ok(ScalePoint {
+            value: self
+                .value
+                .as_ref()
+                .map(|value| value.relocate(rows, columns))
+                .transpose()?,
+            ..self.clone()
+        })
+    }
+
+    fn to_json(&self, with_color: bool) -> Result<Json, WebExcelError> {
+        let mut json = Json::object([("type", self.kind.as_str()?.into())]);
+        if let Some(value) = &self.value {
+            json.set("formula", formula_text(value)?.into());
+        }
+        if with_color {
+            let color = self.color.clone().ok_or(WebExcelError::FormulaBuildError)?;
+            json.set("color", color.into());
+        }
+        Ok(json)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Rule {
+    CellValue {
+        operator: CellValueOperator,
+        formula1: Expr,
+        formula2: Option<Expr>,
+    },
+    Custom(Expr),
+    ColorScale(Vec<ScalePoint>),
+    DataBar {
+        lower: ScalePoint,
+        upper: ScalePoint,
+        color: String,
+    },
+    IconSet {
+        style: IconSetStyle,
+        thresholds: Vec<ScalePoint>,
+        reverse: bool,
+        icons_only: bool,
+    },
+    TopBottom {
+        kind: TopBottomKind,
+        rank: u32,
+    },
+    Preset {
+        unique: bool,
+    },
+    Text {
+        operator: TextOperator,
+        text: String,
+    },
+}
+
+/// Conditional format of a range, built here and applied with Office JS:
+///
+/// ```js
+/// const { type, range, ...properties } = format.to_js();
+/// sheet.getRange(range).conditionalFormats.add(type).set(properties);
+/// ```
+///
+/// Formulas are written for the top-left cell of the range and Excel moves their relative
+/// references for the other cells, like a formula filled over the range. Use `relative_to`
+/// when a formula was written for another cell.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConditionalFormat {
+    range: Range,
+    rule: Rule,
+    font_color: Option<String>,
+    fill_color: Option<String>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    stop_if_true: bool,
+}
+
+#[wasm_bindgen]
+impl ConditionalFormat {
+    /// Compare cell values against one formula, or two for `Between` and `NotBetween`,
+    /// e.g. `cell_value(range, GreaterThan, "=$A$1", None)`.
+    pub fn cell_value(
+        range: &Range,
+        operator: CellValueOperator,
+        formula1: &str,
+        formula2: Option<String>,
+    ) -> Result<ConditionalFormat, WebExcelError> {
+        operator.as_str()?;
+        if operator.takes_two() != formula2.is_some() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+
+        let rule = Rule::CellValue {
+            operator,
+            formula1: checked_formula(formula1)?,
+            formula2: formula2.as_deref().map(checked_formula).transpose()?,
+        };
+        Ok(ConditionalFormat::with_rule(range, rule))
+    }
+
+    /// Format the cells for which a formula is true, e.g. `=MOD(ROW(A1),2)=0` on `A1:D10`.
+    pub fn custom(range: &Range, formula: &str) -> Result<ConditionalFormat, WebExcelError> {
+        let rule = Rule::Custom(checked_formula(formula)?);
+        Ok(ConditionalFormat::with_rule(range, rule))
+    }
+
+    /// Two or three color scale. Every point needs a color.
+    pub fn color_scale(
+        range: &Range,
+        minimum: &ScalePoint,
+        midpoint: Option<ScalePoint>,
+        maximum: &ScalePoint,
+    ) -> Result<ConditionalFormat, WebExcelError> {
+        let points: Vec<ScalePoint> = [Some(minimum.clone()), midpoint, Some(maximum.clone())]
+            .into_iter()
+            .flatten()
+            .collect();
+        if points
+            .iter()
+            .any(|p| p.color.is_none() || p.kind == ScaleKind::Automatic)
+        {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+
+        Ok(ConditionalFormat::with_rule(
+            range,
+            Rule::ColorScale(points),
+        ))
+    }
+
+    /// Data bars filled with `color`, bounds picked automatically unless set with `with_bar_bounds`.
+    pub fn data_bar(range: &Range, color: &str) -> ConditionalFormat {
+        let automatic = ScalePoint {
+            kind: ScaleKind::Automatic,
+            value: None,
+            color: None,
+        };
+        let rule = Rule::DataBar {
+            lower: automatic.clone(),
+            upper: automatic,
+            color: color.to_owned(),
+        };
+        ConditionalFormat::with_rule(range, rule)
+    }
+
+    /// Icon set with one threshold per icon but the first, from low to high, of `kind`
+    /// `Number`, `Percent` or `Percentile`. Cells reaching a threshold get the next icon.
+    pub fn icon_set(
+        range: &Range,
+        style: IconSetStyle,
+        kind: ScaleKind,
+        thresholds: Vec<f64>,
+    ) -> Result<ConditionalFormat, WebExcelError> {
+        if !matches!(
+            kind,
+            ScaleKind::Number | ScaleKind::Percent | ScaleKind::Percentile
+        ) {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+        if thresholds.len() + 1 != style.icons()? {
+            return Err(WebExcelError::DimensionError);
+        }
+
+        let thresholds = thresholds
+            .iter()
+            .map(|t| ScalePoint {
+                kind,
+                value: Some(Expr::Number(*t)),
+                color: None,
+            })
+            .collect();
+        let rule = Rule::IconSet {
+            style,
+            thresholds,
+            reverse: false,
+            icons_only: false,
+        };
+        Ok(ConditionalFormat::with_rule(range, rule))
+    }
+
+    /// Format the top or bottom `rank` items or percent of the values.
+    pub fn top_bottom(
+        range: &Range,
+        kind: TopBottomKind,
+        rank: u32,
+    ) -> Result<ConditionalFormat, WebExcelError> {
+        kind.as_str()?;
+        if rank == 0 || rank > kind.max_rank() {
+            return Err(WebExcelError::OutOfBoundError);
+        }
+        Ok(ConditionalFormat::with_rule(
+            range,
+            Rule::TopBottom { kind, rank },
+        ))
+    }
+
+    /// Format values appearing more than once in the range.
+    pub fn duplicates(range: &Range) -> ConditionalFormat {
+        ConditionalFormat::with_rule(range, Rule::Preset { unique: false })
+    }
+
+    /// Format values appearing once in the range.
+    pub fn unique(range: &Range) -> ConditionalFormat {
+        ConditionalFormat::with_rule(range, Rule::Preset { unique: true })
+    }
+
+    /// Format cells whose text contains `text`, ignoring case like Excel.
+    pub fn text_contains(range: &Range, text: &str) -> Result<ConditionalFormat, WebExcelError> {
+        ConditionalFormat::text(range, TextOperator::Contains, text)
+    }
+
+    /// Format cells whose text matches `text` with `operator`.
+    pub fn text(
+        range: &Range,
+        operator: TextOperator,
+        text: &str,
+    ) -> Result<ConditionalFormat, WebExcelError> {
+        operator.as_str()?;
+        if text.is_empty() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+        if text.chars().count() > MAX_TEXT_LENGTH {
+            return Err(WebExcelError::FormulaLengthError);
+        }
+
+        let rule = Rule::Text {
+            operator,
+            text: text.to_owned(),
+        };
+        Ok(ConditionalFormat::with_rule(range, rule))
+    }
+
+    /// Font color of the formatted cells, e.g. `#9C0006`.
+    pub fn with_font_color(mut self, color: &str) -> ConditionalFormat {
+        self.font_color = Some(color.to_owned());
+        self
+    }
+
+    /// Fill color of the formatted cells, e.g. `#FFC7CE`.
+    pub fn with_fill_color(mut self, color: &str) -> ConditionalFormat {
+        self.fill_color = Some(color.to_owned());
+        self
+    }
+
+    pub fn with_bold(mut self, bold: bool) -> ConditionalFormat {
+        self.bold = Some(bold);
+        self
+    }
+
+    pub fn with_italic(mut self, italic: bool) -> ConditionalFormat {
+        self.italic = Some(italic);
+        self
+    }
+
+    /// Skip the rules of lower priority on cells matching this one.
+    pub fn with_stop_if_true(mut self, stop: bool) -> ConditionalFormat {
+        self.stop_if_true = stop;
+        self
+    }
+
+    /// Bounds of a data bar. Colors of the points are ignored.
+    pub fn with_bar_bounds(
+        mut self,
+        lower: &ScalePoint,
+        upper: &ScalePoint,
+    ) -> Result<ConditionalFormat, WebExcelError> {
+        match &mut self.rule {
+            Rule::DataBar {
+                lower: l, upper: u, ..
+            } => {
+                *l = lower.clone();
+                *u = upper.clone();
+            }
+            _ => return Err(WebExcelError::FormulaBuildError),
+        }
+        Ok(self)
+    }
+
+    /// Reverse the icons of an icon set, or show them without the values.
+    pub fn with_icon_options(
+        mut self,
+        reverse: bool,
+        icons_only: bool,
+    ) -> Result<ConditionalFormat, WebExcelError> {
+        match &mut self.rule {
+            Rule::IconSet {
+                reverse: r,
+                icons_only: i,
+                ..
+            } => {
+                *r = reverse;
+                *i = icons_only;
+            }
+            _ => return Err(WebExcelError::FormulaBuildError),
+        }
+        Ok(self)
+    }
+
+    /// Take the formulas as written for `origin` instead of the top-left cell of the range,
+    /// moving their relative references accordingly: `=A1>0` for origin `A1` on `C3:D4`
+    /// becomes `=C3>0`.
+    pub fn relative_to(self, origin: &Cell) -> Result<ConditionalFormat, WebExcelError> {
+        let top_left = &self.range.cell_start;
+        let rows = top_left.row as i64 - origin.row as i64;
+        let columns = top_left.column as i64 - origin.column as i64;
+        self.relocate(rows, columns)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn range(&self) -> Range {
+        self.range.clone()
+    }
+
+    /// Office JS `ConditionalFormatType` to pass to `conditionalFormats.add`.
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> String {
+        self.names().0.to_owned()
+    }
+
+    /// Formulas of the rule as they apply to `cell`, as an array of strings.
+    /// See `ConditionalFormat::formulas_at`.
+    #[wasm_bindgen(js_name = formulas_at)]
+    pub fn formulas_at_js(&self, cell: &Cell) -> Result<js_sys::Array, WebExcelError> {
+        Ok(self
+            .formulas_at(cell)?
+            .into_iter()
+            .map(JsValue::from)
+            .collect::<js_sys::Array>())
+    }
+
+    /// JSON text of `to_js`.
+    pub fn to_json(&self) -> Result<String, WebExcelError> {
+        Ok(self.to_json_tree()?.to_string())
+    }
+
+    /// Object with the `type` for `conditionalFormats.add`, the `range` address and the
+    /// properties for `ConditionalFormat.set`, e.g. `{ type: "Custom", range, custom: { rule, format } }`.
+    pub fn to_js(&self) -> Result<JsValue, WebExcelError> {
+        Ok(self.to_json_tree()?.to_js())
+    }
+}
+
+impl ConditionalFormat {
+    fn with_rule(range: &Range, rule: Rule) -> ConditionalFormat {
+        ConditionalFormat {
+            range: range.clone(),
+            rule,
+            font_color: None,
+            fill_color: None,
+            bold: None,
+            italic: None,
+            stop_if_true: false,
+        }
+    }
+
+    /// Formulas of the rule as Excel evaluates them for `cell`, relative references moved
+    /// from the top-left cell of the range. Fails when `cell` is outside the range.
+    pub fn formulas_at(&self, cell: &Cell) -> Result<Vec<String>, WebExcelError> {
+        if !self.range.has(cell) {
+            return Err(WebExcelError::OutOfBoundError);
+        }
+        let top_left = &self.range.cell_start;
+        let moved = self.clone().relocate(
+            cell.row as i64 - top_left.row as i64,
+            cell.column as i64 - top_left.column as i64,
+        )?;
+
+        moved.formulas().into_iter().map(formula_text).collect()
+    }
+
+    /// Every formula of the rule, in the order they are serialized.
+    fn formulas(&self) -> Vec<&Expr> {
+        fn points(points: &[ScalePoint]) -> Vec<&Expr> {
+            points.iter().filter_map(|p| p.value.as_ref()).collect()
+        }
+        match &self.rule {
+            Rule::CellValue {
+                formula1, formula2, ..
+            } => [Some(formula1), formula2.as_ref()]
+                .into_iter()
+                .flatten()
+                .collect(),
+            Rule::Custom(formula) => vec![formula],
+            Rule::ColorScale(scale) => points(scale),
+            Rule::DataBar { lower, upper, .. } => {
+                [&lower.value, &upper.value].into_iter().flatten().collect()
+            }
+            Rule::IconSet { thresholds, .. } => points(thresholds),
+            _ => vec![],
+        }
+    }
+
+    fn relocate(mut self, rows: i64, columns: i64) -> Result<ConditionalFormat, WebExcelError> {
+        let points = |points: &[ScalePoint]| -> Result<Vec<ScalePoint>, WebExcelError> {
+            points.iter().map(|p| p.relocate(rows, columns)).collect()
+        };
+        self.rule = match &self.rule {
+            Rule::CellValue {
+                operator,
+                formula1,
+                formula2,
+            } => Rule::CellValue {
+                operator: *operator,
+                formula1: formula1.relocate(rows, columns)?,
+                formula2: formula2
+                    .as_ref()
+                    .map(|f| f.relocate(rows, columns))
+                    .transpose()?,
+            },
+            Rule::Custom(formula) => Rule::Custom(formula.relocate(rows, columns)?),
+            Rule::ColorScale(scale) => Rule::ColorScale(points(scale)?),
+            Rule::DataBar {
+                lower,
+                upper,
+                color,
+            } => Rule::DataBar {
+                lower: lower.relocate(rows, columns)?,
+                upper: upper.relocate(rows, columns)?,
+                color: color.clone(),
+            },
+            Rule::IconSet {
+                style,
+                thresholds,
+                reverse,
+                icons_only,
+            } => Rule::IconSet {
+                style: *style,
+                thresholds: points(thresholds)?,
+                reverse: *reverse,
+                icons_only: *icons_only,
+            },
+            other => other.clone(),
+        };
+        Ok(self)
+    }
+
+    /// Office JS type and property name of the rule.
+    fn names(&self) -> (&'static str, &'static str) {
+        match self.rule {
+            Rule::CellValue { .. } => ("CellValue", "cellValue"),
+            Rule::Custom(_) => ("Custom", "custom"),
+            Rule::ColorScale(_) => ("ColorScale", "colorScale"),
+            Rule::DataBar { .. } => ("DataBar", "dataBar"),
+            Rule::IconSet { .. } => ("IconSet", "iconSet"),
+            Rule::TopBottom { .. } => ("TopBottom", "topBottom"),
+            Rule::Preset { .. } => ("PresetCriteria", "preset"),
+            Rule::Text { .. } => ("ContainsText", "textComparison"),
+        }
+    }
+
+    /// `format` property of the rules formatting the cells they match.
+    fn format_json(&self) -> Json {
+        let mut format = Json::object([]);
+        if let Some(color) = &self.fill_color {
+            format.set("fill", Json::object([("color", color.as_str().into())]));
+        }
+
+        let mut font = Json::object([]);
+        if let Some(color) = &self.font_color {
+            font.set("color", color.as_str().into());
+        }
+        if let Some(bold) = self.bold {
+            font.set("bold", bold.into());
+        }
+        if let Some(italic) = self.italic {
+            font.set("italic", italic.into());
+        }
+        if font != Json::object([]) {
+            format.set("font", font);
+        }
+        format
+    }
+
+    fn to_json_tree(&self) -> Result<Json, WebExcelError> {
+        let properties = match &self.rule {
+            Rule::CellValue {
+                operator,
+                formula1,
+                formula2,
+            } => {
+                let mut rule = Json::object([
+                    ("formula1", formula_text(formula1)?.into()),
+                    ("operator", operator.as_str()?.into()),
+                ]);
+                if let Some(formula2) = formula2 {
+                    rule.set("formula2", formula_text(formula2)?.into());
+                }
+                Json::object([("format", self.format_json()), ("rule", rule)])
+            }
+            Rule::Custom(formula) => Json::object([
+                ("format", self.format_json()),
+                (
+                    "rule",
+                    Json::object([("formula", formula_text(formula)?.into())]),
+                ),
+            ]),
+            Rule::ColorScale(points) => {
+                let names: &[&str] = if points.len() == 3 {
+                    &["minimum", "midpoint", "maximum"]
+                } else {
+                    &["minimum", "maximum"]
+                };
+                let mut criteria = Json::object([]);
+                for (name, point) in names.iter().zip(points) {
+                    criteria.set(name, point.to_json(true)?);
+                }
+                Json::object([("criteria", criteria)])
+            }
+            Rule::DataBar {
+                lower,
+                upper,
+                color,
+            } => Json::object([
+                ("lowerBoundRule", lower.to_json(false)?),
+                ("upperBoundRule", upper.to_json(false)?),
+                (
+                    "positiveFormat",
+                    Json::object([("fillColor", color.as_str().into())]),
+                ),
+            ]),
+            Rule::IconSet {
+                style,
+                thresholds,
+                reverse,
+                icons_only,
+            } => {
+                // The first icon has no threshold, Office JS expects an empty criterion
+                let mut criteria = vec![Json::object([])];
+                for threshold in thresholds {
+                    let mut criterion = threshold.to_json(false)?;
+                    criterion.set("operator", "GreaterThanOrEqual".into());
+                    criteria.push(criterion);
+                }
+                Json::object([
+                    ("style", style.as_str()?.into()),
+                    ("reverseIconOrder", (*reverse).into()),
+                    ("showIconOnly", (*icons_only).into()),
+                    ("criteria", Json::Array(criteria)),
+                ])
+            }
+            Rule::TopBottom { kind, rank } => Json::object([
+                ("format", self.format_json()),
+                (
+                    "rule",
+                    Json::object([("rank", (*rank).into()), ("type", kind.as_str()?.into())]),
+                ),
+            ]),
+            Rule::Preset { unique } => {
+                let criterion = if *unique {
+                    "UniqueValues"
+                } else {
+                    "DuplicateValues"
+                };
+                Json::object([
+                    ("format", self.format_json()),
+                    ("rule", Json::object([("criterion", criterion.into())])),
+                ])
+            }
+            Rule::Text { operator, text } => Json::object([
+                ("format", self.format_json()),
+                (
+                    "rule",
+                    Json::object([
+                        ("operator", operator.as_str()?.into()),
+                        ("text", text.as_str().into()),
+                    ]),
+                ),
+            ]),
+        };
+
+        let (kind, property) = self.names();
+        let mut json = Json::object([
+            ("type", kind.into()),
+            (
+                "range",
+                RangeAreas::from(self.range.clone())
+                    .to_str_address()?
+                    .into(),
+            ),
+            (property, properties),
+        ]);
+        if self.stop_if_true {
+            json.set("stopIfTrue", true.into());
+        }
+        Ok(json)
+    }
+}
@@ -0,0 +1,397 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::eval::{number_to_text, parse_number, CellValue};
+use crate::math::format::date_to_serial;
+use crate::math::parser::ErrorValue;
+use crate::range::Range;
+use crate::workbook::{from_js_grid, to_js_grid, GENERAL_FORMAT};
+use wasm_bindgen::prelude::*;
+
+/// Number formats given to inferred dates and times.
+pub const CSV_DATE_FORMAT: &str = "yyyy-mm-dd";
+pub const CSV_DATE_TIME_FORMAT: &str = "yyyy-mm-dd hh:mm:ss";
+pub const CSV_TIME_FORMAT: &str = "hh:mm:ss";
+
+/// Line break written after each record.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    /// `\r\n`, as RFC 4180 asks
+    CrLf,
+    Lf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Lf => "\n",
+        }
+    }
+}
+
+/// Fields written between double quotes.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuoteStyle {
+    /// Fields containing the delimiter, a quote or a line break, and text that would
+    /// otherwise be read back as another type, e.g. `"42"` or `"TRUE"`
+    Necessary,
+    /// Every field
+    Always,
+    /// Every field but numbers and empty ones
+    NonNumeric,
+}
+
+/// How CSV text is read and written. Defaults to commas, `\r\n` line endings,
+/// quoting only when necessary and inferring types.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CsvOptions {
+    delimiter: char,
+    pub quote_style: QuoteStyle,
+    pub line_ending: LineEnding,
+    /// Read numbers, booleans, errors, dates and times instead of keeping every field as text.
+    pub infer_types: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            quote_style: QuoteStyle::Necessary,
+            line_ending: LineEnding::CrLf,
+            infer_types: true,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl CsvOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CsvOptions {
+        CsvOptions::default()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn delimiter(&self) -> char {
+        self.delimiter
+    }
+
+    /// Field separator such as `;` or `\t`. Quotes and line breaks cannot separate fields.
+    pub fn with_delimiter(mut self, delimiter: char) -> Result<CsvOptions, WebExcelError> {
+        if matches!(delimiter, '"' | '\r' | '\n') {
+            return Err(WebExcelError::ParseError);
+        }
+        self.delimiter = delimiter;
+        Ok(self)
+    }
+
+    pub fn with_quote_style(mut self, quote_style: QuoteStyle) -> CsvOptions {
+        self.quote_style = quote_style;
+        self
+    }
+
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> CsvOptions {
+        self.line_ending = line_ending;
+        self
+    }
+
+    pub fn with_infer_types(mut self, infer_types: bool) -> CsvOptions {
+        self.infer_types = infer_types;
+        self
+    }
+}
+
+/// Values read from CSV text, as rows of equal length. Short records are padded with
+/// empty values. Inferred dates and times are serial numbers with a number format.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvData {
+    values: Vec<Vec<CellValue>>,
+    formats: Vec<Vec<Option<&'static str>>>,
+}
+
+#[wasm_bindgen]
+impl CsvData {
+    /// Read RFC 4180 text. Fails on a quote left open or text right after a closing quote.
+    #[wasm_bindgen(constructor)]
+    pub fn parse(text: &str, options: &CsvOptions) -> Result<CsvData, WebExcelError> {
+        let records = parse_records(text, options.delimiter)?;
+        let width = records.iter().map(Vec::len).max().unwrap_or(0);
+
+        let mut values = vec![];
+        let mut formats = vec![];
+        for record in records {
+            let (mut row, mut row_formats): (Vec<CellValue>, Vec<Option<&'static str>>) = record
+                .into_iter()
+                .map(|field| field.value(options.infer_types))
+                .unzip();
+            row.resize(width, CellValue::Empty);
+            row_formats.resize(width, None);
+            values.push(row);
+            formats.push(row_formats);
+        }
+        Ok(CsvData { values, formats })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn rows(&self) -> u32 {
+        self.values.len() as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn columns(&self) -> u32 {
+        self.values.first().map_or(0, Vec::len) as u32
+    }
+
+    /// Range the values fill with `anchor` as its top-left cell. Fails when there are no
+    /// values, or when they would go past the sheet.
+    pub fn range_at(&self, anchor: &Cell) -> Result<Range, WebExcelError> {
+        if self.rows() == 0 || self.columns() == 0 {
+            return Err(WebExcelError::DimensionError);
+        }
+        let delta = |n: u32| i32::try_from(n - 1).map_err(|_| WebExcelError::OutOfBoundError);
+        Range::new(anchor, anchor)?.resize(delta(self.rows())?, delta(self.columns())?)
+    }
+
+    /// Values as an array of rows, ready for `Worksheet.set_values_at`.
+    #[wasm_bindgen(js_name = values)]
+    pub fn values_js(&self) -> js_sys::Array {
+        to_js_grid(self.values.clone(), CellValue::to_js)
+    }
+
+    /// Number formats as an array of rows, `General` for anything but dates and times.
+    pub fn number_formats(&self) -> js_sys::Array {
+        to_js_grid(self.formats.clone(), |format| {
+            JsValue::from_str(format.unwrap_or(GENERAL_FORMAT))
+        })
+    }
+}
+
+impl CsvData {
+    pub fn values(&self) -> &[Vec<CellValue>] {
+        &self.values
+    }
+
+    /// Number format of each value, `None` for `General`.
+    pub fn formats(&self) -> &[Vec<Option<&'static str>>] {
+        &self.formats
+    }
+
+    pub fn into_values(self) -> Vec<Vec<CellValue>> {
+        self.values
+    }
+}
+
+/// Write rows of values as CSV, each record followed by the line ending.
+/// Numbers are written like `TEXT(n, "General")`, booleans as `TRUE` and `FALSE`
+/// and errors as their literal.
+pub fn to_csv(values: &[Vec<CellValue>], options: &CsvOptions) -> String {
+    let mut text = String::new();
+    for row in values {
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                text.push(options.delimiter);
+            }
+            write_field(&mut text, value, options);
+        }
+        text.push_str(options.line_ending.as_str());
+    }
+    text
+}
+
+/// Write an array of rows given by JavaScript as CSV. See `to_csv`.
+#[wasm_bindgen(js_name = to_csv)]
+pub fn to_csv_js(values: &js_sys::Array, options: &CsvOptions) -> String {
+    to_csv(&from_js_grid(values, CellValue::from_js), options)
+}
+
+fn write_field(text: &mut String, value: &CellValue, options: &CsvOptions) {
+    let field = match value {
+        CellValue::Empty => String::new(),
+        CellValue::Number(n) => number_to_text(*n),
+        CellValue::Text(s) => s.clone(),
+        CellValue::Bool(b) => if *b { "TRUE" } else { "FALSE" }.to_owned(),
+        CellValue::Error(e) => e.as_str().to_owned(),
+    };
+
+    let quoted = match options.quote_style {
+        QuoteStyle::Always => true,
+        QuoteStyle::NonNumeric => !matches!(value, CellValue::Empty | CellValue::Number(_)),
+        QuoteStyle::Necessary => {
+            field.contains([options.delimiter, '"', '\r', '\n'])
+                || matches!(value, CellValue::Text(s)
+                    if Field::plain(s).value(true).0 != CellValue::Text(s.clone()))
+        }
+    };
+    if quoted {
+        text.push('"');
+        text.push_str(&field.replace('"', "\"\""));
+        text.push('"');
+    } else {
+        text.push_str(&field);
+    }
+}
+
+/// A field as written, quoted fields being always text.
+struct Field {
+    text: String,
+    quoted: bool,
+}
+
+impl Field {
+    fn plain(text: &str) -> Field {
+        Field {
+            text: text.to_owned(),
+            quoted: false,
+        }
+    }
+
+    /// Value of the field and the number format it asks for.
+    fn value(self, infer_types: bool) -> (CellValue, Option<&'static str>) {
+        if self.text.is_empty() {
+            return (CellValue::Empty, None);
+        }
+        if self.quoted || !infer_types {
+            return (CellValue::Text(self.text), None);
+        }
+        match infer(&self.text) {
+            Some(inferred) => inferred,
+            None => (CellValue::Text(self.text), None),
+        }
+    }
+}
+
+/// Type of an unquoted field: a boolean, an error such as `#N/A`, an ISO 8601 date or
+/// time, or a number. Numbers with leading zeros such as zip codes stay text.
+fn infer(text: &str) -> Option<(CellValue, Option<&'static str>)> {
+    if text.eq_ignore_ascii_case("TRUE") || text.eq_ignore_ascii_case("FALSE") {
+        return Some((CellValue::Bool(text.eq_ignore_ascii_case("TRUE")), None));
+    }
+    if let Some(e) = ErrorValue::ALL.into_iter().find(|e| e.as_str() == text) {
+        return Some((CellValue::Error(e), None));
+    }
+    if let Some(date) = parse_date_time(text) {
+        return Some(date);
+    }
+
+    let digits = text.trim_start_matches(['-', '+']);
+    let leading_zero = digits.len() > 1
+        && digits.starts_with('0')
+        && digits[1..].starts_with(|c: char| c.is_ascii_digit());
+    if leading_zero || text.trim() != text {
+        return None;
+    }
+    // Numbers beyond the range of a double, e.g. `1E400`, stay text
+    parse_number(text)
+        .filter(|n| n.is_finite())
+        .map(|n| (CellValue::Number(n), None))
+}
+
+/// `yyyy-mm-dd`, `hh:mm[:ss]`, or both separated by a space or `T`.
+pub(crate) fn parse_date_time(text: &str) -> Option<(CellValue, Option<&'static str>)> {
+    if let Some(time) = parse_time(text) {
+        return Some((CellValue::Number(time), Some(CSV_TIME_FORMAT)));
+    }
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(parse_time(time)?)),
+        None => (text, None),
+    };
+
+    let parts: Vec<&str> = date.split('-').collect();
+    let widths: Vec<usize> = parts.iter().map(|p| p.len()).collect();
+    if widths != [4, 2, 2] || !date.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        return None;
+    }
+    let serial = date_to_serial(
+        parts[0].parse().ok()?,
+        parts[1].parse().ok()?,
+        parts[2].parse().ok()?,
+    )?;
+    Some(match time {
+        Some(time) => (CellValue::Number(serial + time), Some(CSV_DATE_TIME_FORMAT)),
+        None => (CellValue::Number(serial), Some(CSV_DATE_FORMAT)),
+    })
+}
+
+/// Fraction of a day for `hh:mm` or `hh:mm:ss` within a day.
+fn parse_time(text: &str) -> Option<f64> {
+    let parts: Vec<&str> = text.split(':').collect();
+    if !(2..=3).contains(&parts.len())
+        || parts
+            .iter()
+            .any(|p| p.len() != 2 || !p.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+    let numbers: Vec<u32> = parts.iter().filter_map(|p| p.parse().ok()).collect();
+    let (hour, minute, second) = (numbers[0], numbers[1], *numbers.get(2).unwrap_or(&0));
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    Some((hour * 3600 + minute * 60 + second) as f64 / 86_400.0)
+}
+
+/// Split text into records of fields. Records end with `\r\n`, `\n` or `\r`, a line
+/// break closing the text adds no empty record, and a leading byte order mark is skipped.
+fn parse_records(text: &str, delimiter: char) -> Result<Vec<Vec<Field>>, WebExcelError> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut chars = text
+        .strip_prefix('\u{feff}')
+        .unwrap_or(text)
+        .chars()
+        .peekable();
+
+    while chars.peek().is_some() {
+        let mut field = Field::plain("");
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            field.quoted = true;
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.text.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.text.push(c),
+                    None => return Err(WebExcelError::ParseError),
+                }
+            }
+        }
+
+        loop {
+            match chars.next() {
+                Some(c) if c == delimiter => {
+                    record.push(field);
+                    // A delimiter ending the text leaves one more empty field
+                    if chars.peek().is_none() {
+                        record.push(Field::plain(""));
+                    }
+                    break;
+                }
+                Some(c @ ('\r' | '\n')) => {
+                    if c == '\r' && chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                    record.push(field);
+                    records.push(std::mem::take(&mut record));
+                    break;
+                }
+                Some(_) if field.quoted => return Err(WebExcelError::ParseError),
+                Some(c) => field.text.push(c),
+                None => {
+                    record.push(field);
+                    break;
+                }
+            }
+        }
+    }
+
+    if !record.is_empty() {
+        records.push(record);
+    }
+    Ok(records)
+}
@@ -8,6 +8,18 @@ pub enum WebExcelError {
     OutOfBoundError,
     RelocateError,
     RangeDiffSheetError,
+    FormulaParseError,
+    FormulaBuildError,
+    FormulaNestingError,
+    FormulaLengthError,
+    NameError,
+    CircularReferenceError,
+    SheetNotFoundError,
+    LastSheetError,
+    DimensionError,
+    MergeOverlapError,
+    MessageLengthError,
+    FileFormatError,
 }
 
 impl fmt::Display for WebExcelError {
@@ -20,6 +32,36 @@ impl fmt::Display for WebExcelError {
                 f,
                 "WebExcel cannot create range with two different sheet for cells"
             ),
+            WebExcelError::FormulaParseError => write!(f, "WebExcel formula parse error"),
+            WebExcelError::FormulaBuildError => write!(f, "WebExcel formula build error"),
+            WebExcelError::FormulaNestingError => {
+                write!(f, "WebExcel formula exceeds 64 levels of nested functions")
+            }
+            WebExcelError::FormulaLengthError => write!(
+                f,
+                "WebExcel formula exceeds 8192 characters or text literal exceeds 255 characters"
+            ),
+            WebExcelError::NameError => write!(f, "WebExcel invalid or duplicated name"),
+            WebExcelError::CircularReferenceError => {
+                write!(f, "WebExcel circular reference error")
+            }
+            WebExcelError::SheetNotFoundError => write!(f, "WebExcel sheet not found"),
+            WebExcelError::LastSheetError => {
+                write!(f, "WebExcel cannot delete the only sheet of a workbook")
+            }
+            WebExcelError::DimensionError => {
+                write!(f, "WebExcel values do not match the size of the range")
+            }
+            WebExcelError::MergeOverlapError => {
+                write!(f, "WebExcel merged areas cannot overlap")
+            }
+            WebExcelError::MessageLengthError => write!(
+                f,
+                "WebExcel title exceeds 32 characters or message exceeds 255 characters"
+            ),
+            WebExcelError::FileFormatError => {
+                write!(f, "WebExcel invalid or unsupported file format")
+            }
         }
     }
 }
@@ -1,23 +1,82 @@
 pub mod cell;
+pub mod conditional;
+pub mod csv;
 pub mod error;
+pub mod merge;
 pub mod range;
+pub mod range_areas;
+pub mod range_chunk;
+pub mod range_index;
+pub mod range_iter;
+pub mod range_slice;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod validation;
+pub mod workbook;
+pub mod xlsx;
 
 pub use cell::*;
+pub use conditional::*;
+pub use csv::*;
+pub use merge::*;
 pub use range::*;
+pub use range_areas::*;
+pub use range_chunk::*;
+pub use range_index::*;
+pub use range_iter::*;
+pub use range_slice::*;
+#[cfg(feature = "serde")]
+pub use serialize::*;
+pub use validation::*;
+pub use workbook::*;
+pub use xlsx::*;
 
 pub mod util {
     #[macro_use]
     pub mod macros;
     pub mod cell_handle;
+    pub mod deflate;
+    pub mod inflate;
+    pub mod json;
+    pub mod rtree;
+    pub mod xml;
+    pub mod zip;
 }
 
 pub mod math {
-    mod func;
+    pub mod array;
+    pub mod criteria;
+    pub mod engine;
+    pub mod eval;
+    pub mod format;
+    pub mod func;
+    pub mod graph;
+    pub mod names;
+    pub mod parser;
 }
 
 #[cfg(test)]
 mod test {
+    mod common;
+    mod test_array;
     mod test_cell;
+    mod test_conditional;
+    mod test_criteria;
+    mod test_csv;
+    mod test_engine;
+    mod test_eval;
+    mod test_format;
+    mod test_func;
+    mod test_graph;
+    mod test_merge;
+    mod test_parser;
     mod test_range;
+    mod test_range_areas;
+    mod test_range_index;
+    #[cfg(feature = "serde")]
+    mod test_serialize;
     mod test_util;
+    mod test_validation;
+    mod test_workbook;
+    mod test_xlsx;
 }
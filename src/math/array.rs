@@ -0,0 +1,481 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::eval::*;
+use crate::math::parser::ErrorValue;
+use crate::range::Range;
+use crate::util::cell_handle::*;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Most cells a generated array may hold, e.g. a full column of `SEQUENCE`.
+/// Larger arrays give `#NUM!` instead of being allocated.
+pub const MAX_ARRAY_CELLS: usize = 1 << 22;
+
+/// Row by row grid of values.
+type Grid = Vec<Vec<CellValue>>;
+
+/// Area an array result covers when it is entered at `anchor`.
+/// Scalars only cover the anchor itself.
+pub fn spill_range(anchor: &Cell, value: &Value) -> Result<Range, WebExcelError> {
+    let (rows, columns) = value.size();
+    let last_row = anchor.row as u64 + rows.max(1) as u64 - 1;
+    let last_column = anchor.column as u64 + columns.max(1) as u64 - 1;
+    if last_row > MAX_ROW as u64 || last_column > MAX_COLUMN as u64 {
+        return Err(WebExcelError::OutOfBoundError);
+    }
+
+    let start = Cell::new(anchor.row, anchor.column, anchor.sheet.clone())?;
+    let end = Cell::new(last_row as u32, last_column as u32, anchor.sheet.clone())?;
+    Range::new(&start, &end)
+}
+
+/// Spill `value` from `anchor`. Fails with `#SPILL!` when the array would run off the sheet
+/// or when any cell it covers, other than the anchor, is not empty.
+pub fn check_spill(
+    anchor: &Cell,
+    value: &Value,
+    source: &dyn CellSource,
+) -> Result<Range, ErrorValue> {
+    let range = spill_range(anchor, value).map_err(|_| ErrorValue::Spill)?;
+
+    for row in range.cell_start.row..=range.cell_end.row {
+        for column in range.cell_start.column..=range.cell_end.column {
+            if row == anchor.row && column == anchor.column {
+                continue;
+            }
+            let cell = Cell {
+                row,
+                column,
+                sheet: anchor.sheet.clone(),
+                ..Default::default()
+            };
+            if !source.value(&cell).is_empty() {
+                return Err(ErrorValue::Spill);
+            }
+        }
+    }
+
+    Ok(range)
+}
+
+/// Number of arguments accepted by a dynamic array function, `None` for other names.
+pub(crate) fn array_arity(name: &str) -> Option<(usize, usize)> {
+    let arity = match name {
+        "FILTER" => (2, 3),
+        "SORT" => (1, 4),
+        "SORTBY" => (2, 255),
+        "UNIQUE" => (1, 3),
+        "SEQUENCE" => (1, 4),
+        "RANDARRAY" => (0, 5),
+        "TAKE" | "DROP" => (2, 3),
+        "CHOOSECOLS" | "CHOOSEROWS" => (2, 255),
+        "VSTACK" | "HSTACK" => (1, 255),
+        "TOCOL" | "TOROW" => (1, 3),
+        _ => return None,
+    };
+    Some(arity)
+}
+
+/// Dynamic array functions. The argument count has already been checked against `array_arity`.
+/// `random` gives numbers in `[0, 1)` for `RANDARRAY`.
+pub(crate) fn array_function(name: &str, values: Vec<Value>, random: &dyn Fn() -> f64) -> Value {
+    let result = match name {
+        "FILTER" => filter(&values),
+        "SORT" => sort(&values),
+        "SORTBY" => sort_by(&values),
+        "UNIQUE" => unique(&values),
+        "SEQUENCE" => sequence(&values),
+        "RANDARRAY" => rand_array(&values, random),
+        "TAKE" | "DROP" => take_or_drop(&values, name == "TAKE"),
+        "CHOOSECOLS" => grid(&values[0])
+            .and_then(|g| choose_rows(&values, transpose(g)))
+            .map(transpose),
+        "CHOOSEROWS" => grid(&values[0]).and_then(|g| choose_rows(&values, g)),
+        "VSTACK" => stack(&values),
+        "HSTACK" => values
+            .iter()
+            .map(|v| part(v).map(transpose))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|grids| transpose(stack_grids(grids))),
+        "TOCOL" | "TOROW" => to_line(&values, name == "TOCOL"),
+        _ => Err(ErrorValue::Name),
+    };
+
+    match result {
+        Ok(rows) if rows.is_empty() || rows[0].is_empty() => Value::error(ErrorValue::Calc),
+        Ok(rows) => Value::Array(rows).simplify(),
+        Err(e) => Value::error(e),
+    }
+}
+
+/// Argument as a grid. Error values are returned as errors.
+fn grid(value: &Value) -> Result<Grid, ErrorValue> {
+    match value {
+        Value::Scalar(CellValue::Error(e)) => Err(*e),
+        Value::Lambda(_) => Err(ErrorValue::Calc),
+        other => Ok(other.clone().into_array()),
+    }
+}
+
+/// Argument of `VSTACK` or `HSTACK`. Error values are kept as part of the result.
+fn part(value: &Value) -> Result<Grid, ErrorValue> {
+    match value {
+        Value::Lambda(_) => Err(ErrorValue::Calc),
+        other => Ok(other.clone().into_array()),
+    }
+}
+
+/// Optional numeric argument at `index`. Omitted and empty arguments give `default`.
+fn number_arg(values: &[Value], index: usize, default: f64) -> Result<f64, ErrorValue> {
+    match values.get(index).map(|v| v.clone().into_scalar()) {
+        None | Some(CellValue::Empty) => Ok(default),
+        Some(v) => v.as_number(),
+    }
+}
+
+/// Optional boolean argument at `index`. Omitted and empty arguments give `default`.
+fn flag_arg(values: &[Value], index: usize, default: bool) -> Result<bool, ErrorValue> {
+    match values.get(index).map(|v| v.clone().into_scalar()) {
+        None | Some(CellValue::Empty) => Ok(default),
+        Some(v) => v.as_bool(),
+    }
+}
+
+fn transpose(grid: Grid) -> Grid {
+    let columns = grid.first().map_or(0, Vec::len);
+    (0..columns)
+        .map(|column| grid.iter().map(|row| row[column].clone()).collect())
+        .collect()
+}
+
+/// Resolve a 1-based index that may count from the end when negative.
+fn position(index: f64, len: usize) -> Result<usize, ErrorValue> {
+    let index = index.trunc();
+    if index >= 1.0 && index <= len as f64 {
+        Ok(index as usize - 1)
+    } else if index <= -1.0 && -index <= len as f64 {
+        Ok(len - (-index) as usize)
+    } else {
+        Err(ErrorValue::Value)
+    }
+}
+
+/// Sort order argument, `1` for ascending and `-1` for descending.
+fn sort_order(values: &[Value], index: usize) -> Result<bool, ErrorValue> {
+    let order = number_arg(values, index, 1.0)?;
+    if order == 1.0 {
+        Ok(true)
+    } else if order == -1.0 {
+        Ok(false)
+    } else {
+        Err(ErrorValue::Value)
+    }
+}
+
+/// `FILTER(array, include, [if_empty])`
+fn filter(values: &[Value]) -> Result<Grid, ErrorValue> {
+    let array = grid(&values[0])?;
+    let include = grid(&values[1])?;
+    let (rows, columns) = (array.len(), array[0].len());
+
+    // A column of flags picks rows, a row of flags picks columns
+    let by_rows = if include.len() == rows && include[0].len() == 1 {
+        true
+    } else if include.len() == 1 && include[0].len() == columns {
+        false
+    } else {
+        return Err(ErrorValue::Value);
+    };
+
+    let mut keep = vec![];
+    for flag in include.iter().flatten() {
+        keep.push(flag.as_bool()?);
+    }
+
+    let pick = |grid: Grid| -> Grid {
+        grid.into_iter()
+            .zip(&keep)
+            .filter(|(_, keep)| **keep)
+            .map(|(row, _)| row)
+            .collect()
+    };
+    let filtered = if by_rows {
+        pick(array)
+    } else {
+        transpose(pick(transpose(array)))
+    };
+
+    if filtered.is_empty() || filtered[0].is_empty() {
+        return match values.get(2) {
+            Some(if_empty) => grid(if_empty),
+            None => Err(ErrorValue::Calc),
+        };
+    }
+    Ok(filtered)
+}
+
+/// `SORT(array, [sort_index], [sort_order], [by_col])`
+fn sort(values: &[Value]) -> Result<Grid, ErrorValue> {
+    let by_col = flag_arg(values, 3, false)?;
+    let ascending = sort_order(values, 2)?;
+    let mut array = grid(&values[0])?;
+    if by_col {
+        array = transpose(array);
+    }
+
+    let index = position(number_arg(values, 1, 1.0)?, array[0].len())?;
+    array.sort_by(|a, b| {
+        let ordering = compare_values(&a[index], &b[index]);
+        if ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    });
+
+    Ok(if by_col { transpose(array) } else { array })
+}
+
+/// `SORTBY(array, by_array1, [sort_order1], [by_array2, sort_order2], ...)`
+fn sort_by(values: &[Value]) -> Result<Grid, ErrorValue> {
+    let array = grid(&values[0])?;
+    let (rows, columns) = (array.len(), array[0].len());
+
+    let mut keys: Vec<(Vec<CellValue>, bool)> = vec![];
+    let mut by_col = None;
+    for (i, _) in values.iter().enumerate().skip(1).step_by(2) {
+        let by = grid(&values[i])?;
+        let (key, column_key) = if by.len() == rows && by[0].len() == 1 {
+            (by.into_iter().flatten().collect(), false)
+        } else if by.len() == 1 && by[0].len() == columns {
+            (by.into_iter().flatten().collect(), true)
+        } else {
+            return Err(ErrorValue::Value);
+        };
+
+        // Every key must sort in the same direction
+        if by_col.is_some_and(|c| c != column_key) {
+            return Err(ErrorValue::Value);
+        }
+        by_col = Some(column_key);
+        keys.push((key, sort_order(values, i + 1)?));
+    }
+
+    let by_col = by_col.unwrap_or(false);
+    let lines = if by_col { transpose(array) } else { array };
+
+    let mut order: Vec<usize> = (0..lines.len()).collect();
+    order.sort_by(|&a, &b| {
+        keys.iter()
+            .map(|(key, ascending)| {
+                let ordering = compare_values(&key[a], &key[b]);
+                if *ascending {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            })
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let sorted: Grid = order.into_iter().map(|i| lines[i].clone()).collect();
+    Ok(if by_col { transpose(sorted) } else { sorted })
+}
+
+/// `UNIQUE(array, [by_col], [exactly_once])`
+fn unique(values: &[Value]) -> Result<Grid, ErrorValue> {
+    let by_col = flag_arg(values, 1, false)?;
+    let exactly_once = flag_arg(values, 2, false)?;
+    let array = grid(&values[0])?;
+    let lines = if by_col { transpose(array) } else { array };
+
+    let keys: Vec<Vec<UniqueKey>> = lines
+        .iter()
+        .map(|line| line.iter().map(UniqueKey::new).collect())
+        .collect();
+    let mut counts: HashMap<&[UniqueKey], usize> = HashMap::new();
+    for key in &keys {
+        *counts.entry(key).or_default() += 1;
+    }
+
+    let mut seen = HashSet::new();
+    let result: Grid = lines
+        .into_iter()
+        .zip(&keys)
+        .filter(|(_, key)| seen.insert(key.as_slice()))
+        .filter(|(_, key)| !exactly_once || counts[key.as_slice()] == 1)
+        .map(|(line, _)| line)
+        .collect();
+    Ok(if by_col { transpose(result) } else { result })
+}
+
+/// Value `UNIQUE` compares lines by. Text ignores case and an empty cell counts as zero.
+#[derive(PartialEq, Eq, Hash)]
+enum UniqueKey {
+    Number(u64),
+    Text(String),
+    Bool(bool),
+    Error(ErrorValue),
+}
+
+impl UniqueKey {
+    fn new(value: &CellValue) -> UniqueKey {
+        match value {
+            CellValue::Empty => UniqueKey::Number(0f64.to_bits()),
+            // Adding zero folds -0 into 0
+            CellValue::Number(n) => UniqueKey::Number((n + 0.0).to_bits()),
+            CellValue::Text(s) => UniqueKey::Text(s.to_lowercase()),
+            CellValue::Bool(b) => UniqueKey::Bool(*b),
+            CellValue::Error(e) => UniqueKey::Error(*e),
+        }
+    }
+}
+
+/// Size of a generated array. Zero gives `#CALC!`, negative sizes and sizes larger
+/// than the sheet give `#VALUE!`, more than `MAX_ARRAY_CELLS` cells give `#NUM!`.
+fn array_size(values: &[Value], rows: usize, columns: usize) -> Result<(usize, usize), ErrorValue> {
+    let rows = number_arg(values, rows, 1.0)?.trunc();
+    let columns = number_arg(values, columns, 1.0)?.trunc();
+
+    if rows < 0.0 || columns < 0.0 {
+        return Err(ErrorValue::Value);
+    }
+    if rows == 0.0 || columns == 0.0 {
+        return Err(ErrorValue::Calc);
+    }
+    if rows > MAX_ROW as f64 + 1.0 || columns > MAX_COLUMN as f64 + 1.0 {
+        return Err(ErrorValue::Value);
+    }
+    if rows * columns > MAX_ARRAY_CELLS as f64 {
+        return Err(ErrorValue::Num);
+    }
+    Ok((rows as usize, columns as usize))
+}
+
+/// `SEQUENCE(rows, [columns], [start], [step])`
+fn sequence(values: &[Value]) -> Result<Grid, ErrorValue> {
+    let (rows, columns) = array_size(values, 0, 1)?;
+    let start = number_arg(values, 2, 1.0)?;
+    let step = number_arg(values, 3, 1.0)?;
+
+    Ok((0..rows)
+        .map(|row| {
+            (0..columns)
+                .map(|column| CellValue::Number(start + step * (row * columns + column) as f64))
+                .collect()
+        })
+        .collect())
+}
+
+/// `RANDARRAY([rows], [columns], [min], [max], [integer])`
+fn rand_array(values: &[Value], random: &dyn Fn() -> f64) -> Result<Grid, ErrorValue> {
+    let (rows, columns) = array_size(values, 0, 1)?;
+    let min = number_arg(values, 2, 0.0)?;
+    let max = number_arg(values, 3, 1.0)?;
+    let integer = flag_arg(values, 4, false)?;
+
+    if min > max || (integer && (min.fract() != 0.0 || max.fract() != 0.0)) {
+        return Err(ErrorValue::Value);
+    }
+
+    let next = || {
+        if integer {
+            (min + random() * (max - min + 1.0)).floor()
+        } else {
+            min + random() * (max - min)
+        }
+    };
+    Ok((0..rows)
+        .map(|_| (0..columns).map(|_| CellValue::Number(next())).collect())
+        .collect())
+}
+
+/// `TAKE(array, rows, [columns])` and `DROP(array, rows, [columns])`.
+/// Negative counts work from the end of the array.
+fn take_or_drop(values: &[Value], take: bool) -> Result<Grid, ErrorValue> {
+    let array = grid(&values[0])?;
+
+    let span = |index: usize, len: usize| -> Result<(usize, usize), ErrorValue> {
+        let count = match values.get(index).map(|v| v.clone().into_scalar()) {
+            None | Some(CellValue::Empty) => return Ok((0, len)),
+            Some(v) => v.as_number()?.trunc(),
+        };
+        let n = (count.abs() as usize).min(len);
+        Ok(match (take, count >= 0.0) {
+            (true, true) => (0, n),
+            (true, false) => (len - n, len),
+            (false, true) => (n, len),
+            (false, false) => (0, len - n),
+        })
+    };
+
+    let (top, bottom) = span(1, array.len())?;
+    let (left, right) = span(2, array[0].len())?;
+    Ok(array[top..bottom]
+        .iter()
+        .map(|row| row[left..right].to_vec())
+        .collect())
+}
+
+/// `CHOOSEROWS(array, row_num1, [row_num2], ...)`. Indexes may be arrays themselves.
+fn choose_rows(values: &[Value], array: Grid) -> Result<Grid, ErrorValue> {
+    let mut chosen = vec![];
+    for index in flatten(&values[1..]) {
+        let index = position(index.as_number()?, array.len())?;
+        chosen.push(array[index].clone());
+    }
+    Ok(chosen)
+}
+
+/// `VSTACK(array1, [array2], ...)`
+fn stack(values: &[Value]) -> Result<Grid, ErrorValue> {
+    let grids = values.iter().map(part).collect::<Result<Vec<_>, _>>()?;
+    Ok(stack_grids(grids))
+}
+
+/// Append grids below each other. Narrower grids are padded with `#N/A`.
+fn stack_grids(grids: Vec<Grid>) -> Grid {
+    let width = grids
+        .iter()
+        .map(|g| g.first().map_or(0, Vec::len))
+        .max()
+        .unwrap_or(0);
+
+    grids
+        .into_iter()
+        .flatten()
+        .map(|mut row| {
+            row.resize(width, CellValue::Error(ErrorValue::NA));
+            row
+        })
+        .collect()
+}
+
+/// `TOCOL(array, [ignore], [scan_by_column])` and `TOROW(array, [ignore], [scan_by_column])`.
+/// `ignore` is 0 to keep everything, 1 to skip blanks, 2 to skip errors and 3 to skip both.
+fn to_line(values: &[Value], column: bool) -> Result<Grid, ErrorValue> {
+    let ignore = number_arg(values, 1, 0.0)?.trunc();
+    if !(0.0..=3.0).contains(&ignore) {
+        return Err(ErrorValue::Value);
+    }
+    let (skip_blanks, skip_errors) = (ignore == 1.0 || ignore == 3.0, ignore >= 2.0);
+
+    let mut array = grid(&values[0])?;
+    if flag_arg(values, 2, false)? {
+        array = transpose(array);
+    }
+
+    let line: Vec<CellValue> = array
+        .into_iter()
+        .flatten()
+        .filter(|v| !(skip_blanks && v.is_empty()))
+        .filter(|v| !(skip_errors && matches!(v, CellValue::Error(_))))
+        .collect();
+
+    Ok(if column {
+        line.into_iter().map(|v| vec![v]).collect()
+    } else {
+        vec![line]
+    })
+}
@@ -0,0 +1,294 @@
+use crate::math::eval::*;
+use crate::math::parser::ErrorValue;
+use std::cmp::Ordering;
+
+/// Comparison at the start of a criteria string, e.g. the `>=` of `">=5"`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Value compared against by a criteria.
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    /// `"="` only matches empty cells, `""` matches empty text as well.
+    Blank {
+        strict: bool,
+    },
+    Number(f64),
+    Bool(bool),
+    Error(ErrorValue),
+    /// Text, compared case insensitively. `*`, `?` and `~` are wildcards for `=` and `<>`.
+    Text(String),
+}
+
+/// Criteria of `COUNTIF`, `SUMIFS` and friends, e.g. `">=5"`, `"<>"`, `"ab*c?"` or `5`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Criteria {
+    operator: Operator,
+    operand: Operand,
+}
+
+impl Criteria {
+    /// Read a criteria argument. Numbers, booleans and errors match equal values,
+    /// an empty cell is read as `0` and text is parsed for a leading operator.
+    pub fn new(value: &CellValue) -> Criteria {
+        let operand = match value {
+            CellValue::Empty => Operand::Number(0.0),
+            CellValue::Number(n) => Operand::Number(*n),
+            CellValue::Bool(b) => Operand::Bool(*b),
+            CellValue::Error(e) => Operand::Error(*e),
+            CellValue::Text(s) => return Criteria::parse(s),
+        };
+
+        Criteria {
+            operator: Operator::Equal,
+            operand,
+        }
+    }
+
+    /// Parse a criteria string, e.g. `">=5"`, `"<>"` or `"~*"`.
+    pub fn parse(criteria: &str) -> Criteria {
+        let operators = [
+            (">=", Operator::GreaterOrEqual),
+            ("<=", Operator::LessOrEqual),
+            ("<>", Operator::NotEqual),
+            ("=", Operator::Equal),
+            (">", Operator::Greater),
+            ("<", Operator::Less),
+        ];
+        let (operator, rest, explicit) = operators
+            .iter()
+            .find_map(|(symbol, op)| criteria.strip_prefix(symbol).map(|rest| (*op, rest, true)))
+            .unwrap_or((Operator::Equal, criteria, false));
+
+        let operand = if rest.is_empty() {
+            match operator {
+                Operator::Equal | Operator::NotEqual => Operand::Blank { strict: explicit },
+                _ => Operand::Text(String::new()),
+            }
+        } else if let Some(n) = parse_number(rest) {
+            Operand::Number(n)
+        } else if rest.eq_ignore_ascii_case("TRUE") || rest.eq_ignore_ascii_case("FALSE") {
+            Operand::Bool(rest.eq_ignore_ascii_case("TRUE"))
+        } else if let Some(e) = ErrorValue::ALL
+            .iter()
+            .find(|e| e.as_str().eq_ignore_ascii_case(rest))
+        {
+            Operand::Error(*e)
+        } else {
+            Operand::Text(rest.to_owned())
+        };
+
+        Criteria { operator, operand }
+    }
+
+    /// Whether `value` satisfies the criteria.
+    pub fn matches(&self, value: &CellValue) -> bool {
+        match self.operator {
+            Operator::Equal => self.equals(value),
+            Operator::NotEqual => !self.equals(value),
+            op => {
+                // Ordering only holds between values of the same kind
+                let ordering = match (&self.operand, value) {
+                    (Operand::Number(n), CellValue::Number(v)) => v.partial_cmp(n),
+                    (Operand::Text(t), CellValue::Text(v)) => Some(compare_values(
+                        &CellValue::Text(v.clone()),
+                        &CellValue::Text(t.clone()),
+                    )),
+                    (Operand::Bool(b), CellValue::Bool(v)) => Some(v.cmp(b)),
+                    _ => None,
+                };
+                match ordering {
+                    Some(Ordering::Less) => {
+                        matches!(op, Operator::Less | Operator::LessOrEqual)
+                    }
+                    Some(Ordering::Equal) => {
+                        matches!(op, Operator::LessOrEqual | Operator::GreaterOrEqual)
+                    }
+                    Some(Ordering::Greater) => {
+                        matches!(op, Operator::Greater | Operator::GreaterOrEqual)
+                    }
+                    None => false,
+                }
+            }
+        }
+    }
+
+    fn equals(&self, value: &CellValue) -> bool {
+        match (&self.operand, value) {
+            (Operand::Blank { .. }, CellValue::Empty) => true,
+            (Operand::Blank { strict: false }, CellValue::Text(t)) => t.is_empty(),
+            (Operand::Number(n), CellValue::Number(v)) => v == n,
+            // Text that reads as the number matches as well, e.g. "5" for 5
+            (Operand::Number(n), CellValue::Text(t)) => parse_number(t) == Some(*n),
+            (Operand::Bool(b), CellValue::Bool(v)) => v == b,
+            (Operand::Error(e), CellValue::Error(v)) => v == e,
+            (Operand::Text(pattern), CellValue::Text(t)) => wildcard_match(pattern, t),
+            _ => false,
+        }
+    }
+}
+
+/// Match `text` against a pattern where `*` is any run of characters, `?` any single character
+/// and `~` escapes the next character. Case insensitive.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    #[derive(PartialEq)]
+    enum Token {
+        Char(char),
+        Any,
+        Many,
+    }
+
+    let mut tokens = vec![];
+    let mut chars = pattern.chars().flat_map(char::to_lowercase);
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '~' => match chars.next() {
+                Some(escaped) => Token::Char(escaped),
+                None => Token::Char('~'),
+            },
+            '?' => Token::Any,
+            '*' => Token::Many,
+            c => Token::Char(c),
+        });
+    }
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+
+    // Greedy matching, backtracking to the last `*` on a mismatch
+    let (mut t, mut p) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::Many) => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(Token::Any) => {
+                p += 1;
+                t += 1;
+            }
+            Some(Token::Char(c)) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    tokens[p..].iter().all(|token| *token == Token::Many)
+}
+
+/// Number of arguments accepted by a conditional aggregate, `None` for other names.
+pub(crate) fn criteria_arity(name: &str) -> Option<(usize, usize)> {
+    let arity = match name {
+        "COUNTIF" => (2, 2),
+        "SUMIF" | "AVERAGEIF" => (2, 3),
+        "COUNTIFS" => (2, 254),
+        "SUMIFS" | "AVERAGEIFS" | "MAXIFS" | "MINIFS" => (3, 255),
+        _ => return None,
+    };
+    Some(arity)
+}
+
+/// Conditional aggregates. The argument count has already been checked against `criteria_arity`.
+/// An array of criteria gives an array of results, e.g. `COUNTIF(A1:A9, {"a","b"})`.
+pub(crate) fn criteria_function(name: &str, values: Vec<Value>) -> Value {
+    let grids: Vec<Vec<Vec<CellValue>>> = values.into_iter().map(Value::into_array).collect();
+
+    // Target values followed by `(range, criteria)` pairs
+    let (target, pairs) = match name {
+        "COUNTIF" | "COUNTIFS" => (None, &grids[..]),
+        "SUMIF" | "AVERAGEIF" => (Some(grids.get(2).unwrap_or(&grids[0])), &grids[..2]),
+        _ => (Some(&grids[0]), &grids[1..]),
+    };
+    if !pairs.len().is_multiple_of(2) {
+        return Value::error(ErrorValue::Value);
+    }
+
+    // Multi criteria functions need ranges of the same size
+    let size = |grid: &Vec<Vec<CellValue>>| (grid.len(), grid[0].len());
+    let (rows, columns) = size(&pairs[0]);
+    let mut shapes = pairs.iter().step_by(2).map(size);
+    if name.ends_with('S')
+        && (shapes.any(|s| s != (rows, columns))
+            || target.is_some_and(|t| size(t) != (rows, columns)))
+    {
+        return Value::error(ErrorValue::Value);
+    }
+
+    // Shape of the result, stretching single criteria
+    let criteria: Vec<&Vec<Vec<CellValue>>> = pairs.iter().skip(1).step_by(2).collect();
+    let out_rows = criteria.iter().map(|c| c.len()).max().unwrap_or(1);
+    let out_columns = criteria.iter().map(|c| c[0].len()).max().unwrap_or(1);
+    let pick = |grid: &Vec<Vec<CellValue>>, row: usize, column: usize| {
+        let r = if grid.len() == 1 { 0 } else { row };
+        let c = if grid[0].len() == 1 { 0 } else { column };
+        grid.get(r).and_then(|line| line.get(c)).cloned()
+    };
+
+    let result: Vec<Vec<CellValue>> = (0..out_rows)
+        .map(|out_row| {
+            (0..out_columns)
+                .map(|out_column| {
+                    let mut tests = vec![];
+                    for pair in pairs.chunks(2) {
+                        match pick(&pair[1], out_row, out_column) {
+                            Some(value) => tests.push((&pair[0], Criteria::new(&value))),
+                            None => return CellValue::Error(ErrorValue::NA),
+                        }
+                    }
+
+                    let mut count = 0;
+                    let mut numbers = vec![];
+                    for row in 0..rows {
+                        for column in 0..columns {
+                            let matched = tests
+                                .iter()
+                                .all(|(range, criteria)| criteria.matches(&range[row][column]));
+                            if !matched {
+                                continue;
+                            }
+                            count += 1;
+
+                            let value = target.and_then(|t| t.get(row)?.get(column));
+                            match value {
+                                Some(CellValue::Number(n)) => numbers.push(*n),
+                                Some(CellValue::Error(e)) => return CellValue::Error(*e),
+                                _ => {}
+                            }
+                        }
+                    }
+
+                    aggregate(name, count, numbers)
+                })
+                .collect()
+        })
+        .collect();
+
+    Value::Array(result).simplify()
+}
+
+fn aggregate(name: &str, count: usize, numbers: Vec<f64>) -> CellValue {
+    match name {
+        "COUNTIF" | "COUNTIFS" => CellValue::Number(count as f64),
+        "SUMIF" | "SUMIFS" => CellValue::from(Ok(total(&numbers))),
+        "AVERAGEIF" | "AVERAGEIFS" if numbers.is_empty() => CellValue::Error(ErrorValue::Div0),
+        "AVERAGEIF" | "AVERAGEIFS" => CellValue::from(Ok(total(&numbers) / numbers.len() as f64)),
+        "MAXIFS" => CellValue::from(Ok(numbers.into_iter().reduce(f64::max).unwrap_or(0.0))),
+        "MINIFS" => CellValue::from(Ok(numbers.into_iter().reduce(f64::min).unwrap_or(0.0))),
+        _ => CellValue::Error(ErrorValue::Name),
+    }
+}
@@ -0,0 +1,548 @@
+use crate::cell::{same_sheet, Cell};
+use crate::error::WebExcelError;
+use crate::math::array::spill_range;
+use crate::math::eval::*;
+use crate::math::graph::DependencyGraph;
+use crate::math::names::DefinedNames;
+use crate::math::parser::*;
+use crate::range::Range;
+use std::collections::{BTreeMap, BTreeSet};
+use wasm_bindgen::prelude::*;
+
+/// Array result spilled from an anchor cell.
+#[derive(Debug, Clone)]
+struct Spill {
+    range: Range,
+    values: Vec<Vec<CellValue>>,
+}
+
+/// Iterative calculation options, as in Excel's File > Options > Formulas.
+/// When disabled, formulas in a circular reference show 0.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IterationSettings {
+    pub enabled: bool,
+    /// Most passes over a circular reference, 100 by default.
+    pub max_iterations: u32,
+    /// Iteration stops once no value changes by more than this, 0.001 by default.
+    pub max_change: f64,
+}
+
+impl Default for IterationSettings {
+    fn default() -> Self {
+        IterationSettings {
+            enabled: false,
+            max_iterations: 100,
+            max_change: 0.001,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl IterationSettings {
+    #[wasm_bindgen(constructor)]
+    pub fn new(enabled: bool, max_iterations: u32, max_change: f64) -> IterationSettings {
+        IterationSettings {
+            enabled,
+            max_iterations,
+            max_change: max_change.abs(),
+        }
+    }
+}
+
+/// Outcome of calculating one circular reference.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleStatus {
+    cells: Vec<Cell>,
+    /// Passes made over the cells, 0 when iterative calculation is off.
+    pub iterations: u32,
+    /// Whether the last pass changed no value by more than the maximum change.
+    pub converged: bool,
+    /// Largest change of a value during the last pass. Changes between non numbers count as infinite.
+    pub last_change: f64,
+}
+
+#[wasm_bindgen]
+impl CycleStatus {
+    /// Formula cells of the circular reference, as an array of `Cell`.
+    #[wasm_bindgen(getter)]
+    pub fn cells(&self) -> js_sys::Array {
+        self.cells.iter().cloned().map(JsValue::from).collect()
+    }
+}
+
+impl CycleStatus {
+    pub fn members(&self) -> &[Cell] {
+        &self.cells
+    }
+}
+
+/// Keeps formula results up to date. Edits only mark cells as dirty, `recalculate` then
+/// evaluates the formulas depending on them, and the volatile ones, in dependency order.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone)]
+pub struct CalcEngine {
+    /// Values entered by hand.
+    inputs: BTreeMap<Cell, CellValue>,
+    formulas: BTreeMap<Cell, Expr>,
+    /// Last result of each formula, the top-left value for array results.
+    results: BTreeMap<Cell, CellValue>,
+    spills: BTreeMap<Cell, Spill>,
+    /// Area an anchor showing `#SPILL!` would cover if it were not blocked.
+    blocked: BTreeMap<Cell, Range>,
+    graph: DependencyGraph,
+    /// Formulas calling a volatile function, calculated every time.
+    volatile: BTreeSet<Cell>,
+    names: DefinedNames,
+    /// Cells edited since the last calculation.
+    dirty: BTreeSet<Cell>,
+    /// Formula cells to calculate on the next calculation.
+    stale: BTreeSet<Cell>,
+    /// Cells whose calculated value went away since the last calculation.
+    cleared: BTreeSet<Cell>,
+    seed: u64,
+    iteration: IterationSettings,
+    /// Circular references met by the last calculation.
+    cycles: Vec<CycleStatus>,
+}
+
+#[wasm_bindgen]
+impl CalcEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CalcEngine {
+        CalcEngine::default()
+    }
+
+    pub fn set_number(&mut self, cell: &Cell, value: f64) {
+        self.set_value(cell, CellValue::from(Ok(value)));
+    }
+
+    pub fn set_text(&mut self, cell: &Cell, value: &str) {
+        self.set_value(cell, CellValue::Text(value.to_owned()));
+    }
+
+    pub fn set_boolean(&mut self, cell: &Cell, value: bool) {
+        self.set_value(cell, CellValue::Bool(value));
+    }
+
+    /// Put a formula into `cell`, e.g. `=SUM(A1:A10)`.
+    pub fn set_formula(&mut self, cell: &Cell, formula: &str) -> Result<(), WebExcelError> {
+        self.set_expr(cell, parse_formula(formula)?);
+        Ok(())
+    }
+
+    /// Remove the value or formula of `cell`.
+    pub fn clear(&mut self, cell: &Cell) {
+        self.remove_formula(cell);
+        self.inputs.remove(cell);
+        self.touch(cell);
+    }
+
+    pub fn has_formula(&self, cell: &Cell) -> bool {
+        self.formulas.contains_key(cell)
+    }
+
+    /// Current value of `cell` as a number, string, boolean, error string such as `"#DIV/0!"`,
+    /// or `null` when empty.
+    pub fn value_at(&self, cell: &Cell) -> JsValue {
+        self.value(cell).to_js()
+    }
+
+    /// Area covered by the array spilled from `anchor`, if any.
+    pub fn spill_of(&self, anchor: &Cell) -> Option<Range> {
+        self.spills.get(anchor).map(|spill| spill.range.clone())
+    }
+
+    /// Use `names` for defined names and named `LAMBDA`s. Every formula is calculated again.
+    pub fn set_names(&mut self, names: &DefinedNames) {
+        self.names = names.clone();
+        self.stale.extend(self.formulas.keys().cloned());
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn iteration(&self) -> IterationSettings {
+        self.iteration
+    }
+
+    /// Change the iterative calculation options. Circular references are calculated again.
+    #[wasm_bindgen(setter)]
+    pub fn set_iteration(&mut self, settings: IterationSettings) {
+        self.iteration = settings;
+        for component in self.graph.components() {
+            if self.graph.is_circular(&component) {
+                self.stale.extend(component);
+            }
+        }
+    }
+
+    /// Status of each circular reference met by the last calculation, as an array of `CycleStatus`.
+    pub fn cycle_status(&self) -> js_sys::Array {
+        self.cycles.iter().cloned().map(JsValue::from).collect()
+    }
+
+    /// Calculate formulas affected by edits since the last calculation, and volatile ones.
+    /// Returns the cells whose value changed, including cells of spilled arrays.
+    pub fn recalculate(&mut self) -> js_sys::Array {
+        self.calculate(false)
+            .into_iter()
+            .map(JsValue::from)
+            .collect()
+    }
+
+    /// Calculate every formula. Returns the cells whose value changed.
+    pub fn recalculate_all(&mut self) -> js_sys::Array {
+        self.calculate(true)
+            .into_iter()
+            .map(JsValue::from)
+            .collect()
+    }
+}
+
+impl CalcEngine {
+    /// Enter a value by hand, replacing any formula. `Empty` clears the cell.
+    pub fn set_value(&mut self, cell: &Cell, value: CellValue) {
+        if value.is_empty() {
+            return self.clear(cell);
+        }
+        self.remove_formula(cell);
+        self.inputs.insert(cell.clone(), value);
+        self.touch(cell);
+    }
+
+    /// Put a parsed formula into `cell`.
+    pub fn set_expr(&mut self, cell: &Cell, expr: Expr) {
+        self.remove_formula(cell);
+        self.inputs.remove(cell);
+
+        self.graph.set_expr(cell, &expr);
+        if is_volatile(&expr) {
+            self.volatile.insert(cell.clone());
+        }
+        self.formulas.insert(cell.clone(), expr);
+        self.touch(cell);
+    }
+
+    /// Current value of `cell`: its input, its formula result or a value spilled into it.
+    pub fn value(&self, cell: &Cell) -> CellValue {
+        if let Some(value) = self.inputs.get(cell).or_else(|| self.results.get(cell)) {
+            return value.clone();
+        }
+        if self.formulas.contains_key(cell) {
+            return CellValue::Empty;
+        }
+
+        self.spills
+            .values()
+            .find(|spill| spill.range.has(cell))
+            .map(|spill| {
+                let row = (cell.row - spill.range.cell_start.row) as usize;
+                let column = (cell.column - spill.range.cell_start.column) as usize;
+                spill.values[row][column].clone()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn formula(&self, cell: &Cell) -> Option<&Expr> {
+        self.formulas.get(cell)
+    }
+
+    pub fn graph(&self) -> &DependencyGraph {
+        &self.graph
+    }
+
+    /// Circular references met by the last calculation.
+    pub fn cycles(&self) -> &[CycleStatus] {
+        &self.cycles
+    }
+
+    /// Calculate dirty formulas, their dependents and volatile formulas, or every formula
+    /// when `full`. Returns the cells whose value changed, in sheet order.
+    pub fn calculate(&mut self, full: bool) -> Vec<Cell> {
+        let roots: BTreeSet<Cell> = if full {
+            self.formulas.keys().cloned().collect()
+        } else {
+            let mut roots = std::mem::take(&mut self.dirty);
+            roots.append(&mut self.stale);
+            roots.extend(self.volatile.iter().cloned());
+            roots
+        };
+        self.dirty.clear();
+        self.stale.clear();
+
+        let mut pending = BTreeSet::new();
+        for root in &roots {
+            if self.formulas.contains_key(root) {
+                pending.insert(root.clone());
+            }
+            pending.extend(self.graph.all_dependents(root));
+        }
+
+        self.cycles.clear();
+        let mut changed = std::mem::take(&mut self.cleared);
+        let now = current_serial();
+
+        // Spills are not in the dependency graph. When one moves, the formulas reading it are
+        // calculated again, giving up after as many rounds as there are formulas.
+        let mut rounds = 0;
+        while !pending.is_empty() && rounds <= self.formulas.len() {
+            rounds += 1;
+            let mut done = BTreeSet::new();
+            let mut again = BTreeSet::new();
+
+            // `pending` holds every dependent of its cells, so ordering it alone is enough
+            for component in &self.graph.components_of(&pending) {
+                let moved = if self.graph.is_circular(component) {
+                    self.calculate_circular(component, now, &mut changed)
+                } else {
+                    self.calculate_cell(&component[0], now, &mut changed)
+                };
+                done.extend(component.iter().cloned());
+
+                for area in moved {
+                    let mut affected = self.graph.area_dependents(&area);
+                    affected.extend(
+                        self.blocked
+                            .iter()
+                            .filter(|(_, blocked)| blocked.intersects(&area))
+                            .map(|(anchor, _)| anchor.clone()),
+                    );
+                    for cell in affected {
+                        let mut cells = self.graph.all_dependents(&cell);
+                        cells.push(cell);
+                        again.extend(
+                            cells
+                                .into_iter()
+                                .filter(|c| done.contains(c) || !pending.contains(c)),
+                        );
+                    }
+                }
+            }
+            pending = again;
+        }
+
+        changed.into_iter().collect()
+    }
+
+    /// Calculate formulas referencing each other in a loop. With iterative calculation
+    /// they are evaluated in turn, starting from their previous values, until no value
+    /// changes by more than the maximum change. Otherwise they show 0, the way Excel does.
+    fn calculate_circular(
+        &mut self,
+        component: &[Cell],
+        now: f64,
+        changed: &mut BTreeSet<Cell>,
+    ) -> Vec<Range> {
+        let mut moved = vec![];
+        let mut status = CycleStatus {
+            cells: component.to_vec(),
+            iterations: 0,
+            converged: false,
+            last_change: f64::INFINITY,
+        };
+
+        if !self.iteration.enabled {
+            for cell in component {
+                moved.extend(self.store(cell, Value::number(0.0), changed));
+            }
+            self.cycles.push(status);
+            return moved;
+        }
+
+        while status.iterations < self.iteration.max_iterations {
+            status.iterations += 1;
+            status.last_change = 0.0;
+
+            for cell in component {
+                let before = self.value(cell);
+                moved.extend(self.calculate_cell(cell, now, changed));
+                let change = match (&before, &self.value(cell)) {
+                    (a, b) if a == b => 0.0,
+                    (CellValue::Number(a), CellValue::Number(b)) => (a - b).abs(),
+                    (CellValue::Empty, CellValue::Number(n))
+                    | (CellValue::Number(n), CellValue::Empty) => n.abs(),
+                    _ => f64::INFINITY,
+                };
+                status.last_change = status.last_change.max(change);
+            }
+
+            if status.last_change <= self.iteration.max_change {
+                status.converged = true;
+                break;
+            }
+        }
+
+        self.cycles.push(status);
+        moved.dedup();
+        moved
+    }
+
+    /// Evaluate the formula in `cell` and store its result.
+    /// Returns the areas of a spill that moved or changed.
+    fn calculate_cell(
+        &mut self,
+        cell: &Cell,
+        now: f64,
+        changed: &mut BTreeSet<Cell>,
+    ) -> Vec<Range> {
+        let seed = self.next_seed();
+        let value = match self.formulas.get(cell) {
+            Some(expr) => Evaluator::new(self)
+                .with_names(&self.names)
+                .on_sheet(cell.sheet.clone())
+                .with_seed(seed)
+                .with_now(now)
+                .evaluate(expr),
+            None => return vec![],
+        };
+        self.store(cell, value, changed)
+    }
+
+    /// Store the result of the formula in `cell`, spilling arrays when there is room.
+    /// Cells whose value changed are added to `changed`. Returns the areas of a spill
+    /// that moved or changed.
+    fn store(&mut self, cell: &Cell, value: Value, changed: &mut BTreeSet<Cell>) -> Vec<Range> {
+        let single = Range::new(cell, cell).unwrap_or_default();
+        let old_area = self
+            .spills
+            .get(cell)
+            .map_or(single.clone(), |spill| spill.range.clone());
+        let before: BTreeMap<Cell, CellValue> = old_area
+            .iter_cells()
+            .map(|c| {
+                let value = self.value(&c);
+                (c, value)
+            })
+            .collect();
+
+        self.spills.remove(cell);
+        self.blocked.remove(cell);
+        let result = match value {
+            Value::Lambda(_) => CellValue::Error(ErrorValue::Calc),
+            value if value.is_array() => match spill_range(cell, &value) {
+                Ok(range) if !self.occupied(&range, cell) => {
+                    let values = value.into_array();
+                    let top_left = values[0][0].clone();
+                    self.spills.insert(cell.clone(), Spill { range, values });
+                    top_left
+                }
+                Ok(range) => {
+                    self.blocked.insert(cell.clone(), range);
+                    CellValue::Error(ErrorValue::Spill)
+                }
+                Err(_) => CellValue::Error(ErrorValue::Spill),
+            },
+            value => value.into_scalar(),
+        };
+        self.results.insert(cell.clone(), result);
+
+        let new_area = self
+            .spills
+            .get(cell)
+            .map_or(single, |spill| spill.range.clone());
+        let mut spill_changed = false;
+        for c in old_area.iter_cells().chain(new_area.iter_cells()) {
+            let old = before.get(&c).cloned().unwrap_or_default();
+            if old != self.value(&c) {
+                spill_changed |= &c != cell;
+                changed.insert(c);
+            }
+        }
+
+        match (spill_changed, old_area == new_area) {
+            (false, _) => vec![],
+            (true, true) => vec![new_area],
+            (true, false) => vec![old_area, new_area],
+        }
+    }
+
+    /// Whether anything other than `anchor` holds a value in `range`.
+    fn occupied(&self, range: &Range, anchor: &Cell) -> bool {
+        let taken = |cell: &Cell| cell != anchor && range.has(cell);
+        self.inputs.keys().any(taken)
+            || self.formulas.keys().any(taken)
+            || self
+                .spills
+                .iter()
+                .any(|(other, spill)| other != anchor && spill.range.intersects(range))
+    }
+
+    /// Forget the formula of `cell`, along with its result and spill.
+    fn remove_formula(&mut self, cell: &Cell) {
+        if self.formulas.remove(cell).is_none() {
+            return;
+        }
+        self.graph.remove(cell);
+        self.volatile.remove(cell);
+        self.results.remove(cell);
+        self.blocked.remove(cell);
+
+        if let Some(spill) = self.spills.remove(cell) {
+            self.cleared
+                .extend(spill.range.iter_cells().filter(|c| c != cell));
+            self.stale.extend(self.graph.area_dependents(&spill.range));
+        }
+    }
+
+    /// Mark `cell` as edited. Arrays spilling over it, or blocked by it, are calculated again.
+    fn touch(&mut self, cell: &Cell) {
+        self.dirty.insert(cell.clone());
+
+        let spilling = self
+            .spills
+            .iter()
+            .filter(|(anchor, spill)| *anchor != cell && spill.range.has(cell))
+            .map(|(anchor, _)| anchor.clone());
+        let blocked = self
+            .blocked
+            .iter()
+            .filter(|(anchor, range)| *anchor != cell && range.has(cell))
+            .map(|(anchor, _)| anchor.clone());
+        let anchors: Vec<Cell> = spilling.chain(blocked).collect();
+        self.stale.extend(anchors);
+    }
+
+    fn next_seed(&mut self) -> u64 {
+        self.seed = self.seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.seed
+    }
+}
+
+impl CellSource for CalcEngine {
+    fn value(&self, cell: &Cell) -> CellValue {
+        CalcEngine::value(self, cell)
+    }
+
+    fn used_bounds(&self, sheet: Option<&str>) -> Option<(u32, u32)> {
+        let sheet = sheet.map(str::to_owned);
+        let ends = self
+            .inputs
+            .keys()
+            .chain(self.formulas.keys())
+            .chain(self.spills.values().map(|spill| &spill.range.cell_end))
+            .filter(|cell| same_sheet(&cell.sheet, &sheet));
+
+        ends.fold(None, |bounds, cell| match bounds {
+            None => Some((cell.row, cell.column)),
+            Some((row, column)) => Some((row.max(cell.row), column.max(cell.column))),
+        })
+    }
+
+    fn spill_range(&self, anchor: &Cell) -> Option<Range> {
+        self.spill_of(anchor)
+    }
+}
+
+/// Whether the formula calls a volatile function, directly or inside another call.
+fn is_volatile(expr: &Expr) -> bool {
+    let mut volatile = false;
+    expr.walk(&mut |e| {
+        if let Expr::Function(name, _) = e {
+            let name = name
+                .trim_start_matches("_XLFN.")
+                .trim_start_matches("_XLWS.");
+            volatile |= VOLATILE_FUNCTIONS.contains(&name);
+        }
+    });
+    volatile
+}
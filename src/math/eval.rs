@@ -0,0 +1,1308 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::array::*;
+use crate::math::criteria::*;
+use crate::math::format::format_text;
+use crate::math::names::DefinedNames;
+use crate::math::parser::*;
+use crate::range::Range;
+use crate::util::cell_handle::*;
+use std::cmp::Ordering;
+use std::rc::Rc;
+use wasm_bindgen::JsValue;
+
+/// Functions whose result may change on every calculation.
+pub const VOLATILE_FUNCTIONS: [&str; 7] = [
+    "NOW",
+    "TODAY",
+    "RAND",
+    "RANDBETWEEN",
+    "RANDARRAY",
+    "OFFSET",
+    "INDIRECT",
+];
+
+/// Seed of the random number generator unless `Evaluator::with_seed` is used.
+const DEFAULT_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Deepest chain of `LAMBDA` invocations before evaluation gives up with `#NUM!`.
+pub const MAX_CALL_DEPTH: usize = 256;
+
+/// Value stored in a single cell.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum CellValue {
+    #[default]
+    Empty,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(ErrorValue),
+}
+
+impl CellValue {
+    pub fn is_empty(&self) -> bool {
+        matches!(self, CellValue::Empty)
+    }
+
+    /// Coerce to a number the way arithmetic operators do. `TRUE` is 1, empty is 0
+    /// and text is parsed, e.g. `"12.5"` or `"50%"`.
+    pub fn as_number(&self) -> Result<f64, ErrorValue> {
+        match self {
+            CellValue::Empty => Ok(0.0),
+            CellValue::Number(n) => Ok(*n),
+            CellValue::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            CellValue::Text(s) => parse_number(s).ok_or(ErrorValue::Value),
+            CellValue::Error(e) => Err(*e),
+        }
+    }
+
+    /// Coerce to text the way `&` does.
+    pub fn as_text(&self) -> Result<String, ErrorValue> {
+        match self {
+            CellValue::Empty => Ok(String::new()),
+            CellValue::Number(n) => Ok(number_to_text(*n)),
+            CellValue::Bool(true) => Ok("TRUE".to_owned()),
+            CellValue::Bool(false) => Ok("FALSE".to_owned()),
+            CellValue::Text(s) => Ok(s.clone()),
+            CellValue::Error(e) => Err(*e),
+        }
+    }
+
+    /// Coerce to a boolean the way `IF` reads its condition.
+    pub fn as_bool(&self) -> Result<bool, ErrorValue> {
+        match self {
+            CellValue::Empty => Ok(false),
+            CellValue::Number(n) => Ok(*n != 0.0),
+            CellValue::Bool(b) => Ok(*b),
+            CellValue::Text(s) if s.eq_ignore_ascii_case("TRUE") => Ok(true),
+            CellValue::Text(s) if s.eq_ignore_ascii_case("FALSE") => Ok(false),
+            CellValue::Text(_) => Err(ErrorValue::Value),
+            CellValue::Error(e) => Err(*e),
+        }
+    }
+
+    /// Convert to a number, string, boolean, error string such as `"#DIV/0!"`,
+    /// or `null` when empty.
+    pub fn to_js(&self) -> JsValue {
+        match self {
+            CellValue::Empty => JsValue::NULL,
+            CellValue::Number(n) => JsValue::from_f64(*n),
+            CellValue::Text(s) => JsValue::from_str(s),
+            CellValue::Bool(b) => JsValue::from_bool(*b),
+            CellValue::Error(e) => JsValue::from_str(e.as_str()),
+        }
+    }
+
+    /// Read a value given by JavaScript, the reverse of `to_js`.
+    /// Error strings such as `"#N/A"` become errors, anything else that is not
+    /// a number, string or boolean is empty.
+    pub fn from_js(value: &JsValue) -> CellValue {
+        if let Some(n) = value.as_f64() {
+            CellValue::from(Ok(n))
+        } else if let Some(b) = value.as_bool() {
+            CellValue::Bool(b)
+        } else if let Some(s) = value.as_string() {
+            match ErrorValue::ALL.into_iter().find(|e| e.as_str() == s) {
+                Some(e) => CellValue::Error(e),
+                None => CellValue::Text(s),
+            }
+        } else {
+            CellValue::Empty
+        }
+    }
+}
+
+impl From<Result<f64, ErrorValue>> for CellValue {
+    fn from(result: Result<f64, ErrorValue>) -> Self {
+        match result {
+            Ok(n) if n.is_finite() => CellValue::Number(n),
+            Ok(_) => CellValue::Error(ErrorValue::Num),
+            Err(e) => CellValue::Error(e),
+        }
+    }
+}
+
+/// Current date and time as an Excel serial number, days since 1899-12-30 in UTC.
+pub fn current_serial() -> f64 {
+    // Days between 1899-12-30 and the Unix epoch
+    const UNIX_EPOCH_SERIAL: f64 = 25569.0;
+    const MILLISECONDS_PER_DAY: f64 = 86_400_000.0;
+
+    #[cfg(target_arch = "wasm32")]
+    let millis = js_sys::Date::now();
+    #[cfg(not(target_arch = "wasm32"))]
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_millis() as f64);
+
+    UNIX_EPOCH_SERIAL + millis / MILLISECONDS_PER_DAY
+}
+
+/// Parse text into a number, accepting a trailing `%`.
+pub fn parse_number(s: &str) -> Option<f64> {
+    let trimmed = s.trim();
+    let (digits, scale) = match trimmed.strip_suffix('%') {
+        Some(rest) => (rest.trim_end(), 0.01),
+        None => (trimmed, 1.0),
+    };
+
+    if !digits.chars().any(|c| c.is_ascii_digit())
+        || !digits
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'))
+    {
+        return None;
+    }
+    digits.parse::<f64>().ok().map(|n| n * scale)
+}
+
+/// Render a number the way Excel converts it to text, with up to 15 significant digits.
+pub fn number_to_text(n: f64) -> String {
+    if n == 0.0 {
+        return "0".to_owned();
+    }
+
+    let magnitude = n.abs();
+    if !(1e-9..1e21).contains(&magnitude) {
+        // Scientific notation, e.g. 1.5E+21
+        let formatted = format!("{:.14E}", n);
+        let (mantissa, exponent) = formatted.split_once('E').unwrap();
+        let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+        let exponent: i32 = exponent.parse().unwrap();
+        let sign = if exponent < 0 { '-' } else { '+' };
+        return format!("{}E{}{:02}", mantissa, sign, exponent.abs());
+    }
+
+    let digits = 15 - (magnitude.log10().floor() as i32 + 1);
+    let formatted = format!("{:.*}", digits.max(0) as usize, n);
+    if formatted.contains('.') {
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_owned()
+    } else {
+        formatted
+    }
+}
+
+/// Excel's ordering for comparisons: numbers < text < booleans.
+/// Text is compared case insensitively. Empty compares as 0, `""` or `FALSE`.
+pub fn compare_values(a: &CellValue, b: &CellValue) -> Ordering {
+    fn rank(v: &CellValue) -> u8 {
+        match v {
+            CellValue::Number(_) => 0,
+            CellValue::Text(_) => 1,
+            CellValue::Bool(_) => 2,
+            _ => 3,
+        }
+    }
+
+    let (a, b) = match (a, b) {
+        (CellValue::Empty, CellValue::Empty) => return Ordering::Equal,
+        (CellValue::Empty, other) => (empty_like(other), other.clone()),
+        (other, CellValue::Empty) => (other.clone(), empty_like(other)),
+        (a, b) => (a.clone(), b.clone()),
+    };
+
+    match (&a, &b) {
+        (CellValue::Number(x), CellValue::Number(y)) => x.partial_cmp(y).unwrap_or(Ordering::Equal),
+        (CellValue::Text(x), CellValue::Text(y)) => x.to_lowercase().cmp(&y.to_lowercase()),
+        (CellValue::Bool(x), CellValue::Bool(y)) => x.cmp(y),
+        _ => rank(&a).cmp(&rank(&b)),
+    }
+}
+
+fn empty_like(other: &CellValue) -> CellValue {
+    match other {
+        CellValue::Text(_) => CellValue::Text(String::new()),
+        CellValue::Bool(_) => CellValue::Bool(false),
+        _ => CellValue::Number(0.0),
+    }
+}
+
+/// Function value created by `LAMBDA`, holding the variables visible where it was defined.
+#[derive(Clone, Debug)]
+pub struct Lambda {
+    pub params: Vec<String>,
+    pub body: Expr,
+    scope: Scope,
+}
+
+/// Result of evaluating an expression.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Scalar(CellValue),
+    /// Row by row grid of values, e.g. the content of a range or the result of `SEQUENCE`.
+    Array(Vec<Vec<CellValue>>),
+    Lambda(Rc<Lambda>),
+}
+
+impl Value {
+    pub fn error(e: ErrorValue) -> Value {
+        Value::Scalar(CellValue::Error(e))
+    }
+
+    fn number_result(result: Result<f64, ErrorValue>) -> Value {
+        Value::Scalar(CellValue::from(result))
+    }
+
+    pub fn number(n: f64) -> Value {
+        Value::Scalar(CellValue::from(Ok(n)))
+    }
+
+    /// Single value of the result. Arrays give their top-left value, lambdas `#CALC!`.
+    pub fn into_scalar(self) -> CellValue {
+        match self {
+            Value::Scalar(v) => v,
+            Value::Array(rows) => rows
+                .into_iter()
+                .next()
+                .and_then(|row| row.into_iter().next())
+                .unwrap_or_default(),
+            Value::Lambda(_) => CellValue::Error(ErrorValue::Calc),
+        }
+    }
+
+    /// Result as a grid. Scalars become a 1x1 grid.
+    pub fn into_array(self) -> Vec<Vec<CellValue>> {
+        match self {
+            Value::Array(rows) => rows,
+            other => vec![vec![other.into_scalar()]],
+        }
+    }
+
+    /// Number of rows and columns of the result. Scalars and lambdas are 1x1.
+    pub fn size(&self) -> (usize, usize) {
+        match self {
+            Value::Array(rows) => (rows.len(), rows.first().map_or(0, Vec::len)),
+            _ => (1, 1),
+        }
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, Value::Array(rows) if rows.len() > 1 || rows.first().map_or(0, Vec::len) > 1)
+    }
+
+    /// Collapse a 1x1 array into a scalar.
+    pub(crate) fn simplify(self) -> Value {
+        match self {
+            Value::Array(ref rows) if rows.len() == 1 && rows[0].len() == 1 => {
+                Value::Scalar(self.into_scalar())
+            }
+            other => other,
+        }
+    }
+}
+
+/// Where the evaluator reads cell values from.
+pub trait CellSource {
+    /// Value of `cell`. Unqualified references are given the evaluator's sheet,
+    /// which is `None` unless set with `Evaluator::on_sheet`.
+    fn value(&self, cell: &Cell) -> CellValue;
+
+    /// Last used row and column of `sheet`, used to clip whole row and column references.
+    /// Without bounds those references are read as empty.
+    fn used_bounds(&self, _sheet: Option<&str>) -> Option<(u32, u32)> {
+        None
+    }
+
+    /// Area currently spilled from `anchor`, read by spill references such as `A1#`.
+    /// `None` if `anchor` holds no array result.
+    fn spill_range(&self, _anchor: &Cell) -> Option<Range> {
+        None
+    }
+}
+
+/// Variables bound by `LET` and `LAMBDA`, innermost last.
+#[derive(Clone, Debug, Default)]
+struct Scope {
+    vars: Vec<(String, Value)>,
+    depth: usize,
+}
+
+impl Scope {
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    fn bind(&self, name: &str, value: Value) -> Scope {
+        let mut scope = self.clone();
+        scope.vars.push((name.to_owned(), value));
+        scope
+    }
+}
+
+/// Evaluates parsed formulas against a `CellSource`.
+pub struct Evaluator<'a> {
+    source: &'a dyn CellSource,
+    names: Option<&'a DefinedNames>,
+    sheet: Option<String>,
+    /// State of the random number generator used by `RAND` and `RANDARRAY`
+    seed: std::cell::Cell<u64>,
+    /// Serial date and time returned by `NOW`, the clock is read when `None`
+    now: Option<f64>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(source: &'a dyn CellSource) -> Evaluator<'a> {
+        Evaluator {
+            source,
+            names: None,
+            sheet: None,
+            seed: std::cell::Cell::new(DEFAULT_SEED),
+            now: None,
+        }
+    }
+
+    /// Fix the serial date and time returned by `NOW` and `TODAY`, e.g. `45292.5` for
+    /// noon on 2024-01-01. Without it the clock is read.
+    pub fn with_now(mut self, serial: f64) -> Evaluator<'a> {
+        self.now = Some(serial);
+        self
+    }
+
+    /// Seed the random number generator, so that `RANDARRAY` results can be reproduced.
+    pub fn with_seed(self, seed: u64) -> Evaluator<'a> {
+        // Zero would make xorshift return zero forever
+        self.seed.set(if seed == 0 { DEFAULT_SEED } else { seed });
+        self
+    }
+
+    /// Resolve defined names and named `LAMBDA`s from `names`.
+    pub fn with_names(mut self, names: &'a DefinedNames) -> Evaluator<'a> {
+        self.names = Some(names);
+        self
+    }
+
+    /// Sheet the formula lives on. Unqualified references are read from it.
+    pub fn on_sheet(mut self, sheet: Option<String>) -> Evaluator<'a> {
+        self.sheet = sheet;
+        self
+    }
+
+    pub fn evaluate(&self, expr: &Expr) -> Value {
+        self.eval(expr, &Scope::default()).simplify()
+    }
+
+    pub fn evaluate_formula(&self, formula: &str) -> Result<Value, WebExcelError> {
+        Ok(self.evaluate(&parse_formula(formula)?))
+    }
+
+    fn eval(&self, expr: &Expr, scope: &Scope) -> Value {
+        match expr {
+            Expr::Number(n) => Value::number(*n),
+            Expr::Text(s) => Value::Scalar(CellValue::Text(s.clone())),
+            Expr::Bool(b) => Value::Scalar(CellValue::Bool(*b)),
+            Expr::Error(e) => Value::error(*e),
+            Expr::Missing => Value::Scalar(CellValue::Empty),
+            Expr::Reference(r) => self.fetch(r),
+            Expr::Name(name) => self.eval_name(name, scope),
+            Expr::Array(rows) => Value::Array(
+                rows.iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|e| self.eval(e, scope).into_scalar())
+                            .collect()
+                    })
+                    .collect(),
+            ),
+            Expr::Unary(op, e) => {
+                let op = *op;
+                map_value(self.eval(e, scope), &|v| {
+                    let n = v.as_number();
+                    CellValue::from(match op {
+                        UnaryOp::Plus => return v.clone(),
+                        UnaryOp::Minus => n.map(|n| -n),
+                        UnaryOp::Percent => n.map(|n| n / 100.0),
+                    })
+                })
+            }
+            Expr::Binary(op, l, r) => {
+                let op = *op;
+                let l = self.eval(l, scope);
+                let r = self.eval(r, scope);
+                broadcast(l, r, &|a, b| binary(op, a, b))
+            }
+            Expr::Function(name, args) => self.call_function(name, args, scope),
+            Expr::Call(callee, args) => {
+                let callee = self.eval(callee, scope);
+                let args: Vec<Value> = args.iter().map(|a| self.eval(a, scope)).collect();
+                self.invoke(&callee, args, scope)
+            }
+        }
+    }
+
+    /// Next random number in `[0, 1)`, from a xorshift64* generator.
+    fn random(&self) -> f64 {
+        let mut x = self.seed.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.seed.set(x);
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn eval_name(&self, name: &str, scope: &Scope) -> Value {
+        if let Some(value) = scope.lookup(name) {
+            return value.clone();
+        }
+
+        match self.names.and_then(|names| names.lookup(name)) {
+            Some(formula) if scope.depth < MAX_CALL_DEPTH => {
+                let inner = Scope {
+                    vars: vec![],
+                    depth: scope.depth + 1,
+                };
+                self.eval(formula.expr(), &inner)
+            }
+            Some(_) => Value::error(ErrorValue::Num),
+            None => Value::error(ErrorValue::Name),
+        }
+    }
+
+    /// Read the values covered by a reference, always as a grid.
+    fn fetch(&self, reference: &Reference) -> Value {
+        if reference.workbook.is_some() {
+            // External workbooks are not available to the evaluator
+            return Value::error(ErrorValue::Ref);
+        }
+
+        let sheet = reference.start.sheet.clone().or_else(|| self.sheet.clone());
+
+        if reference.spill {
+            let anchor = Cell {
+                sheet: sheet.clone(),
+                ..reference.start.clone()
+            };
+            return match self.source.spill_range(&anchor) {
+                Some(range) => self.fetch(&Reference {
+                    workbook: None,
+                    start: range.cell_start,
+                    end: Some(range.cell_end),
+                    spill: false,
+                }),
+                None => Value::error(ErrorValue::Ref),
+            };
+        }
+
+        let start = &reference.start;
+        let end = reference.end.as_ref().unwrap_or(start);
+
+        // Only the part up to the used bounds is read of a reference running to the edge
+        // of the sheet, e.g. `A:A` or `A2:A1048576`, or of one too large to hold, e.g.
+        // `B1:Z1000000`. Without bounds the sheet is empty.
+        let size = |last_row: u32, last_column: u32| {
+            (last_row.saturating_sub(start.row) as u64 + 1)
+                * (last_column.saturating_sub(start.column) as u64 + 1)
+        };
+        let too_large = size(end.row, end.column) > MAX_ARRAY_CELLS as u64;
+        let (mut last_row, mut last_column) = (end.row, end.column);
+        if too_large || end.row == MAX_ROW || end.column == MAX_COLUMN {
+            let (row, column) = self
+                .source
+                .used_bounds(sheet.as_deref())
+                .unwrap_or((start.row, start.column));
+            if too_large || end.row == MAX_ROW {
+                last_row = row.clamp(start.row, end.row);
+            }
+            if too_large || end.column == MAX_COLUMN {
+                last_column = column.clamp(start.column, end.column);
+            }
+        }
+        if size(last_row, last_column) > MAX_ARRAY_CELLS as u64 {
+            return Value::error(ErrorValue::Num);
+        }
+
+        let rows = (start.row..=last_row)
+            .map(|row| {
+                (start.column..=last_column)
+                    .map(|column| {
+                        self.source.value(&Cell {
+                            row,
+                            column,
+                            sheet: sheet.clone(),
+                            ..Default::default()
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Value::Array(rows)
+    }
+
+    /// Apply a function value to already evaluated arguments.
+    fn invoke(&self, callee: &Value, args: Vec<Value>, scope: &Scope) -> Value {
+        let lambda = match callee {
+            Value::Lambda(lambda) => lambda,
+            Value::Scalar(CellValue::Error(e)) => return Value::error(*e),
+            _ => return Value::error(ErrorValue::Value),
+        };
+
+        if lambda.params.len() != args.len() {
+            return Value::error(ErrorValue::Value);
+        }
+        if scope.depth >= MAX_CALL_DEPTH {
+            return Value::error(ErrorValue::Num);
+        }
+
+        let mut inner = lambda.scope.clone();
+        inner.depth = scope.depth + 1;
+        for (param, arg) in lambda.params.iter().zip(args) {
+            inner = inner.bind(param, arg);
+        }
+
+        self.eval(&lambda.body, &inner)
+    }
+
+    fn call_function(&self, name: &str, args: &[Expr], scope: &Scope) -> Value {
+        let name = name
+            .trim_start_matches("_XLFN.")
+            .trim_start_matches("_XLWS.");
+
+        match name {
+            "LET" => self.eval_let(args, scope),
+            "LAMBDA" => self.eval_lambda(args, scope),
+            "MAP" => self.eval_map(args, scope),
+            "REDUCE" => self.eval_reduce(args, scope, false),
+            "SCAN" => self.eval_reduce(args, scope, true),
+            "BYROW" => self.eval_by(args, scope, true),
+            "BYCOL" => self.eval_by(args, scope, false),
+            "NOW" | "TODAY" | "RAND" if !args.is_empty() => Value::error(ErrorValue::Value),
+            "NOW" => Value::number(self.now.unwrap_or_else(current_serial)),
+            "TODAY" => Value::number(self.now.unwrap_or_else(current_serial).floor()),
+            "RAND" => Value::number(self.random()),
+            "RANDBETWEEN" => self.eval_randbetween(args, scope),
+            "OFFSET" => self.eval_offset(args, scope),
+            "INDIRECT" => self.eval_indirect(args, scope),
+            "ROWS" | "COLUMNS" if matches!(args, [Expr::Reference(r)] if !r.spill) => {
+                // The size of the reference itself, which `fetch` may have trimmed
+                let Expr::Reference(reference) = &args[0] else {
+                    unreachable!()
+                };
+                let start = &reference.start;
+                let end = reference.end.as_ref().unwrap_or(start);
+                let size = if name == "ROWS" {
+                    end.row - start.row + 1
+                } else {
+                    end.column - start.column + 1
+                };
+                Value::number(size as f64)
+            }
+            "IF" => self.eval_if(args, scope),
+            "IFS" => self.eval_ifs(args, scope),
+            "SWITCH" => self.eval_switch(args, scope),
+            "IFERROR" | "IFNA" => {
+                if args.len() != 2 {
+                    return Value::error(ErrorValue::Value);
+                }
+                let value = self.eval(&args[0], scope);
+                let fallback = || self.eval(&args[1], scope);
+                match (name, &value) {
+                    ("IFNA", Value::Scalar(CellValue::Error(ErrorValue::NA))) => fallback(),
+                    ("IFERROR", Value::Scalar(CellValue::Error(_))) => fallback(),
+                    _ => value,
+                }
+            }
+            _ => {
+                let values: Vec<Value> = args.iter().map(|a| self.eval(a, scope)).collect();
+                let arity = builtin_arity(name)
+                    .or_else(|| array_arity(name))
+                    .or_else(|| criteria_arity(name));
+                match arity {
+                    Some((min, max)) if values.len() < min || values.len() > max => {
+                        Value::error(ErrorValue::Value)
+                    }
+                    Some(_) if builtin_arity(name).is_some() => builtin(name, values),
+                    Some(_) if array_arity(name).is_some() => {
+                        array_function(name, values, &|| self.random())
+                    }
+                    Some(_) => criteria_function(name, values),
+                    // Not a built-in: try a `LET` bound or defined `LAMBDA`
+                    None => match self.eval_name(name, scope) {
+                        callee @ Value::Lambda(_) => self.invoke(&callee, values, scope),
+                        Value::Scalar(CellValue::Error(ErrorValue::Num)) => {
+                            Value::error(ErrorValue::Num)
+                        }
+                        _ => Value::error(ErrorValue::Name),
+                    },
+                }
+            }
+        }
+    }
+
+    /// `LET(name1, value1, [name2, value2, ...], calculation)`
+    fn eval_let(&self, args: &[Expr], scope: &Scope) -> Value {
+        if args.len() < 3 || args.len().is_multiple_of(2) {
+            return Value::error(ErrorValue::Value);
+        }
+
+        let mut inner = scope.clone();
+        for pair in args[..args.len() - 1].chunks(2) {
+            let name = match &pair[0] {
+                Expr::Name(name) => name,
+                _ => return Value::error(ErrorValue::Value),
+            };
+            let value = self.eval(&pair[1], &inner);
+            inner = inner.bind(name, value);
+        }
+
+        self.eval(&args[args.len() - 1], &inner)
+    }
+
+    /// `LAMBDA([param1, ...], calculation)`
+    fn eval_lambda(&self, args: &[Expr], scope: &Scope) -> Value {
+        let (body, params) = match args.split_last() {
+            Some(split) => split,
+            None => return Value::error(ErrorValue::Value),
+        };
+
+        let mut names = Vec::with_capacity(params.len());
+        for param in params {
+            match param {
+                Expr::Name(name) => names.push(name.clone()),
+                _ => return Value::error(ErrorValue::Value),
+            }
+        }
+
+        Value::Lambda(Rc::new(Lambda {
+            params: names,
+            body: body.clone(),
+            scope: scope.clone(),
+        }))
+    }
+
+    /// Evaluate all arguments and split off the trailing `LAMBDA`.
+    fn with_lambda(&self, args: &[Expr], scope: &Scope) -> Result<(Vec<Value>, Value), Value> {
+        let mut values: Vec<Value> = args.iter().map(|a| self.eval(a, scope)).collect();
+        match values.pop() {
+            Some(lambda @ Value::Lambda(_)) => Ok((values, lambda)),
+            Some(Value::Scalar(CellValue::Error(e))) => Err(Value::error(e)),
+            _ => Err(Value::error(ErrorValue::Value)),
+        }
+    }
+
+    /// Call `lambda` and require a single value back, as MAP, SCAN and BYROW do.
+    fn invoke_scalar(&self, lambda: &Value, args: Vec<Value>, scope: &Scope) -> CellValue {
+        match self.invoke(lambda, args, scope).simplify() {
+            Value::Scalar(v) => v,
+            _ => CellValue::Error(ErrorValue::Calc),
+        }
+    }
+
+    /// `MAP(array1, [array2, ...], lambda)`
+    fn eval_map(&self, args: &[Expr], scope: &Scope) -> Value {
+        let (arrays, lambda) = match self.with_lambda(args, scope) {
+            Ok(split) => split,
+            Err(e) => return e,
+        };
+        if arrays.is_empty() {
+            return Value::error(ErrorValue::Value);
+        }
+
+        let arrays: Vec<Vec<Vec<CellValue>>> = arrays.into_iter().map(Value::into_array).collect();
+        let (rows, columns) = (arrays[0].len(), arrays[0][0].len());
+        if arrays
+            .iter()
+            .any(|a| a.len() != rows || a[0].len() != columns)
+        {
+            return Value::error(ErrorValue::Value);
+        }
+
+        let result = (0..rows)
+            .map(|r| {
+                (0..columns)
+                    .map(|c| {
+                        let args = arrays
+                            .iter()
+                            .map(|a| Value::Scalar(a[r][c].clone()))
+                            .collect();
+                        self.invoke_scalar(&lambda, args, scope)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Value::Array(result)
+    }
+
+    /// `REDUCE(initial, array, lambda)` and `SCAN(initial, array, lambda)`.
+    /// SCAN keeps every intermediate accumulator in an array shaped like `array`.
+    fn eval_reduce(&self, args: &[Expr], scope: &Scope, keep: bool) -> Value {
+        let (values, lambda) = match self.with_lambda(args, scope) {
+            Ok(split) => split,
+            Err(e) => return e,
+        };
+        let (initial, array) = match <[Value; 2]>::try_from(values) {
+            Ok([initial, array]) => (initial, array.into_array()),
+            Err(_) => return Value::error(ErrorValue::Value),
+        };
+
+        let mut accumulator = initial;
+        let mut steps = Vec::with_capacity(array.len());
+        for row in array {
+            let mut step_row = Vec::with_capacity(row.len());
+            for value in row {
+                accumulator = self.invoke(&lambda, vec![accumulator, Value::Scalar(value)], scope);
+                if keep {
+                    step_row.push(accumulator.clone().simplify().into_scalar());
+                }
+            }
+            steps.push(step_row);
+        }
+
+        if keep {
+            Value::Array(steps)
+        } else {
+            accumulator
+        }
+    }
+
+    /// `BYROW(array, lambda)` gives one value per row, `BYCOL(array, lambda)` one per column.
+    fn eval_by(&self, args: &[Expr], scope: &Scope, by_row: bool) -> Value {
+        let (values, lambda) = match self.with_lambda(args, scope) {
+            Ok(split) => split,
+            Err(e) => return e,
+        };
+        let array = match <[Value; 1]>::try_from(values) {
+            Ok([array]) => array.into_array(),
+            Err(_) => return Value::error(ErrorValue::Value),
+        };
+
+        if by_row {
+            let result = array
+                .into_iter()
+                .map(|row| vec![self.invoke_scalar(&lambda, vec![Value::Array(vec![row])], scope)])
+                .collect();
+            Value::Array(result)
+        } else {
+            let result = (0..array[0].len())
+                .map(|c| {
+                    let column = array.iter().map(|row| vec![row[c].clone()]).collect();
+                    self.invoke_scalar(&lambda, vec![Value::Array(column)], scope)
+                })
+                .collect();
+            Value::Array(vec![result])
+        }
+    }
+
+    /// `IF(condition, then, [otherwise])`, evaluating only the chosen branch.
+    fn eval_if(&self, args: &[Expr], scope: &Scope) -> Value {
+        if args.len() < 2 || args.len() > 3 {
+            return Value::error(ErrorValue::Value);
+        }
+
+        match self.eval(&args[0], scope).into_scalar().as_bool() {
+            Ok(true) => self.eval(&args[1], scope),
+            Ok(false) => match args.get(2) {
+                Some(otherwise) => self.eval(otherwise, scope),
+                None => Value::Scalar(CellValue::Bool(false)),
+            },
+            Err(e) => Value::error(e),
+        }
+    }
+
+    /// `RANDBETWEEN(bottom, top)`
+    fn eval_randbetween(&self, args: &[Expr], scope: &Scope) -> Value {
+        if args.len() != 2 {
+            return Value::error(ErrorValue::Value);
+        }
+        let bottom = self.eval(&args[0], scope).into_scalar().as_number();
+        let top = self.eval(&args[1], scope).into_scalar().as_number();
+
+        Value::number_result(bottom.and_then(|bottom| {
+            let (bottom, top) = (bottom.ceil(), top?.floor());
+            if bottom > top {
+                return Err(ErrorValue::Num);
+            }
+            Ok((bottom + self.random() * (top - bottom + 1.0)).floor())
+        }))
+    }
+
+    /// `OFFSET(reference, rows, columns, [height], [width])`.
+    /// `reference` must be written as a reference, the moved area is then read.
+    fn eval_offset(&self, args: &[Expr], scope: &Scope) -> Value {
+        if !(3..=5).contains(&args.len()) {
+            return Value::error(ErrorValue::Value);
+        }
+        let reference = match &args[0] {
+            Expr::Reference(reference) if !reference.spill => reference,
+            _ => return Value::error(ErrorValue::Value),
+        };
+        let start = &reference.start;
+        let end = reference.end.as_ref().unwrap_or(start);
+
+        let number = |i: usize, default: u32| match args.get(i) {
+            None | Some(Expr::Missing) => Ok(default as f64),
+            Some(e) => self
+                .eval(e, scope)
+                .into_scalar()
+                .as_number()
+                .map(f64::trunc),
+        };
+        let moved = (|| {
+            let rows = number(1, 0)?;
+            let columns = number(2, 0)?;
+            let height = number(3, end.row - start.row + 1)?;
+            let width = number(4, end.column - start.column + 1)?;
+            Ok::<_, ErrorValue>((rows, columns, height, width))
+        })();
+        let (rows, columns, height, width) = match moved {
+            Ok(moved) => moved,
+            Err(e) => return Value::error(e),
+        };
+
+        let top = start.row as f64 + rows;
+        let left = start.column as f64 + columns;
+        let bottom = top + height - 1.0;
+        let right = left + width - 1.0;
+        if height < 1.0
+            || width < 1.0
+            || top < 0.0
+            || left < 0.0
+            || bottom > MAX_ROW as f64
+            || right > MAX_COLUMN as f64
+        {
+            return Value::error(ErrorValue::Ref);
+        }
+
+        let at = |row: f64, column: f64| Cell {
+            row: row as u32,
+            column: column as u32,
+            sheet: start.sheet.clone(),
+            ..Default::default()
+        };
+        self.fetch(&Reference {
+            workbook: reference.workbook.clone(),
+            start: at(top, left),
+            end: Some(at(bottom, right)),
+            spill: false,
+        })
+    }
+
+    /// `INDIRECT(ref_text, [a1])`. Only `A1` style text is supported.
+    fn eval_indirect(&self, args: &[Expr], scope: &Scope) -> Value {
+        if args.is_empty() || args.len() > 2 {
+            return Value::error(ErrorValue::Value);
+        }
+        let text = match self.eval(&args[0], scope).into_scalar().as_text() {
+            Ok(text) => text,
+            Err(e) => return Value::error(e),
+        };
+        if let Some(style) = args.get(1) {
+            if self.eval(style, scope).into_scalar().as_bool() == Ok(false) {
+                return Value::error(ErrorValue::Ref);
+            }
+        }
+
+        match parse_formula(&text) {
+            Ok(Expr::Reference(reference)) => self.fetch(&reference),
+            _ => Value::error(ErrorValue::Ref),
+        }
+    }
+
+    /// `IFS(condition1, value1, [condition2, value2, ...])`
+    fn eval_ifs(&self, args: &[Expr], scope: &Scope) -> Value {
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Value::error(ErrorValue::Value);
+        }
+
+        for pair in args.chunks(2) {
+            match self.eval(&pair[0], scope).into_scalar().as_bool() {
+                Ok(true) => return self.eval(&pair[1], scope),
+                Ok(false) => continue,
+                Err(e) => return Value::error(e),
+            }
+        }
+
+        Value::error(ErrorValue::NA)
+    }
+
+    /// `SWITCH(expression, value1, result1, [value2, result2, ...], [default])`
+    fn eval_switch(&self, args: &[Expr], scope: &Scope) -> Value {
+        if args.len() < 3 {
+            return Value::error(ErrorValue::Value);
+        }
+
+        let target = self.eval(&args[0], scope).into_scalar();
+        if let CellValue::Error(e) = target {
+            return Value::error(e);
+        }
+
+        let cases = &args[1..];
+        for pair in cases.chunks(2) {
+            if pair.len() == 1 {
+                return self.eval(&pair[0], scope);
+            }
+            let value = self.eval(&pair[0], scope).into_scalar();
+            if compare_values(&value, &target) == Ordering::Equal {
+                return self.eval(&pair[1], scope);
+            }
+        }
+
+        Value::error(ErrorValue::NA)
+    }
+}
+
+/// Apply `f` to every value of a scalar or array.
+pub(crate) fn map_value(value: Value, f: &dyn Fn(&CellValue) -> CellValue) -> Value {
+    match value {
+        Value::Scalar(v) => Value::Scalar(f(&v)),
+        Value::Array(rows) => {
+            Value::Array(rows.iter().map(|row| row.iter().map(f).collect()).collect())
+        }
+        Value::Lambda(_) => Value::error(ErrorValue::Calc),
+    }
+}
+
+/// Combine two operands element by element. Single values, single rows and single columns are
+/// stretched to the other operand's size; cells outside a smaller array become `#N/A`.
+pub(crate) fn broadcast(
+    l: Value,
+    r: Value,
+    f: &dyn Fn(&CellValue, &CellValue) -> CellValue,
+) -> Value {
+    let (l, r) = (l.simplify(), r.simplify());
+    match (l, r) {
+        (Value::Lambda(_), _) | (_, Value::Lambda(_)) => Value::error(ErrorValue::Calc),
+        (Value::Scalar(a), Value::Scalar(b)) => Value::Scalar(f(&a, &b)),
+        (l, r) => {
+            let (a, b) = (l.into_array(), r.into_array());
+            let rows = a.len().max(b.len());
+            let columns = a[0].len().max(b[0].len());
+
+            let pick = |grid: &Vec<Vec<CellValue>>, row: usize, column: usize| {
+                let r = if grid.len() == 1 { 0 } else { row };
+                let c = if grid[0].len() == 1 { 0 } else { column };
+                grid.get(r)
+                    .and_then(|line| line.get(c))
+                    .cloned()
+                    .unwrap_or(CellValue::Error(ErrorValue::NA))
+            };
+
+            let result = (0..rows)
+                .map(|row| {
+                    (0..columns)
+                        .map(|column| {
+                            let (x, y) = (pick(&a, row, column), pick(&b, row, column));
+                            match (&x, &y) {
+                                (CellValue::Error(e), _) | (_, CellValue::Error(e)) => {
+                                    CellValue::Error(*e)
+                                }
+                                _ => f(&x, &y),
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            Value::Array(result)
+        }
+    }
+}
+
+fn binary(op: BinaryOp, a: &CellValue, b: &CellValue) -> CellValue {
+    if let CellValue::Error(e) = a {
+        return CellValue::Error(*e);
+    }
+    if let CellValue::Error(e) = b {
+        return CellValue::Error(*e);
+    }
+
+    let arithmetic = |f: &dyn Fn(f64, f64) -> Result<f64, ErrorValue>| {
+        CellValue::from(
+            a.as_number()
+                .and_then(|x| b.as_number().and_then(|y| f(x, y))),
+        )
+    };
+    let comparison = |f: &dyn Fn(Ordering) -> bool| CellValue::Bool(f(compare_values(a, b)));
+
+    match op {
+        BinaryOp::Add => arithmetic(&|x, y| Ok(x + y)),
+        BinaryOp::Sub => arithmetic(&|x, y| Ok(x - y)),
+        BinaryOp::Mul => arithmetic(&|x, y| Ok(x * y)),
+        BinaryOp::Div => arithmetic(&|x, y| {
+            if y == 0.0 {
+                Err(ErrorValue::Div0)
+            } else {
+                Ok(x / y)
+            }
+        }),
+        BinaryOp::Pow => arithmetic(&|x, y| {
+            if x == 0.0 && y == 0.0 {
+                Err(ErrorValue::Num)
+            } else if x == 0.0 && y < 0.0 {
+                Err(ErrorValue::Div0)
+            } else {
+                Ok(x.powf(y))
+            }
+        }),
+        BinaryOp::Concat => match (a.as_text(), b.as_text()) {
+            (Ok(x), Ok(y)) => CellValue::Text(x + &y),
+            (Err(e), _) | (_, Err(e)) => CellValue::Error(e),
+        },
+        BinaryOp::Eq => comparison(&|o| o == Ordering::Equal),
+        BinaryOp::Ne => comparison(&|o| o != Ordering::Equal),
+        BinaryOp::Lt => comparison(&|o| o == Ordering::Less),
+        BinaryOp::Le => comparison(&|o| o != Ordering::Greater),
+        BinaryOp::Gt => comparison(&|o| o == Ordering::Greater),
+        BinaryOp::Ge => comparison(&|o| o != Ordering::Less),
+    }
+}
+
+/// Numbers of the arguments the way SUM reads them: direct values are coerced,
+/// text and booleans inside ranges and arrays are skipped, errors are returned.
+pub(crate) fn collect_numbers(values: &[Value]) -> Result<Vec<f64>, ErrorValue> {
+    let mut numbers = vec![];
+    for value in values {
+        match value {
+            Value::Scalar(CellValue::Empty) => {}
+            Value::Scalar(v) => numbers.push(v.as_number()?),
+            Value::Array(rows) => {
+                for v in rows.iter().flatten() {
+                    match v {
+                        CellValue::Number(n) => numbers.push(*n),
+                        CellValue::Error(e) => return Err(*e),
+                        _ => {}
+                    }
+                }
+            }
+            Value::Lambda(_) => return Err(ErrorValue::Calc),
+        }
+    }
+    Ok(numbers)
+}
+
+/// Every value of the arguments, flattened row by row.
+pub(crate) fn flatten(values: &[Value]) -> Vec<CellValue> {
+    values
+        .iter()
+        .flat_map(|v| v.clone().into_array().into_iter().flatten())
+        .collect()
+}
+
+fn scalar_args(values: Vec<Value>) -> Vec<CellValue> {
+    values.into_iter().map(Value::into_scalar).collect()
+}
+
+/// Sum of the numbers, `0` rather than `-0` when there are none.
+pub(crate) fn total(numbers: &[f64]) -> f64 {
+    numbers.iter().fold(0.0, |sum, n| sum + n)
+}
+
+fn round_to(n: f64, digits: f64, mode: fn(f64) -> f64) -> f64 {
+    let factor = 10f64.powi(digits.trunc().clamp(-308.0, 308.0) as i32);
+    let scaled = n * factor;
+    if !scaled.is_finite() {
+        return n;
+    }
+    // Drop the binary representation error first, so `1.005 * 100` rounds as `100.5`
+    let scaled: f64 = format!("{:.14e}", scaled).parse().unwrap_or(scaled);
+    mode(scaled) / factor
+}
+
+/// Number of arguments accepted by a built-in function, `None` if `name` is not built in.
+pub(crate) fn builtin_arity(name: &str) -> Option<(usize, usize)> {
+    let arity = match name {
+        "SUM" | "PRODUCT" | "MIN" | "MAX" | "AVERAGE" | "COUNT" | "COUNTA" | "COUNTBLANK" => {
+            (1, 255)
+        }
+        "AND" | "OR" | "XOR" | "CONCAT" | "CONCATENATE" => (1, 255),
+        "TRUE" | "FALSE" => (0, 0),
+        "NOT" | "ABS" | "INT" | "SQRT" | "LEN" | "UPPER" | "LOWER" | "TRIM" => (1, 1),
+        "ISERROR" | "ISNA" | "ISBLANK" | "ISNUMBER" | "ISTEXT" | "ISLOGICAL" => (1, 1),
+        "ROWS" | "COLUMNS" => (1, 1),
+        "ROUND" | "ROUNDUP" | "ROUNDDOWN" | "MOD" | "POWER" => (2, 2),
+        "LEFT" | "RIGHT" => (1, 2),
+        "TEXT" => (2, 2),
+        "MID" => (3, 3),
+        _ => return None,
+    };
+    Some(arity)
+}
+
+/// Built-in functions that take evaluated arguments.
+/// The argument count has already been checked against `builtin_arity`.
+fn builtin(name: &str, values: Vec<Value>) -> Value {
+    let first = || values[0].clone();
+    match name {
+        "SUM" => Value::number_result(collect_numbers(&values).map(|n| total(&n))),
+        "PRODUCT" => Value::number_result(collect_numbers(&values).map(|n| n.iter().product())),
+        "MIN" => Value::number_result(
+            collect_numbers(&values).map(|n| n.into_iter().reduce(f64::min).unwrap_or(0.0)),
+        ),
+        "MAX" => Value::number_result(
+            collect_numbers(&values).map(|n| n.into_iter().reduce(f64::max).unwrap_or(0.0)),
+        ),
+        "AVERAGE" => Value::number_result(collect_numbers(&values).and_then(|n| {
+            if n.is_empty() {
+                Err(ErrorValue::Div0)
+            } else {
+                Ok(total(&n) / n.len() as f64)
+            }
+        })),
+        "COUNT" => Value::number(
+            flatten(&values)
+                .iter()
+                .filter(|v| matches!(v, CellValue::Number(_)))
+                .count() as f64,
+        ),
+        "COUNTA" => Value::number(flatten(&values).iter().filter(|v| !v.is_empty()).count() as f64),
+        "COUNTBLANK" => Value::number(
+            flatten(&values)
+                .iter()
+                .filter(|v| v.is_empty() || **v == CellValue::Text(String::new()))
+                .count() as f64,
+        ),
+        "AND" | "OR" | "XOR" => {
+            let mut flags = vec![];
+            for v in flatten(&values) {
+                match v {
+                    CellValue::Empty | CellValue::Text(_) => {}
+                    other => match other.as_bool() {
+                        Ok(b) => flags.push(b),
+                        Err(e) => return Value::error(e),
+                    },
+                }
+            }
+            if flags.is_empty() {
+                return Value::error(ErrorValue::Value);
+            }
+            let result = match name {
+                "AND" => flags.iter().all(|b| *b),
+                "OR" => flags.iter().any(|b| *b),
+                _ => flags.iter().filter(|b| **b).count() % 2 == 1,
+            };
+            Value::Scalar(CellValue::Bool(result))
+        }
+        "NOT" => map_value(first(), &|v| match v.as_bool() {
+            Ok(b) => CellValue::Bool(!b),
+            Err(e) => CellValue::Error(e),
+        }),
+        "TRUE" => Value::Scalar(CellValue::Bool(true)),
+        "FALSE" => Value::Scalar(CellValue::Bool(false)),
+        "ABS" => map_value(first(), &|v| CellValue::from(v.as_number().map(f64::abs))),
+        "INT" => map_value(first(), &|v| CellValue::from(v.as_number().map(f64::floor))),
+        "SQRT" => map_value(first(), &|v| {
+            CellValue::from(v.as_number().and_then(|n| {
+                if n < 0.0 {
+                    Err(ErrorValue::Num)
+                } else {
+                    Ok(n.sqrt())
+                }
+            }))
+        }),
+        "ROUND" | "ROUNDUP" | "ROUNDDOWN" => {
+            let mode: fn(f64) -> f64 = match name {
+                "ROUND" => f64::round,
+                "ROUNDUP" => |n| if n < 0.0 { n.floor() } else { n.ceil() },
+                _ => f64::trunc,
+            };
+            broadcast(first(), values[1].clone(), &|n, d| {
+                CellValue::from(
+                    n.as_number()
+                        .and_then(|n| d.as_number().map(|d| round_to(n, d, mode))),
+                )
+            })
+        }
+        "MOD" => broadcast(first(), values[1].clone(), &|n, d| {
+            CellValue::from(n.as_number().and_then(|n| {
+                d.as_number().and_then(|d| {
+                    if d == 0.0 {
+                        Err(ErrorValue::Div0)
+                    } else {
+                        Ok(n - d * (n / d).floor())
+                    }
+                })
+            }))
+        }),
+        "POWER" => broadcast(first(), values[1].clone(), &|a, b| {
+            binary(BinaryOp::Pow, a, b)
+        }),
+        "ISERROR" => map_value(first(), &|v| {
+            CellValue::Bool(matches!(v, CellValue::Error(_)))
+        }),
+        "ISNA" => map_value(first(), &|v| {
+            CellValue::Bool(*v == CellValue::Error(ErrorValue::NA))
+        }),
+        "ISBLANK" => map_value(first(), &|v| CellValue::Bool(v.is_empty())),
+        "ISNUMBER" => map_value(first(), &|v| {
+            CellValue::Bool(matches!(v, CellValue::Number(_)))
+        }),
+        "ISTEXT" => map_value(first(), &|v| {
+            CellValue::Bool(matches!(v, CellValue::Text(_)))
+        }),
+        "ISLOGICAL" => map_value(first(), &|v| {
+            CellValue::Bool(matches!(v, CellValue::Bool(_)))
+        }),
+        "CONCAT" | "CONCATENATE" => {
+            let mut text = String::new();
+            for v in flatten(&values) {
+                match v.as_text() {
+                    Ok(s) => text.push_str(&s),
+                    Err(e) => return Value::error(e),
+                }
+            }
+            Value::Scalar(CellValue::Text(text))
+        }
+        "LEN" => map_value(first(), &|v| match v.as_text() {
+            Ok(s) => CellValue::Number(s.chars().count() as f64),
+            Err(e) => CellValue::Error(e),
+        }),
+        "UPPER" | "LOWER" | "TRIM" => {
+            let convert: fn(&str) -> String = match name {
+                "UPPER" => str::to_uppercase,
+                "LOWER" => str::to_lowercase,
+                _ => |s| s.split_whitespace().collect::<Vec<_>>().join(" "),
+            };
+            map_value(first(), &|v| match v.as_text() {
+                Ok(s) => CellValue::Text(convert(&s)),
+                Err(e) => CellValue::Error(e),
+            })
+        }
+        "LEFT" | "RIGHT" | "MID" => {
+            let args = scalar_args(values);
+            let text: Vec<char> = match args[0].as_text() {
+                Ok(s) => s.chars().collect(),
+                Err(e) => return Value::error(e),
+            };
+            let number = |i: usize| match args.get(i) {
+                Some(CellValue::Empty) | None => Ok(1.0),
+                Some(v) => v.as_number(),
+            };
+            let (start, length) = match (name, number(1), number(2)) {
+                (_, Err(e), _) | ("MID", _, Err(e)) => return Value::error(e),
+                ("MID", Ok(s), Ok(l)) if s >= 1.0 && l >= 0.0 => (s as usize - 1, l as usize),
+                ("LEFT", Ok(l), _) if l >= 0.0 => (0, l as usize),
+                ("RIGHT", Ok(l), _) if l >= 0.0 => {
+                    let l = (l as usize).min(text.len());
+                    (text.len() - l, l)
+                }
+                _ => return Value::error(ErrorValue::Value),
+            };
+            Value::Scalar(CellValue::Text(
+                text.iter().skip(start).take(length).collect(),
+            ))
+        }
+        "TEXT" => broadcast(first(), values[1].clone(), &|v, code| match code
+            .as_text()
+            .and_then(|code| format_text(v, &code))
+        {
+            Ok(text) => CellValue::Text(text),
+            Err(e) => CellValue::Error(e),
+        }),
+        "ROWS" | "COLUMNS" => {
+            let array = first().into_array();
+            let size = if name == "ROWS" {
+                array.len()
+            } else {
+                array[0].len()
+            };
+            Value::number(size as f64)
+        }
+        _ => Value::error(ErrorValue::Name),
+    }
+}
@@ -0,0 +1,1080 @@
+use crate::error::WebExcelError;
+use crate::math::eval::{number_to_text, parse_number, CellValue};
+use crate::math::parser::{BinaryOp, ErrorValue};
+use crate::workbook::GENERAL_FORMAT;
+use wasm_bindgen::prelude::*;
+
+/// Sections of a format code allowed by Excel: positive, negative, zero and text.
+pub const MAX_SECTIONS: usize = 4;
+/// Last serial number Excel displays as a date, 9999-12-31.
+pub const MAX_DATE_SERIAL: f64 = 2_958_465.0;
+
+const COLORS: [&str; 8] = [
+    "Black", "Blue", "Cyan", "Green", "Magenta", "Red", "White", "Yellow",
+];
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Digit placeholder: `0` pads with zeros, `?` with spaces, `#` shows nothing.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Placeholder {
+    Zero,
+    Hash,
+    Question,
+}
+
+impl Placeholder {
+    fn pad(&self) -> &'static str {
+        match self {
+            Placeholder::Zero => "0",
+            Placeholder::Hash => "",
+            Placeholder::Question => " ",
+        }
+    }
+}
+
+/// Date and time tokens, with the number of letters written.
+#[derive(Clone, Debug, PartialEq)]
+enum DatePart {
+    Year(usize),
+    Month(usize),
+    Day(usize),
+    Hour(usize),
+    Minute(usize),
+    Second(usize),
+    /// Decimals of a second, e.g. `.00`
+    SubSecond(usize),
+    /// `AM/PM` or `A/P`, as written
+    AmPm(String, String),
+    /// Elapsed time in `h`, `m` or `s`, e.g. `[h]`
+    Elapsed(char, usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Literal(String),
+    Digit(Placeholder),
+    Point,
+    Comma,
+    Percent,
+    /// `E+` or `E-`, true when positive exponents show their sign
+    Exponent(bool),
+    Slash,
+    /// Fixed fraction denominator, e.g. `8` in `# ?/8`
+    Denominator(u32),
+    /// `@`, the text value
+    Text,
+    Date(DatePart),
+    General,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Condition {
+    op: BinaryOp,
+    value: f64,
+}
+
+impl Condition {
+    fn holds(&self, n: f64) -> bool {
+        match self.op {
+            BinaryOp::Eq => n == self.value,
+            BinaryOp::Ne => n != self.value,
+            BinaryOp::Lt => n < self.value,
+            BinaryOp::Le => n <= self.value,
+            BinaryOp::Gt => n > self.value,
+            _ => n >= self.value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Section {
+    tokens: Vec<Token>,
+    color: Option<String>,
+    condition: Option<Condition>,
+}
+
+impl Section {
+    fn is_date(&self) -> bool {
+        self.tokens.iter().any(|t| matches!(t, Token::Date(_)))
+    }
+
+    fn is_text(&self) -> bool {
+        self.tokens.contains(&Token::Text)
+    }
+}
+
+/// Parsed number format code such as `#,##0.00;[Red]-#,##0.00` or `yyyy-mm-dd hh:mm`,
+/// rendering values the way Excel displays them.
+///
+/// Without conditions, one section formats every number, two split positive and zero
+/// from negative numbers and three add a zero section. A fourth section formats text.
+/// Numbers picked by the negative section lose their sign, which the section is expected
+/// to show. Sections with conditions such as `[>=100]` keep the sign, a section without
+/// a condition standing for any other number.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberFormat {
+    code: String,
+    sections: Vec<Section>,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat::general()
+    }
+}
+
+#[wasm_bindgen]
+impl NumberFormat {
+    #[wasm_bindgen(constructor)]
+    pub fn new(code: &str) -> Result<NumberFormat, WebExcelError> {
+        let sections = split_sections(code)?
+            .iter()
+            .map(|section| parse_section(section))
+            .collect::<Result<Vec<Section>, WebExcelError>>()?;
+        if sections.len() > MAX_SECTIONS {
+            return Err(WebExcelError::ParseError);
+        }
+
+        Ok(NumberFormat {
+            code: code.to_owned(),
+            sections,
+        })
+    }
+
+    pub fn general() -> NumberFormat {
+        NumberFormat {
+            code: GENERAL_FORMAT.to_owned(),
+            sections: vec![Section {
+                tokens: vec![Token::General],
+                color: None,
+                condition: None,
+            }],
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> String {
+        self.code.clone()
+    }
+
+    /// Whether numbers are shown as dates or times, judging by the first section.
+    pub fn is_date(&self) -> bool {
+        self.sections.first().is_some_and(Section::is_date)
+    }
+
+    /// Text displayed for a value given by JavaScript. See `NumberFormat::display`.
+    #[wasm_bindgen(js_name = display)]
+    pub fn display_js(&self, value: JsValue) -> String {
+        self.display(&CellValue::from_js(&value))
+    }
+
+    /// Color name of the section picked for a value, e.g. `Red` or `Color10`.
+    #[wasm_bindgen(js_name = color)]
+    pub fn color_js(&self, value: JsValue) -> Option<String> {
+        self.color(&CellValue::from_js(&value))
+    }
+}
+
+impl NumberFormat {
+    /// Render `value` with the format. Booleans show as `TRUE` and `FALSE`, empty as nothing.
+    /// Fails with the error of an error value, and with `#VALUE!` for numbers no section
+    /// takes or dates outside of years 1900 to 9999.
+    pub fn format(&self, value: &CellValue) -> Result<String, ErrorValue> {
+        match value {
+            CellValue::Empty => Ok(String::new()),
+            CellValue::Bool(b) => Ok(if *b { "TRUE" } else { "FALSE" }.to_owned()),
+            CellValue::Error(e) => Err(*e),
+            CellValue::Text(s) => Ok(match self.text_section() {
+                Some(section) => render_text(&section.tokens, s),
+                None => s.clone(),
+            }),
+            CellValue::Number(n) if !n.is_finite() => Err(ErrorValue::Num),
+            CellValue::Number(n) => {
+                let (section, negate) = match self.number_section(*n) {
+                    Some(found) => found,
+                    None if self.numeric_sections().is_empty() => return Ok(number_to_text(*n)),
+                    None => return Err(ErrorValue::Value),
+                };
+                if section.is_date() {
+                    return render_date(&section.tokens, *n);
+                }
+
+                if !(n * percent_scale(&section.tokens)).is_finite() {
+                    return Err(ErrorValue::Num);
+                }
+                let text = render_number(&section.tokens, n.abs());
+                if *n < 0.0 && !negate && !text.is_empty() {
+                    Ok(format!("-{}", text))
+                } else {
+                    Ok(text)
+                }
+            }
+        }
+    }
+
+    /// Text displayed for `value`: errors show as their literal, e.g. `#N/A`,
+    /// and numbers that cannot be shown as `#`, like a too narrow column.
+    pub fn display(&self, value: &CellValue) -> String {
+        match (value, self.format(value)) {
+            (_, Ok(text)) => text,
+            (CellValue::Error(e), Err(_)) => e.as_str().to_owned(),
+            (_, Err(_)) => "#".repeat(8),
+        }
+    }
+
+    /// Color name of the section picked for `value`.
+    pub fn color(&self, value: &CellValue) -> Option<String> {
+        let section = match value {
+            CellValue::Number(n) => self.number_section(*n)?.0,
+            CellValue::Text(_) => self.text_section()?,
+            _ => return None,
+        };
+        section.color.clone()
+    }
+
+    /// Sections formatting numbers, all but a text section.
+    fn numeric_sections(&self) -> &[Section] {
+        match self.text_section() {
+            Some(_) => &self.sections[..self.sections.len() - 1],
+            None => &self.sections,
+        }
+    }
+
+    /// The fourth section, or a last section showing `@`.
+    fn text_section(&self) -> Option<&Section> {
+        let last = self.sections.last()?;
+        if self.sections.len() == MAX_SECTIONS || last.is_text() {
+            Some(last)
+        } else {
+            None
+        }
+    }
+
+    /// Section formatting `n`, and whether it drops the minus sign.
+    fn number_section(&self, n: f64) -> Option<(&Section, bool)> {
+        let sections = self.numeric_sections();
+        if sections.iter().any(|s| s.condition.is_some()) {
+            return sections
+                .iter()
+                .find(|s| s.condition.is_none_or(|c| c.holds(n)))
+                .map(|s| (s, false));
+        }
+
+        match (sections.len(), n) {
+            (0, _) => None,
+            (1, _) => Some((&sections[0], false)),
+            (2, n) if n >= 0.0 => Some((&sections[0], false)),
+            (_, n) if n < 0.0 => Some((&sections[1], true)),
+            (_, n) if n > 0.0 => Some((&sections[0], false)),
+            _ => Some((&sections[2], false)),
+        }
+    }
+}
+
+/// Format `value` with `code` the way the `TEXT` function does: numeric text is taken
+/// as a number and empty as 0.
+pub fn format_text(value: &CellValue, code: &str) -> Result<String, ErrorValue> {
+    let format = NumberFormat::new(code).map_err(|_| ErrorValue::Value)?;
+    let value = match value {
+        CellValue::Empty => CellValue::Number(0.0),
+        CellValue::Text(s) => match parse_number(s) {
+            Some(n) => CellValue::Number(n),
+            None => value.clone(),
+        },
+        other => other.clone(),
+    };
+    format.format(&value)
+}
+
+/// Split a format code on the `;` outside quotes, brackets and escapes.
+fn split_sections(code: &str) -> Result<Vec<Vec<char>>, WebExcelError> {
+    let mut sections = vec![vec![]];
+    let mut chars = code.chars();
+    while let Some(c) = chars.next() {
+        let current = sections.last_mut().unwrap();
+        match c {
+            ';' => {
+                sections.push(vec![]);
+                continue;
+            }
+            '"' | '[' => {
+                let close = if c == '"' { '"' } else { ']' };
+                current.push(c);
+                loop {
+                    match chars.next() {
+                        Some(next) => {
+                            current.push(next);
+                            if next == close {
+                                break;
+                            }
+                        }
+                        None => return Err(WebExcelError::ParseError),
+                    }
+                }
+            }
+            '\\' | '_' | '*' => {
+                current.push(c);
+                current.push(chars.next().ok_or(WebExcelError::ParseError)?);
+            }
+            _ => current.push(c),
+        }
+    }
+    Ok(sections)
+}
+
+fn parse_section(code: &[char]) -> Result<Section, WebExcelError> {
+    let mut section = Section {
+        tokens: vec![],
+        color: None,
+        condition: None,
+    };
+    let rest = |i: usize| code[i..].iter().collect::<String>().to_ascii_lowercase();
+
+    let mut i = 0;
+    while i < code.len() {
+        let c = code[i];
+        let lower = c.to_ascii_lowercase();
+        let token = match c {
+            '"' => {
+                let end = i + 1 + code[i + 1..].iter().position(|&q| q == '"').unwrap();
+                let text = code[i + 1..end].iter().collect();
+                i = end;
+                Token::Literal(text)
+            }
+            '\\' => {
+                i += 1;
+                Token::Literal(code[i].to_string())
+            }
+            // Padding as wide as the next character
+            '_' => {
+                i += 1;
+                Token::Literal(" ".to_owned())
+            }
+            // Repeat the next character to fill the cell, nothing to fill in text
+            '*' => {
+                i += 2;
+                continue;
+            }
+            '[' => {
+                let end = i + code[i..].iter().position(|&b| b == ']').unwrap();
+                let content: String = code[i + 1..end].iter().collect();
+                i = end;
+                match parse_bracket(&content, &mut section)? {
+                    Some(token) => token,
+                    None => {
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+            '0' => Token::Digit(Placeholder::Zero),
+            '#' => Token::Digit(Placeholder::Hash),
+            '?' => Token::Digit(Placeholder::Question),
+            '.' => Token::Point,
+            ',' => Token::Comma,
+            '%' => Token::Percent,
+            '@' => Token::Text,
+            'E' | 'e' if matches!(code.get(i + 1), Some('+') | Some('-')) => {
+                i += 1;
+                Token::Exponent(code[i] == '+')
+            }
+            '/' => {
+                section.tokens.push(Token::Slash);
+                let digits: String = code[i + 1..]
+                    .iter()
+                    .take_while(|d| d.is_ascii_digit())
+                    .collect();
+                match digits.parse::<u32>() {
+                    Ok(denominator) if !digits.starts_with('0') && denominator > 0 => {
+                        i += digits.len();
+                        Token::Denominator(denominator)
+                    }
+                    _ => {
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+            _ if rest(i).starts_with("general") => {
+                i += GENERAL_FORMAT.len();
+                section.tokens.push(Token::General);
+                continue;
+            }
+            _ if rest(i).starts_with("am/pm") => {
+                i += 4;
+                Token::Date(DatePart::AmPm("AM".to_owned(), "PM".to_owned()))
+            }
+            _ if rest(i).starts_with("a/p") => {
+                i += 2;
+                Token::Date(DatePart::AmPm(code[i - 2].to_string(), code[i].to_string()))
+            }
+            _ if matches!(lower, 'y' | 'm' | 'd' | 'h' | 's') => {
+                let count = code[i..]
+                    .iter()
+                    .take_while(|l| l.to_ascii_lowercase() == lower)
+                    .count();
+                i += count - 1;
+                Token::Date(match lower {
+                    'y' => DatePart::Year(if count <= 2 { 2 } else { 4 }),
+                    'm' => DatePart::Month(count.min(5)),
+                    'd' => DatePart::Day(count.min(4)),
+                    'h' => DatePart::Hour(count.min(2)),
+                    _ => DatePart::Second(count.min(2)),
+                })
+            }
+            _ => Token::Literal(c.to_string()),
+        };
+        section.tokens.push(token);
+        i += 1;
+    }
+
+    if section.is_date() {
+        section.tokens = date_tokens(section.tokens);
+    } else {
+        section.tokens = number_tokens(section.tokens);
+    }
+    Ok(section)
+}
+
+/// Read a `[...]` part: a color, a condition, elapsed time or a currency symbol.
+fn parse_bracket(content: &str, section: &mut Section) -> Result<Option<Token>, WebExcelError> {
+    let lower = content.to_ascii_lowercase();
+    if let Some(color) = COLORS.iter().find(|c| c.eq_ignore_ascii_case(content)) {
+        section.color = Some(color.to_string());
+        return Ok(None);
+    }
+    if let Some(index) = lower.strip_prefix("color") {
+        match index.parse::<u32>() {
+            Ok(n) if (1..=56).contains(&n) => {
+                section.color = Some(format!("Color{}", n));
+                return Ok(None);
+            }
+            _ => return Err(WebExcelError::ParseError),
+        }
+    }
+
+    if content.starts_with(['<', '>', '=']) {
+        let split = content
+            .find(|c| !matches!(c, '<' | '>' | '='))
+            .ok_or(WebExcelError::ParseError)?;
+        let op = match &content[..split] {
+            "=" => BinaryOp::Eq,
+            "<>" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            _ => return Err(WebExcelError::ParseError),
+        };
+        let value = content[split..]
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| WebExcelError::ParseError)?;
+        section.condition = Some(Condition { op, value });
+        return Ok(None);
+    }
+
+    let first = lower.chars().next();
+    if let Some(unit @ ('h' | 'm' | 's')) = first {
+        if lower.chars().all(|c| c == unit) {
+            return Ok(Some(Token::Date(DatePart::Elapsed(unit, lower.len()))));
+        }
+    }
+
+    // Currency and locale, e.g. `[$€-407]` shows `€`
+    if let Some(currency) = content.strip_prefix('$') {
+        let symbol = currency.split('-').next().unwrap_or_default();
+        return Ok((!symbol.is_empty()).then(|| Token::Literal(symbol.to_owned())));
+    }
+    // East Asian numeral systems, e.g. `[DBNum1]`, are shown as plain digits
+    if lower.starts_with("dbnum") || lower.starts_with("natnum") {
+        return Ok(None);
+    }
+    Err(WebExcelError::ParseError)
+}
+
+/// Turn number tokens of a date section into literals, read sub-seconds and tell
+/// minutes from months: `m` right after hours or before seconds is minutes.
+fn date_tokens(tokens: Vec<Token>) -> Vec<Token> {
+    let mut result: Vec<Token> = vec![];
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        let token = match token {
+            Token::Point if iter.peek() == Some(&Token::Digit(Placeholder::Zero)) => {
+                let mut digits = 0;
+                while iter.peek() == Some(&Token::Digit(Placeholder::Zero)) {
+                    iter.next();
+                    digits += 1;
+                }
+                Token::Date(DatePart::SubSecond(digits.min(3)))
+            }
+            Token::Point => Token::Literal(".".to_owned()),
+            Token::Comma => Token::Literal(",".to_owned()),
+            Token::Slash => Token::Literal("/".to_owned()),
+            Token::Percent => Token::Literal("%".to_owned()),
+            Token::Digit(Placeholder::Zero) => Token::Literal("0".to_owned()),
+            Token::Digit(Placeholder::Hash) => Token::Literal("#".to_owned()),
+            Token::Digit(Placeholder::Question) => Token::Literal("?".to_owned()),
+            Token::Denominator(n) => Token::Literal(n.to_string()),
+            Token::Exponent(plus) => Token::Literal(if plus { "E+" } else { "E-" }.to_owned()),
+            other => other,
+        };
+        result.push(token);
+    }
+
+    let parts: Vec<usize> = (0..result.len())
+        .filter(|&i| matches!(result[i], Token::Date(_)))
+        .collect();
+    for (k, &i) in parts.iter().enumerate() {
+        let count = match result[i] {
+            Token::Date(DatePart::Month(count)) if count <= 2 => count,
+            _ => continue,
+        };
+        let after_hour = k > 0
+            && matches!(
+                result[parts[k - 1]],
+                Token::Date(DatePart::Hour(_)) | Token::Date(DatePart::Elapsed('h', _))
+            );
+        let before_second = parts.get(k + 1).is_some_and(|&next| {
+            matches!(
+                result[next],
+                Token::Date(DatePart::Second(_)) | Token::Date(DatePart::Elapsed('s', _))
+            )
+        });
+        if after_hour || before_second {
+            result[i] = Token::Date(DatePart::Minute(count));
+        }
+    }
+    result
+}
+
+/// A slash only makes a fraction after a digit placeholder, it is plain text otherwise.
+fn number_tokens(tokens: Vec<Token>) -> Vec<Token> {
+    let mut result: Vec<Token> = vec![];
+    for token in tokens {
+        let token = match token {
+            Token::Slash if !matches!(result.last(), Some(Token::Digit(_))) => {
+                Token::Literal("/".to_owned())
+            }
+            Token::Denominator(n) if result.last() != Some(&Token::Slash) => {
+                Token::Literal(n.to_string())
+            }
+            other => other,
+        };
+        result.push(token);
+    }
+    result
+}
+
+fn render_text(tokens: &[Token], text: &str) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Text => text.to_owned(),
+            Token::Literal(s) => s.clone(),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+/// Digits of `n` rounded to `decimals`, split around the decimal point, with at most
+/// 15 significant digits like Excel. The integer part is empty for 0.
+/// Rounding works on the decimal digits, so large numbers cannot overflow.
+fn digits(n: f64, decimals: usize) -> (String, String) {
+    // 15 significant digits `d.dddddddddddddd` and the exponent of the first one
+    let text = format!("{:.14e}", n.abs());
+    let (mantissa, exponent) = text.split_once('e').unwrap_or(("0", "0"));
+    let significant: Vec<char> = mantissa.chars().filter(char::is_ascii_digit).collect();
+    let exponent: i64 = exponent.parse().unwrap_or_default();
+
+    // Digits kept down to the last decimal place, rounding half away from zero
+    let kept = exponent + 1 + decimals as i64;
+    let mut all: Vec<char> = significant
+        .iter()
+        .copied()
+        .chain(std::iter::repeat('0'))
+        .take(kept.max(0) as usize)
+        .collect();
+    let next = usize::try_from(kept).ok().and_then(|i| significant.get(i));
+    if next.is_some_and(|d| *d >= '5') {
+        round_up(&mut all);
+    }
+
+    while all.len() <= decimals {
+        all.insert(0, '0');
+    }
+    let split = all.len() - decimals;
+    let integer: String = all[..split].iter().collect();
+    let fraction: String = all[split..].iter().collect();
+    (integer.trim_start_matches('0').to_owned(), fraction)
+}
+
+/// Add one to the last of the decimal `digits`, carrying to the left.
+fn round_up(digits: &mut Vec<char>) {
+    for d in digits.iter_mut().rev() {
+        if *d == '9' {
+            *d = '0';
+        } else {
+            *d = (*d as u8 + 1) as char;
+            return;
+        }
+    }
+    digits.insert(0, '1');
+}
+
+/// Fill integer placeholders right to left with `digits`, the first one taking any extra
+/// digits. Each placeholder gets its text and the place of its last digit.
+fn fill_integer(digits: &str, placeholders: &[Placeholder]) -> Vec<(String, usize)> {
+    let digits: Vec<char> = digits.chars().collect();
+    let count = placeholders.len();
+    placeholders
+        .iter()
+        .enumerate()
+        .map(|(j, placeholder)| {
+            let place = count - 1 - j;
+            let mut text = String::new();
+            if j == 0 && digits.len() > count {
+                text.extend(&digits[..digits.len() - count]);
+            }
+            match digits.len().checked_sub(place + 1) {
+                Some(index) => text.push(digits[index]),
+                None => text.push_str(placeholder.pad()),
+            }
+            (text, place)
+        })
+        .collect()
+}
+
+/// Text of an integer placeholder, with a thousands separator after every third place.
+fn grouped(text: &str, last_place: usize, grouping: bool) -> String {
+    if !grouping {
+        return text.to_owned();
+    }
+    let count = text.chars().count();
+    let mut result = String::new();
+    for (k, c) in text.chars().enumerate() {
+        result.push(c);
+        let place = last_place + count - 1 - k;
+        if c.is_ascii_digit() && place > 0 && place.is_multiple_of(3) {
+            result.push(',');
+        }
+    }
+    result
+}
+
+/// Fill decimal placeholders left to right, trailing zeros hidden or padded by `#` and `?`.
+fn fill_decimals(digits: &str, placeholders: &[Placeholder]) -> Vec<String> {
+    let mut texts: Vec<String> = digits.chars().map(String::from).collect();
+    for (j, placeholder) in placeholders.iter().enumerate().rev() {
+        if *placeholder == Placeholder::Zero || texts[j] != "0" {
+            break;
+        }
+        texts[j] = placeholder.pad().to_owned();
+    }
+    texts
+}
+
+fn placeholders(tokens: &[Token]) -> Vec<Placeholder> {
+    tokens
+        .iter()
+        .filter_map(|t| match t {
+            Token::Digit(p) => Some(*p),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Render a positive number with a number section.
+/// Factor of the `%` signs of a section, each multiplying by 100.
+fn percent_scale(tokens: &[Token]) -> f64 {
+    100f64.powi(tokens.iter().filter(|t| **t == Token::Percent).count() as i32)
+}
+
+fn render_number(tokens: &[Token], n: f64) -> String {
+    let n = n * percent_scale(tokens);
+
+    if let Some(slash) = tokens.iter().position(|t| *t == Token::Slash) {
+        return render_fraction(tokens, slash, n);
+    }
+    if let Some(exponent) = tokens.iter().position(|t| matches!(t, Token::Exponent(_))) {
+        return render_scientific(tokens, exponent, n);
+    }
+
+    let end = tokens.len();
+    let point = tokens
+        .iter()
+        .position(|t| *t == Token::Point)
+        .unwrap_or(end);
+    let grouping = groups_thousands(&tokens[..point]);
+    let n = n / 1000f64.powi(scaling_commas(tokens) as i32);
+    render_mantissa(tokens, point, n, grouping)
+}
+
+/// Render the tokens of a plain number, the decimal point at `point` or none if it is the end.
+fn render_mantissa(tokens: &[Token], point: usize, n: f64, grouping: bool) -> String {
+    let integer_places = placeholders(&tokens[..point]);
+    let decimal_places = placeholders(&tokens[point..]);
+    let (integer, fraction) = digits(n, decimal_places.len());
+
+    let mut integers = fill_integer(&integer, &integer_places).into_iter();
+    let mut decimals = fill_decimals(&fraction, &decimal_places).into_iter();
+    let mut text = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Digit(_) if i < point => {
+                let (digits, place) = integers.next().unwrap_or_default();
+                text.push_str(&grouped(&digits, place, grouping));
+            }
+            Token::Digit(_) => text.push_str(&decimals.next().unwrap_or_default()),
+            Token::Point => {
+                // A format without integer placeholders still shows the integer part
+                if integer_places.is_empty() {
+                    text.push_str(&grouped(&integer, 0, grouping));
+                }
+                text.push('.');
+            }
+            Token::Percent => text.push('%'),
+            Token::Literal(s) => text.push_str(s),
+            Token::General => text.push_str(&number_to_text(n)),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Whether a comma between integer placeholders groups thousands.
+fn groups_thousands(tokens: &[Token]) -> bool {
+    let first = tokens.iter().position(|t| matches!(t, Token::Digit(_)));
+    let last = tokens.iter().rposition(|t| matches!(t, Token::Digit(_)));
+    match (first, last) {
+        (Some(first), Some(last)) => tokens[first..last].contains(&Token::Comma),
+        _ => false,
+    }
+}
+
+/// Commas right after the last digit placeholder, each dividing by 1000.
+fn scaling_commas(tokens: &[Token]) -> usize {
+    match tokens.iter().rposition(|t| matches!(t, Token::Digit(_))) {
+        Some(last) => tokens[last + 1..]
+            .iter()
+            .take_while(|t| **t == Token::Comma)
+            .count(),
+        None => 0,
+    }
+}
+
+fn render_scientific(tokens: &[Token], exponent: usize, n: f64) -> String {
+    let mantissa_tokens = &tokens[..exponent];
+    let point = mantissa_tokens
+        .iter()
+        .position(|t| *t == Token::Point)
+        .unwrap_or(exponent);
+    let integer_places = placeholders(&mantissa_tokens[..point]);
+    let decimals = placeholders(&mantissa_tokens[point..]).len();
+
+    // `##0.0E+0` keeps exponents a multiple of 3, `00.0E+0` shows two integer digits
+    let width = integer_places.len().max(1) as i32;
+    let engineering = integer_places.contains(&Placeholder::Hash);
+    let exponent_for = |n: f64| -> i32 {
+        if n == 0.0 {
+            return 0;
+        }
+        let magnitude = n.log10().floor() as i32;
+        if engineering {
+            magnitude.div_euclid(width) * width
+        } else {
+            magnitude - (width - 1)
+        }
+    };
+
+    let mut power = exponent_for(n);
+    let mut mantissa = n / 10f64.powi(power);
+    let rounded = (mantissa * 10f64.powi(decimals as i32)).round() / 10f64.powi(decimals as i32);
+    if n != 0.0 && rounded >= 10f64.powi(width) {
+        power = exponent_for(rounded * 10f64.powi(power));
+        mantissa = n / 10f64.powi(power);
+    }
+
+    let mut text = render_mantissa(mantissa_tokens, point, mantissa, false);
+    let plus = matches!(tokens[exponent], Token::Exponent(true));
+    text.push('E');
+    if power < 0 {
+        text.push('-');
+    } else if plus {
+        text.push('+');
+    }
+
+    let exponent_places = placeholders(&tokens[exponent + 1..]);
+    let exponent_places = if exponent_places.is_empty() {
+        vec![Placeholder::Zero]
+    } else {
+        exponent_places
+    };
+    let mut digits = fill_integer(&power.unsigned_abs().to_string(), &exponent_places).into_iter();
+    for token in &tokens[exponent + 1..] {
+        match token {
+            Token::Digit(_) => text.push_str(&digits.next().unwrap_or_default().0),
+            Token::Literal(s) => text.push_str(s),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Best fraction for `n` with a denominator of at most `max`.
+fn approximate(n: f64, max: u32) -> (u64, u64) {
+    let mut best = (n.round() as u64, 1, (n - n.round()).abs());
+    for denominator in 2..=max as u64 {
+        let numerator = (n * denominator as f64).round();
+        let error = (n - numerator / denominator as f64).abs();
+        if error < best.2 - 1e-12 {
+            best = (numerator as u64, denominator, error);
+        }
+    }
+    (best.0, best.1)
+}
+
+fn render_fraction(tokens: &[Token], slash: usize, n: f64) -> String {
+    // Numerator placeholders sit right before the slash, whole number ones before them
+    let numerator_start = tokens[..slash]
+        .iter()
+        .rposition(|t| !matches!(t, Token::Digit(_)))
+        .map_or(0, |i| i + 1);
+    let whole_places = placeholders(&tokens[..numerator_start]);
+    let numerator_places = placeholders(&tokens[numerator_start..slash]);
+    let denominator_places = placeholders(&tokens[slash + 1..]);
+    let fixed = tokens[slash + 1..].iter().find_map(|t| match t {
+        Token::Denominator(d) => Some(*d),
+        _ => None,
+    });
+
+    let mut whole = if whole_places.is_empty() {
+        0.0
+    } else {
+        n.trunc()
+    };
+    let (mut numerator, denominator) = match fixed {
+        Some(d) => (((n - whole) * d as f64).round() as u64, d as u64),
+        None => {
+            let digits = denominator_places.len().clamp(1, 4) as u32;
+            approximate(n - whole, 10u32.pow(digits) - 1)
+        }
+    };
+    if !whole_places.is_empty() && numerator == denominator {
+        whole += 1.0;
+        numerator = 0;
+    }
+
+    let blank_fraction = numerator == 0 && !whole_places.is_empty();
+    let whole_digits = match (whole == 0.0, blank_fraction) {
+        (true, true) => "0".to_owned(),
+        (true, false) => String::new(),
+        _ => digits(whole, 0).0,
+    };
+
+    let mut wholes = fill_integer(&whole_digits, &whole_places).into_iter();
+    let mut numerators = fill_integer(&numerator.to_string(), &numerator_places).into_iter();
+    let denominator_text = denominator.to_string();
+    let mut denominators = fill_denominator(&denominator_text, &denominator_places).into_iter();
+
+    let mut text = String::new();
+    let mut fraction = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let target = if i >= numerator_start {
+            &mut fraction
+        } else {
+            &mut text
+        };
+        match token {
+            Token::Digit(_) if i < numerator_start => {
+                let (digits, place) = wholes.next().unwrap_or_default();
+                target.push_str(&grouped(&digits, place, false));
+            }
+            Token::Digit(_) if i < slash => {
+                target.push_str(&numerators.next().unwrap_or_default().0)
+            }
+            Token::Digit(_) => target.push_str(&denominators.next().unwrap_or_default()),
+            Token::Slash => target.push('/'),
+            Token::Denominator(d) => target.push_str(&d.to_string()),
+            Token::Literal(s) => target.push_str(s),
+            Token::Percent => target.push('%'),
+            _ => {}
+        }
+    }
+
+    if blank_fraction {
+        // A whole number keeps the fraction's width blank, so fractions stay aligned
+        let width = fraction.chars().count();
+        text.push_str(&" ".repeat(width));
+    } else {
+        text.push_str(&fraction);
+    }
+    text
+}
+
+/// Fill denominator placeholders left to right, the last one taking any extra digits.
+fn fill_denominator(digits: &str, placeholders: &[Placeholder]) -> Vec<String> {
+    let digits: Vec<char> = digits.chars().collect();
+    placeholders
+        .iter()
+        .enumerate()
+        .map(|(j, placeholder)| {
+            if j + 1 == placeholders.len() && digits.len() > j {
+                digits[j..].iter().collect()
+            } else {
+                match digits.get(j) {
+                    Some(d) => d.to_string(),
+                    None => placeholder.pad().to_owned(),
+                }
+            }
+        })
+        .collect()
+}
+
+/// Year, month and day of a serial date. Serial 60 is 1900-02-29, which Excel keeps
+/// for compatibility with Lotus 1-2-3, and serial 0 shows as 1900-01-00.
+pub fn serial_to_date(days: i64) -> (i64, u32, u32) {
+    match days {
+        0 => return (1900, 1, 0),
+        60 => return (1900, 2, 29),
+        _ => {}
+    }
+    let days = if days < 60 { days + 1 } else { days };
+
+    // Days since 1970-01-01, then the civil calendar from Howard Hinnant's algorithm
+    let z = days - 25569 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Serial number of a date, the reverse of `serial_to_date`. `None` for days that do not
+/// exist and years outside of 1900 to 9999.
+pub fn date_to_serial(year: i64, month: u32, day: u32) -> Option<f64> {
+    if (year, month, day) == (1900, 2, 29) {
+        return Some(60.0);
+    }
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1900..=9999).contains(&year) || !(1..=month_days).contains(&day) {
+        return None;
+    }
+
+    // Days since 1970-01-01, also from Howard Hinnant's algorithm
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = (month as i64 + 9) % 12;
+    let day_of_year = (153 * shifted_month + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let serial = era * 146_097 + day_of_era - 719_468 + 25569;
+    Some(if serial < 61 { serial - 1 } else { serial } as f64)
+}
+
+fn render_date(tokens: &[Token], serial: f64) -> Result<String, ErrorValue> {
+    if !(0.0..MAX_DATE_SERIAL + 1.0).contains(&serial) {
+        return Err(ErrorValue::Value);
+    }
+
+    let sub_digits = tokens
+        .iter()
+        .filter_map(|t| match t {
+            Token::Date(DatePart::SubSecond(d)) => Some(*d),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    let units = 10u64.pow(sub_digits as u32);
+    let total = (serial * 86_400.0 * units as f64).round() as u64;
+    let seconds = total / units;
+    let sub = total % units;
+
+    let days = (seconds / 86_400) as i64;
+    let (hour, minute, second) = (seconds % 86_400 / 3600, seconds % 3600 / 60, seconds % 60);
+    let (year, month, day) = serial_to_date(days);
+    let weekday = (days + 6).rem_euclid(7) as usize;
+    let twelve_hours = tokens
+        .iter()
+        .any(|t| matches!(t, Token::Date(DatePart::AmPm(..))));
+
+    let padded = |n: u64, width: usize| format!("{:0width$}", n, width = width);
+    let mut text = String::new();
+    for token in tokens {
+        match token {
+            Token::Date(part) => text.push_str(&match part {
+                DatePart::Year(2) => padded(year.rem_euclid(100) as u64, 2),
+                DatePart::Year(_) => year.to_string(),
+                DatePart::Month(count) => match count {
+                    1 | 2 => padded(month as u64, *count),
+                    3 => MONTHS[month as usize - 1][..3].to_owned(),
+                    4 => MONTHS[month as usize - 1].to_owned(),
+                    _ => MONTHS[month as usize - 1][..1].to_owned(),
+                },
+                DatePart::Day(count) => match count {
+                    1 | 2 => padded(day as u64, *count),
+                    3 => WEEKDAYS[weekday][..3].to_owned(),
+                    _ => WEEKDAYS[weekday].to_owned(),
+                },
+                DatePart::Hour(count) if twelve_hours => {
+                    padded(if hour % 12 == 0 { 12 } else { hour % 12 }, *count)
+                }
+                DatePart::Hour(count) => padded(hour, *count),
+                DatePart::Minute(count) => padded(minute, *count),
+                DatePart::Second(count) => padded(second, *count),
+                DatePart::SubSecond(count) => {
+                    let digits = padded(sub, sub_digits);
+                    format!(".{}", &digits[..*count])
+                }
+                DatePart::AmPm(am, pm) => if hour < 12 { am } else { pm }.clone(),
+                DatePart::Elapsed(unit, count) => {
+                    let elapsed = match unit {
+                        'h' => seconds / 3600,
+                        'm' => seconds / 60,
+                        _ => seconds,
+                    };
+                    padded(elapsed, *count)
+                }
+            }),
+            Token::Literal(s) => text.push_str(s),
+            Token::General => text.push_str(&number_to_text(serial)),
+            _ => {}
+        }
+    }
+    Ok(text)
+}
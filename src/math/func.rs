@@ -1,15 +1,87 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::eval::{number_to_text, VOLATILE_FUNCTIONS};
+use crate::math::names::is_valid_name;
+use crate::math::parser::*;
+use crate::range::Range;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
 use wasm_bindgen::prelude::*;
 
+/// Nested levels of functions allowed by Excel.
+pub const MAX_NESTING: usize = 64;
+/// Length of formula contents allowed by Excel.
+pub const MAX_FORMULA_LENGTH: usize = 8192;
+/// Length of a text value inside a formula allowed by Excel.
+pub const MAX_TEXT_LENGTH: usize = 255;
+
+/// Comparison operators usable in conditions, e.g. `A1>=10`.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug)]
+pub enum ComparisonOperator {
+    Equal = "=",
+    NotEqual = "<>",
+    Less = "<",
+    LessOrEqual = "<=",
+    Greater = ">",
+    GreaterOrEqual = ">=",
+}
+
+impl ComparisonOperator {
+    fn as_binary(&self) -> Result<BinaryOp, WebExcelError> {
+        match self {
+            ComparisonOperator::Equal => Ok(BinaryOp::Eq),
+            ComparisonOperator::NotEqual => Ok(BinaryOp::Ne),
+            ComparisonOperator::Less => Ok(BinaryOp::Lt),
+            ComparisonOperator::LessOrEqual => Ok(BinaryOp::Le),
+            ComparisonOperator::Greater => Ok(BinaryOp::Gt),
+            ComparisonOperator::GreaterOrEqual => Ok(BinaryOp::Ge),
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+
+    fn symbol(&self) -> Result<&'static str, WebExcelError> {
+        Ok(self.as_binary()?.symbol())
+    }
+}
+
+/// Functions taking `criteria_range, criteria` pairs, extended with `criteria_case`.
+const CRITERIA_FUNCTIONS: [&str; 5] = ["COUNTIFS", "SUMIFS", "AVERAGEIFS", "MAXIFS", "MINIFS"];
+
+/// Sheet referenced by a formula.
+/// `workbook` is only set when the sheet lives in another workbook, e.g. `[Book2.xlsx]Sheet1!A1`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SheetDependency {
+    pub workbook: Option<String>,
+    pub sheet: String,
+}
+
+impl SheetDependency {
+    pub fn is_external(&self) -> bool {
+        self.workbook.is_some()
+    }
+
+    /// Sheet name with its workbook prefix, e.g. `[Book2.xlsx]Sheet1`
+    pub fn to_qualified(&self) -> String {
+        match &self.workbook {
+            Some(workbook) => format!("[{}]{}", workbook, self.sheet),
+            None => self.sheet.clone(),
+        }
+    }
+}
+
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
 pub struct FunctionBuilder {
+    /// Formula text including the leading `=`, ready to be written with Office JS.
     #[wasm_bindgen(getter_with_clone)]
     pub function: String,
 
     pub value: i64,
 
-    #[wasm_bindgen(getter_with_clone)]
-    pub sheet_dependency: Box<[js_sys::JsString]>,
+    expr: Expr,
+    dependencies: BTreeSet<SheetDependency>,
 }
 
 impl PartialEq for FunctionBuilder {
@@ -18,8 +90,840 @@ impl PartialEq for FunctionBuilder {
     }
 }
 
-// #[wasm_bindgen]
-// impl FunctionBuilder {
-//     #[wasm_bindgen(constructor)]
-//     pub fn new<T>(starting_value: T) {}
-// }
+#[wasm_bindgen]
+impl FunctionBuilder {
+    /// Parse a handwritten formula, e.g. `=SUM(Sheet2!A1:A10)`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(formula: &str) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::from_expr(parse_formula(formula)?)
+    }
+
+    /// Formula referencing a single cell, e.g. `=Sheet1!B2`.
+    pub fn from_cell(cell: &Cell) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::from_expr(Expr::Reference(reference_of(cell, None)))
+    }
+
+    /// Formula referencing a range, e.g. `=Sheet1!B2:C5`.
+    pub fn from_range(range: &Range) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::from_expr(Expr::Reference(reference_of(
+            &range.cell_start,
+            Some(&range.cell_end),
+        )))
+    }
+
+    /// Formula referencing the array spilled from `anchor`, e.g. `=Sheet1!B2#`.
+    pub fn from_spill(anchor: &Cell) -> Result<FunctionBuilder, WebExcelError> {
+        let mut reference = reference_of(anchor, None);
+        reference.spill = true;
+        FunctionBuilder::from_expr(Expr::Reference(reference))
+    }
+
+    /// Numeric literal operand.
+    pub fn number(value: f64) -> Result<FunctionBuilder, WebExcelError> {
+        if !value.is_finite() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+        FunctionBuilder::from_expr(Expr::Number(value))
+    }
+
+    /// Text literal operand. Quotes are escaped when rendered, `say "hi"` becomes `"say ""hi"""`.
+    pub fn text(value: &str) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::from_expr(Expr::Text(value.to_owned()))
+    }
+
+    /// Boolean literal operand.
+    pub fn boolean(value: bool) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::from_expr(Expr::Bool(value))
+    }
+
+    /// Condition comparing two operands, e.g. `A1>=10`.
+    pub fn compare(
+        &self,
+        operator: ComparisonOperator,
+        other: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::from_expr(Expr::Binary(
+            operator.as_binary()?,
+            Box::new(self.expr.clone()),
+            Box::new(other.expr.clone()),
+        ))
+    }
+
+    /// `AND` of both conditions. Chained calls extend the same `AND`, e.g. `AND(c1,c2,c3)`.
+    pub fn and(&self, other: &FunctionBuilder) -> Result<FunctionBuilder, WebExcelError> {
+        self.extend_or_call("AND", vec![other.clone()])
+    }
+
+    /// `OR` of both conditions. Chained calls extend the same `OR`, e.g. `OR(c1,c2,c3)`.
+    pub fn or(&self, other: &FunctionBuilder) -> Result<FunctionBuilder, WebExcelError> {
+        self.extend_or_call("OR", vec![other.clone()])
+    }
+
+    pub fn not(&self) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call("NOT", vec![self.clone()])
+    }
+
+    /// `IF(condition, then, otherwise)`
+    pub fn if_then(
+        condition: &FunctionBuilder,
+        then: &FunctionBuilder,
+        otherwise: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call(
+            "IF",
+            vec![condition.clone(), then.clone(), otherwise.clone()],
+        )
+    }
+
+    /// `IFS(condition, value)`. Add more pairs with `ifs_case`.
+    pub fn ifs(
+        condition: &FunctionBuilder,
+        value: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call("IFS", vec![condition.clone(), value.clone()])
+    }
+
+    /// Append a `condition, value` pair to an `IFS` built with `ifs`.
+    pub fn ifs_case(
+        &self,
+        condition: &FunctionBuilder,
+        value: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        self.extend("IFS", vec![condition.clone(), value.clone()])
+    }
+
+    /// `SWITCH(expression, value, result)`. Add more pairs with `switch_case`.
+    pub fn switch(
+        expression: &FunctionBuilder,
+        value: &FunctionBuilder,
+        result: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call(
+            "SWITCH",
+            vec![expression.clone(), value.clone(), result.clone()],
+        )
+    }
+
+    /// Append a `value, result` pair to a `SWITCH` built with `switch`.
+    pub fn switch_case(
+        &self,
+        value: &FunctionBuilder,
+        result: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        if self.switch_has_default() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+        self.extend("SWITCH", vec![value.clone(), result.clone()])
+    }
+
+    /// Close a `SWITCH` with the result used when no value matches.
+    pub fn switch_default(
+        &self,
+        result: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        if self.switch_has_default() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+        self.extend("SWITCH", vec![result.clone()])
+    }
+
+    /// `IFERROR(self, fallback)`
+    pub fn iferror(&self, fallback: &FunctionBuilder) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call("IFERROR", vec![self.clone(), fallback.clone()])
+    }
+
+    /// `IFNA(self, fallback)`
+    pub fn ifna(&self, fallback: &FunctionBuilder) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call("IFNA", vec![self.clone(), fallback.clone()])
+    }
+
+    /// Criteria of `COUNTIFS` and friends, e.g. `">=5"` or `">="&B1`.
+    /// Text is matched as written, so wildcard characters in it are escaped with `~`.
+    pub fn criteria(
+        operator: ComparisonOperator,
+        value: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        let symbol = operator.symbol()?;
+        let exact = matches!(
+            operator,
+            ComparisonOperator::Equal | ComparisonOperator::NotEqual
+        );
+
+        let literal = match &value.expr {
+            Expr::Number(n) => number_to_text(*n),
+            Expr::Bool(true) => "TRUE".to_owned(),
+            Expr::Bool(false) => "FALSE".to_owned(),
+            Expr::Text(t) if exact => escape_wildcards(t),
+            Expr::Text(t) => t.clone(),
+            // Computed values are joined to the operator, e.g. `"<>"&B1`
+            other if symbol == "=" => return FunctionBuilder::from_expr(other.clone()),
+            other => {
+                return FunctionBuilder::from_expr(Expr::Binary(
+                    BinaryOp::Concat,
+                    Box::new(Expr::Text(symbol.to_owned())),
+                    Box::new(other.clone()),
+                ))
+            }
+        };
+
+        // `=` is implied, unless the text itself starts like an operator
+        let implied = symbol == "=" && !literal.is_empty() && !literal.starts_with(['=', '<', '>']);
+        let text = if implied {
+            literal
+        } else {
+            format!("{}{}", symbol, literal)
+        };
+
+        match &value.expr {
+            Expr::Number(n) if implied => FunctionBuilder::number(*n),
+            Expr::Bool(b) if implied => FunctionBuilder::boolean(*b),
+            _ => FunctionBuilder::text(&text),
+        }
+    }
+
+    /// Wildcard criteria, e.g. `"ab*c?"`, where `*` is any text and `?` any character.
+    /// `negate` matches everything else, e.g. `"<>ab*c?"`.
+    pub fn criteria_pattern(pattern: &str, negate: bool) -> Result<FunctionBuilder, WebExcelError> {
+        let prefix = if negate {
+            "<>"
+        } else if pattern.starts_with(['=', '<', '>']) {
+            "="
+        } else {
+            ""
+        };
+        FunctionBuilder::text(&format!("{}{}", prefix, pattern))
+    }
+
+    /// `COUNTIF(range, criteria)`
+    pub fn count_if(
+        range: &Range,
+        criteria: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call(
+            "COUNTIF",
+            vec![FunctionBuilder::from_range(range)?, criteria.clone()],
+        )
+    }
+
+    /// `SUMIF(range, criteria, sum_range)`
+    pub fn sum_if(
+        range: &Range,
+        criteria: &FunctionBuilder,
+        sum_range: &Range,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call(
+            "SUMIF",
+            vec![
+                FunctionBuilder::from_range(range)?,
+                criteria.clone(),
+                FunctionBuilder::from_range(sum_range)?,
+            ],
+        )
+    }
+
+    /// `AVERAGEIF(range, criteria, average_range)`
+    pub fn average_if(
+        range: &Range,
+        criteria: &FunctionBuilder,
+        average_range: &Range,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call(
+            "AVERAGEIF",
+            vec![
+                FunctionBuilder::from_range(range)?,
+                criteria.clone(),
+                FunctionBuilder::from_range(average_range)?,
+            ],
+        )
+    }
+
+    /// `COUNTIFS(criteria_range, criteria)`. Add more pairs with `criteria_case`.
+    pub fn count_ifs(
+        criteria_range: &Range,
+        criteria: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::call(
+            "COUNTIFS",
+            vec![
+                FunctionBuilder::from_range(criteria_range)?,
+                criteria.clone(),
+            ],
+        )
+    }
+
+    /// `SUMIFS(sum_range, criteria_range, criteria)`. Add more pairs with `criteria_case`.
+    pub fn sum_ifs(
+        sum_range: &Range,
+        criteria_range: &Range,
+        criteria: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::aggregate_ifs("SUMIFS", sum_range, criteria_range, criteria)
+    }
+
+    /// `AVERAGEIFS(average_range, criteria_range, criteria)`. Add more pairs with `criteria_case`.
+    pub fn average_ifs(
+        average_range: &Range,
+        criteria_range: &Range,
+        criteria: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::aggregate_ifs("AVERAGEIFS", average_range, criteria_range, criteria)
+    }
+
+    /// `MAXIFS(max_range, criteria_range, criteria)`. Add more pairs with `criteria_case`.
+    pub fn max_ifs(
+        max_range: &Range,
+        criteria_range: &Range,
+        criteria: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::aggregate_ifs("MAXIFS", max_range, criteria_range, criteria)
+    }
+
+    /// `MINIFS(min_range, criteria_range, criteria)`. Add more pairs with `criteria_case`.
+    pub fn min_ifs(
+        min_range: &Range,
+        criteria_range: &Range,
+        criteria: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::aggregate_ifs("MINIFS", min_range, criteria_range, criteria)
+    }
+
+    /// Append a `criteria_range, criteria` pair to a `COUNTIFS`, `SUMIFS`, `AVERAGEIFS`,
+    /// `MAXIFS` or `MINIFS`. The range must have the same size as the ranges already used.
+    pub fn criteria_case(
+        &self,
+        criteria_range: &Range,
+        criteria: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        let (name, first) = match &self.expr {
+            Expr::Function(name, args) if CRITERIA_FUNCTIONS.contains(&name.as_str()) => {
+                (name.clone(), &args[0])
+            }
+            _ => return Err(WebExcelError::FormulaBuildError),
+        };
+
+        if let Expr::Reference(reference) = first {
+            let range = reference.range()?;
+            if (range.rows, range.columns) != (criteria_range.rows, criteria_range.columns) {
+                return Err(WebExcelError::FormulaBuildError);
+            }
+        }
+
+        self.extend(
+            &name,
+            vec![
+                FunctionBuilder::from_range(criteria_range)?,
+                criteria.clone(),
+            ],
+        )
+    }
+
+    /// Variable bound by `LET` or `LAMBDA`, or a defined name.
+    pub fn variable(name: &str) -> Result<FunctionBuilder, WebExcelError> {
+        if !is_valid_name(name) {
+            return Err(WebExcelError::NameError);
+        }
+        FunctionBuilder::from_expr(Expr::Name(name.to_owned()))
+    }
+
+    /// `LET(name, value, body)`. When `body` is a `LET` itself, the bindings are merged,
+    /// e.g. `LET(x, 1, LET(y, 2, x+y))` becomes `LET(x,1,y,2,x+y)`.
+    pub fn let_in(
+        name: &str,
+        value: &FunctionBuilder,
+        body: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::let_bindings(vec![(name, value.clone())], body.clone())
+    }
+
+    /// `LAMBDA(param1, ..., body)`, ready to be registered as a defined name.
+    pub fn lambda(
+        params: Vec<js_sys::JsString>,
+        body: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        let params: Vec<String> = params.iter().map(String::from).collect();
+        let params: Vec<&str> = params.iter().map(String::as_str).collect();
+
+        FunctionBuilder::lambda_of(&params, body.clone())
+    }
+
+    pub fn is_lambda(&self) -> bool {
+        matches!(&self.expr, Expr::Function(name, _) if name == "LAMBDA")
+    }
+
+    /// Hoist sub-expressions used more than once into `LET` variables named `v_1`, `v_2`, ...
+    /// e.g. `=IF(SUM(A1:A9)>10,SUM(A1:A9),0)` becomes `=LET(v_1,SUM(A1:A9),IF(v_1>10,v_1,0))`.
+    /// Volatile functions and expressions using other variables or names are left in place.
+    pub fn with_let(&self) -> Result<FunctionBuilder, WebExcelError> {
+        let mut taken = vec![];
+        self.expr.walk(&mut |e| {
+            if let Expr::Name(name) = e {
+                taken.push(name.to_ascii_uppercase());
+            }
+        });
+
+        let mut generated: Vec<String> = vec![];
+        let mut bindings: Vec<(String, Expr)> = vec![];
+        let mut body = self.expr.clone();
+
+        while let Some(repeated) = most_repeated(&body, &bindings, &generated) {
+            let mut index = generated.len() + 1;
+            let name = loop {
+                let candidate = format!("v_{}", index);
+                if !taken.contains(&candidate.to_ascii_uppercase()) {
+                    break candidate;
+                }
+                index += 1;
+            };
+
+            let variable = Expr::Name(name.clone());
+            body = replace_expr(&body, &repeated, &variable);
+            for (_, value) in bindings.iter_mut() {
+                *value = replace_expr(value, &repeated, &variable);
+            }
+            bindings.push((name.clone(), repeated));
+            generated.push(name);
+        }
+
+        if bindings.is_empty() {
+            return Ok(self.clone());
+        }
+
+        let mut args = vec![];
+        for (name, value) in order_bindings(bindings) {
+            args.push(Expr::Name(name));
+            args.push(value);
+        }
+        args.push(body);
+        FunctionBuilder::from_expr(Expr::Function("LET".to_owned(), args))
+    }
+
+    /// Sheets of the current workbook the formula depends on.
+    /// Unqualified references (e.g. `A1`) point at the sheet the formula is written to and are not listed.
+    #[wasm_bindgen(getter)]
+    pub fn sheet_dependency(&self) -> Box<[js_sys::JsString]> {
+        self.dependencies
+            .iter()
+            .filter(|d| !d.is_external())
+            .map(|d| js_sys::JsString::from(d.sheet.as_str()))
+            .collect()
+    }
+
+    /// Sheets outside the current workbook, formatted as `[Book2.xlsx]Sheet1`.
+    #[wasm_bindgen(getter)]
+    pub fn external_dependency(&self) -> Box<[js_sys::JsString]> {
+        self.dependencies
+            .iter()
+            .filter(|d| d.is_external())
+            .map(|d| js_sys::JsString::from(d.to_qualified()))
+            .collect()
+    }
+
+    pub fn has_external_dependency(&self) -> bool {
+        self.dependencies.iter().any(|d| d.is_external())
+    }
+
+    /// Check whether the formula references `sheet` of the current workbook.
+    /// Sheet names are compared case insensitively, like Excel does.
+    pub fn depends_on(&self, sheet: &str) -> bool {
+        self.dependencies
+            .iter()
+            .any(|d| !d.is_external() && d.sheet.to_lowercase() == sheet.to_lowercase())
+    }
+
+    /// Referenced sheets of the current workbook that are not among `sheets`.
+    /// Run this with the workbook's sheet names before writing the formula.
+    pub fn missing_sheets(&self, sheets: Vec<js_sys::JsString>) -> Box<[js_sys::JsString]> {
+        let existing: Vec<String> = sheets.iter().map(String::from).collect();
+
+        self.find_missing_sheets(&existing)
+            .into_iter()
+            .map(js_sys::JsString::from)
+            .collect()
+    }
+}
+
+impl FunctionBuilder {
+    /// Build from a syntax tree, checking Excel's nesting and length limits.
+    pub fn from_expr(expr: Expr) -> Result<FunctionBuilder, WebExcelError> {
+        if expr.nesting_depth() > MAX_NESTING {
+            return Err(WebExcelError::FormulaNestingError);
+        }
+
+        let mut long_text = false;
+        expr.walk(&mut |e| {
+            if let Expr::Text(s) = e {
+                long_text |= s.chars().count() > MAX_TEXT_LENGTH;
+            }
+        });
+        if long_text {
+            return Err(WebExcelError::FormulaLengthError);
+        }
+
+        let function = format!("={}", expr.to_formula()?);
+        if function.chars().count() > MAX_FORMULA_LENGTH {
+            return Err(WebExcelError::FormulaLengthError);
+        }
+
+        let dependencies = expr
+            .references()
+            .into_iter()
+            .filter_map(|r| {
+                r.sheet().map(|sheet| SheetDependency {
+                    workbook: r.workbook.clone(),
+                    sheet: sheet.to_owned(),
+                })
+            })
+            .collect();
+
+        Ok(FunctionBuilder {
+            function,
+            value: 0,
+            expr,
+            dependencies,
+        })
+    }
+
+    /// Call function `name` with `args`, e.g. `call("AND", vec![a, b, c])`.
+    pub fn call(name: &str, args: Vec<FunctionBuilder>) -> Result<FunctionBuilder, WebExcelError> {
+        FunctionBuilder::from_expr(Expr::Function(
+            name.to_ascii_uppercase(),
+            args.into_iter().map(|a| a.expr).collect(),
+        ))
+    }
+
+    /// `LET` with several bindings, evaluated in order.
+    pub fn let_bindings(
+        bindings: Vec<(&str, FunctionBuilder)>,
+        body: FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        if bindings.is_empty() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+
+        let mut args = vec![];
+        for (name, value) in bindings {
+            if !is_valid_name(name) {
+                return Err(WebExcelError::NameError);
+            }
+            args.push(Expr::Name(name.to_owned()));
+            args.push(value.expr);
+        }
+
+        match body.expr {
+            Expr::Function(name, inner) if name == "LET" => args.extend(inner),
+            other => args.push(other),
+        }
+        FunctionBuilder::from_expr(Expr::Function("LET".to_owned(), args))
+    }
+
+    /// `LAMBDA(param1, ..., body)`. Parameter names must be valid and distinct.
+    pub fn lambda_of(
+        params: &[&str],
+        body: FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        let mut args = vec![];
+        for (i, param) in params.iter().enumerate() {
+            let duplicate = params[..i].iter().any(|p| p.eq_ignore_ascii_case(param));
+            if !is_valid_name(param) || duplicate {
+                return Err(WebExcelError::NameError);
+            }
+            args.push(Expr::Name(param.to_string()));
+        }
+        args.push(body.expr);
+
+        FunctionBuilder::from_expr(Expr::Function("LAMBDA".to_owned(), args))
+    }
+
+    /// Invoke this `LAMBDA` in place, e.g. `LAMBDA(x,x*2)(A1)`.
+    pub fn invoke(&self, args: Vec<FunctionBuilder>) -> Result<FunctionBuilder, WebExcelError> {
+        if !self.is_lambda() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+        FunctionBuilder::from_expr(Expr::Call(
+            Box::new(self.expr.clone()),
+            args.into_iter().map(|a| a.expr).collect(),
+        ))
+    }
+
+    fn aggregate_ifs(
+        name: &str,
+        target: &Range,
+        criteria_range: &Range,
+        criteria: &FunctionBuilder,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        if (target.rows, target.columns) != (criteria_range.rows, criteria_range.columns) {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+        FunctionBuilder::call(
+            name,
+            vec![
+                FunctionBuilder::from_range(target)?,
+                FunctionBuilder::from_range(criteria_range)?,
+                criteria.clone(),
+            ],
+        )
+    }
+
+    /// Append `args` to the outer `name` call of this formula.
+    fn extend(
+        &self,
+        name: &str,
+        args: Vec<FunctionBuilder>,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        match &self.expr {
+            Expr::Function(own, own_args) if own == name => {
+                let mut all = own_args.clone();
+                all.extend(args.into_iter().map(|a| a.expr));
+                FunctionBuilder::from_expr(Expr::Function(own.clone(), all))
+            }
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+
+    /// Append to the outer `name` call, or start a new one with `self` as first argument.
+    fn extend_or_call(
+        &self,
+        name: &str,
+        args: Vec<FunctionBuilder>,
+    ) -> Result<FunctionBuilder, WebExcelError> {
+        match &self.expr {
+            Expr::Function(own, _) if own == name => self.extend(name, args),
+            _ => {
+                let mut all = vec![self.clone()];
+                all.extend(args);
+                FunctionBuilder::call(name, all)
+            }
+        }
+    }
+
+    /// A `SWITCH` has a default result once its argument count is even.
+    fn switch_has_default(&self) -> bool {
+        matches!(&self.expr, Expr::Function(name, args) if name == "SWITCH" && args.len() % 2 == 0)
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Every sheet the formula depends on, external ones included, in sorted order.
+    pub fn dependencies(&self) -> impl Iterator<Item = &SheetDependency> {
+        self.dependencies.iter()
+    }
+
+    pub fn find_missing_sheets(&self, sheets: &[String]) -> Vec<String> {
+        self.dependencies
+            .iter()
+            .filter(|d| !d.is_external())
+            .filter(|d| {
+                !sheets
+                    .iter()
+                    .any(|s| s.to_lowercase() == d.sheet.to_lowercase())
+            })
+            .map(|d| d.sheet.clone())
+            .collect()
+    }
+}
+
+/// Turn cells into a formula reference. A sheet name like `[Book2.xlsx]Sheet1` is split
+/// into its external workbook and sheet.
+pub(crate) fn reference_of(start: &Cell, end: Option<&Cell>) -> Reference {
+    let (workbook, sheet) = match &start.sheet {
+        Some(qualified) => {
+            let (workbook, sheet) = split_workbook(qualified);
+            (workbook, Some(sheet))
+        }
+        None => (None, None),
+    };
+
+    let relocate = |cell: &Cell| Cell {
+        sheet: sheet.clone(),
+        ..cell.clone()
+    };
+
+    Reference {
+        workbook,
+        start: relocate(start),
+        end: end.map(relocate),
+        spill: false,
+    }
+}
+
+/// Parse a rule formula, e.g. of a conditional format or a data validation.
+/// Rule formulas cannot refer to other workbooks.
+pub(crate) fn checked_formula(formula: &str) -> Result<Expr, WebExcelError> {
+    let expr = parse_formula(formula)?;
+    if expr.references().iter().any(|r| r.workbook.is_some()) {
+        return Err(WebExcelError::FormulaBuildError);
+    }
+    Ok(expr)
+}
+
+/// Formula text with the leading `=` Office JS expects.
+pub(crate) fn formula_text(expr: &Expr) -> Result<String, WebExcelError> {
+    Ok(format!("={}", expr.to_formula()?))
+}
+
+/// Escape `*`, `?` and `~` so that criteria text only matches itself.
+fn escape_wildcards(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '~') {
+            escaped.push('~');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Whether `expr` may be moved into a `LET` variable by `with_let`.
+/// Operators are only hoisted when they read a reference, a function or another variable,
+/// so literals such as `-1` stay in place.
+fn hoistable(expr: &Expr, generated: &[String]) -> bool {
+    let compound = match expr {
+        Expr::Function(name, _) => {
+            !VOLATILE_FUNCTIONS.contains(&name.as_str()) && name != "LAMBDA" && name != "LET"
+        }
+        Expr::Binary(_, _, _) | Expr::Unary(_, _) => {
+            let mut reads = false;
+            expr.walk(&mut |e| {
+                reads |= match e {
+                    Expr::Reference(_) | Expr::Function(_, _) => true,
+                    Expr::Name(name) => generated.contains(name),
+                    _ => false,
+                }
+            });
+            reads
+        }
+        _ => false,
+    };
+
+    let mut self_contained = true;
+    expr.walk(&mut |e| match e {
+        Expr::Name(name) => self_contained &= generated.contains(name),
+        Expr::Function(name, _) => self_contained &= !VOLATILE_FUNCTIONS.contains(&name.as_str()),
+        Expr::Call(_, _) => self_contained = false,
+        _ => {}
+    });
+
+    compound && self_contained
+}
+
+/// Whether two expressions are written the same way, including the `$` anchors
+/// which `Cell` equality leaves out.
+fn same_expr(a: &Expr, b: &Expr) -> bool {
+    let anchors = |expr: &Expr| {
+        let mut found = vec![];
+        expr.walk(&mut |e| {
+            if let Expr::Reference(r) = e {
+                for cell in std::iter::once(&r.start).chain(&r.end) {
+                    found.push((cell.fixed_row, cell.fixed_column));
+                }
+            }
+        });
+        found
+    };
+    a == b && anchors(a) == anchors(b)
+}
+
+/// Hash of the shape of `expr`, equal for expressions that are `same_expr`.
+fn fingerprint(expr: &Expr) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    expr.walk(&mut |e| {
+        std::mem::discriminant(e).hash(&mut hasher);
+        match e {
+            Expr::Number(n) => n.to_bits().hash(&mut hasher),
+            Expr::Text(text) => text.hash(&mut hasher),
+            Expr::Reference(r) => (r.start.row, r.start.column).hash(&mut hasher),
+            Expr::Unary(op, _) => std::mem::discriminant(op).hash(&mut hasher),
+            Expr::Binary(op, _, _) => std::mem::discriminant(op).hash(&mut hasher),
+            Expr::Function(name, args) => (name, args.len()).hash(&mut hasher),
+            _ => {}
+        }
+    });
+    hasher.finish()
+}
+
+/// Longest hoistable sub-expression, by number of nodes, that appears at least twice
+/// across `body` and the values of `bindings`.
+fn most_repeated(body: &Expr, bindings: &[(String, Expr)], generated: &[String]) -> Option<Expr> {
+    let mut counts: Vec<(&Expr, usize)> = vec![];
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    let trees = std::iter::once(body).chain(bindings.iter().map(|(_, value)| value));
+
+    for tree in trees {
+        tree.walk(&mut |e| {
+            if !hoistable(e, generated) {
+                return;
+            }
+            let indexes = seen.entry(fingerprint(e)).or_default();
+            match indexes.iter().find(|&&i| same_expr(counts[i].0, e)) {
+                Some(&i) => counts[i].1 += 1,
+                None => {
+                    indexes.push(counts.len());
+                    counts.push((e, 1));
+                }
+            }
+        });
+    }
+
+    let size = |expr: &Expr| {
+        let mut nodes = 0;
+        expr.walk(&mut |_| nodes += 1);
+        nodes
+    };
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .max_by_key(|(e, _)| size(e))
+        .map(|(e, _)| e.clone())
+}
+
+/// Replace every sub-expression written as `target` with `replacement`.
+fn replace_expr(expr: &Expr, target: &Expr, replacement: &Expr) -> Expr {
+    if same_expr(expr, target) {
+        return replacement.clone();
+    }
+
+    let replace_all = |exprs: &[Expr]| -> Vec<Expr> {
+        exprs
+            .iter()
+            .map(|e| replace_expr(e, target, replacement))
+            .collect()
+    };
+
+    match expr {
+        Expr::Array(rows) => Expr::Array(rows.iter().map(|row| replace_all(row)).collect()),
+        Expr::Unary(op, e) => Expr::Unary(*op, Box::new(replace_expr(e, target, replacement))),
+        Expr::Binary(op, l, r) => Expr::Binary(
+            *op,
+            Box::new(replace_expr(l, target, replacement)),
+            Box::new(replace_expr(r, target, replacement)),
+        ),
+        Expr::Function(name, args) => Expr::Function(name.clone(), replace_all(args)),
+        Expr::Call(callee, args) => Expr::Call(
+            Box::new(replace_expr(callee, target, replacement)),
+            replace_all(args),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Order `LET` bindings so every variable is defined before it is used.
+fn order_bindings(bindings: Vec<(String, Expr)>) -> Vec<(String, Expr)> {
+    let uses = |value: &Expr, name: &str| {
+        let mut found = false;
+        value.walk(&mut |e| found |= matches!(e, Expr::Name(n) if n == name));
+        found
+    };
+
+    let mut pending = bindings;
+    let mut ordered: Vec<(String, Expr)> = vec![];
+    while !pending.is_empty() {
+        let ready = pending
+            .iter()
+            .position(|(_, value)| pending.iter().all(|(other, _)| !uses(value, other)))
+            .unwrap_or(0);
+        ordered.push(pending.remove(ready));
+    }
+    ordered
+}
@@ -0,0 +1,402 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::func::FunctionBuilder;
+use crate::math::parser::*;
+use crate::range::Range;
+use crate::range_index::RangeIndex;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use wasm_bindgen::prelude::*;
+
+/// Which cells feed which formulas.
+/// Formulas are registered by the cell they live in. References are kept as written,
+/// so `SUM(A:A)` is a single range edge rather than a million cell edges.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone)]
+pub struct DependencyGraph {
+    /// Areas read by each formula cell.
+    precedents: BTreeMap<Cell, Vec<Range>>,
+    /// Every area read by a formula, by id.
+    areas: RangeIndex,
+    /// Formula cell reading each area of `areas`.
+    readers: BTreeMap<u32, Cell>,
+    /// Ids in `areas` of the areas read by each formula cell.
+    edges: BTreeMap<Cell, Vec<u32>>,
+    /// Formula cells as single cell ranges, to find the formulas inside an area.
+    formula_index: RangeIndex,
+    formula_ids: BTreeMap<Cell, u32>,
+    /// Result of `components`, until a formula is set or removed.
+    components: RefCell<Option<Vec<Vec<Cell>>>>,
+}
+
+#[wasm_bindgen]
+impl DependencyGraph {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> DependencyGraph {
+        DependencyGraph::default()
+    }
+
+    /// Register the formula living in `cell`, replacing any previous one.
+    pub fn set_formula(&mut self, cell: &Cell, formula: &FunctionBuilder) {
+        self.set_expr(cell, formula.expr());
+    }
+
+    /// Forget the formula living in `cell`. Returns whether there was one.
+    pub fn remove(&mut self, cell: &Cell) -> bool {
+        if self.precedents.remove(cell).is_none() {
+            return false;
+        }
+
+        for id in self.edges.remove(cell).unwrap_or_default() {
+            self.areas.remove(id);
+            self.readers.remove(&id);
+        }
+        if let Some(id) = self.formula_ids.remove(cell) {
+            self.formula_index.remove(id);
+        }
+        self.components.replace(None);
+        true
+    }
+
+    pub fn has_formula(&self, cell: &Cell) -> bool {
+        self.precedents.contains_key(cell)
+    }
+
+    /// Areas the formula in `cell` reads, directly or through other formulas when `transitive`.
+    pub fn precedents(&self, cell: &Cell, transitive: bool) -> js_sys::Array {
+        let areas = if transitive {
+            self.all_precedents(cell)
+        } else {
+            self.direct_precedents(cell)
+        };
+        areas.into_iter().map(JsValue::from).collect()
+    }
+
+    /// Formula cells reading `cell`, directly or through other formulas when `transitive`.
+    pub fn dependents(&self, cell: &Cell, transitive: bool) -> js_sys::Array {
+        let cells = if transitive {
+            self.all_dependents(cell)
+        } else {
+            self.direct_dependents(cell)
+        };
+        cells.into_iter().map(JsValue::from).collect()
+    }
+
+    /// Formula cells ordered so that every formula comes after the formulas it reads.
+    /// Fails if formulas reference each other in a loop.
+    pub fn calculation_order(&self) -> Result<js_sys::Array, WebExcelError> {
+        Ok(self.order()?.into_iter().map(JsValue::from).collect())
+    }
+
+    /// Circular reference chains, each an array of `Cell`s where every cell reads the next
+    /// and the last reads the first.
+    pub fn circular_references(&self) -> js_sys::Array {
+        self.cycles()
+            .into_iter()
+            .map(|chain| {
+                chain
+                    .into_iter()
+                    .map(JsValue::from)
+                    .collect::<js_sys::Array>()
+            })
+            .collect()
+    }
+}
+
+impl DependencyGraph {
+    /// Register a parsed formula living in `cell`, replacing any previous one.
+    /// References without a sheet are read from the sheet of `cell`.
+    /// External workbook references and defined names are not tracked.
+    pub fn set_expr(&mut self, cell: &Cell, expr: &Expr) {
+        self.remove(cell);
+
+        let mut areas: Vec<Range> = vec![];
+        for reference in expr.references() {
+            if reference.workbook.is_some() {
+                continue;
+            }
+
+            let locate = |c: &Cell| Cell {
+                sheet: c.sheet.clone().or_else(|| cell.sheet.clone()),
+                ..c.clone()
+            };
+            let start = locate(&reference.start);
+            // `A1#` reads whatever the anchor spills, so it depends on the anchor's formula
+            let end = match &reference.end {
+                Some(end) if !reference.spill => locate(end),
+                _ => start.clone(),
+            };
+
+            if let Ok(area) = Range::new(&start, &end) {
+                if !areas.contains(&area) {
+                    areas.push(area);
+                }
+            }
+        }
+
+        let mut ids = vec![];
+        for area in &areas {
+            let id = self.areas.insert(area);
+            self.readers.insert(id, cell.clone());
+            ids.push(id);
+        }
+        if let Ok(point) = Range::new(cell, cell) {
+            let id = self.formula_index.insert(&point);
+            self.formula_ids.insert(cell.clone(), id);
+        }
+        self.edges.insert(cell.clone(), ids);
+        self.precedents.insert(cell.clone(), areas);
+        self.components.replace(None);
+    }
+
+    /// Cells holding a registered formula.
+    pub fn formulas(&self) -> impl Iterator<Item = &Cell> {
+        self.precedents.keys()
+    }
+
+    pub fn direct_precedents(&self, cell: &Cell) -> Vec<Range> {
+        self.precedents.get(cell).cloned().unwrap_or_default()
+    }
+
+    /// Areas read by `cell`, and by every formula inside those areas, recursively.
+    pub fn all_precedents(&self, cell: &Cell) -> Vec<Range> {
+        let mut found: Vec<Range> = vec![];
+        let mut visited = BTreeSet::from([cell.clone()]);
+        let mut queue = VecDeque::from([cell.clone()]);
+
+        while let Some(current) = queue.pop_front() {
+            for area in self.precedents.get(&current).into_iter().flatten() {
+                if !found.contains(area) {
+                    found.push(area.clone());
+                }
+                for formula in self.formulas_in(area) {
+                    if visited.insert(formula.clone()) {
+                        queue.push_back(formula);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    pub fn direct_dependents(&self, cell: &Cell) -> Vec<Cell> {
+        self.readers_of(self.areas.containing(cell))
+    }
+
+    /// Formula cells reading any cell of `area`, e.g. the cells reading a spilled array.
+    pub fn area_dependents(&self, area: &Range) -> Vec<Cell> {
+        self.readers_of(self.areas.intersecting(area))
+    }
+
+    /// Formula cells reading the areas with `ids`, in sheet order.
+    fn readers_of(&self, ids: Vec<u32>) -> Vec<Cell> {
+        let found: BTreeSet<&Cell> = ids.iter().filter_map(|id| self.readers.get(id)).collect();
+        found.into_iter().cloned().collect()
+    }
+
+    /// Formula cells that change when `cell` changes, nearest first.
+    pub fn all_dependents(&self, cell: &Cell) -> Vec<Cell> {
+        let mut found = vec![];
+        let mut visited = BTreeSet::from([cell.clone()]);
+        let mut queue = VecDeque::from([cell.clone()]);
+
+        while let Some(current) = queue.pop_front() {
+            for dependent in self.direct_dependents(&current) {
+                if visited.insert(dependent.clone()) {
+                    found.push(dependent.clone());
+                    queue.push_back(dependent);
+                }
+            }
+        }
+        found
+    }
+
+    /// Formula cells in calculation order, or `CircularReferenceError` on a loop.
+    pub fn order(&self) -> Result<Vec<Cell>, WebExcelError> {
+        let mut order = vec![];
+        for component in self.components() {
+            if self.is_circular(&component) {
+                return Err(WebExcelError::CircularReferenceError);
+            }
+            order.extend(component);
+        }
+        Ok(order)
+    }
+
+    /// Formula cells grouped into strongly connected components, in calculation order.
+    /// A component with more than one cell, or a cell reading itself, is a circular reference.
+    /// The result is kept until a formula is set or removed.
+    pub fn components(&self) -> Vec<Vec<Cell>> {
+        if let Some(components) = self.components.borrow().as_ref() {
+            return components.clone();
+        }
+        let components = self.components_among(self.precedents.keys());
+        self.components.replace(Some(components.clone()));
+        components
+    }
+
+    /// Like `components`, only for the formulas among `cells` and the edges between them.
+    /// The order is right for recalculating `cells` as long as every formula reading one of
+    /// them is included, e.g. a set of cells together with `all_dependents` of each.
+    pub fn components_of(&self, cells: &BTreeSet<Cell>) -> Vec<Vec<Cell>> {
+        self.components_among(cells.iter().filter(|c| self.has_formula(c)))
+    }
+
+    fn components_among<'a>(&self, cells: impl Iterator<Item = &'a Cell>) -> Vec<Vec<Cell>> {
+        let cells: Vec<&Cell> = cells.collect();
+        let index_of: BTreeMap<&Cell, usize> =
+            cells.iter().enumerate().map(|(i, c)| (*c, i)).collect();
+        let edges: Vec<Vec<usize>> = cells
+            .iter()
+            .map(|cell| {
+                self.formula_precedents(cell)
+                    .iter()
+                    .filter_map(|p| index_of.get(p).copied())
+                    .collect()
+            })
+            .collect();
+
+        tarjan(&edges)
+            .into_iter()
+            .map(|component| component.into_iter().map(|i| cells[i].clone()).collect())
+            .collect()
+    }
+
+    /// One chain per circular reference, starting from its first cell in sheet order.
+    /// Each cell of a chain reads the next one, and the last cell reads the first.
+    pub fn cycles(&self) -> Vec<Vec<Cell>> {
+        self.components()
+            .into_iter()
+            .filter(|c| self.is_circular(c))
+            .map(|component| self.shortest_loop(&component))
+            .collect()
+    }
+
+    /// Formula cells inside `area`, without visiting every cell of it.
+    fn formulas_in(&self, area: &Range) -> Vec<Cell> {
+        self.formula_index
+            .intersecting(area)
+            .into_iter()
+            .filter_map(|id| self.formula_index.get(id))
+            .map(|point| point.cell_start)
+            .collect()
+    }
+
+    /// Formula cells read by the formula in `cell`.
+    fn formula_precedents(&self, cell: &Cell) -> BTreeSet<Cell> {
+        self.precedents
+            .get(cell)
+            .into_iter()
+            .flatten()
+            .flat_map(|area| self.formulas_in(area))
+            .collect()
+    }
+
+    /// Whether a component returned by `components` is a circular reference.
+    pub fn is_circular(&self, component: &[Cell]) -> bool {
+        match component {
+            [cell] => self.formula_precedents(cell).contains(cell),
+            _ => component.len() > 1,
+        }
+    }
+
+    /// Shortest path from the first cell of `component` back to itself.
+    fn shortest_loop(&self, component: &[Cell]) -> Vec<Cell> {
+        let start = &component[0];
+        let mut previous: BTreeMap<Cell, Cell> = BTreeMap::new();
+        let mut queue = VecDeque::from([start.clone()]);
+
+        while let Some(current) = queue.pop_front() {
+            for next in self.formula_precedents(&current) {
+                if &next == start {
+                    // Walk back from `current` to rebuild the chain
+                    let mut chain = vec![current.clone()];
+                    let mut cell = &current;
+                    while cell != start {
+                        cell = &previous[cell];
+                        chain.push(cell.clone());
+                    }
+                    chain.reverse();
+                    return chain;
+                }
+                if component.contains(&next) && !previous.contains_key(&next) {
+                    previous.insert(next.clone(), current.clone());
+                    queue.push_back(next);
+                }
+            }
+        }
+        component.to_vec()
+    }
+}
+
+/// Tarjan's strongly connected components over `edges[node] = nodes it points to`.
+/// Components come out after every component they point to.
+fn tarjan(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct State {
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next: usize,
+        components: Vec<Vec<usize>>,
+    }
+
+    let n = edges.len();
+    let mut state = State {
+        index: vec![None; n],
+        low: vec![0; n],
+        on_stack: vec![false; n],
+        stack: vec![],
+        next: 0,
+        components: vec![],
+    };
+
+    // Iterative depth first search, so long chains of formulas cannot overflow the stack
+    for root in 0..n {
+        if state.index[root].is_some() {
+            continue;
+        }
+
+        let mut work: Vec<(usize, usize)> = vec![(root, 0)];
+        while let Some((node, edge)) = work.pop() {
+            if edge == 0 {
+                state.index[node] = Some(state.next);
+                state.low[node] = state.next;
+                state.next += 1;
+                state.stack.push(node);
+                state.on_stack[node] = true;
+            }
+
+            if let Some(&target) = edges[node].get(edge) {
+                work.push((node, edge + 1));
+                match state.index[target] {
+                    None => work.push((target, 0)),
+                    Some(index) if state.on_stack[target] => {
+                        state.low[node] = state.low[node].min(index);
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            // Every edge of `node` is done
+            if let Some(&(parent, _)) = work.last() {
+                state.low[parent] = state.low[parent].min(state.low[node]);
+            }
+            if Some(state.low[node]) == state.index[node] {
+                let mut component = vec![];
+                while let Some(member) = state.stack.pop() {
+                    state.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                component.sort_unstable();
+                state.components.push(component);
+            }
+        }
+    }
+
+    state.components
+}
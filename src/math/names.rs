@@ -0,0 +1,121 @@
+use crate::error::WebExcelError;
+use crate::math::func::FunctionBuilder;
+use crate::math::parser::*;
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+/// Longest name Excel accepts for defined names and `LET`/`LAMBDA` variables.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Check a name against Excel's naming rules.
+/// It must start with a letter, `_` or `\`, may not contain spaces,
+/// and may not look like a cell reference (`A1`, `R1C1`) or a boolean.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return false,
+    };
+
+    if !(first.is_alphabetic() || first == '_' || first == '\\') {
+        return false;
+    }
+    if !chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '\\' | '?')) {
+        return false;
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return false;
+    }
+
+    let upper = name.to_ascii_uppercase();
+    if upper == "R" || upper == "C" || upper == "TRUE" || upper == "FALSE" {
+        return false;
+    }
+
+    // `R1C1` style references are rejected as well
+    let r1c1 = upper
+        .strip_prefix('R')
+        .map(|rest| rest.trim_start_matches(|c: char| c.is_ascii_digit()))
+        .and_then(|rest| rest.strip_prefix('C'))
+        .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()));
+
+    !r1c1 && !matches!(parse_formula(name), Ok(Expr::Reference(_)))
+}
+
+/// Workbook level defined names, e.g. `TaxRate` => `=Settings!$B$2`
+/// or `Hypotenuse` => `=LAMBDA(a, b, SQRT(a^2 + b^2))`.
+/// Names are case insensitive, as they are in Excel.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone)]
+pub struct DefinedNames {
+    /// Keyed by upper case name, holding the name as written and its formula.
+    names: BTreeMap<String, (String, FunctionBuilder)>,
+}
+
+#[wasm_bindgen]
+impl DefinedNames {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> DefinedNames {
+        DefinedNames::default()
+    }
+
+    /// Register `name` for `formula`. Fails if the name is invalid or already taken.
+    pub fn add(&mut self, name: &str, formula: &FunctionBuilder) -> Result<(), WebExcelError> {
+        if !is_valid_name(name) {
+            return Err(WebExcelError::NameError);
+        }
+
+        let key = name.to_ascii_uppercase();
+        if self.names.contains_key(&key) {
+            return Err(WebExcelError::NameError);
+        }
+
+        self.names.insert(key, (name.to_owned(), formula.clone()));
+        Ok(())
+    }
+
+    /// Register a custom function. `formula` must be a `LAMBDA`.
+    pub fn add_lambda(
+        &mut self,
+        name: &str,
+        formula: &FunctionBuilder,
+    ) -> Result<(), WebExcelError> {
+        if !formula.is_lambda() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+        self.add(name, formula)
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.names.remove(&name.to_ascii_uppercase()).is_some()
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.names.contains_key(&name.to_ascii_uppercase())
+    }
+
+    pub fn get(&self, name: &str) -> Option<FunctionBuilder> {
+        self.lookup(name).cloned()
+    }
+
+    /// Registered names as written, in case insensitive order.
+    pub fn names(&self) -> js_sys::Array {
+        self.iter()
+            .map(|(name, _)| JsValue::from(name))
+            .collect::<js_sys::Array>()
+    }
+}
+
+impl DefinedNames {
+    pub fn lookup(&self, name: &str) -> Option<&FunctionBuilder> {
+        self.names
+            .get(&name.to_ascii_uppercase())
+            .map(|(_, formula)| formula)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &FunctionBuilder)> {
+        self.names
+            .values()
+            .map(|(name, formula)| (name.as_str(), formula))
+    }
+}
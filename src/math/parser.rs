@@ -0,0 +1,1070 @@
+use crate::cell::{same_sheet, Cell};
+use crate::error::WebExcelError;
+use crate::math::func::{MAX_FORMULA_LENGTH, MAX_NESTING};
+use crate::range::Range;
+use crate::util::cell_handle::*;
+
+/// Error literals that can appear inside a formula, e.g. `=IFERROR(A1, #N/A)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorValue {
+    Null,
+    Div0,
+    Value,
+    Ref,
+    Name,
+    Num,
+    NA,
+    GettingData,
+    Spill,
+    Calc,
+}
+
+impl ErrorValue {
+    /// Every error value, in the order of their `ERROR.TYPE` number.
+    pub const ALL: [ErrorValue; 10] = [
+        ErrorValue::Null,
+        ErrorValue::Div0,
+        ErrorValue::Value,
+        ErrorValue::Ref,
+        ErrorValue::Name,
+        ErrorValue::Num,
+        ErrorValue::NA,
+        ErrorValue::GettingData,
+        ErrorValue::Spill,
+        ErrorValue::Calc,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorValue::Null => "#NULL!",
+            ErrorValue::Div0 => "#DIV/0!",
+            ErrorValue::Value => "#VALUE!",
+            ErrorValue::Ref => "#REF!",
+            ErrorValue::Name => "#NAME?",
+            ErrorValue::Num => "#NUM!",
+            ErrorValue::NA => "#N/A",
+            ErrorValue::GettingData => "#GETTING_DATA",
+            ErrorValue::Spill => "#SPILL!",
+            ErrorValue::Calc => "#CALC!",
+        }
+    }
+
+    /// Match the error literal at the beginning of `s` (case insensitive).
+    fn match_prefix(s: &str) -> Option<ErrorValue> {
+        ErrorValue::ALL.into_iter().find(|e| {
+            let literal = e.as_str();
+            s.len() >= literal.len()
+                && s.is_char_boundary(literal.len())
+                && s[..literal.len()].eq_ignore_ascii_case(literal)
+        })
+    }
+}
+
+/// Prefix and infix operators, listed with the precedence Excel applies.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+impl BinaryOp {
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => 1,
+            BinaryOp::Concat => 2,
+            BinaryOp::Add | BinaryOp::Sub => 3,
+            BinaryOp::Mul | BinaryOp::Div => 4,
+            BinaryOp::Pow => 5,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Concat => "&",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Pow => "^",
+        }
+    }
+
+    fn from_symbol(s: &str) -> Option<BinaryOp> {
+        let op = match s {
+            "=" => BinaryOp::Eq,
+            "<>" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            "&" => BinaryOp::Concat,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "^" => BinaryOp::Pow,
+            _ => return None,
+        };
+        Some(op)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Plus,
+    Minus,
+    Percent,
+}
+
+const PRECEDENCE_UNARY: u8 = 6;
+const PRECEDENCE_PERCENT: u8 = 7;
+const PRECEDENCE_PRIMARY: u8 = 8;
+
+/// A cell or range reference found in a formula.
+/// The sheet lives on the cells themselves, the workbook is only set for external references
+/// such as `[Book2.xlsx]Sheet1!A1`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub workbook: Option<String>,
+    pub start: Cell,
+    pub end: Option<Cell>,
+    /// Spill reference, e.g. `A1#` for the whole array spilled from `A1`
+    pub spill: bool,
+}
+
+impl Reference {
+    pub fn sheet(&self) -> Option<&str> {
+        self.start.sheet.as_deref()
+    }
+
+    /// Area covered by the reference. A single cell reference becomes a 1x1 range.
+    pub fn range(&self) -> Result<Range, WebExcelError> {
+        Range::new(&self.start, self.end.as_ref().unwrap_or(&self.start))
+    }
+
+    /// Reference moved by `rows` and `columns`, see `Expr::relocate`.
+    pub fn relocate(&self, rows: i64, columns: i64) -> Result<Reference, WebExcelError> {
+        let (whole_columns, whole_rows) = match &self.end {
+            Some(end) => (
+                self.start.row == 0 && end.row == MAX_ROW,
+                self.start.column == 0 && end.column == MAX_COLUMN,
+            ),
+            None => (false, false),
+        };
+
+        let shift = |cell: &Cell| -> Result<Cell, WebExcelError> {
+            let moved = |index: u32, fixed: bool, by: i64, max: u32| {
+                if fixed || by == 0 {
+                    return Ok(index);
+                }
+                u32::try_from(index as i64 + by)
+                    .ok()
+                    .filter(|i| *i <= max)
+                    .ok_or(WebExcelError::RelocateError)
+            };
+
+            let mut cell = cell.clone();
+            if !whole_columns {
+                cell.row = moved(cell.row, cell.fixed_row, rows, MAX_ROW)?;
+            }
+            if !whole_rows {
+                cell.column = moved(cell.column, cell.fixed_column, columns, MAX_COLUMN)?;
+            }
+            Ok(cell)
+        };
+
+        Ok(Reference {
+            start: shift(&self.start)?,
+            end: self.end.as_ref().map(shift).transpose()?,
+            ..self.clone()
+        })
+    }
+
+    fn to_formula(&self) -> Result<String, WebExcelError> {
+        if self.spill {
+            let anchor = Reference {
+                spill: false,
+                ..self.clone()
+            };
+            return Ok(format!("{}#", anchor.to_formula()?));
+        }
+
+        let prefix = match self.sheet() {
+            Some(sheet) => format!("{}!", quote_sheet(self.workbook.as_deref(), sheet)),
+            None => String::new(),
+        };
+
+        let end = match &self.end {
+            Some(end) => end,
+            None => {
+                let addr = r1c1_to_address(
+                    self.start.row,
+                    self.start.column,
+                    self.start.fixed_row,
+                    self.start.fixed_column,
+                )?;
+                return Ok(format!("{}{}", prefix, addr));
+            }
+        };
+
+        // Whole column (`A:B`) and whole row (`1:2`) references
+        if self.start.row == 0 && end.row == MAX_ROW {
+            return Ok(format!(
+                "{}{}:{}",
+                prefix,
+                column_letters(self.start.column, self.start.fixed_column)?,
+                column_letters(end.column, end.fixed_column)?
+            ));
+        }
+        if self.start.column == 0 && end.column == MAX_COLUMN {
+            return Ok(format!(
+                "{}{}:{}",
+                prefix,
+                row_digits(self.start.row, self.start.fixed_row),
+                row_digits(end.row, end.fixed_row)
+            ));
+        }
+
+        Ok(format!(
+            "{}{}:{}",
+            prefix,
+            r1c1_to_address(
+                self.start.row,
+                self.start.column,
+                self.start.fixed_row,
+                self.start.fixed_column
+            )?,
+            r1c1_to_address(end.row, end.column, end.fixed_row, end.fixed_column)?
+        ))
+    }
+}
+
+/// Abstract syntax tree of an Excel formula.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(ErrorValue),
+    /// Omitted function argument, e.g. the second argument of `=IF(A1,,1)`
+    Missing,
+    Reference(Reference),
+    /// Defined name or `LET`/`LAMBDA` variable
+    Name(String),
+    /// Array constant, e.g. `{1,2;3,4}`. Stored row by row.
+    Array(Vec<Vec<Expr>>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// Function call. The name is stored in upper case.
+    Function(String, Vec<Expr>),
+    /// Invocation of a function value, e.g. `=LAMBDA(x, x + 1)(2)`
+    Call(Box<Expr>, Vec<Expr>),
+}
+
+impl Expr {
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(op, _, _) => op.precedence(),
+            Expr::Unary(UnaryOp::Percent, _) => PRECEDENCE_PERCENT,
+            Expr::Unary(_, _) => PRECEDENCE_UNARY,
+            Expr::Number(n) if *n < 0.0 => PRECEDENCE_UNARY,
+            _ => PRECEDENCE_PRIMARY,
+        }
+    }
+
+    /// Visit every node of the tree, parent first.
+    pub fn walk<'a>(&'a self, visit: &mut dyn FnMut(&'a Expr)) {
+        visit(self);
+        match self {
+            Expr::Array(rows) => rows.iter().flatten().for_each(|e| e.walk(visit)),
+            Expr::Unary(_, e) => e.walk(visit),
+            Expr::Binary(_, l, r) => {
+                l.walk(visit);
+                r.walk(visit);
+            }
+            Expr::Function(_, args) => args.iter().for_each(|e| e.walk(visit)),
+            Expr::Call(callee, args) => {
+                callee.walk(visit);
+                args.iter().for_each(|e| e.walk(visit));
+            }
+            _ => {}
+        }
+    }
+
+    /// Collect every cell or range reference in the formula.
+    pub fn references(&self) -> Vec<&Reference> {
+        let mut found = vec![];
+        self.walk(&mut |e| {
+            if let Expr::Reference(r) = e {
+                found.push(r);
+            }
+        });
+        found
+    }
+
+    /// Move the relative parts of every reference by `rows` and `columns`, as Excel does when a
+    /// formula is copied to another cell: `=A1+$B$1` moved by one row becomes `=A2+$B$1`.
+    /// Whole row and whole column references keep their full span.
+    /// Fails with `RelocateError` when a reference would leave the sheet.
+    pub fn relocate(&self, rows: i64, columns: i64) -> Result<Expr, WebExcelError> {
+        let all = |exprs: &[Expr]| -> Result<Vec<Expr>, WebExcelError> {
+            exprs.iter().map(|e| e.relocate(rows, columns)).collect()
+        };
+
+        Ok(match self {
+            Expr::Reference(r) => Expr::Reference(r.relocate(rows, columns)?),
+            Expr::Array(items) => {
+                Expr::Array(items.iter().map(|row| all(row)).collect::<Result<_, _>>()?)
+            }
+            Expr::Unary(op, e) => Expr::Unary(*op, Box::new(e.relocate(rows, columns)?)),
+            Expr::Binary(op, l, r) => Expr::Binary(
+                *op,
+                Box::new(l.relocate(rows, columns)?),
+                Box::new(r.relocate(rows, columns)?),
+            ),
+            Expr::Function(name, args) => Expr::Function(name.clone(), all(args)?),
+            Expr::Call(callee, args) => {
+                Expr::Call(Box::new(callee.relocate(rows, columns)?), all(args)?)
+            }
+            other => other.clone(),
+        })
+    }
+
+    /// Deepest chain of nested function calls, e.g. `IF(A1, SUM(B1:B2))` is nested 2 levels.
+    pub fn nesting_depth(&self) -> usize {
+        match self {
+            Expr::Array(rows) => rows
+                .iter()
+                .flatten()
+                .map(Expr::nesting_depth)
+                .max()
+                .unwrap_or(0),
+            Expr::Unary(_, e) => e.nesting_depth(),
+            Expr::Binary(_, l, r) => l.nesting_depth().max(r.nesting_depth()),
+            Expr::Function(_, args) => 1 + args.iter().map(Expr::nesting_depth).max().unwrap_or(0),
+            Expr::Call(callee, args) => {
+                1 + args
+                    .iter()
+                    .map(Expr::nesting_depth)
+                    .max()
+                    .unwrap_or(0)
+                    .max(callee.nesting_depth())
+            }
+            _ => 0,
+        }
+    }
+
+    /// Render the tree back into formula text (without the leading `=`).
+    pub fn to_formula(&self) -> Result<String, WebExcelError> {
+        let text = match self {
+            Expr::Number(n) => format_number(*n),
+            Expr::Text(s) => quote_text(s),
+            Expr::Bool(true) => "TRUE".to_owned(),
+            Expr::Bool(false) => "FALSE".to_owned(),
+            Expr::Error(e) => e.as_str().to_owned(),
+            Expr::Missing => String::new(),
+            Expr::Reference(r) => r.to_formula()?,
+            Expr::Name(name) => name.clone(),
+            Expr::Array(rows) => {
+                let mut rendered = Vec::with_capacity(rows.len());
+                for row in rows {
+                    rendered.push(join_formula(row, ",")?);
+                }
+                format!("{{{}}}", rendered.join(";"))
+            }
+            Expr::Unary(UnaryOp::Percent, e) => {
+                format!("{}%", e.to_formula_within(PRECEDENCE_PERCENT, false)?)
+            }
+            Expr::Unary(op, e) => {
+                let sign = if *op == UnaryOp::Minus { "-" } else { "+" };
+                format!("{}{}", sign, e.to_formula_within(PRECEDENCE_UNARY, false)?)
+            }
+            Expr::Binary(op, l, r) => format!(
+                "{}{}{}",
+                l.to_formula_within(op.precedence(), false)?,
+                op.symbol(),
+                r.to_formula_within(op.precedence(), true)?
+            ),
+            Expr::Function(name, args) => format!("{}({})", name, join_formula(args, ",")?),
+            Expr::Call(callee, args) => {
+                let callee = match callee.as_ref() {
+                    Expr::Function(_, _) | Expr::Name(_) => callee.to_formula()?,
+                    other => format!("({})", other.to_formula()?),
+                };
+                format!("{}({})", callee, join_formula(args, ",")?)
+            }
+        };
+
+        Ok(text)
+    }
+
+    /// Render as an operand of an operator with `precedence`, adding parentheses if needed.
+    /// Operators are left associative, so a right operand of equal precedence needs them too.
+    fn to_formula_within(&self, precedence: u8, right: bool) -> Result<String, WebExcelError> {
+        let own = self.precedence();
+        if own < precedence || (right && own == precedence) {
+            Ok(format!("({})", self.to_formula()?))
+        } else {
+            self.to_formula()
+        }
+    }
+}
+
+fn join_formula(exprs: &[Expr], separator: &str) -> Result<String, WebExcelError> {
+    let mut rendered = Vec::with_capacity(exprs.len());
+    for e in exprs {
+        rendered.push(e.to_formula()?);
+    }
+    Ok(rendered.join(separator))
+}
+
+fn format_number(n: f64) -> String {
+    format!("{}", n)
+}
+
+/// Wrap a string literal in double quotes, doubling any embedded quote.
+pub fn quote_text(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// Sheet prefix as written in a formula, quoted with `'` when the name requires it.
+/// `Sheet1` stays as is, `My Sheet` becomes `'My Sheet'`, `[Book.xlsx]Data` keeps its brackets.
+pub fn quote_sheet(workbook: Option<&str>, sheet: &str) -> String {
+    let plain = !sheet.is_empty()
+        && sheet
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        && !sheet.starts_with(|c: char| c.is_ascii_digit())
+        && parse_cell_word(sheet).is_none()
+        && workbook.iter().all(|w| {
+            w.chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        });
+
+    let qualified = match workbook {
+        Some(w) => format!("[{}]{}", w, sheet),
+        None => sheet.to_owned(),
+    };
+
+    if plain {
+        qualified
+    } else {
+        format!("'{}'", qualified.replace('\'', "''"))
+    }
+}
+
+fn column_letters(column: u32, fixed: bool) -> Result<String, WebExcelError> {
+    let addr = r1c1_to_address(0, column, false, fixed)?;
+    Ok(addr
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .to_owned())
+}
+
+fn row_digits(row: u32, fixed: bool) -> String {
+    if fixed {
+        format!("${}", row + 1)
+    } else {
+        format!("{}", row + 1)
+    }
+}
+
+/// Parse `A1`, `$A$1`, `A$1` and `$A1` into a cell, keeping the anchors.
+fn parse_cell_word(word: &str) -> Option<Cell> {
+    let (fixed_column, rest) = match word.strip_prefix('$') {
+        Some(rest) => (true, rest),
+        None => (false, word),
+    };
+    let letters = rest.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+    if letters == 0 || letters > 3 {
+        return None;
+    }
+    let (column_part, rest) = rest.split_at(letters);
+    let (fixed_row, row_part) = match rest.strip_prefix('$') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    if row_part.is_empty() || !row_part.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut cell = address_to_r1c1(&format!("{}{}", column_part, row_part)).ok()?;
+    if cell.row > MAX_ROW || cell.column > MAX_COLUMN {
+        return None;
+    }
+    cell.fixed_row = fixed_row;
+    cell.fixed_column = fixed_column;
+    Some(cell)
+}
+
+/// Parse a whole-column bound such as `A` or `$XFD`.
+fn parse_column_word(word: &str) -> Option<(u32, bool)> {
+    let (fixed, rest) = match word.strip_prefix('$') {
+        Some(rest) => (true, rest),
+        None => (false, word),
+    };
+    if rest.is_empty() || rest.len() > 3 || !rest.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let cell = address_to_r1c1(&format!("{}1", rest)).ok()?;
+    if cell.column > MAX_COLUMN {
+        return None;
+    }
+    Some((cell.column, fixed))
+}
+
+/// Parse a whole-row bound such as `3` or `$3`.
+fn parse_row_word(word: &str) -> Option<(u32, bool)> {
+    let (fixed, rest) = match word.strip_prefix('$') {
+        Some(rest) => (true, rest),
+        None => (false, word),
+    };
+    if rest.is_empty() || !rest.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let row: u32 = rest.parse().ok()?;
+    if row == 0 || row - 1 > MAX_ROW {
+        return None;
+    }
+    Some((row - 1, fixed))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64, String),
+    Text(String),
+    Error(ErrorValue),
+    Word(String),
+    /// Sheet qualifier ending with `!`, with the optional external workbook
+    Sheet(Option<String>, String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Semicolon,
+    Colon,
+    Hash,
+}
+
+fn is_word_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '\\' || c == '$'
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '\\' | '$' | '.' | '?')
+}
+
+/// Split `[Book.xlsx]Sheet1` into its workbook and sheet parts.
+pub fn split_workbook(qualified: &str) -> (Option<String>, String) {
+    if let Some(rest) = qualified.strip_prefix('[') {
+        if let Some(close) = rest.find(']') {
+            return (Some(rest[..close].to_owned()), rest[close + 1..].to_owned());
+        }
+    }
+    (None, qualified.to_owned())
+}
+
+fn tokenize(formula: &str) -> Result<Vec<Token>, WebExcelError> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '"' => {
+                // String literal, `""` stands for a single quote character
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('"') if chars.get(i + 1) == Some(&'"') => {
+                            text.push('"');
+                            i += 2;
+                        }
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(ch) => {
+                            text.push(*ch);
+                            i += 1;
+                        }
+                        None => return Err(WebExcelError::FormulaParseError),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            '\'' => {
+                // Quoted sheet name, `''` stands for a single apostrophe
+                let mut name = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                            name.push('\'');
+                            i += 2;
+                        }
+                        Some('\'') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(ch) => {
+                            name.push(*ch);
+                            i += 1;
+                        }
+                        None => return Err(WebExcelError::FormulaParseError),
+                    }
+                }
+                if chars.get(i) != Some(&'!') {
+                    return Err(WebExcelError::FormulaParseError);
+                }
+                i += 1;
+                let (workbook, sheet) = split_workbook(&name);
+                tokens.push(Token::Sheet(workbook, sheet));
+            }
+            '[' => {
+                // Unquoted external reference, e.g. `[Book2.xlsx]Sheet1!A1`
+                let start = i;
+                while i < chars.len() && chars[i] != '!' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(WebExcelError::FormulaParseError);
+                }
+                let name: String = chars[start..i].iter().collect();
+                i += 1;
+                let (workbook, sheet) = split_workbook(&name);
+                tokens.push(Token::Sheet(workbook, sheet));
+            }
+            '#' => {
+                let rest: String = chars[i..].iter().collect();
+                match ErrorValue::match_prefix(&rest) {
+                    Some(e) => {
+                        i += e.as_str().chars().count();
+                        tokens.push(Token::Error(e));
+                    }
+                    None => {
+                        i += 1;
+                        tokens.push(Token::Hash);
+                    }
+                }
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())) =>
+            {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let value = text
+                    .parse::<f64>()
+                    .map_err(|_| WebExcelError::FormulaParseError)?;
+                tokens.push(Token::Number(value, text));
+            }
+            c if is_word_start(c) => {
+                let start = i;
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if chars.get(i) == Some(&'!') {
+                    i += 1;
+                    tokens.push(Token::Sheet(None, word));
+                } else {
+                    tokens.push(Token::Word(word));
+                }
+            }
+            '<' | '>' => {
+                let op = match (c, chars.get(i + 1)) {
+                    ('<', Some('>')) => "<>",
+                    ('<', Some('=')) => "<=",
+                    ('>', Some('=')) => ">=",
+                    ('<', _) => "<",
+                    _ => ">",
+                };
+                i += op.len();
+                tokens.push(Token::Op(op));
+            }
+            _ => {
+                let token = match c {
+                    '+' => Token::Op("+"),
+                    '-' => Token::Op("-"),
+                    '*' => Token::Op("*"),
+                    '/' => Token::Op("/"),
+                    '^' => Token::Op("^"),
+                    '&' => Token::Op("&"),
+                    '=' => Token::Op("="),
+                    '%' => Token::Op("%"),
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '{' => Token::LBrace,
+                    '}' => Token::RBrace,
+                    ',' => Token::Comma,
+                    ';' => Token::Semicolon,
+                    ':' => Token::Colon,
+                    _ => return Err(WebExcelError::FormulaParseError),
+                };
+                i += 1;
+                tokens.push(token);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Nested levels of parentheses, function calls, arrays and signs the parser accepts, so that
+/// deep formulas fail before they exhaust the stack. Twice Excel's limit on nested functions,
+/// which `FunctionBuilder` checks once parsed, leaves room for parentheses and signs.
+pub const MAX_PARSE_DEPTH: usize = 2 * MAX_NESTING;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Nested expressions being parsed.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), WebExcelError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            _ => Err(WebExcelError::FormulaParseError),
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, WebExcelError> {
+        self.parse_binary(1)
+    }
+
+    /// Precedence climbing over the binary operators. `^` and below are all left associative.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, WebExcelError> {
+        let mut lhs = self.parse_unary()?;
+
+        while let Some(Token::Op(symbol)) = self.peek() {
+            let op = match BinaryOp::from_symbol(symbol) {
+                Some(op) if op.precedence() >= min_precedence => op,
+                _ => break,
+            };
+            self.next();
+            let rhs = self.parse_binary(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    /// Every nested expression is parsed from here, which keeps track of the depth.
+    fn parse_unary(&mut self) -> Result<Expr, WebExcelError> {
+        if self.depth == MAX_PARSE_DEPTH {
+            return Err(WebExcelError::FormulaNestingError);
+        }
+        self.depth += 1;
+        let expr = self.parse_signed();
+        self.depth -= 1;
+        expr
+    }
+
+    fn parse_signed(&mut self) -> Result<Expr, WebExcelError> {
+        match self.peek() {
+            Some(Token::Op("-")) => {
+                self.next();
+                Ok(Expr::Unary(UnaryOp::Minus, Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Op("+")) => {
+                self.next();
+                Ok(Expr::Unary(UnaryOp::Plus, Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, WebExcelError> {
+        let mut expr = self.parse_primary()?;
+
+        loop {
+            match self.peek() {
+                Some(Token::Op("%")) => {
+                    self.next();
+                    expr = Expr::Unary(UnaryOp::Percent, Box::new(expr));
+                }
+                // Spill operator, only valid right after a single cell
+                Some(Token::Hash) => match &mut expr {
+                    Expr::Reference(r) if r.end.is_none() && !r.spill => {
+                        self.next();
+                        r.spill = true;
+                    }
+                    _ => return Err(WebExcelError::FormulaParseError),
+                },
+                Some(Token::LParen) if matches!(expr, Expr::Function(_, _)) => {
+                    self.next();
+                    let args = self.parse_arguments()?;
+                    expr = Expr::Call(Box::new(expr), args);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, WebExcelError> {
+        match self.next() {
+            Some(Token::Number(n, text)) => {
+                // `1:3` is a whole-row reference rather than a number
+                if self.peek() == Some(&Token::Colon) {
+                    return self.parse_reference(None, None, Token::Number(n, text));
+                }
+                Ok(Expr::Number(n))
+            }
+            Some(Token::Text(s)) => Ok(Expr::Text(s)),
+            Some(Token::Error(e)) => Ok(Expr::Error(e)),
+            Some(Token::LParen) => {
+                let inner = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                if self.peek() == Some(&Token::LParen) {
+                    self.next();
+                    let args = self.parse_arguments()?;
+                    return Ok(Expr::Call(Box::new(inner), args));
+                }
+                Ok(inner)
+            }
+            Some(Token::LBrace) => self.parse_array(),
+            Some(Token::Sheet(workbook, sheet)) => match self.next() {
+                Some(token) => self.parse_reference(workbook, Some(sheet), token),
+                None => Err(WebExcelError::FormulaParseError),
+            },
+            Some(Token::Word(word)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.next();
+                    let args = self.parse_arguments()?;
+                    return Ok(Expr::Function(word.to_ascii_uppercase(), args));
+                }
+                if word.eq_ignore_ascii_case("TRUE") {
+                    return Ok(Expr::Bool(true));
+                }
+                if word.eq_ignore_ascii_case("FALSE") {
+                    return Ok(Expr::Bool(false));
+                }
+                if self.peek() == Some(&Token::Colon) || parse_cell_word(&word).is_some() {
+                    return self.parse_reference(None, None, Token::Word(word));
+                }
+                Ok(Expr::Name(word))
+            }
+            _ => Err(WebExcelError::FormulaParseError),
+        }
+    }
+
+    /// Parse function arguments after the opening parenthesis. Empty arguments become `Missing`.
+    fn parse_arguments(&mut self) -> Result<Vec<Expr>, WebExcelError> {
+        let mut args = vec![];
+        if self.peek() == Some(&Token::RParen) {
+            self.next();
+            return Ok(args);
+        }
+
+        loop {
+            match self.peek() {
+                Some(Token::Comma) | Some(Token::RParen) => args.push(Expr::Missing),
+                _ => args.push(self.parse_expr()?),
+            }
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => break,
+                _ => return Err(WebExcelError::FormulaParseError),
+            }
+        }
+
+        Ok(args)
+    }
+
+    fn parse_array(&mut self) -> Result<Expr, WebExcelError> {
+        let mut rows = vec![vec![]];
+        loop {
+            let element = self.parse_unary()?;
+            rows.last_mut().unwrap().push(element);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::Semicolon) => rows.push(vec![]),
+                Some(Token::RBrace) => break,
+                _ => return Err(WebExcelError::FormulaParseError),
+            }
+        }
+
+        let width = rows[0].len();
+        if rows.iter().any(|row| row.len() != width) {
+            return Err(WebExcelError::FormulaParseError);
+        }
+        Ok(Expr::Array(rows))
+    }
+
+    /// Parse a cell, range, whole-column or whole-row reference starting at `first`.
+    fn parse_reference(
+        &mut self,
+        workbook: Option<String>,
+        sheet: Option<String>,
+        first: Token,
+    ) -> Result<Expr, WebExcelError> {
+        let first = match first {
+            Token::Word(word) => word,
+            Token::Number(_, text) => text,
+            _ => return Err(WebExcelError::FormulaParseError),
+        };
+
+        if self.peek() != Some(&Token::Colon) {
+            let mut start = parse_cell_word(&first).ok_or(WebExcelError::FormulaParseError)?;
+            start.sheet = sheet;
+            return Ok(Expr::Reference(Reference {
+                workbook,
+                start,
+                end: None,
+                spill: false,
+            }));
+        }
+        self.next();
+
+        // The end of a range may repeat the sheet, e.g. `Sheet1!A1:Sheet1!B2`
+        let second = match self.next() {
+            Some(Token::Sheet(w, s)) if w == workbook && same_sheet(&Some(s.clone()), &sheet) => {
+                self.next()
+            }
+            Some(Token::Sheet(_, _)) => return Err(WebExcelError::RangeDiffSheetError),
+            token => token,
+        };
+        let second = match second {
+            Some(Token::Word(word)) => word,
+            Some(Token::Number(_, text)) => text,
+            _ => return Err(WebExcelError::FormulaParseError),
+        };
+
+        let (mut start, mut end) = if let (Some(s), Some(e)) =
+            (parse_cell_word(&first), parse_cell_word(&second))
+        {
+            (s, e)
+        } else if let (Some(s), Some(e)) = (parse_column_word(&first), parse_column_word(&second)) {
+            (whole_column(s.0, s.1, 0), whole_column(e.0, e.1, MAX_ROW))
+        } else if let (Some(s), Some(e)) = (parse_row_word(&first), parse_row_word(&second)) {
+            (whole_row(s.0, s.1, 0), whole_row(e.0, e.1, MAX_COLUMN))
+        } else {
+            return Err(WebExcelError::FormulaParseError);
+        };
+
+        // Keep the start above and left of the end, the way `Range` does
+        if start.row > end.row {
+            std::mem::swap(&mut start.row, &mut end.row);
+            std::mem::swap(&mut start.fixed_row, &mut end.fixed_row);
+        }
+        if start.column > end.column {
+            std::mem::swap(&mut start.column, &mut end.column);
+            std::mem::swap(&mut start.fixed_column, &mut end.fixed_column);
+        }
+        start.sheet = sheet.clone();
+        end.sheet = sheet;
+
+        Ok(Expr::Reference(Reference {
+            workbook,
+            start,
+            end: Some(end),
+            spill: false,
+        }))
+    }
+}
+
+fn whole_column(column: u32, fixed: bool, row: u32) -> Cell {
+    Cell {
+        row,
+        column,
+        fixed_column: fixed,
+        ..Default::default()
+    }
+}
+
+fn whole_row(row: u32, fixed: bool, column: u32) -> Cell {
+    Cell {
+        row,
+        column,
+        fixed_row: fixed,
+        ..Default::default()
+    }
+}
+
+/// Parse formula text into an `Expr`. The leading `=` is optional.
+///
+/// # Examples
+///
+/// ```
+/// let expr = parse_formula("=SUM(Sheet2!A1:B3) * 2").unwrap();
+/// assert_eq!(expr.to_formula().unwrap(), "SUM(Sheet2!A1:B3)*2");
+/// ```
+///
+pub fn parse_formula(formula: &str) -> Result<Expr, WebExcelError> {
+    let body = formula.trim_start();
+    let body = body.strip_prefix('=').unwrap_or(body);
+    if body.chars().count() > MAX_FORMULA_LENGTH {
+        return Err(WebExcelError::FormulaLengthError);
+    }
+
+    let tokens = tokenize(body)?;
+    if tokens.is_empty() {
+        return Err(WebExcelError::FormulaParseError);
+    }
+
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.parse_expr()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(WebExcelError::FormulaParseError);
+    }
+
+    Ok(expr)
+}
@@ -0,0 +1,100 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::range::Range;
+use crate::range_index::RangeIndex;
+use wasm_bindgen::prelude::*;
+
+/// Merged areas of the sheets of a workbook, which never overlap.
+/// Selecting any cell of a merge selects the whole of it, and its value lives in the top-left cell.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone)]
+pub struct MergedCells {
+    index: RangeIndex,
+}
+
+#[wasm_bindgen]
+impl MergedCells {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> MergedCells {
+        MergedCells::default()
+    }
+
+    /// Merge `range`. Fails if it overlaps an existing merge. A single cell is left alone.
+    pub fn merge(&mut self, range: &Range) -> Result<(), WebExcelError> {
+        if !self.index.intersecting(range).is_empty() {
+            return Err(WebExcelError::MergeOverlapError);
+        }
+        if range.cells > 1 {
+            self.index.insert(range);
+        }
+        Ok(())
+    }
+
+    /// Remove every merge overlapping `range`, returning how many were removed.
+    pub fn unmerge(&mut self, range: &Range) -> usize {
+        let ids = self.index.intersecting(range);
+        for id in &ids {
+            self.index.remove(*id);
+        }
+        ids.len()
+    }
+
+    pub fn is_merged(&self, cell: &Cell) -> bool {
+        !self.index.containing(cell).is_empty()
+    }
+
+    /// Merged area containing `cell`, if any.
+    pub fn merge_of(&self, cell: &Cell) -> Option<Range> {
+        let id = *self.index.containing(cell).first()?;
+        self.index.get(id)
+    }
+
+    /// Top-left cell of the merge containing `cell`, which holds its value, or `cell` itself.
+    pub fn anchor(&self, cell: &Cell) -> Cell {
+        match self.merge_of(cell) {
+            Some(range) => range.cell_start,
+            None => cell.clone(),
+        }
+    }
+
+    /// Smallest range covering `range` and every merge it touches, the way Excel grows a selection.
+    /// Growing can reach further merges, which are covered as well.
+    pub fn expand(&self, range: &Range) -> Range {
+        let mut expanded = range.clone();
+        loop {
+            let grown = self
+                .index
+                .intersecting(&expanded)
+                .into_iter()
+                .filter_map(|id| self.index.get(id))
+                .fold(expanded.clone(), |bounds, merge| {
+                    bounds.union(&merge).unwrap_or(bounds)
+                });
+            if grown == expanded {
+                return expanded;
+            }
+            expanded = grown;
+        }
+    }
+
+    /// Merged areas as an array of `Range`, in the order they were merged.
+    pub fn areas(&self) -> js_sys::Array {
+        self.iter().cloned().map(JsValue::from).collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
+
+impl MergedCells {
+    /// Merged areas in the order they were merged.
+    pub fn iter(&self) -> impl Iterator<Item = &Range> {
+        self.index.iter().map(|(_, range)| range)
+    }
+}
@@ -1,5 +1,6 @@
-use crate::cell::Cell;
+use crate::cell::{same_sheet, Cell};
 use crate::error::WebExcelError;
+use crate::util::cell_handle::{MAX_COLUMN, MAX_ROW};
 use std::mem;
 use wasm_bindgen::prelude::*;
 
@@ -24,7 +25,9 @@ pub struct Range {
 
     pub columns: u32,
     pub rows: u32,
-    pub cells: u32,
+    /// Number of cells, up to 2^34 for the whole sheet.
+    #[wasm_bindgen(skip)]
+    pub cells: u64,
 }
 
 impl PartialEq for Range {
@@ -57,7 +60,7 @@ impl Range {
 
     #[wasm_bindgen(constructor)]
     pub fn new(start: &Cell, end: &Cell) -> Result<Range, WebExcelError> {
-        if start.sheet != end.sheet {
+        if !same_sheet(&start.sheet, &end.sheet) {
             return Err(WebExcelError::RangeDiffSheetError);
         }
 
@@ -66,7 +69,8 @@ impl Range {
             cell_end: end.clone(),
             columns: start.column.abs_diff(end.column) + 1,
             rows: start.row.abs_diff(end.row) + 1,
-            cells: (start.column.abs_diff(end.column) + 1) * (start.row.abs_diff(end.row) + 1),
+            cells: (start.column.abs_diff(end.column) as u64 + 1)
+                * (start.row.abs_diff(end.row) as u64 + 1),
         };
 
         // Check starting cell and ending cell, re-arragne them if necessary
@@ -75,6 +79,12 @@ impl Range {
         Ok(range)
     }
 
+    /// Number of cells, as a JS number since a whole sheet does not fit in 32 bits.
+    #[wasm_bindgen(getter = cells)]
+    pub fn cells_js(&self) -> f64 {
+        self.cells as f64
+    }
+
     /// Convert the range to a string representation.
     pub fn to_str_address(&self) -> Result<String, WebExcelError> {
         let addr_start = self.cell_start.to_str_address()?;
@@ -83,111 +93,322 @@ impl Range {
         Ok(format!("{}:{}", addr_start, addr_end))
     }
 
-    /// Check if a cell is within the range.
+    /// Check if a cell is within the range, on the same sheet.
     pub fn has(&self, target: &Cell) -> bool {
-        self.cell_start.row <= target.row
+        same_sheet(&self.cell_start.sheet, &target.sheet)
+            && self.cell_start.row <= target.row
             && self.cell_end.row >= target.row
             && self.cell_start.column <= target.column
             && self.cell_end.column >= target.column
     }
 
-    /// Check if a range is completely within this range.
+    /// Check if a range is completely within this range, on the same sheet.
     pub fn includes(&self, target: &Range) -> bool {
-        self.cell_start.row <= target.cell_start.row
+        self.same_sheet(target)
+            && self.cell_start.row <= target.cell_start.row
             && self.cell_end.row >= target.cell_end.row
             && self.cell_start.column <= target.cell_start.column
             && self.cell_end.column >= target.cell_end.column
     }
 
-    /// Check if this range intersects with another range.
-    pub fn intersects(&self, other: &Range) -> Result<bool, WebExcelError> {
-        let other_inv_tr = Cell::new(other.cell_start.row, other.cell_end.column, None)?;
-        let other_inv_bl = Cell::new(other.cell_end.row, other.cell_start.column, None)?;
+    /// Check if this range shares at least one cell with another range, on the same sheet.
+    /// Their rows and their columns both have to overlap.
+    pub fn intersects(&self, other: &Range) -> bool {
+        self.same_sheet(other)
+            && self.cell_start.row <= other.cell_end.row
+            && other.cell_start.row <= self.cell_end.row
+            && self.cell_start.column <= other.cell_end.column
+            && other.cell_start.column <= self.cell_end.column
+    }
+
+    /// Check if two ranges on the same sheet share no cell but have a common border,
+    /// e.g. `A1:B2` and `C2:D3`. Ranges meeting only at a corner are not adjacent.
+    pub fn is_adjacent(&self, other: &Range) -> bool {
+        if !self.same_sheet(other) || self.intersects(other) {
+            return false;
+        }
+
+        let rows_overlap =
+            self.cell_start.row <= other.cell_end.row && other.cell_start.row <= self.cell_end.row;
+        let columns_overlap = self.cell_start.column <= other.cell_end.column
+            && other.cell_start.column <= self.cell_end.column;
+
+        (rows_overlap
+            && gap(
+                self.cell_start.column,
+                self.cell_end.column,
+                other.cell_start.column,
+                other.cell_end.column,
+            ) == 0)
+            || (columns_overlap
+                && gap(
+                    self.cell_start.row,
+                    self.cell_end.row,
+                    other.cell_start.row,
+                    other.cell_end.row,
+                ) == 0)
+    }
+
+    /// Check if two ranges on the same sheet have no gap between them:
+    /// they overlap, are adjacent, or meet at a corner like `A1:B2` and `C3`.
+    pub fn touches(&self, other: &Range) -> bool {
+        self.same_sheet(other)
+            && gap(
+                self.cell_start.row,
+                self.cell_end.row,
+                other.cell_start.row,
+                other.cell_end.row,
+            ) == 0
+            && gap(
+                self.cell_start.column,
+                self.cell_end.column,
+                other.cell_start.column,
+                other.cell_end.column,
+            ) == 0
+    }
+
+    /// Check if both ranges are on the same sheet.
+    pub fn same_sheet(&self, other: &Range) -> bool {
+        same_sheet(&self.cell_start.sheet, &other.cell_start.sheet)
+    }
+
+    /// Cells shared by both ranges. `None` if they do not overlap or are on different sheets.
+    pub fn intersection(&self, other: &Range) -> Option<Range> {
+        if !self.intersects(other) {
+            return None;
+        }
+
+        let top = self.cell_start.row.max(other.cell_start.row);
+        let left = self.cell_start.column.max(other.cell_start.column);
+        let bottom = self.cell_end.row.min(other.cell_end.row);
+        let right = self.cell_end.column.min(other.cell_end.column);
+        Some(self.area(top, left, bottom, right))
+    }
+
+    /// Smallest range covering both ranges, like Excel's `A1:B2:D4`.
+    pub fn union(&self, other: &Range) -> Result<Range, WebExcelError> {
+        if !self.same_sheet(other) {
+            return Err(WebExcelError::RangeDiffSheetError);
+        }
+
+        Ok(self.area(
+            self.cell_start.row.min(other.cell_start.row),
+            self.cell_start.column.min(other.cell_start.column),
+            self.cell_end.row.max(other.cell_end.row),
+            self.cell_end.column.max(other.cell_end.column),
+        ))
+    }
+
+    /// Cells of this range outside `other`, as an array of disjoint ranges. See `Range::difference`.
+    #[wasm_bindgen(js_name = difference)]
+    pub fn difference_js(&self, other: &Range) -> js_sys::Array {
+        self.difference(other)
+            .into_iter()
+            .map(JsValue::from)
+            .collect::<js_sys::Array>()
+    }
+
+    /// Range of the same size moved by the given number of rows and columns,
+    /// like `getOffsetRange`. Fails if it would leave the sheet.
+    pub fn offset(&self, row_offset: i32, column_offset: i32) -> Result<Range, WebExcelError> {
+        let (rows, columns) = (row_offset as i64, column_offset as i64);
+        self.bounded(
+            self.cell_start.row as i64 + rows,
+            self.cell_start.column as i64 + columns,
+            self.cell_end.row as i64 + rows,
+            self.cell_end.column as i64 + columns,
+        )
+    }
+
+    /// Range with the same top-left cell and rows and columns added or removed at the end,
+    /// like `getResizedRange`. Fails if no row or column would be left, or past the sheet.
+    pub fn resize(&self, row_delta: i32, column_delta: i32) -> Result<Range, WebExcelError> {
+        self.bounded(
+            self.cell_start.row as i64,
+            self.cell_start.column as i64,
+            self.cell_end.row as i64 + row_delta as i64,
+            self.cell_end.column as i64 + column_delta as i64,
+        )
+    }
+
+    /// Smallest range covering this range and `cell`, which must be on the same sheet.
+    pub fn expand_to(&self, cell: &Cell) -> Result<Range, WebExcelError> {
+        if !same_sheet(&self.cell_start.sheet, &cell.sheet) {
+            return Err(WebExcelError::RangeDiffSheetError);
+        }
+
+        Ok(self.area(
+            self.cell_start.row.min(cell.row),
+            self.cell_start.column.min(cell.column),
+            self.cell_end.row.max(cell.row),
+            self.cell_end.column.max(cell.column),
+        ))
+    }
+
+    /// Whole rows of the range, e.g. `B2:C3` gives `2:3`.
+    pub fn entire_row(&self) -> Range {
+        self.area(self.cell_start.row, 0, self.cell_end.row, MAX_COLUMN)
+    }
+
+    /// Whole columns of the range, e.g. `B2:C3` gives `B:C`.
+    pub fn entire_column(&self) -> Range {
+        self.area(0, self.cell_start.column, MAX_ROW, self.cell_end.column)
+    }
+
+    pub fn first_row(&self) -> Range {
+        let row = self.cell_start.row;
+        self.area(row, self.cell_start.column, row, self.cell_end.column)
+    }
 
-        let self_inv_tr = Cell::new(self.cell_start.row, self.cell_end.column, None)?;
-        let self_inv_bl = Cell::new(self.cell_end.row, self.cell_start.column, None)?;
+    pub fn last_row(&self) -> Range {
+        let row = self.cell_end.row;
+        self.area(row, self.cell_start.column, row, self.cell_end.column)
+    }
 
-        Ok(self.has(&other_inv_bl)
-            || self.has(&other_inv_tr)
-            || other.has(&self_inv_bl)
-            || other.has(&self_inv_tr))
+    pub fn first_column(&self) -> Range {
+        let column = self.cell_start.column;
+        self.area(self.cell_start.row, column, self.cell_end.row, column)
     }
 
-    /// Extract a sub-range of columns from the current range.
+    pub fn last_column(&self) -> Range {
+        let column = self.cell_end.column;
+        self.area(self.cell_start.row, column, self.cell_end.row, column)
+    }
+
+    /// Range shrunk by `cells` on every side. Fails if nothing would be left.
+    pub fn inset(&self, cells: u32) -> Result<Range, WebExcelError> {
+        self.outset(-(cells as i64))
+    }
+
+    /// Range grown by `cells` on every side. Fails if it would leave the sheet.
+    #[wasm_bindgen(js_name = outset)]
+    pub fn outset_js(&self, cells: u32) -> Result<Range, WebExcelError> {
+        self.outset(cells as i64)
+    }
+
+    /// Extract columns `column_start` to `column_end`, excluded, from the current range.
+    /// Fails unless `column_start < column_end <= columns`, see `select_columns`.
     pub fn select_column(
         &self,
         column_start: usize,
         column_end: usize,
     ) -> Result<Range, WebExcelError> {
-        let new_start = Cell::new(
-            self.cell_start.row,
-            self.cell_start.column + column_start as u32,
-            self.cell_start.sheet.clone(),
-        )?;
+        self.select_columns(Some(to_index(column_start)?), Some(to_index(column_end)?))
+    }
 
-        let new_end = Cell::new(
-            self.cell_end.row,
-            self.cell_start.column + (column_end - 1) as u32,
-            self.cell_start.sheet.clone(),
-        )?;
+    /// Extract rows `row_start` to `row_end`, excluded, from the current range.
+    /// Fails unless `row_start < row_end <= rows`, see `select_rows`.
+    pub fn select_row(&self, row_start: usize, row_end: usize) -> Result<Range, WebExcelError> {
+        self.select_rows(Some(to_index(row_start)?), Some(to_index(row_end)?))
+    }
 
-        Range::new(&new_start, &new_end)
+    /// Create an iterator over columns within the range.
+    pub fn iter_col(&self) -> Result<js_sys::Array, WebExcelError> {
+        self.column_ranges()
+            .map(|column| column.to_str_address().map(JsValue::from))
+            .collect()
     }
 
-    /// Extract a sub-range of rows from the current range.
-    pub fn select_row(&self, row_start: usize, row_end: usize) -> Result<Range, WebExcelError> {
-        let new_start = Cell::new(
-            self.cell_start.row + row_start as u32,
-            self.cell_start.column,
-            self.cell_start.sheet.clone(),
-        )?;
+    /// Create an iterator over rows within the range.
+    pub fn iter_row(&self) -> Result<js_sys::Array, WebExcelError> {
+        self.row_ranges()
+            .map(|row| row.to_str_address().map(JsValue::from))
+            .collect()
+    }
+}
 
-        let new_end = Cell::new(
-            self.cell_start.row + (row_end - 1) as u32,
-            self.cell_end.column,
-            self.cell_start.sheet.clone(),
-        )?;
+impl Range {
+    /// Cells of this range outside `other`, as at most four disjoint ranges:
+    /// the full width bands above and below `other`, then the parts left and right of it.
+    /// Empty when `other` covers the whole range.
+    pub fn difference(&self, other: &Range) -> Vec<Range> {
+        let common = match self.intersection(other) {
+            Some(common) => common,
+            None => return vec![self.clone()],
+        };
+        let (top, left) = (self.cell_start.row, self.cell_start.column);
+        let (bottom, right) = (self.cell_end.row, self.cell_end.column);
+        let (inner_top, inner_left) = (common.cell_start.row, common.cell_start.column);
+        let (inner_bottom, inner_right) = (common.cell_end.row, common.cell_end.column);
+
+        let mut parts = vec![];
+        if inner_top > top {
+            parts.push(self.area(top, left, inner_top - 1, right));
+        }
+        if inner_bottom < bottom {
+            parts.push(self.area(inner_bottom + 1, left, bottom, right));
+        }
+        if inner_left > left {
+            parts.push(self.area(inner_top, left, inner_bottom, inner_left - 1));
+        }
+        if inner_right < right {
+            parts.push(self.area(inner_top, inner_right + 1, inner_bottom, right));
+        }
+        parts
+    }
 
-        Range::new(&new_start, &new_end)
+    /// Range grown by `cells` on every side, shrunk for a negative number.
+    pub fn outset(&self, cells: i64) -> Result<Range, WebExcelError> {
+        self.bounded(
+            self.cell_start.row as i64 - cells,
+            self.cell_start.column as i64 - cells,
+            self.cell_end.row as i64 + cells,
+            self.cell_end.column as i64 + cells,
+        )
     }
 
-    /// Create an iterator over columns within the range.
-    pub fn iter_col(&self) -> Result<js_sys::Array, WebExcelError> {
-        let mut addresses: Vec<String> = Vec::new();
-
-        for i in self.cell_start.column..=self.cell_end.column {
-            let column_start = Cell::new(self.cell_start.row, i, self.cell_start.sheet.clone())?;
-            let column_end = Cell::new(self.cell_end.row, i, self.cell_start.sheet.clone())?;
-
-            let column = format!(
-                "{}:{}",
-                column_start.to_str_address()?,
-                column_end.to_str_address()?
-            );
-            addresses.push(column);
+    /// Range on the sheet of this one between the given bounds, checked against the sheet limits.
+    fn bounded(
+        &self,
+        top: i64,
+        left: i64,
+        bottom: i64,
+        right: i64,
+    ) -> Result<Range, WebExcelError> {
+        if top < 0 || left < 0 || bottom > MAX_ROW as i64 || right > MAX_COLUMN as i64 {
+            return Err(WebExcelError::OutOfBoundError);
+        }
+        if top > bottom || left > right {
+            return Err(WebExcelError::OutOfBoundError);
         }
 
-        let boxed = Box::new(addresses);
-        Ok(boxed.iter().map(JsValue::from).collect::<js_sys::Array>())
+        Ok(self.area(top as u32, left as u32, bottom as u32, right as u32))
     }
 
-    /// Create an iterator over rows within the range.
-    pub fn iter_row(&self) -> Result<js_sys::Array, WebExcelError> {
-        let mut addresses: Vec<String> = Vec::new();
-
-        for i in self.cell_start.row..=self.cell_end.row {
-            let row_start = Cell::new(i, self.cell_start.column, self.cell_start.sheet.clone())?;
-            let row_end = Cell::new(i, self.cell_end.column, self.cell_start.sheet.clone())?;
-
-            let row = format!(
-                "{}:{}",
-                row_start.to_str_address()?,
-                row_end.to_str_address()?
-            );
-            addresses.push(row);
+    /// Range on the sheet of this one between the given bounds, which are in order.
+    pub(crate) fn area(&self, top: u32, left: u32, bottom: u32, right: u32) -> Range {
+        let sheet = self.cell_start.sheet.clone();
+        Range {
+            cell_start: Cell {
+                row: top,
+                column: left,
+                sheet: sheet.clone(),
+                ..Default::default()
+            },
+            cell_end: Cell {
+                row: bottom,
+                column: right,
+                sheet,
+                ..Default::default()
+            },
+            columns: right - left + 1,
+            rows: bottom - top + 1,
+            cells: (right - left + 1) as u64 * (bottom - top + 1) as u64,
         }
+    }
+}
+
+fn to_index(index: usize) -> Result<i32, WebExcelError> {
+    i32::try_from(index).map_err(|_| WebExcelError::OutOfBoundError)
+}
 
-        let boxed = Box::new(addresses);
-        Ok(boxed.iter().map(JsValue::from).collect::<js_sys::Array>())
+/// Number of rows or columns between two spans, 0 when they overlap or follow each other.
+fn gap(start: u32, end: u32, other_start: u32, other_end: u32) -> u32 {
+    if end < other_start {
+        other_start - end - 1
+    } else if other_end < start {
+        start - other_end - 1
+    } else {
+        0
     }
 }
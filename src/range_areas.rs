@@ -0,0 +1,224 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::parser::{parse_formula, Expr, Reference};
+use crate::range::Range;
+use wasm_bindgen::prelude::*;
+
+/// Several ranges treated as one, like Office JS `RangeAreas` and addresses such as
+/// `Sheet1!A1:B2,Sheet1!D4:E5`. Areas may be on different sheets.
+/// Areas are kept as added, call `normalize` to merge overlapping and adjacent ones.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RangeAreas {
+    areas: Vec<Range>,
+}
+
+#[wasm_bindgen]
+impl RangeAreas {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> RangeAreas {
+        RangeAreas::default()
+    }
+
+    /// Parse a comma separated list of cells and ranges, e.g. `A1:B2,'My Sheet'!D4`.
+    /// Areas without a sheet are given `sheet`.
+    pub fn from_str_address(
+        address: &str,
+        sheet: Option<String>,
+    ) -> Result<RangeAreas, WebExcelError> {
+        let mut areas = RangeAreas::new();
+        for part in split_areas(address) {
+            let reference = match parse_formula(part.trim()) {
+                Ok(Expr::Reference(reference)) if reference.workbook.is_none() => reference,
+                _ => return Err(WebExcelError::ParseError),
+            };
+            if reference.spill {
+                return Err(WebExcelError::ParseError);
+            }
+
+            let mut range = reference.range()?;
+            if range.cell_start.sheet.is_none() {
+                range.cell_start.sheet = sheet.clone();
+                range.cell_end.sheet = sheet.clone();
+            }
+            areas.areas.push(range);
+        }
+
+        Ok(areas)
+    }
+
+    /// Comma separated address of the areas, with the sheet of each one, e.g. `Sheet1!A1:B2,Sheet1!D4`.
+    pub fn to_str_address(&self) -> Result<String, WebExcelError> {
+        let parts = self
+            .areas
+            .iter()
+            .map(|area| {
+                let single = area.cell_start == area.cell_end;
+                Expr::Reference(Reference {
+                    workbook: None,
+                    start: area.cell_start.clone(),
+                    end: (!single).then(|| area.cell_end.clone()),
+                    spill: false,
+                })
+                .to_formula()
+            })
+            .collect::<Result<Vec<String>, WebExcelError>>()?;
+
+        Ok(parts.join(","))
+    }
+
+    pub fn add(&mut self, range: &Range) {
+        self.areas.push(range.clone());
+    }
+
+    /// Areas as an array of `Range`.
+    pub fn areas(&self) -> js_sys::Array {
+        self.areas
+            .iter()
+            .cloned()
+            .map(JsValue::from)
+            .collect::<js_sys::Array>()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn area_count(&self) -> usize {
+        self.areas.len()
+    }
+
+    /// Number of distinct cells, counting cells shared by several areas once.
+    #[wasm_bindgen(getter = cell_count)]
+    pub fn cell_count_js(&self) -> f64 {
+        self.cell_count() as f64
+    }
+
+    /// Merge the areas into disjoint ones, joining areas that overlap or sit side by side
+    /// into a single rectangle where possible. Areas come out by sheet, row, then column.
+    pub fn normalize(&mut self) {
+        let mut areas = disjoint(&self.areas);
+
+        // Join pairs spanning the same rows or columns that touch, until none are left
+        'merge: loop {
+            for i in 0..areas.len() {
+                for j in i + 1..areas.len() {
+                    if let Some(joined) = join(&areas[i], &areas[j]) {
+                        areas[i] = joined;
+                        areas.remove(j);
+                        continue 'merge;
+                    }
+                }
+            }
+            break;
+        }
+
+        areas.sort_by(|a, b| a.cell_start.cmp(&b.cell_start));
+        self.areas = areas;
+    }
+
+    /// Check if a cell is within any of the areas, on the same sheet.
+    pub fn has(&self, target: &Cell) -> bool {
+        self.areas.iter().any(|area| area.has(target))
+    }
+
+    /// Check if every cell of a range is within the areas, possibly spread over several of them.
+    pub fn includes(&self, target: &Range) -> bool {
+        self.areas
+            .iter()
+            .fold(vec![target.clone()], |rest, area| {
+                rest.iter().flat_map(|part| part.difference(area)).collect()
+            })
+            .is_empty()
+    }
+
+    /// Check if a range shares at least one cell with the areas.
+    pub fn intersects(&self, target: &Range) -> bool {
+        self.areas.iter().any(|area| area.intersects(target))
+    }
+}
+
+impl RangeAreas {
+    pub fn iter(&self) -> impl Iterator<Item = &Range> {
+        self.areas.iter()
+    }
+
+    /// Number of distinct cells, counting cells shared by several areas once.
+    pub fn cell_count(&self) -> u64 {
+        disjoint(&self.areas).iter().map(|area| area.cells).sum()
+    }
+
+    /// Every cell of the areas, area by area and row by row within an area.
+    /// Cells shared by several areas come up more than once unless normalized first.
+    pub fn iter_cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.areas.iter().flat_map(Range::iter_cells)
+    }
+}
+
+impl From<Range> for RangeAreas {
+    fn from(range: Range) -> Self {
+        RangeAreas { areas: vec![range] }
+    }
+}
+
+impl FromIterator<Range> for RangeAreas {
+    fn from_iter<I: IntoIterator<Item = Range>>(iter: I) -> Self {
+        RangeAreas {
+            areas: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a RangeAreas {
+    type Item = &'a Range;
+    type IntoIter = std::slice::Iter<'a, Range>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.areas.iter()
+    }
+}
+
+/// Split an address list on the commas outside quoted sheet names.
+fn split_areas(address: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in address.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&address[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&address[start..]);
+    parts
+}
+
+/// The same cells as `areas` without overlaps, each area cut down to what earlier ones do not cover.
+fn disjoint(areas: &[Range]) -> Vec<Range> {
+    let mut result: Vec<Range> = vec![];
+    for area in areas {
+        let parts = result.iter().fold(vec![area.clone()], |parts, done| {
+            parts
+                .iter()
+                .flat_map(|part| part.difference(done))
+                .collect()
+        });
+        result.extend(parts);
+    }
+    result
+}
+
+/// Single range covering exactly the cells of two disjoint areas, if there is one.
+fn join(a: &Range, b: &Range) -> Option<Range> {
+    let (a_start, a_end, b_start, b_end) = (&a.cell_start, &a.cell_end, &b.cell_start, &b.cell_end);
+    let same_rows = a_start.row == b_start.row && a_end.row == b_end.row;
+    let same_columns = a_start.column == b_start.column && a_end.column == b_end.column;
+    let side_by_side = a_end.column + 1 == b_start.column || b_end.column + 1 == a_start.column;
+    let stacked = a_end.row + 1 == b_start.row || b_end.row + 1 == a_start.row;
+
+    if (same_rows && side_by_side) || (same_columns && stacked) {
+        a.union(b).ok()
+    } else {
+        None
+    }
+}
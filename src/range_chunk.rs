@@ -0,0 +1,123 @@
+use crate::range::Range;
+use wasm_bindgen::prelude::*;
+
+/// Rough size limit of a single Office JS request, in bytes.
+pub const OFFICE_PAYLOAD_LIMIT: f64 = 5_000_000.0;
+
+/// Largest chunk `Range::chunks` may produce. Everything is unlimited unless set.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkLimits {
+    pub max_cells: u32,
+    pub max_rows: u32,
+    pub max_columns: u32,
+    /// Estimated size of a cell once serialized, 0 to ignore `max_bytes`.
+    pub bytes_per_cell: f64,
+    pub max_bytes: f64,
+}
+
+impl Default for ChunkLimits {
+    fn default() -> Self {
+        ChunkLimits {
+            max_cells: u32::MAX,
+            max_rows: u32::MAX,
+            max_columns: u32::MAX,
+            bytes_per_cell: 0.0,
+            max_bytes: OFFICE_PAYLOAD_LIMIT,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl ChunkLimits {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ChunkLimits {
+        ChunkLimits::default()
+    }
+
+    pub fn with_max_cells(mut self, cells: u32) -> ChunkLimits {
+        self.max_cells = cells;
+        self
+    }
+
+    pub fn with_max_rows(mut self, rows: u32) -> ChunkLimits {
+        self.max_rows = rows;
+        self
+    }
+
+    pub fn with_max_columns(mut self, columns: u32) -> ChunkLimits {
+        self.max_columns = columns;
+        self
+    }
+
+    /// Limit chunks to `max_bytes`, counting `bytes_per_cell` for each cell,
+    /// e.g. `with_bytes(40, OFFICE_PAYLOAD_LIMIT)`.
+    pub fn with_bytes(mut self, bytes_per_cell: f64, max_bytes: f64) -> ChunkLimits {
+        self.bytes_per_cell = bytes_per_cell;
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Most cells a chunk may hold, at least 1.
+    pub fn cell_limit(&self) -> u32 {
+        let by_bytes = if self.bytes_per_cell > 0.0 {
+            (self.max_bytes / self.bytes_per_cell)
+                .floor()
+                .min(u32::MAX as f64) as u32
+        } else {
+            u32::MAX
+        };
+        self.max_cells.min(by_bytes).max(1)
+    }
+}
+
+/// Part of a range produced by `Range::chunks`, with its position inside the range.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    #[wasm_bindgen(getter_with_clone)]
+    pub range: Range,
+    /// Rows between the top of the parent range and the top of the chunk.
+    pub row_offset: u32,
+    /// Columns between the left of the parent range and the left of the chunk.
+    pub column_offset: u32,
+}
+
+#[wasm_bindgen]
+impl Range {
+    /// Split the range into chunks within `limits`, as an array of `Chunk`. See `Range::chunks`.
+    #[wasm_bindgen(js_name = chunks)]
+    pub fn chunks_js(&self, limits: &ChunkLimits) -> js_sys::Array {
+        self.chunks(limits)
+            .into_iter()
+            .map(JsValue::from)
+            .collect::<js_sys::Array>()
+    }
+}
+
+impl Range {
+    /// Split the range into chunks within `limits`, covering each cell once, in row-major order.
+    /// Chunks are as wide as allowed, then as tall as the cell limit allows, so full rows are
+    /// kept together when they fit.
+    pub fn chunks(&self, limits: &ChunkLimits) -> Vec<Chunk> {
+        let cells = limits.cell_limit();
+        let width = self.columns.min(limits.max_columns).min(cells).max(1);
+        let height = self.rows.min(limits.max_rows).min(cells / width).max(1);
+
+        let mut chunks = vec![];
+        for row_offset in (0..self.rows).step_by(height as usize) {
+            for column_offset in (0..self.columns).step_by(width as usize) {
+                let top = self.cell_start.row + row_offset;
+                let left = self.cell_start.column + column_offset;
+                let bottom = top + (height - 1).min(self.rows - 1 - row_offset);
+                let right = left + (width - 1).min(self.columns - 1 - column_offset);
+                chunks.push(Chunk {
+                    range: self.area(top, left, bottom, right),
+                    row_offset,
+                    column_offset,
+                });
+            }
+        }
+        chunks
+    }
+}
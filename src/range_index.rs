@@ -0,0 +1,122 @@
+use crate::cell::{sheet_key, Cell};
+use crate::range::Range;
+use crate::util::rtree::{RTree, Rect};
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+/// Spatial index of ranges, answering which ranges contain a cell or overlap a range.
+/// Each range is given an id when inserted. Ranges are kept in one R-tree per sheet,
+/// so lookups only ever see ranges on the sheet asked about, whatever its case.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone)]
+pub struct RangeIndex {
+    sheets: BTreeMap<Option<String>, RTree<u32>>,
+    ranges: BTreeMap<u32, Range>,
+    next_id: u32,
+}
+
+#[wasm_bindgen]
+impl RangeIndex {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> RangeIndex {
+        RangeIndex::default()
+    }
+
+    /// Add a range and return its id. The same range can be added more than once.
+    pub fn insert(&mut self, range: &Range) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.sheets
+            .entry(tree_key(&range.cell_start.sheet))
+            .or_default()
+            .insert(rect(range), id);
+        self.ranges.insert(id, range.clone());
+        id
+    }
+
+    /// Remove the range with `id`. Returns whether it was there.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let range = match self.ranges.remove(&id) {
+            Some(range) => range,
+            None => return false,
+        };
+
+        let sheet = tree_key(&range.cell_start.sheet);
+        if let Some(tree) = self.sheets.get_mut(&sheet) {
+            tree.remove(&rect(&range), &id);
+            if tree.is_empty() {
+                self.sheets.remove(&sheet);
+            }
+        }
+        true
+    }
+
+    pub fn get(&self, id: u32) -> Option<Range> {
+        self.ranges.get(&id).cloned()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sheets.clear();
+        self.ranges.clear();
+    }
+
+    /// Ids of the ranges containing `cell`, in insertion order.
+    #[wasm_bindgen(js_name = containing)]
+    pub fn containing_js(&self, cell: &Cell) -> Vec<u32> {
+        self.containing(cell)
+    }
+
+    /// Ids of the ranges sharing at least one cell with `range`, in insertion order.
+    #[wasm_bindgen(js_name = intersecting)]
+    pub fn intersecting_js(&self, range: &Range) -> Vec<u32> {
+        self.intersecting(range)
+    }
+}
+
+impl RangeIndex {
+    pub fn containing(&self, cell: &Cell) -> Vec<u32> {
+        self.search(&cell.sheet, &Rect::point(cell.row, cell.column))
+    }
+
+    pub fn intersecting(&self, range: &Range) -> Vec<u32> {
+        self.search(&range.cell_start.sheet, &rect(range))
+    }
+
+    /// Ranges in the index with their ids, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &Range)> {
+        self.ranges.iter().map(|(id, range)| (*id, range))
+    }
+
+    fn search(&self, sheet: &Option<String>, window: &Rect) -> Vec<u32> {
+        let mut ids: Vec<u32> = match self.sheets.get(&tree_key(sheet)) {
+            Some(tree) => tree.search(window).into_iter().copied().collect(),
+            None => vec![],
+        };
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// Sheet names are case insensitive, so trees are keyed by the folded name.
+fn tree_key(sheet: &Option<String>) -> Option<String> {
+    sheet.as_deref().map(sheet_key)
+}
+
+fn rect(range: &Range) -> Rect {
+    Rect::new(
+        range.cell_start.row,
+        range.cell_start.column,
+        range.cell_end.row,
+        range.cell_end.column,
+    )
+}
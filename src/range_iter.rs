@@ -0,0 +1,245 @@
+use crate::cell::Cell;
+use crate::range::Range;
+use wasm_bindgen::prelude::*;
+
+/// Cells of a range, row by row or column by column, carrying the sheet of the range.
+/// Created by `Range::iter_cells` and `Range::iter_cells_by_column`.
+#[derive(Debug, Clone)]
+pub struct Cells {
+    range: Range,
+    by_column: bool,
+    /// Position of the next cell in iteration order, counted from 0.
+    front: u64,
+    back: u64,
+}
+
+impl Cells {
+    pub(crate) fn new(range: &Range, by_column: bool) -> Cells {
+        Cells {
+            range: range.clone(),
+            by_column,
+            front: 0,
+            back: range.rows as u64 * range.columns as u64,
+        }
+    }
+
+    /// Number of cells not yet visited, which can exceed `usize` on 32 bit targets.
+    pub fn remaining(&self) -> u64 {
+        self.back - self.front
+    }
+
+    fn cell_at(&self, index: u64) -> Cell {
+        let (rows, columns) = (self.range.rows as u64, self.range.columns as u64);
+        let (row, column) = if self.by_column {
+            (index % rows, index / rows)
+        } else {
+            (index / columns, index % columns)
+        };
+
+        Cell {
+            row: self.range.cell_start.row + row as u32,
+            column: self.range.cell_start.column + column as u32,
+            sheet: self.range.cell_start.sheet.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Iterator for Cells {
+    type Item = Cell;
+
+    fn next(&mut self) -> Option<Cell> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.front += 1;
+        Some(self.cell_at(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.remaining()) {
+            Ok(left) => (left, Some(left)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Cell> {
+        self.front = self.front.saturating_add(n as u64).min(self.back);
+        self.next()
+    }
+}
+
+impl DoubleEndedIterator for Cells {
+    fn next_back(&mut self) -> Option<Cell> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.cell_at(self.back))
+    }
+}
+
+/// Rows or columns of a range as single row or single column ranges.
+/// Created by `Range::row_ranges` and `Range::column_ranges`.
+#[derive(Debug, Clone)]
+pub struct Lines {
+    range: Range,
+    by_column: bool,
+    front: u32,
+    back: u32,
+}
+
+impl Lines {
+    pub(crate) fn new(range: &Range, by_column: bool) -> Lines {
+        Lines {
+            range: range.clone(),
+            by_column,
+            front: 0,
+            back: if by_column { range.columns } else { range.rows },
+        }
+    }
+
+    fn line_at(&self, index: u32) -> Range {
+        let (mut start, mut end) = (self.range.cell_start.clone(), self.range.cell_end.clone());
+        if self.by_column {
+            start.column += index;
+            end.column = start.column;
+        } else {
+            start.row += index;
+            end.row = start.row;
+        }
+
+        Range {
+            columns: end.column - start.column + 1,
+            rows: end.row - start.row + 1,
+            cells: (end.column - start.column + 1) as u64 * (end.row - start.row + 1) as u64,
+            cell_start: start,
+            cell_end: end,
+        }
+    }
+}
+
+impl Iterator for Lines {
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Range> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.front += 1;
+        Some(self.line_at(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = (self.back - self.front) as usize;
+        (left, Some(left))
+    }
+}
+
+impl DoubleEndedIterator for Lines {
+    fn next_back(&mut self) -> Option<Range> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.line_at(self.back))
+    }
+}
+
+impl ExactSizeIterator for Lines {}
+
+#[derive(Debug, Clone)]
+enum Walk {
+    Cells(Cells),
+    Lines(Lines),
+}
+
+/// Lazy iterator for JavaScript, following the iterator protocol: `next()` gives
+/// `{ value, done }` where `value` is a `Cell` or a `Range`. Wrap it to use `for...of`:
+///
+/// ```js
+/// const cells = { [Symbol.iterator]: () => range.cell_iterator(false) };
+/// for (const cell of cells) { ... }
+/// ```
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct RangeIterator {
+    walk: Walk,
+}
+
+#[wasm_bindgen]
+impl RangeIterator {
+    /// Next item as `{ value, done }`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> js_sys::Object {
+        let value = match &mut self.walk {
+            Walk::Cells(cells) => cells.next().map(JsValue::from),
+            Walk::Lines(lines) => lines.next().map(JsValue::from),
+        };
+
+        let result = js_sys::Object::new();
+        let done = value.is_none();
+        let _ = js_sys::Reflect::set(
+            &result,
+            &"value".into(),
+            &value.unwrap_or(JsValue::UNDEFINED),
+        );
+        let _ = js_sys::Reflect::set(&result, &"done".into(), &JsValue::from_bool(done));
+        result
+    }
+
+    /// Number of items not yet visited.
+    #[wasm_bindgen(getter)]
+    pub fn remaining(&self) -> f64 {
+        match &self.walk {
+            Walk::Cells(cells) => cells.remaining() as f64,
+            Walk::Lines(lines) => lines.len() as f64,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Range {
+    /// Lazy iterator over the cells, row by row or column by column with `by_column`.
+    pub fn cell_iterator(&self, by_column: bool) -> RangeIterator {
+        RangeIterator {
+            walk: Walk::Cells(Cells::new(self, by_column)),
+        }
+    }
+
+    /// Lazy iterator over the rows as single row ranges.
+    pub fn row_iterator(&self) -> RangeIterator {
+        RangeIterator {
+            walk: Walk::Lines(Lines::new(self, false)),
+        }
+    }
+
+    /// Lazy iterator over the columns as single column ranges.
+    pub fn column_iterator(&self) -> RangeIterator {
+        RangeIterator {
+            walk: Walk::Lines(Lines::new(self, true)),
+        }
+    }
+}
+
+impl Range {
+    /// Cells of the range row by row: `A1, B1, A2, B2` for `A1:B2`.
+    pub fn iter_cells(&self) -> Cells {
+        Cells::new(self, false)
+    }
+
+    /// Cells of the range column by column: `A1, A2, B1, B2` for `A1:B2`.
+    pub fn iter_cells_by_column(&self) -> Cells {
+        Cells::new(self, true)
+    }
+
+    /// Rows of the range, top to bottom: `A1:B1, A2:B2` for `A1:B2`.
+    pub fn row_ranges(&self) -> Lines {
+        Lines::new(self, false)
+    }
+
+    /// Columns of the range, left to right: `A1:A2, B1:B2` for `A1:B2`.
+    pub fn column_ranges(&self) -> Lines {
+        Lines::new(self, true)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::error::WebExcelError;
+use crate::range::Range;
+use crate::range_areas::RangeAreas;
+use wasm_bindgen::prelude::*;
+
+/// Python style slice of the rows or columns of a range: `start` included, `end` excluded,
+/// both counted from 0 and from the end when negative, taking every `step`th one.
+/// Missing bounds stand for the first and past the last row or column.
+///
+/// Unlike Python, bounds outside the range and empty slices are errors.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slice {
+    pub start: Option<i32>,
+    pub end: Option<i32>,
+    pub step: u32,
+}
+
+impl Default for Slice {
+    fn default() -> Self {
+        Slice::all()
+    }
+}
+
+#[wasm_bindgen]
+impl Slice {
+    #[wasm_bindgen(constructor)]
+    pub fn new(start: Option<i32>, end: Option<i32>, step: Option<u32>) -> Slice {
+        Slice {
+            start,
+            end,
+            step: step.unwrap_or(1),
+        }
+    }
+
+    /// Every row or column, like `[:]`.
+    pub fn all() -> Slice {
+        Slice::new(None, None, None)
+    }
+
+    /// Rows or columns from `start` to `end`, like `[start:end]`.
+    pub fn between(start: Option<i32>, end: Option<i32>) -> Slice {
+        Slice::new(start, end, None)
+    }
+}
+
+impl Slice {
+    /// First and last selected index out of `len`, and the step.
+    /// Fails when a bound falls outside `0..=len`, nothing is selected or the step is 0.
+    pub fn resolve(&self, len: u32) -> Result<(u32, u32, u32), WebExcelError> {
+        let bound = |index: Option<i32>, default: u32| -> Result<u32, WebExcelError> {
+            let index = match index {
+                None => return Ok(default),
+                Some(i) if i < 0 => len as i64 + i as i64,
+                Some(i) => i as i64,
+            };
+            u32::try_from(index)
+                .ok()
+                .filter(|i| *i <= len)
+                .ok_or(WebExcelError::OutOfBoundError)
+        };
+
+        let start = bound(self.start, 0)?;
+        let end = bound(self.end, len)?;
+        if start >= end || self.step == 0 {
+            return Err(WebExcelError::OutOfBoundError);
+        }
+
+        Ok((start, end - 1, self.step))
+    }
+
+    /// Selected spans of `len` indexes: a single one without a step, one per index otherwise.
+    fn spans(&self, len: u32) -> Result<Vec<(u32, u32)>, WebExcelError> {
+        let (first, last, step) = self.resolve(len)?;
+        if step == 1 {
+            return Ok(vec![(first, last)]);
+        }
+
+        Ok((first..=last)
+            .step_by(step as usize)
+            .map(|i| (i, i))
+            .collect())
+    }
+}
+
+#[wasm_bindgen]
+impl Range {
+    /// Rows and columns picked by two slices, e.g. every other row with `Slice::new(None, None, Some(2))`.
+    /// A step gives one area per row or column, top to bottom, then left to right.
+    pub fn slice(&self, rows: &Slice, columns: &Slice) -> Result<RangeAreas, WebExcelError> {
+        let row_spans = rows.spans(self.rows)?;
+        let column_spans = columns.spans(self.columns)?;
+
+        let (top, left) = (self.cell_start.row, self.cell_start.column);
+        Ok(row_spans
+            .iter()
+            .flat_map(|(first_row, last_row)| {
+                column_spans.iter().map(move |(first_column, last_column)| {
+                    self.area(
+                        top + first_row,
+                        left + first_column,
+                        top + last_row,
+                        left + last_column,
+                    )
+                })
+            })
+            .collect())
+    }
+
+    /// Rows from `start` to `end`, excluded, negative indexes counting from the last row.
+    /// `B2:C9` gives `B3:C8` for `select_rows(1, -1)`.
+    pub fn select_rows(
+        &self,
+        start: Option<i32>,
+        end: Option<i32>,
+    ) -> Result<Range, WebExcelError> {
+        let (first, last, _) = Slice::between(start, end).resolve(self.rows)?;
+        let top = self.cell_start.row;
+        Ok(self.area(
+            top + first,
+            self.cell_start.column,
+            top + last,
+            self.cell_end.column,
+        ))
+    }
+
+    /// Columns from `start` to `end`, excluded, negative indexes counting from the last column.
+    pub fn select_columns(
+        &self,
+        start: Option<i32>,
+        end: Option<i32>,
+    ) -> Result<Range, WebExcelError> {
+        let (first, last, _) = Slice::between(start, end).resolve(self.columns)?;
+        let left = self.cell_start.column;
+        Ok(self.area(
+            self.cell_start.row,
+            left + first,
+            self.cell_end.row,
+            left + last,
+        ))
+    }
+}
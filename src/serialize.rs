@@ -0,0 +1,279 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::parser::{parse_formula, Expr, Reference};
+use crate::range::Range;
+use crate::util::cell_handle::{MAX_COLUMN, MAX_ROW};
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+/// Fields of a cell in the structured form, e.g. `{"row": 0, "column": 1, "sheet": "Data"}`.
+/// Anchors are left out when unset.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Cell")]
+struct CellFields {
+    row: u32,
+    column: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sheet: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    fixed_row: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    fixed_column: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl From<&Cell> for CellFields {
+    fn from(cell: &Cell) -> CellFields {
+        CellFields {
+            row: cell.row,
+            column: cell.column,
+            sheet: cell.sheet.clone(),
+            fixed_row: cell.fixed_row,
+            fixed_column: cell.fixed_column,
+        }
+    }
+}
+
+impl CellFields {
+    fn into_cell(self) -> Result<Cell, WebExcelError> {
+        if self.row > MAX_ROW || self.column > MAX_COLUMN {
+            return Err(WebExcelError::OutOfBoundError);
+        }
+        Ok(Cell {
+            row: self.row,
+            column: self.column,
+            sheet: self.sheet,
+            fixed_row: self.fixed_row,
+            fixed_column: self.fixed_column,
+        })
+    }
+}
+
+/// Fields of a range in the structured form, its corners as structured cells.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Range")]
+struct RangeFields {
+    cell_start: CellFields,
+    cell_end: CellFields,
+}
+
+impl From<&Range> for RangeFields {
+    fn from(range: &Range) -> RangeFields {
+        RangeFields {
+            cell_start: (&range.cell_start).into(),
+            cell_end: (&range.cell_end).into(),
+        }
+    }
+}
+
+impl RangeFields {
+    fn into_range(self) -> Result<Range, WebExcelError> {
+        Range::new(&self.cell_start.into_cell()?, &self.cell_end.into_cell()?)
+    }
+}
+
+/// Address as written in a formula, quoting the sheet when needed, e.g. `'My Sheet'!$A$1`.
+fn address(start: &Cell, end: Option<&Cell>) -> Result<String, WebExcelError> {
+    Expr::Reference(Reference {
+        workbook: None,
+        start: start.clone(),
+        end: end.cloned(),
+        spill: false,
+    })
+    .to_formula()
+}
+
+/// Reference of an address such as `A1`, `$B$2:C4` or `'My Sheet'!A1`.
+fn parse_address(address: &str) -> Result<Reference, WebExcelError> {
+    match parse_formula(address.trim()) {
+        Ok(Expr::Reference(reference)) if reference.workbook.is_none() && !reference.spill => {
+            Ok(reference)
+        }
+        _ => Err(WebExcelError::ParseError),
+    }
+}
+
+/// Serialized as its address, e.g. `"Data!$A$1"`. Deserialized from an address or
+/// from the structured form, which needs a self-describing format such as JSON.
+impl Serialize for Cell {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let address = address(self, None).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&address)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cell {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Cell, D::Error> {
+        deserializer.deserialize_any(AddressVisitor::<Cell>::new())
+    }
+}
+
+/// Serialized as its address, e.g. `"Data!A1:C4"`. Deserialized from an address or
+/// from the structured form, which needs a self-describing format such as JSON.
+impl Serialize for Range {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let address =
+            address(&self.cell_start, Some(&self.cell_end)).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&address)
+    }
+}
+
+impl<'de> Deserialize<'de> for Range {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Range, D::Error> {
+        deserializer.deserialize_any(AddressVisitor::<Range>::new())
+    }
+}
+
+/// Values with both an address and a structured form.
+trait Addressed: Sized {
+    const EXPECTING: &'static str;
+    fn from_address(address: &str) -> Result<Self, WebExcelError>;
+    fn from_map<'de, A: MapAccess<'de>>(map: A) -> Result<Self, A::Error>;
+}
+
+impl Addressed for Cell {
+    const EXPECTING: &'static str = "a cell address or a cell object";
+
+    fn from_address(address: &str) -> Result<Cell, WebExcelError> {
+        let reference = parse_address(address)?;
+        match reference.end {
+            None => Ok(reference.start),
+            Some(_) => Err(WebExcelError::ParseError),
+        }
+    }
+
+    fn from_map<'de, A: MapAccess<'de>>(map: A) -> Result<Cell, A::Error> {
+        CellFields::deserialize(de::value::MapAccessDeserializer::new(map))?
+            .into_cell()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Addressed for Range {
+    const EXPECTING: &'static str = "a range address or a range object";
+
+    fn from_address(address: &str) -> Result<Range, WebExcelError> {
+        parse_address(address)?.range()
+    }
+
+    fn from_map<'de, A: MapAccess<'de>>(map: A) -> Result<Range, A::Error> {
+        RangeFields::deserialize(de::value::MapAccessDeserializer::new(map))?
+            .into_range()
+            .map_err(de::Error::custom)
+    }
+}
+
+struct AddressVisitor<T>(std::marker::PhantomData<T>);
+
+impl<T> AddressVisitor<T> {
+    fn new() -> AddressVisitor<T> {
+        AddressVisitor(std::marker::PhantomData)
+    }
+}
+
+impl<'de, T: Addressed> Visitor<'de> for AddressVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(T::EXPECTING)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+        T::from_address(value).map_err(E::custom)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<T, A::Error> {
+        T::from_map(map)
+    }
+}
+
+/// Structured form of `Cell` and `Range`, for fields that should not use the address,
+/// e.g. `#[serde(with = "wxls::structured")]`.
+pub mod structured {
+    use super::{CellFields, RangeFields};
+    use crate::cell::Cell;
+    use crate::range::Range;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// `Cell` or `Range`.
+    pub trait Structured: Sized {
+        fn serialize_structured<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+        fn deserialize_structured<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Self, D::Error>;
+    }
+
+    impl Structured for Cell {
+        fn serialize_structured<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            CellFields::from(self).serialize(serializer)
+        }
+
+        fn deserialize_structured<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Cell, D::Error> {
+            CellFields::deserialize(deserializer)?
+                .into_cell()
+                .map_err(de::Error::custom)
+        }
+    }
+
+    impl Structured for Range {
+        fn serialize_structured<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            RangeFields::from(self).serialize(serializer)
+        }
+
+        fn deserialize_structured<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Range, D::Error> {
+            RangeFields::deserialize(deserializer)?
+                .into_range()
+                .map_err(de::Error::custom)
+        }
+    }
+
+    pub fn serialize<T: Structured, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.serialize_structured(serializer)
+    }
+
+    pub fn deserialize<'de, T: Structured, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        T::deserialize_structured(deserializer)
+    }
+}
+
+#[wasm_bindgen]
+impl Cell {
+    /// Plain object of the cell, e.g. `{ row: 0, column: 1, sheet: "Data" }`, which can be
+    /// posted to a worker or stored in settings.
+    pub fn to_object(&self) -> Result<JsValue, WebExcelError> {
+        serde_wasm_bindgen::to_value(&CellFields::from(self)).map_err(|_| WebExcelError::ParseError)
+    }
+
+    /// Cell of a plain object made by `to_object`, or of an address such as `Data!B1`.
+    pub fn from_object(value: JsValue) -> Result<Cell, WebExcelError> {
+        serde_wasm_bindgen::from_value(value).map_err(|_| WebExcelError::ParseError)
+    }
+}
+
+#[wasm_bindgen]
+impl Range {
+    /// Plain object of the range, with its corners as in `Cell.to_object`.
+    pub fn to_object(&self) -> Result<JsValue, WebExcelError> {
+        serde_wasm_bindgen::to_value(&RangeFields::from(self))
+            .map_err(|_| WebExcelError::ParseError)
+    }
+
+    /// Range of a plain object made by `to_object`, or of an address such as `Data!A1:C4`.
+    pub fn from_object(value: JsValue) -> Result<Range, WebExcelError> {
+        serde_wasm_bindgen::from_value(value).map_err(|_| WebExcelError::ParseError)
+    }
+}
@@ -0,0 +1,90 @@
+use crate::cell::Cell;
+use crate::math::eval::*;
+use crate::range::Range;
+
+/// Cell at an address such as `B2` or `Data!B2`, on `sheet` unless the address names one.
+pub fn cell(address: &str, sheet: Option<&str>) -> Cell {
+    let (sheet, address) = match address.split_once('!') {
+        Some((named, address)) => (Some(named), address),
+        None => (sheet, address),
+    };
+    Cell::from_str_address(address, sheet.map(str::to_owned)).unwrap()
+}
+
+/// Range at an address such as `A1:C4`, `B2` or `Data!A1:C4`, on `sheet` unless the
+/// address names one.
+pub fn area(address: &str, sheet: Option<&str>) -> Range {
+    let (sheet, address) = match address.split_once('!') {
+        Some((named, address)) => (Some(named), address),
+        None => (sheet, address),
+    };
+    let (start, end) = address.split_once(':').unwrap_or((address, address));
+    Range::new(&cell(start, sheet), &cell(end, sheet)).unwrap()
+}
+
+/// Cell values keyed by address, e.g. `("A1", 1.0)`, with the arrays spilled from
+/// the ranges given to `with_spill`
+pub struct Sheet {
+    cells: Vec<(Cell, CellValue)>,
+    spills: Vec<Range>,
+}
+
+impl Sheet {
+    pub fn new(cells: &[(&str, CellValue)]) -> Sheet {
+        cells
+            .iter()
+            .map(|(addr, value)| (cell(addr, None), value.clone()))
+            .collect()
+    }
+
+    /// The same sheet with an array spilled over `address`, e.g. `D1:D3`.
+    pub fn with_spill(mut self, address: &str) -> Sheet {
+        self.spills.push(area(address, None));
+        self
+    }
+}
+
+impl FromIterator<(Cell, CellValue)> for Sheet {
+    fn from_iter<I: IntoIterator<Item = (Cell, CellValue)>>(iter: I) -> Self {
+        Sheet {
+            cells: iter.into_iter().collect(),
+            spills: vec![],
+        }
+    }
+}
+
+impl CellSource for Sheet {
+    fn value(&self, cell: &Cell) -> CellValue {
+        self.cells
+            .iter()
+            .find(|(c, _)| c == cell)
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default()
+    }
+
+    fn used_bounds(&self, _sheet: Option<&str>) -> Option<(u32, u32)> {
+        self.cells
+            .iter()
+            .map(|(c, _)| (c.row, c.column))
+            .reduce(|(row, column), (r, c)| (row.max(r), column.max(c)))
+    }
+
+    fn spill_range(&self, anchor: &Cell) -> Option<Range> {
+        self.spills
+            .iter()
+            .find(|range| range.cell_start == *anchor)
+            .cloned()
+    }
+}
+
+pub fn evaluate(source: &Sheet, formula: &str) -> Value {
+    Evaluator::new(source).evaluate_formula(formula).unwrap()
+}
+
+pub fn scalar(source: &Sheet, formula: &str) -> CellValue {
+    evaluate(source, formula).into_scalar()
+}
+
+pub fn array(source: &Sheet, formula: &str) -> Vec<Vec<CellValue>> {
+    evaluate(source, formula).into_array()
+}
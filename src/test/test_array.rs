@@ -0,0 +1,302 @@
+use crate::cell::*;
+use crate::math::array::*;
+use crate::math::eval::*;
+use crate::math::parser::{parse_formula, ErrorValue};
+use crate::test::common::*;
+
+/// Cell values keyed by address, with one array spilled from `D1`
+fn sheet() -> Sheet {
+    let text = |s: &str| CellValue::Text(s.to_owned());
+    Sheet::new(&[
+        ("A1", text("pear")),
+        ("A2", text("apple")),
+        ("A3", text("fig")),
+        ("A4", text("Apple")),
+        ("B1", CellValue::Number(3.0)),
+        ("B2", CellValue::Number(1.0)),
+        ("B3", CellValue::Number(2.0)),
+        ("B4", CellValue::Number(1.0)),
+        ("D1", CellValue::Number(10.0)),
+        ("D2", CellValue::Number(20.0)),
+        ("D3", CellValue::Number(30.0)),
+    ])
+    .with_spill("D1:D3")
+}
+
+fn numbers(rows: &[&[f64]]) -> Vec<Vec<CellValue>> {
+    rows.iter()
+        .map(|row| row.iter().map(|n| CellValue::Number(*n)).collect())
+        .collect()
+}
+
+fn texts(column: &[&str]) -> Vec<Vec<CellValue>> {
+    column
+        .iter()
+        .map(|s| vec![CellValue::Text(s.to_string())])
+        .collect()
+}
+
+fn error(e: ErrorValue) -> Vec<Vec<CellValue>> {
+    vec![vec![CellValue::Error(e)]]
+}
+
+#[test]
+fn test_array_generate() {
+    let sheet = sheet();
+
+    assert_eq!(
+        array(&sheet, "=SEQUENCE(2,3)"),
+        numbers(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]])
+    );
+    assert_eq!(
+        array(&sheet, "=SEQUENCE(3,,10,-5)"),
+        numbers(&[&[10.0], &[5.0], &[0.0]])
+    );
+    assert_eq!(array(&sheet, "=SEQUENCE(0)"), error(ErrorValue::Calc));
+    assert_eq!(array(&sheet, "=SEQUENCE(-1)"), error(ErrorValue::Value));
+    assert_eq!(
+        array(&sheet, "=SEQUENCE(1048576,16384)"),
+        error(ErrorValue::Num)
+    );
+    assert_eq!(
+        array(&sheet, "=RANDARRAY(1048576,16384)"),
+        error(ErrorValue::Num)
+    );
+    assert_eq!(
+        array(&sheet, "=ROWS(SEQUENCE(1048576))"),
+        numbers(&[&[1048576.0]])
+    );
+
+    let random = array(&sheet, "=RANDARRAY(3,2,5,7,TRUE)");
+    assert_eq!((random.len(), random[0].len()), (3, 2));
+    assert!(random.iter().flatten().all(|v| matches!(
+        v,
+        CellValue::Number(n) if (5.0..=7.0).contains(n) && n.fract() == 0.0
+    )));
+    assert_eq!(
+        array(&sheet, "=RANDARRAY(1,1,2,1)"),
+        error(ErrorValue::Value)
+    );
+
+    // The same seed gives the same numbers
+    let seeded = |seed| {
+        Evaluator::new(&sheet)
+            .with_seed(seed)
+            .evaluate_formula("=RANDARRAY(2,2)")
+            .unwrap()
+            .into_array()
+    };
+    assert_eq!(seeded(7), seeded(7));
+    assert_ne!(seeded(7), seeded(8));
+}
+
+#[test]
+fn test_array_filter_sort() {
+    let sheet = sheet();
+
+    assert_eq!(
+        array(&sheet, "=FILTER(A1:A4,B1:B4=1)"),
+        texts(&["apple", "Apple"])
+    );
+    assert_eq!(
+        array(&sheet, "=FILTER(A1:B4,B1:B4>5,\"none\")"),
+        texts(&["none"])
+    );
+    assert_eq!(
+        array(&sheet, "=FILTER(A1:B4,B1:B4>5)"),
+        error(ErrorValue::Calc)
+    );
+    assert_eq!(
+        array(&sheet, "=FILTER(A1:B4,B1:B2>5)"),
+        error(ErrorValue::Value)
+    );
+    assert_eq!(
+        array(&sheet, "=FILTER({1,2,3},{TRUE,FALSE,TRUE})"),
+        numbers(&[&[1.0, 3.0]])
+    );
+
+    assert_eq!(
+        array(&sheet, "=SORT(A1:A3)"),
+        texts(&["apple", "fig", "pear"])
+    );
+    assert_eq!(
+        array(&sheet, "=SORT(B1:B3,1,-1)"),
+        numbers(&[&[3.0], &[2.0], &[1.0]])
+    );
+    assert_eq!(
+        array(&sheet, "=SORT({3,1,2},,,TRUE)"),
+        numbers(&[&[1.0, 2.0, 3.0]])
+    );
+    assert_eq!(array(&sheet, "=SORT(B1:B3,2)"), error(ErrorValue::Value));
+    assert_eq!(array(&sheet, "=SORT(B1:B3,1,0)"), error(ErrorValue::Value));
+
+    assert_eq!(
+        array(&sheet, "=SORTBY(A1:A3,B1:B3)"),
+        texts(&["apple", "fig", "pear"])
+    );
+    assert_eq!(
+        array(&sheet, "=SORTBY(A1:A4,B1:B4,1,A1:A4,-1)"),
+        texts(&["apple", "Apple", "fig", "pear"])
+    );
+    assert_eq!(
+        array(&sheet, "=SORTBY(A1:A4,B1:B2)"),
+        error(ErrorValue::Value)
+    );
+}
+
+#[test]
+fn test_array_unique() {
+    let sheet = sheet();
+
+    assert_eq!(
+        array(&sheet, "=UNIQUE(A1:A4)"),
+        texts(&["pear", "apple", "fig"])
+    );
+    assert_eq!(
+        array(&sheet, "=UNIQUE(A1:A4,,TRUE)"),
+        texts(&["pear", "fig"])
+    );
+    assert_eq!(
+        array(&sheet, "=UNIQUE({1,1,2},TRUE)"),
+        numbers(&[&[1.0, 2.0]])
+    );
+    assert_eq!(
+        array(&sheet, "=UNIQUE({1;1},,TRUE)"),
+        error(ErrorValue::Calc)
+    );
+    assert_eq!(
+        scalar(&sheet, "=ROWS(UNIQUE(SEQUENCE(100000)))"),
+        CellValue::Number(100000.0)
+    );
+}
+
+#[test]
+fn test_array_reshape() {
+    let sheet = sheet();
+    let grid = "{1,2,3;4,5,6;7,8,9}";
+
+    assert_eq!(
+        array(&sheet, &format!("=TAKE({},2)", grid)),
+        numbers(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]])
+    );
+    assert_eq!(
+        array(&sheet, &format!("=TAKE({},-1,-2)", grid)),
+        numbers(&[&[8.0, 9.0]])
+    );
+    assert_eq!(
+        array(&sheet, &format!("=TAKE({},,1)", grid)),
+        numbers(&[&[1.0], &[4.0], &[7.0]])
+    );
+    assert_eq!(
+        array(&sheet, &format!("=DROP({},2,1)", grid)),
+        numbers(&[&[8.0, 9.0]])
+    );
+    assert_eq!(
+        array(&sheet, &format!("=DROP({},-2,-2)", grid)),
+        numbers(&[&[1.0]])
+    );
+    assert_eq!(
+        array(&sheet, &format!("=DROP({},3)", grid)),
+        error(ErrorValue::Calc)
+    );
+    assert_eq!(
+        array(&sheet, &format!("=TAKE({},0)", grid)),
+        error(ErrorValue::Calc)
+    );
+
+    assert_eq!(
+        array(&sheet, &format!("=CHOOSEROWS({},3,-3)", grid)),
+        numbers(&[&[7.0, 8.0, 9.0], &[1.0, 2.0, 3.0]])
+    );
+    assert_eq!(
+        array(&sheet, &format!("=CHOOSECOLS({},{{2,1}})", grid)),
+        numbers(&[&[2.0, 1.0], &[5.0, 4.0], &[8.0, 7.0]])
+    );
+    assert_eq!(
+        array(&sheet, &format!("=CHOOSECOLS({},4)", grid)),
+        error(ErrorValue::Value)
+    );
+
+    let na = CellValue::Error(ErrorValue::NA);
+    assert_eq!(
+        array(&sheet, "=VSTACK({1,2},3)"),
+        vec![
+            vec![CellValue::Number(1.0), CellValue::Number(2.0)],
+            vec![CellValue::Number(3.0), na.clone()]
+        ]
+    );
+    assert_eq!(
+        array(&sheet, "=HSTACK({1;2},3)"),
+        vec![
+            vec![CellValue::Number(1.0), CellValue::Number(3.0)],
+            vec![CellValue::Number(2.0), na]
+        ]
+    );
+
+    assert_eq!(
+        array(&sheet, "=TOCOL({1,2;3,4})"),
+        numbers(&[&[1.0], &[2.0], &[3.0], &[4.0]])
+    );
+    assert_eq!(
+        array(&sheet, "=TOROW({1,2;3,4},,TRUE)"),
+        numbers(&[&[1.0, 3.0, 2.0, 4.0]])
+    );
+    assert_eq!(
+        array(&sheet, "=TOROW(HSTACK(1,C1,1/0),3)"),
+        numbers(&[&[1.0]])
+    );
+    assert_eq!(array(&sheet, "=TOCOL(1,4)"), error(ErrorValue::Value));
+}
+
+#[test]
+fn test_array_spill() {
+    let sheet = sheet();
+    let anchor = Cell::from_str_address("F1", None).unwrap();
+    let value = Evaluator::new(&sheet)
+        .evaluate_formula("=SEQUENCE(2,3)")
+        .unwrap();
+
+    let range = spill_range(&anchor, &value).unwrap();
+    assert_eq!(range.to_str_address().unwrap(), "F1:H2");
+    assert_eq!(check_spill(&anchor, &value, &sheet).unwrap(), range);
+
+    // B1:B4 is taken, so a spill from B1 is blocked, but a scalar is not
+    let anchor = Cell::from_str_address("B1", None).unwrap();
+    assert_eq!(
+        check_spill(&anchor, &value, &sheet).unwrap_err(),
+        ErrorValue::Spill
+    );
+    assert!(check_spill(&anchor, &Value::number(1.0), &sheet).is_ok());
+
+    // Running off the sheet
+    let anchor = Cell::from_str_address("XFD1", None).unwrap();
+    assert!(spill_range(&anchor, &value).is_err());
+    assert_eq!(
+        check_spill(&anchor, &value, &sheet).unwrap_err(),
+        ErrorValue::Spill
+    );
+}
+
+#[test]
+fn test_array_spill_reference() {
+    let sheet = sheet();
+
+    let expr = parse_formula("=SUM(D1#)*2").unwrap();
+    assert_eq!(expr.to_formula().unwrap(), "SUM(D1#)*2");
+    assert_eq!(array(&sheet, "=SUM(D1#)"), numbers(&[&[60.0]]));
+    assert_eq!(
+        array(&sheet, "=D1#*2"),
+        numbers(&[&[20.0], &[40.0], &[60.0]])
+    );
+    assert_eq!(array(&sheet, "=A1#"), error(ErrorValue::Ref));
+
+    assert_eq!(
+        parse_formula("=Sheet2!$D$1#")
+            .unwrap()
+            .to_formula()
+            .unwrap(),
+        "Sheet2!$D$1#"
+    );
+    assert!(parse_formula("=D1:D2#").is_err());
+    assert!(parse_formula("=D1##").is_err());
+}
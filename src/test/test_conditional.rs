@@ -0,0 +1,246 @@
+use crate::cell::*;
+use crate::conditional::*;
+use crate::error::WebExcelError;
+use crate::range::*;
+use crate::test::common;
+use matches::assert_matches;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+fn cell(address: &str) -> Cell {
+    common::cell(address, Some("Sheet1"))
+}
+
+fn area(address: &str) -> Range {
+    common::area(address, Some("Sheet1"))
+}
+
+#[test]
+fn test_conditional_cell_value() {
+    let format = ConditionalFormat::cell_value(
+        &area("B2:C5"),
+        CellValueOperator::Between,
+        "1",
+        Some("=$A$1*2".to_owned()),
+    )
+    .unwrap()
+    .with_fill_color("#FFC7CE")
+    .with_font_color("#9C0006")
+    .with_bold(true);
+
+    assert_eq!(format.kind(), "CellValue");
+    assert_eq!(
+        format.to_json().unwrap(),
+        concat!(
+            r##"{"type":"CellValue","range":"Sheet1!B2:C5","cellValue":{"format":"##,
+            r##"{"fill":{"color":"#FFC7CE"},"font":{"color":"#9C0006","bold":true}},"##,
+            r##""rule":{"formula1":"=1","operator":"Between","formula2":"=$A$1*2"}}}"##
+        )
+    );
+
+    // Two formulas exactly for the between operators
+    assert!(
+        ConditionalFormat::cell_value(&area("B2:C5"), CellValueOperator::Between, "1", None)
+            .is_err()
+    );
+    assert!(ConditionalFormat::cell_value(
+        &area("B2:C5"),
+        CellValueOperator::GreaterThan,
+        "1",
+        Some("2".to_owned())
+    )
+    .is_err());
+    assert_matches!(
+        ConditionalFormat::cell_value(&area("B2"), CellValueOperator::EqualTo, "=1+", None),
+        Err(WebExcelError::FormulaParseError)
+    );
+}
+
+#[test]
+fn test_conditional_custom_relative() {
+    let format = ConditionalFormat::custom(&area("C3:D6"), "=AND($C3>0,D$1<>\"\")")
+        .unwrap()
+        .with_stop_if_true(true);
+    assert_eq!(
+        format.to_json().unwrap(),
+        concat!(
+            r#"{"type":"Custom","range":"Sheet1!C3:D6","custom":{"format":{},"#,
+            r#""rule":{"formula":"=AND($C3>0,D$1<>\"\")"}},"stopIfTrue":true}"#
+        )
+    );
+
+    // References move from the top-left cell to the evaluated one
+    assert_eq!(
+        format.formulas_at(&cell("D5")).unwrap(),
+        vec!["=AND($C5>0,E$1<>\"\")"]
+    );
+    assert_matches!(
+        format.formulas_at(&cell("A1")),
+        Err(WebExcelError::OutOfBoundError)
+    );
+
+    // A formula written for A1 is moved to the top-left cell
+    let format = ConditionalFormat::custom(&area("C3:D6"), "=A1>$A$1")
+        .unwrap()
+        .relative_to(&cell("A1"))
+        .unwrap();
+    assert_eq!(format.formulas_at(&cell("C3")).unwrap(), vec!["=C3>$A$1"]);
+    assert_matches!(
+        ConditionalFormat::custom(&area("A1:B2"), "=B2")
+            .unwrap()
+            .relative_to(&cell("C3")),
+        Err(WebExcelError::RelocateError)
+    );
+
+    // External workbooks are not allowed in conditional formats
+    assert!(ConditionalFormat::custom(&area("A1"), "=[Book2.xlsx]Sheet1!A1>0").is_err());
+}
+
+#[test]
+fn test_conditional_scales() {
+    let minimum = ScalePoint::at(ScaleKind::LowestValue, Some("#F8696B".to_owned())).unwrap();
+    let midpoint = ScalePoint::new(
+        ScaleKind::Percentile,
+        Some("50".to_owned()),
+        Some("#FFEB84".to_owned()),
+    )
+    .unwrap();
+    let maximum = ScalePoint::at(ScaleKind::HighestValue, Some("#63BE7B".to_owned())).unwrap();
+    let format =
+        ConditionalFormat::color_scale(&area("A1:A9"), &minimum, Some(midpoint), &maximum).unwrap();
+    assert_eq!(
+        format.to_json().unwrap(),
+        concat!(
+            r##"{"type":"ColorScale","range":"Sheet1!A1:A9","colorScale":{"criteria":{"##,
+            r##""minimum":{"type":"LowestValue","color":"#F8696B"},"##,
+            r##""midpoint":{"type":"Percentile","formula":"=50","color":"#FFEB84"},"##,
+            r##""maximum":{"type":"HighestValue","color":"#63BE7B"}}}}"##
+        )
+    );
+
+    // Points need a value exactly when placed by one, scales need colors
+    assert!(ScalePoint::new(ScaleKind::Number, None, None).is_err());
+    assert!(ScalePoint::new(ScaleKind::LowestValue, Some("1".to_owned()), None).is_err());
+    let colorless = ScalePoint::at(ScaleKind::LowestValue, None).unwrap();
+    assert!(ConditionalFormat::color_scale(&area("A1:A9"), &colorless, None, &maximum).is_err());
+
+    let format = ConditionalFormat::data_bar(&area("B1:B9"), "#638EC6")
+        .with_bar_bounds(
+            &ScalePoint::new(ScaleKind::Number, Some("0".to_owned()), None).unwrap(),
+            &ScalePoint::at(ScaleKind::Automatic, None).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(
+        format.to_json().unwrap(),
+        concat!(
+            r##"{"type":"DataBar","range":"Sheet1!B1:B9","dataBar":{"##,
+            r##""lowerBoundRule":{"type":"Number","formula":"=0"},"##,
+            r##""upperBoundRule":{"type":"Automatic"},"##,
+            r##""positiveFormat":{"fillColor":"#638EC6"}}}"##
+        )
+    );
+    assert!(format.with_icon_options(true, false).is_err());
+}
+
+#[test]
+fn test_conditional_icon_set() {
+    let format = ConditionalFormat::icon_set(
+        &area("C1:C9"),
+        IconSetStyle::ThreeTrafficLights1,
+        ScaleKind::Percent,
+        vec![33.0, 67.0],
+    )
+    .unwrap()
+    .with_icon_options(true, true)
+    .unwrap();
+    assert_eq!(
+        format.to_json().unwrap(),
+        concat!(
+            r#"{"type":"IconSet","range":"Sheet1!C1:C9","iconSet":{"style":"ThreeTrafficLights1","#,
+            r#""reverseIconOrder":true,"showIconOnly":true,"criteria":[{},"#,
+            r#"{"type":"Percent","formula":"=33","operator":"GreaterThanOrEqual"},"#,
+            r#"{"type":"Percent","formula":"=67","operator":"GreaterThanOrEqual"}]}}"#
+        )
+    );
+
+    assert_matches!(
+        ConditionalFormat::icon_set(
+            &area("C1:C9"),
+            IconSetStyle::FiveRating,
+            ScaleKind::Number,
+            vec![1.0, 2.0]
+        ),
+        Err(WebExcelError::DimensionError)
+    );
+    assert!(ConditionalFormat::icon_set(
+        &area("C1:C9"),
+        IconSetStyle::ThreeArrows,
+        ScaleKind::LowestValue,
+        vec![1.0, 2.0]
+    )
+    .is_err());
+}
+
+#[test]
+fn test_conditional_presets() {
+    let top = ConditionalFormat::top_bottom(&area("A1:A20"), TopBottomKind::TopPercent, 10)
+        .unwrap()
+        .with_italic(true);
+    assert_eq!(
+        top.to_json().unwrap(),
+        concat!(
+            r#"{"type":"TopBottom","range":"Sheet1!A1:A20","topBottom":{"#,
+            r#""format":{"font":{"italic":true}},"rule":{"rank":10,"type":"TopPercent"}}}"#
+        )
+    );
+    assert!(ConditionalFormat::top_bottom(&area("A1"), TopBottomKind::BottomPercent, 101).is_err());
+    assert!(ConditionalFormat::top_bottom(&area("A1"), TopBottomKind::TopItems, 0).is_err());
+
+    let duplicates = ConditionalFormat::duplicates(&area("A1:B4")).with_fill_color("yellow");
+    assert_eq!(
+        duplicates.to_json().unwrap(),
+        concat!(
+            r#"{"type":"PresetCriteria","range":"Sheet1!A1:B4","preset":{"#,
+            r#""format":{"fill":{"color":"yellow"}},"rule":{"criterion":"DuplicateValues"}}}"#
+        )
+    );
+    assert!(ConditionalFormat::unique(&area("A1:B4"))
+        .to_json()
+        .unwrap()
+        .contains("UniqueValues"));
+
+    let text = ConditionalFormat::text_contains(&area("D1:D5"), "say \"hi\"").unwrap();
+    assert_eq!(text.kind(), "ContainsText");
+    assert!(text
+        .to_json()
+        .unwrap()
+        .contains(r#""rule":{"operator":"Contains","text":"say \"hi\""}"#));
+    assert!(text.formulas_at(&cell("D2")).unwrap().is_empty());
+    assert!(ConditionalFormat::text_contains(&area("D1:D5"), "").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_conditional_js() {
+    let format =
+        ConditionalFormat::cell_value(&area("B2:C5"), CellValueOperator::GreaterThan, "1", None)
+            .unwrap()
+            .with_fill_color("#FFC7CE");
+
+    let object = format.to_js().unwrap();
+    let field = |object: &JsValue, name: &str| {
+        js_sys::Reflect::get(object, &JsValue::from_str(name)).unwrap()
+    };
+    assert_eq!(
+        field(&object, "type").as_string(),
+        Some("CellValue".to_owned())
+    );
+    assert_eq!(
+        field(&object, "range").as_string(),
+        Some("Sheet1!B2:C5".to_owned())
+    );
+    let fill = field(&field(&field(&object, "cellValue"), "format"), "fill");
+    assert_eq!(
+        field(&fill, "color").as_string(),
+        Some("#FFC7CE".to_owned())
+    );
+}
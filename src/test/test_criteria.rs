@@ -0,0 +1,170 @@
+use crate::cell::*;
+use crate::math::criteria::*;
+use crate::math::eval::*;
+use crate::math::parser::ErrorValue;
+use crate::test::common::*;
+
+/// Fruits in column A, quantities in column B and prices in column C, rows 1 to 6
+fn sheet() -> Sheet {
+    let rows = [
+        ("apple", 3.0, 1.5),
+        ("Apricot", 5.0, 2.0),
+        ("banana", 12.0, 0.5),
+        ("*special", 1.0, 9.0),
+        ("", 7.0, 3.0),
+        ("cherry", 5.0, 4.0),
+    ];
+
+    let mut cells = vec![];
+    for (i, (name, quantity, price)) in rows.iter().enumerate() {
+        let cell = |column: u32| Cell::new(i as u32, column, None).unwrap();
+        if !name.is_empty() {
+            cells.push((cell(0), CellValue::Text(name.to_string())));
+        }
+        cells.push((cell(1), CellValue::Number(*quantity)));
+        cells.push((cell(2), CellValue::Number(*price)));
+    }
+    cells.into_iter().collect()
+}
+
+fn text(s: &str) -> CellValue {
+    CellValue::Text(s.to_owned())
+}
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match("ab*c?", "abXYZcd"));
+    assert!(wildcard_match("ab*c?", "ABcD"));
+    assert!(!wildcard_match("ab*c?", "abXYZc"));
+    assert!(wildcard_match("*", ""));
+    assert!(wildcard_match("a*b*c", "aXbYbZc"));
+    assert!(wildcard_match("~*", "*"));
+    assert!(!wildcard_match("~*", "x"));
+    assert!(wildcard_match("what~?", "What?"));
+    assert!(wildcard_match("~~", "~"));
+}
+
+#[test]
+fn test_criteria_operators() {
+    let number = CellValue::Number(5.0);
+
+    assert!(Criteria::parse(">=5").matches(&number));
+    assert!(!Criteria::parse(">5").matches(&number));
+    assert!(Criteria::parse("<5.5").matches(&number));
+    assert!(Criteria::parse("<>4").matches(&number));
+    assert!(Criteria::parse("=5").matches(&number));
+    assert!(Criteria::parse("5").matches(&text("5")));
+    assert!(Criteria::new(&number).matches(&number));
+
+    // Numbers and text never order against each other
+    assert!(!Criteria::parse(">1").matches(&text("9")));
+    assert!(!Criteria::parse("<z").matches(&number));
+    assert!(Criteria::parse("<b").matches(&text("Apple")));
+
+    assert!(Criteria::parse("true").matches(&CellValue::Bool(true)));
+    assert!(Criteria::parse("#N/A").matches(&CellValue::Error(ErrorValue::NA)));
+    assert!(!Criteria::parse("#N/A").matches(&CellValue::Error(ErrorValue::Ref)));
+
+    // An empty criteria cell is read as zero
+    assert!(Criteria::new(&CellValue::Empty).matches(&CellValue::Number(0.0)));
+}
+
+#[test]
+fn test_criteria_blanks() {
+    let empty = CellValue::Empty;
+    let empty_text = text("");
+
+    assert!(Criteria::parse("").matches(&empty));
+    assert!(Criteria::parse("").matches(&empty_text));
+    assert!(Criteria::parse("=").matches(&empty));
+    assert!(!Criteria::parse("=").matches(&empty_text));
+    assert!(!Criteria::parse("<>").matches(&empty));
+    assert!(Criteria::parse("<>").matches(&empty_text));
+    assert!(Criteria::parse("<>").matches(&CellValue::Number(0.0)));
+
+    // Text criteria never match blanks, negated text criteria always do
+    assert!(!Criteria::parse("*").matches(&empty));
+    assert!(Criteria::parse("<>a*").matches(&empty));
+}
+
+#[test]
+fn test_criteria_functions() {
+    let sheet = sheet();
+
+    assert_eq!(
+        scalar(&sheet, "=COUNTIF(A1:A6,\"ap*\")"),
+        CellValue::Number(2.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=COUNTIF(A1:A6,\"~*special\")"),
+        CellValue::Number(1.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=COUNTIF(A1:A6,\"<>\")"),
+        CellValue::Number(5.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=COUNTIF(A1:A6,\"\")"),
+        CellValue::Number(1.0)
+    );
+    assert_eq!(scalar(&sheet, "=COUNTIF(B1:B6,5)"), CellValue::Number(2.0));
+    assert_eq!(
+        scalar(&sheet, "=SUMIF(B1:B6,\">4\")"),
+        CellValue::Number(29.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=SUMIF(A1:A6,\"a*\",C1:C6)"),
+        CellValue::Number(3.5)
+    );
+    assert_eq!(
+        scalar(&sheet, "=AVERAGEIF(B1:B6,\"<5\")"),
+        CellValue::Number(2.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=AVERAGEIF(B1:B6,\">100\")"),
+        CellValue::Error(ErrorValue::Div0)
+    );
+
+    assert_eq!(
+        scalar(&sheet, "=COUNTIFS(B1:B6,5,C1:C6,\">3\")"),
+        CellValue::Number(1.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=SUMIFS(C1:C6,B1:B6,\">=5\",A1:A6,\"<>\")"),
+        CellValue::Number(6.5)
+    );
+    assert_eq!(
+        scalar(&sheet, "=AVERAGEIFS(B1:B6,C1:C6,\"<=2\")"),
+        CellValue::Number(20.0 / 3.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=MAXIFS(C1:C6,A1:A6,\"?*\")"),
+        CellValue::Number(9.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=MINIFS(C1:C6,B1:B6,\">100\")"),
+        CellValue::Number(0.0)
+    );
+    match scalar(&sheet, "=SUMIFS(C1:C6,B1:B6,\">100\")") {
+        CellValue::Number(n) => assert!(n == 0.0 && n.is_sign_positive()),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(
+        scalar(&sheet, "=SUMIFS(C1:C6,B1:B5,\">1\")"),
+        CellValue::Error(ErrorValue::Value)
+    );
+    assert_eq!(
+        scalar(&sheet, "=COUNTIFS(B1:B6,5,C1:C6)"),
+        CellValue::Error(ErrorValue::Value)
+    );
+
+    // An array of criteria gives an array of results
+    assert_eq!(
+        evaluate(&sheet, "=COUNTIF(B1:B6,{5,3,100})").into_array(),
+        vec![vec![
+            CellValue::Number(2.0),
+            CellValue::Number(1.0),
+            CellValue::Number(0.0)
+        ]]
+    );
+}
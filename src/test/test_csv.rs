@@ -0,0 +1,174 @@
+use crate::cell::Cell;
+use crate::csv::*;
+use crate::error::WebExcelError;
+use crate::math::eval::CellValue;
+use crate::math::parser::ErrorValue;
+use matches::assert_matches;
+
+fn text(s: &str) -> CellValue {
+    CellValue::Text(s.to_owned())
+}
+
+fn parse(csv: &str) -> CsvData {
+    CsvData::parse(csv, &CsvOptions::new()).unwrap()
+}
+
+#[test]
+fn test_csv_parse_fields() {
+    let data = parse("name,note\r\n\"Doe, Jane\",\"said \"\"hi\"\"\nthen left\"\r\nBob,\r\n");
+    assert_eq!(data.rows(), 3);
+    assert_eq!(data.columns(), 2);
+    assert_eq!(
+        data.values(),
+        &[
+            vec![text("name"), text("note")],
+            vec![text("Doe, Jane"), text("said \"hi\"\nthen left")],
+            vec![text("Bob"), CellValue::Empty],
+        ]
+    );
+
+    // Bare line feeds and carriage returns, a byte order mark, ragged and blank records
+    let data = parse("\u{feff}a\n\nb,c,d\rlast,");
+    assert_eq!(
+        data.values(),
+        &[
+            vec![text("a"), CellValue::Empty, CellValue::Empty],
+            vec![CellValue::Empty, CellValue::Empty, CellValue::Empty],
+            vec![text("b"), text("c"), text("d")],
+            vec![text("last"), CellValue::Empty, CellValue::Empty],
+        ]
+    );
+
+    assert_eq!(parse("").rows(), 0);
+    assert_matches!(
+        CsvData::parse("a,\"open\n", &CsvOptions::new()),
+        Err(WebExcelError::ParseError)
+    );
+    assert_matches!(
+        CsvData::parse("\"closed\"x,b", &CsvOptions::new()),
+        Err(WebExcelError::ParseError)
+    );
+}
+
+#[test]
+fn test_csv_type_inference() {
+    let data = parse("1.5,-2,1e3,50%,007,0.25,\"42\",TRUE,false, 3,#N/A,1E400,-1e999%\n");
+    assert_eq!(
+        data.values()[0],
+        vec![
+            CellValue::Number(1.5),
+            CellValue::Number(-2.0),
+            CellValue::Number(1000.0),
+            CellValue::Number(0.5),
+            text("007"),
+            CellValue::Number(0.25),
+            text("42"),
+            CellValue::Bool(true),
+            CellValue::Bool(false),
+            text(" 3"),
+            CellValue::Error(ErrorValue::NA),
+            text("1E400"),
+            text("-1e999%"),
+        ]
+    );
+
+    let data =
+        parse("2024-03-05,1900-02-29,2024-03-05 14:30,2024-03-05T06:00:00,18:00,2023-02-29\n");
+    assert_eq!(
+        data.values()[0],
+        vec![
+            CellValue::Number(45356.0),
+            CellValue::Number(60.0),
+            CellValue::Number(45356.0 + 14.5 / 24.0),
+            CellValue::Number(45356.25),
+            CellValue::Number(0.75),
+            text("2023-02-29"),
+        ]
+    );
+    assert_eq!(
+        data.formats()[0],
+        vec![
+            Some(CSV_DATE_FORMAT),
+            Some(CSV_DATE_FORMAT),
+            Some(CSV_DATE_TIME_FORMAT),
+            Some(CSV_DATE_TIME_FORMAT),
+            Some(CSV_TIME_FORMAT),
+            None,
+        ]
+    );
+
+    let options = CsvOptions::new()
+        .with_delimiter(';')
+        .unwrap()
+        .with_infer_types(false);
+    let data = CsvData::parse("1;TRUE;2024-01-01", &options).unwrap();
+    assert_eq!(
+        data.values()[0],
+        vec![text("1"), text("TRUE"), text("2024-01-01")]
+    );
+}
+
+#[test]
+fn test_csv_range_at() {
+    let data = parse("a,b,c\n1,2,3\n");
+    let anchor = Cell::from_str_address("B2", Some("Data".to_owned())).unwrap();
+    let range = data.range_at(&anchor).unwrap();
+    assert_eq!(range.to_str_address().unwrap(), "Data!B2:Data!D3");
+
+    let corner = Cell::from_str_address("XFD1", None).unwrap();
+    assert_matches!(data.range_at(&corner), Err(WebExcelError::OutOfBoundError));
+    assert_matches!(
+        parse("").range_at(&anchor),
+        Err(WebExcelError::DimensionError)
+    );
+}
+
+#[test]
+fn test_csv_export() {
+    let values = vec![
+        vec![
+            text("id"),
+            text("name, full"),
+            text("quote \"q\""),
+            text("TRUE"),
+        ],
+        vec![
+            CellValue::Number(1.5),
+            text("007"),
+            CellValue::Bool(true),
+            text("42"),
+        ],
+        vec![
+            CellValue::Empty,
+            CellValue::Error(ErrorValue::Div0),
+            text("line\nbreak"),
+            CellValue::Empty,
+        ],
+    ];
+    let csv = to_csv(&values, &CsvOptions::new());
+    assert_eq!(
+        csv,
+        "id,\"name, full\",\"quote \"\"q\"\"\",\"TRUE\"\r\n1.5,007,TRUE,\"42\"\r\n,#DIV/0!,\"line\nbreak\",\r\n"
+    );
+    // Text read back as text, the rest as before
+    assert_eq!(parse(&csv).values(), values.as_slice());
+
+    let options = CsvOptions::new()
+        .with_delimiter('\t')
+        .unwrap()
+        .with_quote_style(QuoteStyle::NonNumeric)
+        .with_line_ending(LineEnding::Lf);
+    assert_eq!(
+        to_csv(&values[1..2], &options),
+        "1.5\t\"007\"\t\"TRUE\"\t\"42\"\n"
+    );
+    let options = CsvOptions::new().with_quote_style(QuoteStyle::Always);
+    assert_eq!(
+        to_csv(&[vec![CellValue::Number(2.0), CellValue::Empty]], &options),
+        "\"2\",\"\"\r\n"
+    );
+    assert_matches!(
+        CsvOptions::new().with_delimiter('"'),
+        Err(WebExcelError::ParseError)
+    );
+}
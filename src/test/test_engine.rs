@@ -0,0 +1,261 @@
+use crate::cell::*;
+use crate::math::engine::*;
+use crate::math::eval::CellValue;
+use crate::math::parser::ErrorValue;
+use crate::test::common;
+
+fn cell(address: &str) -> Cell {
+    common::cell(address, Some("S"))
+}
+
+fn addresses(cells: Vec<Cell>) -> Vec<String> {
+    cells
+        .iter()
+        .map(|c| c.to_str_address().unwrap().replace("S!", ""))
+        .collect()
+}
+
+fn number(engine: &CalcEngine, address: &str) -> f64 {
+    match engine.value(&cell(address)) {
+        CellValue::Number(n) => n,
+        other => panic!("{} holds {:?}", address, other),
+    }
+}
+
+#[test]
+fn test_engine_incremental() {
+    let mut engine = CalcEngine::new();
+    engine.set_number(&cell("A1"), 1.0);
+    engine.set_number(&cell("A2"), 2.0);
+    engine.set_formula(&cell("B1"), "=A1*10").unwrap();
+    engine.set_formula(&cell("B2"), "=A2*10").unwrap();
+    engine.set_formula(&cell("C1"), "=B1+B2").unwrap();
+
+    assert_eq!(addresses(engine.calculate(false)), vec!["B1", "C1", "B2"]);
+    assert_eq!(number(&engine, "C1"), 30.0);
+
+    // Only A1's dependents are calculated, B2 keeps its value
+    engine.set_number(&cell("A1"), 5.0);
+    assert_eq!(addresses(engine.calculate(false)), vec!["B1", "C1"]);
+    assert_eq!(number(&engine, "C1"), 70.0);
+
+    // A calculation that gives the same value reports nothing
+    engine.set_number(&cell("A2"), 2.0);
+    assert!(engine.calculate(false).is_empty());
+    assert!(engine.calculate(false).is_empty());
+
+    engine.clear(&cell("A1"));
+    assert_eq!(addresses(engine.calculate(false)), vec!["B1", "C1"]);
+    assert_eq!(number(&engine, "C1"), 20.0);
+}
+
+#[test]
+fn test_engine_formula_order() {
+    // Formulas entered out of order still calculate precedents first
+    let mut engine = CalcEngine::new();
+    engine.set_formula(&cell("A1"), "=A2+1").unwrap();
+    engine.set_formula(&cell("A2"), "=A3+1").unwrap();
+    engine.set_number(&cell("A3"), 1.0);
+    engine.calculate(false);
+    assert_eq!(number(&engine, "A1"), 3.0);
+
+    // Replacing a formula by a value. Values entered by hand are not reported
+    engine.set_number(&cell("A2"), 10.0);
+    assert_eq!(addresses(engine.calculate(false)), vec!["A1"]);
+    assert_eq!(number(&engine, "A1"), 11.0);
+    assert!(!engine.has_formula(&cell("A2")));
+}
+
+#[test]
+fn test_engine_sheet_case() {
+    // Sheet names are case insensitive, in reading and in recalculation
+    let mut engine = CalcEngine::new();
+    engine.set_number(&cell("A1"), 2.0);
+    engine.set_formula(&cell("B1"), "=s!A1*10").unwrap();
+    engine.calculate(false);
+    assert_eq!(number(&engine, "B1"), 20.0);
+
+    let lower = Cell::from_str_address("A1", Some("s".to_owned())).unwrap();
+    engine.set_number(&lower, 3.0);
+    assert_eq!(addresses(engine.calculate(false)), vec!["B1"]);
+    assert_eq!(number(&engine, "B1"), 30.0);
+
+    // Whole columns are read up to the used bounds of the sheet, whatever its case
+    engine.set_number(&cell("A3"), 4.0);
+    engine.set_formula(&cell("C1"), "=SUM(s!A:A)").unwrap();
+    engine.calculate(false);
+    assert_eq!(number(&engine, "C1"), 7.0);
+}
+
+#[test]
+fn test_engine_large_sheet() {
+    let mut engine = CalcEngine::new();
+    for row in 1..=3001 {
+        engine.set_number(&cell(&format!("A{}", row)), 1.0);
+    }
+    for row in 1..=3000 {
+        let formula = format!("=SUM(A{}:A{})", row, row + 1);
+        engine
+            .set_formula(&cell(&format!("B{}", row)), &formula)
+            .unwrap();
+    }
+    assert_eq!(engine.calculate(false).len(), 3000);
+
+    engine.set_number(&cell("A1"), 5.0);
+    assert_eq!(addresses(engine.calculate(false)), vec!["B1"]);
+    assert_eq!(number(&engine, "B1"), 6.0);
+}
+
+#[test]
+fn test_engine_volatile() {
+    let mut engine = CalcEngine::new();
+    engine.set_formula(&cell("A1"), "=RAND()").unwrap();
+    engine.set_formula(&cell("B1"), "=A1*0+1").unwrap();
+    engine.set_formula(&cell("C1"), "=A1").unwrap();
+    engine.set_formula(&cell("D1"), "=1").unwrap();
+    engine.calculate(false);
+
+    let first = number(&engine, "A1");
+    assert!((0.0..1.0).contains(&first));
+
+    // Nothing was edited, yet RAND and its dependents are calculated again
+    assert_eq!(addresses(engine.calculate(false)), vec!["A1", "C1"]);
+    assert_ne!(number(&engine, "A1"), first);
+    assert_eq!(number(&engine, "C1"), number(&engine, "A1"));
+}
+
+#[test]
+fn test_engine_offset_indirect() {
+    let mut engine = CalcEngine::new();
+    engine.set_number(&cell("A1"), 1.0);
+    engine.set_number(&cell("A2"), 2.0);
+    engine.set_number(&cell("A3"), 3.0);
+    engine.set_text(&cell("C1"), "S!A3");
+    engine
+        .set_formula(&cell("B1"), "=SUM(OFFSET(A1,1,0,2))")
+        .unwrap();
+    engine.set_formula(&cell("B2"), "=INDIRECT(C1)*2").unwrap();
+    engine.set_formula(&cell("B3"), "=OFFSET(A1,-1,0)").unwrap();
+    engine.calculate(false);
+
+    assert_eq!(number(&engine, "B1"), 5.0);
+    assert_eq!(number(&engine, "B2"), 6.0);
+    assert_eq!(engine.value(&cell("B3")), CellValue::Error(ErrorValue::Ref));
+
+    // Both are volatile, so they notice A3 even though they do not reference it
+    engine.set_number(&cell("A3"), 4.0);
+    assert_eq!(addresses(engine.calculate(false)), vec!["B1", "B2"]);
+}
+
+#[test]
+fn test_engine_spill() {
+    let mut engine = CalcEngine::new();
+    engine.set_number(&cell("A1"), 3.0);
+    engine.set_formula(&cell("B1"), "=SEQUENCE(A1)").unwrap();
+    engine.set_formula(&cell("C1"), "=SUM(B1#)").unwrap();
+    engine.set_formula(&cell("D1"), "=B3").unwrap();
+
+    assert_eq!(
+        addresses(engine.calculate(false)),
+        vec!["B1", "C1", "D1", "B2", "B3"]
+    );
+    assert_eq!(number(&engine, "C1"), 6.0);
+    assert_eq!(number(&engine, "D1"), 3.0);
+    assert_eq!(
+        engine
+            .spill_of(&cell("B1"))
+            .unwrap()
+            .to_str_address()
+            .unwrap(),
+        "S!B1:S!B3"
+    );
+
+    // Shrinking the array clears the cells it no longer covers
+    engine.set_number(&cell("A1"), 2.0);
+    assert_eq!(addresses(engine.calculate(false)), vec!["C1", "D1", "B3"]);
+    assert_eq!(engine.value(&cell("D1")), CellValue::Empty);
+
+    // A value in the way blocks the spill until it is removed
+    engine.set_text(&cell("B2"), "x");
+    engine.calculate(false);
+    assert_eq!(
+        engine.value(&cell("B1")),
+        CellValue::Error(ErrorValue::Spill)
+    );
+    assert_eq!(engine.value(&cell("C1")), CellValue::Error(ErrorValue::Ref));
+
+    engine.clear(&cell("B2"));
+    engine.calculate(false);
+    assert_eq!(number(&engine, "B2"), 2.0);
+    assert_eq!(number(&engine, "C1"), 3.0);
+}
+
+#[test]
+fn test_engine_circular() {
+    let mut engine = CalcEngine::new();
+    engine.set_formula(&cell("A1"), "=B1+1").unwrap();
+    engine.set_formula(&cell("B1"), "=A1+1").unwrap();
+    engine.set_formula(&cell("C1"), "=A1+5").unwrap();
+    engine.calculate(false);
+
+    assert_eq!(number(&engine, "A1"), 0.0);
+    assert_eq!(number(&engine, "C1"), 5.0);
+}
+
+#[test]
+fn test_engine_iteration() {
+    // Interest on the average of the opening and closing balance
+    let mut engine = CalcEngine::new();
+    engine.set_number(&cell("A1"), 1000.0);
+    engine.set_number(&cell("A2"), 0.1);
+    engine.set_formula(&cell("B1"), "=A1+B2").unwrap();
+    engine.set_formula(&cell("B2"), "=(A1+B1)/2*A2").unwrap();
+    engine.calculate(false);
+
+    let status = &engine.cycles()[0];
+    assert_eq!(status.members().len(), 2);
+    assert_eq!(status.iterations, 0);
+    assert!(!status.converged);
+    assert_eq!(number(&engine, "B2"), 0.0);
+
+    engine.set_iteration(IterationSettings::new(true, 100, 0.001));
+    let changed = engine.calculate(false);
+    assert_eq!(addresses(changed), vec!["B1", "B2"]);
+
+    // B2 = (2000 + B2) / 20, so B2 = 2000 / 19
+    let status = &engine.cycles()[0];
+    assert!(status.converged);
+    assert!(status.iterations > 1 && status.iterations < 100);
+    assert!(status.last_change <= 0.001);
+    assert!((number(&engine, "B2") - 2000.0 / 19.0).abs() < 0.001);
+    assert!((number(&engine, "B1") - 1000.0 - 2000.0 / 19.0).abs() < 0.001);
+
+    // Starts from the previous values when calculated again
+    engine.set_number(&cell("A2"), 0.2);
+    engine.calculate(false);
+    assert!(engine.cycles()[0].converged);
+    assert!((number(&engine, "B2") - 2000.0 / 9.0).abs() < 0.001);
+}
+
+#[test]
+fn test_engine_iteration_limit() {
+    let mut engine = CalcEngine::new();
+    engine.set_iteration(IterationSettings::new(true, 10, 0.001));
+    engine.set_formula(&cell("A1"), "=B1+1").unwrap();
+    engine.set_formula(&cell("B1"), "=A1+1").unwrap();
+    engine.calculate(false);
+
+    let status = &engine.cycles()[0];
+    assert_eq!(status.iterations, 10);
+    assert!(!status.converged);
+    assert_eq!(status.last_change, 2.0);
+    assert_eq!(number(&engine, "B1"), 20.0);
+
+    // Stops as soon as values settle
+    engine.set_iteration(IterationSettings::new(true, 10, 5.0));
+    engine.calculate(false);
+    let status = &engine.cycles()[0];
+    assert_eq!(status.iterations, 1);
+    assert!(status.converged);
+    assert_eq!(number(&engine, "B1"), 22.0);
+}
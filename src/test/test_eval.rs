@@ -0,0 +1,289 @@
+use crate::math::eval::*;
+use crate::math::func::*;
+use crate::math::names::*;
+use crate::math::parser::ErrorValue;
+use crate::test::common::*;
+
+fn numbers() -> Sheet {
+    Sheet::new(&[
+        ("A1", CellValue::Number(1.0)),
+        ("A2", CellValue::Number(2.0)),
+        ("A3", CellValue::Number(3.0)),
+        ("B1", CellValue::Number(10.0)),
+        ("B2", CellValue::Number(20.0)),
+        ("B3", CellValue::Number(30.0)),
+        ("C1", CellValue::Text("x".to_owned())),
+    ])
+}
+
+fn row(values: &[f64]) -> Vec<CellValue> {
+    values.iter().map(|n| CellValue::Number(*n)).collect()
+}
+
+#[test]
+fn test_eval_operators() {
+    let sheet = numbers();
+
+    assert_eq!(scalar(&sheet, "=1+2*3"), CellValue::Number(7.0));
+    assert_eq!(scalar(&sheet, "=-2^2"), CellValue::Number(4.0));
+    assert_eq!(scalar(&sheet, "=A1/0"), CellValue::Error(ErrorValue::Div0));
+    assert_eq!(scalar(&sheet, "=C1+1"), CellValue::Error(ErrorValue::Value));
+    assert_eq!(scalar(&sheet, "=\"1.5\"*2"), CellValue::Number(3.0));
+    assert_eq!(
+        scalar(&sheet, "=A1&\"-\"&TRUE"),
+        CellValue::Text("1-TRUE".to_owned())
+    );
+    assert_eq!(scalar(&sheet, "=\"abc\"=\"ABC\""), CellValue::Bool(true));
+    assert_eq!(scalar(&sheet, "=\"a\">1"), CellValue::Bool(true));
+    assert_eq!(scalar(&sheet, "=SUM(A1:B3)"), CellValue::Number(66.0));
+    assert_eq!(scalar(&sheet, "=SUM(A1:C1)"), CellValue::Number(11.0));
+    assert_eq!(
+        array(&sheet, "=A1:A3*B1:B3"),
+        vec![row(&[10.0]), row(&[40.0]), row(&[90.0])]
+    );
+}
+
+#[test]
+fn test_eval_whole_references() {
+    let sheet = numbers();
+
+    assert_eq!(scalar(&sheet, "=SUM(A:A)"), CellValue::Number(6.0));
+    assert_eq!(scalar(&sheet, "=SUM(A2:B1048576)"), CellValue::Number(55.0));
+    assert_eq!(scalar(&sheet, "=COUNTA(1:1)"), CellValue::Number(3.0));
+    assert_eq!(scalar(&sheet, "=ROWS(A:A)"), CellValue::Number(1048576.0));
+    assert_eq!(scalar(&sheet, "=COLUMNS(1:2)"), CellValue::Number(16384.0));
+    assert_eq!(scalar(&sheet, "=ROWS(A:A*1)"), CellValue::Number(3.0));
+
+    let empty = Sheet::new(&[]);
+    assert_eq!(scalar(&empty, "=SUM(A:XFD)"), CellValue::Number(0.0));
+    assert_eq!(scalar(&empty, "=ROWS(A:XFD)"), CellValue::Number(1048576.0));
+
+    // References too large to hold are read up to the used bounds as well
+    assert_eq!(scalar(&sheet, "=SUM(B1:Z1000000)"), CellValue::Number(60.0));
+    assert_eq!(
+        scalar(&sheet, "=SUM(OFFSET(A1,0,0,1000000,26))"),
+        CellValue::Number(66.0)
+    );
+    assert_eq!(scalar(&empty, "=SUM(B1:Z1000000)"), CellValue::Number(0.0));
+    let far = Sheet::new(&[("Z1000000", CellValue::Number(1.0))]);
+    assert_eq!(
+        scalar(&far, "=SUM(A1:Z1000000)"),
+        CellValue::Error(ErrorValue::Num)
+    );
+    assert_eq!(scalar(&far, "=SUM(Z1:Z1000000)"), CellValue::Number(1.0));
+}
+
+#[test]
+fn test_eval_rounding() {
+    let sheet = numbers();
+
+    match scalar(&sheet, "=SUM(C1)") {
+        CellValue::Number(n) => assert!(n == 0.0 && n.is_sign_positive()),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(scalar(&sheet, "=ROUND(1.005,2)"), CellValue::Number(1.01));
+    assert_eq!(scalar(&sheet, "=ROUND(-1.005,2)"), CellValue::Number(-1.01));
+    assert_eq!(scalar(&sheet, "=ROUND(2.5,0)"), CellValue::Number(3.0));
+    assert_eq!(
+        scalar(&sheet, "=ROUNDDOWN(1.999,2)"),
+        CellValue::Number(1.99)
+    );
+    assert_eq!(
+        scalar(&sheet, "=ROUND(1234.5,-2)"),
+        CellValue::Number(1200.0)
+    );
+    assert_eq!(scalar(&sheet, "=ROUND(1E308,2)"), CellValue::Number(1E308));
+}
+
+#[test]
+fn test_eval_conditions() {
+    let sheet = numbers();
+
+    assert_eq!(
+        scalar(&sheet, "=IF(A1>0,\"pos\",1/0)"),
+        CellValue::Text("pos".to_owned())
+    );
+    assert_eq!(
+        scalar(&sheet, "=IFS(A2>5,1,A2>1,2)"),
+        CellValue::Number(2.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=SWITCH(A3,1,\"a\",3,\"c\",\"z\")"),
+        CellValue::Text("c".to_owned())
+    );
+    assert_eq!(scalar(&sheet, "=IFERROR(1/0,-1)"), CellValue::Number(-1.0));
+    assert_eq!(
+        scalar(&sheet, "=IFNA(1/0,-1)"),
+        CellValue::Error(ErrorValue::Div0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=AND(A1>0,OR(FALSE,A2=2))"),
+        CellValue::Bool(true)
+    );
+    assert_eq!(
+        scalar(&sheet, "=NOSUCHFUNCTION(1)"),
+        CellValue::Error(ErrorValue::Name)
+    );
+}
+
+#[test]
+fn test_eval_let() {
+    let sheet = numbers();
+
+    assert_eq!(
+        scalar(&sheet, "=LET(x,A2,y,x*10,x+y)"),
+        CellValue::Number(22.0)
+    );
+    // Inner bindings shadow outer ones
+    assert_eq!(
+        scalar(&sheet, "=LET(x,1,LET(x,2,x)+x)"),
+        CellValue::Number(3.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=LET(x,1,y)"),
+        CellValue::Error(ErrorValue::Name)
+    );
+    assert_eq!(
+        scalar(&sheet, "=LET(x,1)"),
+        CellValue::Error(ErrorValue::Value)
+    );
+}
+
+#[test]
+fn test_eval_lambda() {
+    let sheet = numbers();
+
+    assert_eq!(
+        scalar(&sheet, "=LAMBDA(x,y,x*y)(3,4)"),
+        CellValue::Number(12.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=LET(f,LAMBDA(x,x+A1),f(5))"),
+        CellValue::Number(6.0)
+    );
+    assert_eq!(
+        scalar(&sheet, "=LAMBDA(x,x)(1,2)"),
+        CellValue::Error(ErrorValue::Value)
+    );
+    assert_eq!(
+        scalar(&sheet, "=LAMBDA(x,x)"),
+        CellValue::Error(ErrorValue::Calc)
+    );
+
+    // Closures keep the variables of their definition
+    assert_eq!(
+        scalar(&sheet, "=LET(k,10,f,LAMBDA(x,x*k),LET(k,100,f(2)))"),
+        CellValue::Number(20.0)
+    );
+}
+
+#[test]
+fn test_eval_lambda_helpers() {
+    let sheet = numbers();
+
+    assert_eq!(
+        array(&sheet, "=MAP(A1:A3,LAMBDA(v,v*v))"),
+        vec![row(&[1.0]), row(&[4.0]), row(&[9.0])]
+    );
+    assert_eq!(
+        array(&sheet, "=MAP(A1:A3,B1:B3,LAMBDA(a,b,a+b))"),
+        vec![row(&[11.0]), row(&[22.0]), row(&[33.0])]
+    );
+    assert_eq!(
+        scalar(&sheet, "=REDUCE(0,A1:B3,LAMBDA(acc,v,acc+v))"),
+        CellValue::Number(66.0)
+    );
+    assert_eq!(
+        array(&sheet, "=SCAN(0,A1:A3,LAMBDA(acc,v,acc+v))"),
+        vec![row(&[1.0]), row(&[3.0]), row(&[6.0])]
+    );
+    assert_eq!(
+        array(&sheet, "=BYROW(A1:B3,LAMBDA(r,SUM(r)))"),
+        vec![row(&[11.0]), row(&[22.0]), row(&[33.0])]
+    );
+    assert_eq!(
+        array(&sheet, "=BYCOL(A1:B3,LAMBDA(c,MAX(c)))"),
+        vec![row(&[3.0, 30.0])]
+    );
+    assert_eq!(
+        array(&sheet, "=BYROW(A1:B3,LAMBDA(r,r))")[0][0],
+        CellValue::Error(ErrorValue::Calc)
+    );
+    assert_eq!(
+        scalar(&sheet, "=MAP(A1:A3,1)"),
+        CellValue::Error(ErrorValue::Value)
+    );
+}
+
+#[test]
+fn test_eval_defined_lambda() {
+    let sheet = numbers();
+    let mut names = DefinedNames::new();
+
+    let body = FunctionBuilder::new("=SQRT(a^2+b^2)").unwrap();
+    let hypotenuse = FunctionBuilder::lambda_of(&["a", "b"], body).unwrap();
+    assert_eq!(hypotenuse.function, "=LAMBDA(a,b,SQRT(a^2+b^2))");
+
+    names.add_lambda("Hypotenuse", &hypotenuse).unwrap();
+    names
+        .add("Rate", &FunctionBuilder::new("=B1").unwrap())
+        .unwrap();
+    // Recursive lambda, counting down to zero
+    let countdown = FunctionBuilder::new("=LAMBDA(n,IF(n<=0,0,1+Countdown(n-1)))").unwrap();
+    names.add_lambda("Countdown", &countdown).unwrap();
+
+    let evaluator = Evaluator::new(&sheet).with_names(&names);
+    let evaluate = |f: &str| evaluator.evaluate_formula(f).unwrap().into_scalar();
+
+    assert_eq!(evaluate("=hypotenuse(3,4)"), CellValue::Number(5.0));
+    assert_eq!(evaluate("=Rate*2"), CellValue::Number(20.0));
+    assert_eq!(evaluate("=Countdown(5)"), CellValue::Number(5.0));
+    assert_eq!(
+        evaluate("=Countdown(100000)"),
+        CellValue::Error(ErrorValue::Num)
+    );
+}
+
+#[test]
+fn test_number_to_text() {
+    assert_eq!(number_to_text(1.0), "1");
+    assert_eq!(number_to_text(-2.5), "-2.5");
+    assert_eq!(number_to_text(0.1 + 0.2), "0.3");
+    assert_eq!(number_to_text(1e21), "1E+21");
+    assert_eq!(number_to_text(1.5e-10), "1.5E-10");
+}
+
+#[test]
+fn test_eval_volatile() {
+    let sheet = numbers();
+    let evaluator = Evaluator::new(&sheet).with_now(45292.75).with_seed(3);
+    let evaluate = |f: &str| evaluator.evaluate_formula(f).unwrap().into_scalar();
+
+    assert_eq!(evaluate("=NOW()"), CellValue::Number(45292.75));
+    assert_eq!(evaluate("=TODAY()"), CellValue::Number(45292.0));
+    assert_eq!(
+        evaluate("=RANDBETWEEN(5,4)"),
+        CellValue::Error(ErrorValue::Num)
+    );
+    for _ in 0..20 {
+        match evaluate("=RANDBETWEEN(1,3)") {
+            CellValue::Number(n) => assert!([1.0, 2.0, 3.0].contains(&n)),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    assert_eq!(
+        evaluate("=SUM(OFFSET(A1,0,0,3,2))"),
+        CellValue::Number(66.0)
+    );
+    assert_eq!(evaluate("=OFFSET(A1,2,1)"), CellValue::Number(30.0));
+    assert_eq!(
+        evaluate("=OFFSET(1,0,0)"),
+        CellValue::Error(ErrorValue::Value)
+    );
+    assert_eq!(evaluate("=INDIRECT(\"B\"&2)"), CellValue::Number(20.0));
+    assert_eq!(
+        evaluate("=INDIRECT(\"nowhere\")"),
+        CellValue::Error(ErrorValue::Ref)
+    );
+}
@@ -0,0 +1,281 @@
+use crate::math::eval::CellValue;
+use crate::math::format::*;
+use crate::math::parser::ErrorValue;
+use matches::assert_matches;
+
+fn show(code: &str, n: f64) -> String {
+    NumberFormat::new(code)
+        .unwrap()
+        .format(&CellValue::Number(n))
+        .unwrap()
+}
+
+#[test]
+fn test_format_numbers() {
+    let cases = vec![
+        ("General", 1234.5, "1234.5"),
+        ("General", -0.25, "-0.25"),
+        ("0", 2.5, "3"),
+        ("0", -2.5, "-3"),
+        ("0.00", 3.14158, "3.14"),
+        ("#,##0", 1234567.0, "1,234,567"),
+        ("#,##0.00", -1234.5, "-1,234.50"),
+        ("#,##0", 0.0, "0"),
+        ("#.##", 0.5, ".5"),
+        ("#.##", 12.0, "12."),
+        ("0.0#", 1.5, "1.5"),
+        ("00000", 42.0, "00042"),
+        ("???.??", 1.5, "  1.5 "),
+        ("000-00-0000", 123456789.0, "123-45-6789"),
+        ("0%", 0.256, "26%"),
+        ("0.0%", 1.0, "100.0%"),
+        ("#,##0,", 1234567.0, "1,235"),
+        ("0.0,,\" M\"", 12_345_678.0, "12.3 M"),
+        ("$#,##0.00", -5.0, "-$5.00"),
+        ("[$€-407] #,##0", 1000.0, "€ 1,000"),
+        ("0_);(0)", 5.0, "5 "),
+        ("\\$0", 7.0, "$7"),
+        ("0 \"items\"", 3.0, "3 items"),
+        ("*-0", 7.0, "7"),
+        ("\"Total\"", 9.0, "Total"),
+        (".00", 123.4, "123.40"),
+        ("0", 12345678901234567890.0, "12345678901234600000"),
+        ("0.00", 1.005, "1.01"),
+        ("0.0", 0.05, "0.1"),
+        ("0.00", 99.999, "100.00"),
+        ("#", 0.4, ""),
+        ("0.00", 1e-20, "0.00"),
+    ];
+    for (code, n, expected) in cases {
+        assert_eq!(show(code, n), expected, "{} with {}", code, n);
+    }
+}
+
+#[test]
+fn test_format_scientific_and_fractions() {
+    let cases = vec![
+        ("0.00E+00", 12345.0, "1.23E+04"),
+        ("0.00E+00", 0.00012, "1.20E-04"),
+        ("0.0E-0", 1500.0, "1.5E3"),
+        ("0.00E+00", 0.0, "0.00E+00"),
+        ("0.00E+00", 9.999, "1.00E+01"),
+        ("##0.0E+0", 12345.0, "12.3E+3"),
+        ("##0.0E+0", 123456.0, "123.5E+3"),
+        ("00.0E+0", 12345.0, "12.3E+3"),
+        ("# ?/?", 1.5, "1 1/2"),
+        ("# ?/?", 0.5, " 1/2"),
+        ("# ?/?", 3.0, "3    "),
+        ("# ??/??", 3.14158, "3 14/99"),
+        ("?/?", 1.25, "5/4"),
+        ("# ?/8", 2.3, "2 2/8"),
+        ("# ?/4", 0.99, "1    "),
+        ("# ???/???", 0.25, "   1/4  "),
+        ("0/0", 0.0, "0/1"),
+    ];
+    for (code, n, expected) in cases {
+        assert_eq!(show(code, n), expected, "{} with {}", code, n);
+    }
+}
+
+#[test]
+fn test_format_dates() {
+    // 2024-03-05 14:07:09.25 is a Tuesday
+    let moment = 45356.0 + (14.0 * 3600.0 + 7.0 * 60.0 + 9.25) / 86400.0;
+    let cases = vec![
+        ("yyyy-mm-dd", "2024-03-05"),
+        ("d/m/yy", "5/3/24"),
+        ("dddd, mmmm d", "Tuesday, March 5"),
+        ("ddd mmm", "Tue Mar"),
+        ("mmmmm", "M"),
+        ("hh:mm:ss", "14:07:09"),
+        ("h:mm AM/PM", "2:07 PM"),
+        ("h a/p", "2 p"),
+        ("mm:ss.00", "07:09.25"),
+        ("m", "3"),
+        ("h\"h\"m", "14h7"),
+        ("yyyy-mm-dd hh:mm", "2024-03-05 14:07"),
+    ];
+    for (code, expected) in cases {
+        assert_eq!(show(code, moment), expected, "{}", code);
+    }
+
+    assert_eq!(show("yyyy-mm-dd", 1.0), "1900-01-01");
+    assert_eq!(show("yyyy-mm-dd", 60.0), "1900-02-29");
+    assert_eq!(show("yyyy-mm-dd", 61.0), "1900-03-01");
+    assert_eq!(show("dddd", 61.0), "Thursday");
+    assert_eq!(show("yyyy-mm-dd", 0.0), "1900-01-00");
+    assert_eq!(show("yyyy-mm-dd", 2_958_465.0), "9999-12-31");
+    assert_eq!(show("h:mm:ss", 0.999999), "0:00:00");
+    assert_eq!(show("h:mm AM/PM", 0.0), "12:00 AM");
+    assert_eq!(show("[h]:mm", 1.5), "36:00");
+    assert_eq!(show("[mm]:ss", 0.05), "72:00");
+    assert_eq!(show("[s]", 0.01), "864");
+
+    let format = NumberFormat::new("yyyy").unwrap();
+    assert!(format.is_date());
+    assert_matches!(
+        format.format(&CellValue::Number(-1.0)),
+        Err(ErrorValue::Value)
+    );
+    assert_eq!(format.display(&CellValue::Number(-1.0)), "########");
+    assert!(!NumberFormat::new("0.00").unwrap().is_date());
+}
+
+#[test]
+fn test_format_sections() {
+    let format = NumberFormat::new("#,##0;[Red](#,##0);\"zero\";\"text: \"@").unwrap();
+    let value = |n: f64| format.format(&CellValue::Number(n)).unwrap();
+    assert_eq!(value(1500.0), "1,500");
+    assert_eq!(value(-1500.0), "(1,500)");
+    assert_eq!(value(0.0), "zero");
+    assert_eq!(
+        format.format(&CellValue::Text("abc".to_owned())).unwrap(),
+        "text: abc"
+    );
+    assert_eq!(
+        format.color(&CellValue::Number(-1.0)),
+        Some("Red".to_owned())
+    );
+    assert_eq!(format.color(&CellValue::Number(1.0)), None);
+
+    let two = NumberFormat::new("0.0;[Blue]-0.0").unwrap();
+    assert_eq!(two.format(&CellValue::Number(0.0)).unwrap(), "0.0");
+    assert_eq!(two.format(&CellValue::Number(-2.0)).unwrap(), "-2.0");
+    assert_eq!(two.color(&CellValue::Number(-2.0)), Some("Blue".to_owned()));
+
+    // Conditions replace the sign based choice, other numbers fall to the last section
+    let conditional = NumberFormat::new("[Color10][>=100]0\" big\";[<0]\"neg\";0.0").unwrap();
+    let value = |n: f64| conditional.format(&CellValue::Number(n)).unwrap();
+    assert_eq!(value(150.0), "150 big");
+    assert_eq!(value(-3.0), "-neg");
+    assert_eq!(value(5.0), "5.0");
+    assert_eq!(
+        conditional.color(&CellValue::Number(100.0)),
+        Some("Color10".to_owned())
+    );
+    let unmatched = NumberFormat::new("[>0]0;[<0]0").unwrap();
+    assert!(unmatched.format(&CellValue::Number(0.0)).is_err());
+
+    // Text, booleans, empty and errors
+    let text = NumberFormat::new("\"<\"@\">\"").unwrap();
+    assert_eq!(
+        text.format(&CellValue::Text("x".to_owned())).unwrap(),
+        "<x>"
+    );
+    assert_eq!(text.format(&CellValue::Number(1.5)).unwrap(), "1.5");
+    let plain = NumberFormat::new("0.00").unwrap();
+    assert_eq!(plain.format(&CellValue::Text("x".to_owned())).unwrap(), "x");
+    assert_eq!(plain.format(&CellValue::Bool(true)).unwrap(), "TRUE");
+    assert_eq!(plain.format(&CellValue::Empty).unwrap(), "");
+    assert_matches!(
+        plain.format(&CellValue::Error(ErrorValue::NA)),
+        Err(ErrorValue::NA)
+    );
+    assert_eq!(plain.display(&CellValue::Error(ErrorValue::NA)), "#N/A");
+    assert_eq!(
+        NumberFormat::new("")
+            .unwrap()
+            .format(&CellValue::Number(5.0))
+            .unwrap(),
+        ""
+    );
+    assert_eq!(
+        NumberFormat::new("0;;;")
+            .unwrap()
+            .format(&CellValue::Text("a".to_owned()))
+            .unwrap(),
+        ""
+    );
+
+    assert_eq!(
+        NumberFormat::new("[DBNum1][$-804]0")
+            .unwrap()
+            .format(&CellValue::Number(7.0))
+            .unwrap(),
+        "7"
+    );
+    for broken in [
+        "\"open",
+        "[Red",
+        "0;0;0;0;0",
+        "[Color99]0",
+        "[<=]0",
+        "[Foo]0.00",
+        "0\\",
+    ] {
+        assert!(NumberFormat::new(broken).is_err(), "{}", broken);
+    }
+}
+
+#[test]
+fn test_format_text_function() {
+    assert_eq!(format_text(&CellValue::Number(0.5), "0%").unwrap(), "50%");
+    assert_eq!(
+        format_text(&CellValue::Text("12".to_owned()), "0.0").unwrap(),
+        "12.0"
+    );
+    assert_eq!(
+        format_text(&CellValue::Text("abc".to_owned()), "0.0").unwrap(),
+        "abc"
+    );
+    assert_eq!(format_text(&CellValue::Empty, "0.00").unwrap(), "0.00");
+    assert_matches!(
+        format_text(&CellValue::Number(1.0), "\"open"),
+        Err(ErrorValue::Value)
+    );
+
+    // Huge numbers are rendered from their digits, infinities and NaN are errors
+    let huge = format_text(&CellValue::Number(1e308), "0.00").unwrap();
+    assert!(huge.starts_with("1000000000000000") && huge.ends_with(".00"));
+    assert_eq!(huge.len(), 309 + 3);
+    assert_matches!(
+        format_text(&CellValue::Number(1e308), "0%"),
+        Err(ErrorValue::Num)
+    );
+    for n in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+        assert_matches!(
+            format_text(&CellValue::Number(n), "0.00"),
+            Err(ErrorValue::Num)
+        );
+    }
+}
+
+#[test]
+fn test_format_worksheet_text() {
+    use crate::cell::Cell;
+    use crate::math::eval::Evaluator;
+    use crate::workbook::Worksheet;
+
+    let mut sheet = Worksheet::new("Sheet1").unwrap();
+    let a1 = Cell::from_str_address("A1", Some("Sheet1".to_owned())).unwrap();
+    let b1 = Cell::from_str_address("B1", Some("Sheet1".to_owned())).unwrap();
+    sheet.set_value(&a1, CellValue::Number(1234.5));
+    assert_eq!(sheet.text(&a1), "1234.5");
+    sheet.set_number_format(&a1, "#,##0.00");
+    assert_eq!(sheet.text(&a1), "1,234.50");
+    assert_eq!(sheet.text(&b1), "");
+
+    let evaluate = |formula: &str| {
+        Evaluator::new(&sheet)
+            .on_sheet(Some("Sheet1".to_owned()))
+            .evaluate_formula(formula)
+            .unwrap()
+            .into_scalar()
+    };
+    assert_eq!(
+        evaluate("=TEXT(A1,\"$#,##0\")"),
+        CellValue::Text("$1,235".to_owned())
+    );
+    assert_eq!(
+        evaluate("=TEXT({0.1,0.25},\"0%\")"),
+        CellValue::Text("10%".to_owned())
+    );
+    assert_eq!(
+        evaluate("=TEXT(45356,\"dddd\")"),
+        CellValue::Text("Tuesday".to_owned())
+    );
+    assert_eq!(
+        evaluate("=TEXT(1,\"[Red\")"),
+        CellValue::Error(ErrorValue::Value)
+    );
+}
@@ -0,0 +1,348 @@
+use crate::cell::*;
+use crate::error::WebExcelError;
+use crate::math::func::*;
+use crate::range::*;
+use matches::assert_matches;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn test_function_parse() {
+    let func = FunctionBuilder::new("sum( Sheet2!A1:A10 , 'Q1 Data'!B2)").unwrap();
+    assert_eq!(func.function, "=SUM(Sheet2!A1:A10,'Q1 Data'!B2)");
+}
+
+#[wasm_bindgen_test]
+fn test_function_sheet_dependency() {
+    let func =
+        FunctionBuilder::new("=Sheet2!A1+'Q1 Data'!B2+[Book2.xlsx]Rates!C3+D4+Sheet2!E5").unwrap();
+
+    let dependencies: Vec<String> = func.dependencies().map(|d| d.to_qualified()).collect();
+    assert_eq!(dependencies, vec!["Q1 Data", "Sheet2", "[Book2.xlsx]Rates"]);
+
+    assert!(func.depends_on("sheet2"));
+    assert!(!func.depends_on("Rates"));
+    assert!(func.has_external_dependency());
+
+    let existing = vec!["Sheet1".to_owned(), "Sheet2".to_owned()];
+    assert_eq!(func.find_missing_sheets(&existing), vec!["Q1 Data"]);
+}
+
+#[wasm_bindgen_test]
+fn test_function_local_dependency() {
+    let func = FunctionBuilder::new("=A1*2").unwrap();
+
+    assert_eq!(func.dependencies().count(), 0);
+    assert!(!func.has_external_dependency());
+}
+
+#[wasm_bindgen_test]
+fn test_function_from_range() {
+    let start = Cell::from_str_address("A1", Some("Sheet 3".to_owned())).unwrap();
+    let end = Cell::from_str_address("C4", Some("Sheet 3".to_owned())).unwrap();
+    let range = Range::new(&start, &end).unwrap();
+
+    let func = FunctionBuilder::from_range(&range).unwrap();
+    assert_eq!(func.function, "='Sheet 3'!A1:C4");
+    assert!(func.depends_on("Sheet 3"));
+
+    let external = Cell::from_str_address("B2", Some("[Book2.xlsx]Rates".to_owned())).unwrap();
+    let func = FunctionBuilder::from_cell(&external).unwrap();
+    assert_eq!(func.function, "=[Book2.xlsx]Rates!B2");
+    assert!(func.has_external_dependency());
+}
+
+#[wasm_bindgen_test]
+fn test_function_if_then() {
+    let score = FunctionBuilder::from_cell(&Cell::from_str_address("B2", None).unwrap()).unwrap();
+    let pass = score
+        .compare(
+            ComparisonOperator::GreaterOrEqual,
+            &FunctionBuilder::number(60.0).unwrap(),
+        )
+        .unwrap();
+
+    let func = FunctionBuilder::if_then(
+        &pass,
+        &FunctionBuilder::text("Pass").unwrap(),
+        &FunctionBuilder::text("Say \"retry\"").unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(func.function, "=IF(B2>=60,\"Pass\",\"Say \"\"retry\"\"\")");
+}
+
+#[wasm_bindgen_test]
+fn test_function_and_or() {
+    let a1 = FunctionBuilder::from_cell(&Cell::from_str_address("A1", None).unwrap()).unwrap();
+    let b1 =
+        FunctionBuilder::from_cell(&Cell::from_str_address("B1", Some("Data".to_owned())).unwrap())
+            .unwrap();
+    let zero = FunctionBuilder::number(0.0).unwrap();
+
+    let positive = a1.compare(ComparisonOperator::Greater, &zero).unwrap();
+    let nonzero = b1.compare(ComparisonOperator::NotEqual, &zero).unwrap();
+    let flag = FunctionBuilder::boolean(true).unwrap();
+
+    let all = positive.and(&nonzero).unwrap().and(&flag).unwrap();
+    assert_eq!(all.function, "=AND(A1>0,Data!B1<>0,TRUE)");
+    assert!(all.depends_on("Data"));
+
+    let any = all.or(&positive.not().unwrap()).unwrap();
+    assert_eq!(any.function, "=OR(AND(A1>0,Data!B1<>0,TRUE),NOT(A1>0))");
+}
+
+#[wasm_bindgen_test]
+fn test_function_ifs_switch() {
+    let a1 = FunctionBuilder::from_cell(&Cell::from_str_address("A1", None).unwrap()).unwrap();
+    let one = FunctionBuilder::number(1.0).unwrap();
+    let two = FunctionBuilder::number(2.0).unwrap();
+
+    let ifs = FunctionBuilder::ifs(
+        &a1.compare(ComparisonOperator::Less, &one).unwrap(),
+        &FunctionBuilder::text("low").unwrap(),
+    )
+    .unwrap()
+    .ifs_case(
+        &FunctionBuilder::boolean(true).unwrap(),
+        &FunctionBuilder::text("high").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(ifs.function, "=IFS(A1<1,\"low\",TRUE,\"high\")");
+
+    let switch = FunctionBuilder::switch(&a1, &one, &FunctionBuilder::text("one").unwrap())
+        .unwrap()
+        .switch_case(&two, &FunctionBuilder::text("two").unwrap())
+        .unwrap()
+        .switch_default(&FunctionBuilder::text("many").unwrap())
+        .unwrap();
+    assert_eq!(switch.function, "=SWITCH(A1,1,\"one\",2,\"two\",\"many\")");
+    assert!(switch.switch_case(&one, &two).is_err());
+
+    // `ifs_case` only extends an IFS
+    assert!(switch.ifs_case(&one, &two).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_function_iferror() {
+    let range = Range::new(
+        &Cell::from_str_address("A1", None).unwrap(),
+        &Cell::from_str_address("A10", None).unwrap(),
+    )
+    .unwrap();
+    let sum =
+        FunctionBuilder::call("sum", vec![FunctionBuilder::from_range(&range).unwrap()]).unwrap();
+
+    let func = sum
+        .ifna(&FunctionBuilder::number(0.0).unwrap())
+        .unwrap()
+        .iferror(&FunctionBuilder::text("").unwrap())
+        .unwrap();
+    assert_eq!(func.function, "=IFERROR(IFNA(SUM(A1:A10),0),\"\")");
+}
+
+#[wasm_bindgen_test]
+fn test_function_limits() {
+    let mut func = FunctionBuilder::number(1.0).unwrap();
+    for _ in 0..64 {
+        func = func.not().unwrap();
+    }
+    assert_matches!(func.not(), Err(WebExcelError::FormulaNestingError));
+    assert_matches!(
+        FunctionBuilder::new(&format!("={}1{}", "ABS(".repeat(65), ")".repeat(65))),
+        Err(WebExcelError::FormulaNestingError)
+    );
+
+    assert!(FunctionBuilder::text(&"x".repeat(255)).is_ok());
+    assert_matches!(
+        FunctionBuilder::text(&"x".repeat(256)),
+        Err(WebExcelError::FormulaLengthError)
+    );
+
+    let chunk = FunctionBuilder::text(&"x".repeat(250)).unwrap();
+    let mut long = chunk.clone();
+    for _ in 0..31 {
+        long = long.and(&chunk).unwrap();
+    }
+    assert_matches!(long.and(&chunk), Err(WebExcelError::FormulaLengthError));
+    assert_matches!(
+        FunctionBuilder::number(f64::NAN),
+        Err(WebExcelError::FormulaBuildError)
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_function_let() {
+    let x = FunctionBuilder::variable("x").unwrap();
+    let y = FunctionBuilder::variable("y").unwrap();
+    let body = x.compare(ComparisonOperator::Greater, &y).unwrap();
+
+    let inner =
+        FunctionBuilder::let_in("y", &FunctionBuilder::number(2.0).unwrap(), &body).unwrap();
+    let func =
+        FunctionBuilder::let_in("x", &FunctionBuilder::new("=A1*3").unwrap(), &inner).unwrap();
+    assert_eq!(func.function, "=LET(x,A1*3,y,2,x>y)");
+
+    assert_matches!(
+        FunctionBuilder::variable("A1"),
+        Err(WebExcelError::NameError)
+    );
+    assert_matches!(
+        FunctionBuilder::variable("my name"),
+        Err(WebExcelError::NameError)
+    );
+    assert_matches!(
+        FunctionBuilder::variable("R2C3"),
+        Err(WebExcelError::NameError)
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_function_with_let() {
+    let func = FunctionBuilder::new("=IF(SUM(A1:A9)>10,SUM(A1:A9),0)").unwrap();
+    assert_eq!(
+        func.with_let().unwrap().function,
+        "=LET(v_1,SUM(A1:A9),IF(v_1>10,v_1,0))"
+    );
+
+    // Nested repetition is named from the outside in, defined from the inside out
+    let func = FunctionBuilder::new("=(SUM(A1:A3)*2+1)/(SUM(A1:A3)*2+1)+SUM(A1:A3)*2").unwrap();
+    assert_eq!(
+        func.with_let().unwrap().function,
+        "=LET(v_2,SUM(A1:A3)*2,v_1,v_2+1,v_1/v_1+v_2)"
+    );
+
+    // Literals are not hoisted, and differently anchored references are different
+    let func = FunctionBuilder::new("=IF(A1>-1,-1,B1*-1)+B1*-1").unwrap();
+    assert_eq!(
+        func.with_let().unwrap().function,
+        "=LET(v_1,B1*-1,IF(A1>-1,-1,v_1)+v_1)"
+    );
+    let func = FunctionBuilder::new("=IF(A1>-1,-1,B1*-1)").unwrap();
+    assert_eq!(func.with_let().unwrap(), func);
+    let func = FunctionBuilder::new("=SUM(A1)+SUM($A$1)").unwrap();
+    assert_eq!(func.with_let().unwrap(), func);
+
+    // Volatile functions and lambda parameters stay where they are
+    let func = FunctionBuilder::new("=RAND()+RAND()+LAMBDA(v_1,v_1*2+v_1*2)(1)").unwrap();
+    assert_eq!(func.with_let().unwrap(), func);
+}
+
+#[wasm_bindgen_test]
+fn test_function_lambda() {
+    let body = FunctionBuilder::new("=x*rate").unwrap();
+    let func = FunctionBuilder::lambda_of(&["x", "rate"], body.clone()).unwrap();
+    assert!(func.is_lambda());
+
+    let applied = func
+        .invoke(vec![
+            FunctionBuilder::number(2.0).unwrap(),
+            FunctionBuilder::number(0.1).unwrap(),
+        ])
+        .unwrap();
+    assert_eq!(applied.function, "=LAMBDA(x,rate,x*rate)(2,0.1)");
+
+    assert_matches!(
+        FunctionBuilder::lambda_of(&["x", "X"], body.clone()),
+        Err(WebExcelError::NameError)
+    );
+    assert_matches!(body.invoke(vec![]), Err(WebExcelError::FormulaBuildError));
+
+    let mut names = crate::math::names::DefinedNames::new();
+    assert_matches!(
+        names.add_lambda("Scale", &body),
+        Err(WebExcelError::FormulaBuildError)
+    );
+    names.add_lambda("Scale", &func).unwrap();
+    assert_matches!(names.add("SCALE", &func), Err(WebExcelError::NameError));
+    assert!(names.has("scale"));
+}
+
+#[wasm_bindgen_test]
+fn test_function_spill() {
+    let anchor = Cell::new(0, 3, Some("Data".to_owned())).unwrap();
+    let func = FunctionBuilder::from_spill(&anchor).unwrap();
+    assert_eq!(func.function, "=Data!D1#");
+    assert!(func.depends_on("data"));
+
+    let func = FunctionBuilder::new("=ROWS(Data!D1#)").unwrap();
+    assert_eq!(func.function, "=ROWS(Data!D1#)");
+}
+
+#[wasm_bindgen_test]
+fn test_function_criteria() {
+    let criteria =
+        |op, value: FunctionBuilder| FunctionBuilder::criteria(op, &value).unwrap().function;
+    let number = |n| FunctionBuilder::number(n).unwrap();
+    let text = |t| FunctionBuilder::text(t).unwrap();
+
+    assert_eq!(
+        criteria(ComparisonOperator::GreaterOrEqual, number(5.0)),
+        "=\">=5\""
+    );
+    assert_eq!(criteria(ComparisonOperator::Equal, number(5.0)), "=5");
+    assert_eq!(
+        criteria(ComparisonOperator::NotEqual, text("a*b")),
+        "=\"<>a~*b\""
+    );
+    assert_eq!(criteria(ComparisonOperator::Equal, text("<5")), "=\"=<5\"");
+    assert_eq!(criteria(ComparisonOperator::Less, text("m")), "=\"<m\"");
+    assert_eq!(
+        criteria(
+            ComparisonOperator::Greater,
+            FunctionBuilder::new("=Sheet2!B1").unwrap()
+        ),
+        "=\">\"&Sheet2!B1"
+    );
+    assert_eq!(
+        FunctionBuilder::criteria_pattern("ab*", true)
+            .unwrap()
+            .function,
+        "=\"<>ab*\""
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_function_criteria_aggregate() {
+    let range = |s: &str| {
+        let (start, end) = s.split_once(':').unwrap();
+        let sheet = Some("Data".to_owned());
+        Range::new(
+            &Cell::from_str_address(start, sheet.clone()).unwrap(),
+            &Cell::from_str_address(end, sheet).unwrap(),
+        )
+        .unwrap()
+    };
+    let over_five = FunctionBuilder::criteria(
+        ComparisonOperator::Greater,
+        &FunctionBuilder::number(5.0).unwrap(),
+    )
+    .unwrap();
+    let fruit = FunctionBuilder::criteria_pattern("ap*", false).unwrap();
+
+    let func = FunctionBuilder::sum_ifs(&range("C1:C9"), &range("B1:B9"), &over_five)
+        .unwrap()
+        .criteria_case(&range("A1:A9"), &fruit)
+        .unwrap();
+    assert_eq!(
+        func.function,
+        "=SUMIFS(Data!C1:C9,Data!B1:B9,\">5\",Data!A1:A9,\"ap*\")"
+    );
+    assert!(func.depends_on("Data"));
+
+    let func = FunctionBuilder::count_if(&range("A1:A9"), &fruit).unwrap();
+    assert_eq!(func.function, "=COUNTIF(Data!A1:A9,\"ap*\")");
+    assert_matches!(
+        func.criteria_case(&range("B1:B9"), &fruit),
+        Err(WebExcelError::FormulaBuildError)
+    );
+
+    assert_matches!(
+        FunctionBuilder::max_ifs(&range("C1:C9"), &range("B1:B5"), &over_five),
+        Err(WebExcelError::FormulaBuildError)
+    );
+    let func = FunctionBuilder::count_ifs(&range("A1:A9"), &fruit).unwrap();
+    assert_matches!(
+        func.criteria_case(&range("B1:B5"), &over_five),
+        Err(WebExcelError::FormulaBuildError)
+    );
+}
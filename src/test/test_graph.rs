@@ -0,0 +1,131 @@
+use crate::cell::*;
+use crate::error::WebExcelError;
+use crate::math::func::*;
+use crate::math::graph::*;
+use crate::test::common::cell;
+use matches::assert_matches;
+
+fn graph(formulas: &[(&str, &str)]) -> DependencyGraph {
+    let mut graph = DependencyGraph::new();
+    for (address, formula) in formulas {
+        graph.set_formula(
+            &cell(address, None),
+            &FunctionBuilder::new(formula).unwrap(),
+        );
+    }
+    graph
+}
+
+fn addresses(cells: Vec<Cell>) -> Vec<String> {
+    cells.iter().map(|c| c.to_str_address().unwrap()).collect()
+}
+
+#[test]
+fn test_graph_precedents() {
+    let graph = graph(&[
+        ("S!C1", "=SUM(A1:A100)+B1"),
+        ("S!A50", "=D1*2"),
+        ("S!D1", "=Other!A1"),
+    ]);
+
+    let direct: Vec<String> = graph
+        .direct_precedents(&cell("S!C1", None))
+        .iter()
+        .map(|r| r.to_str_address().unwrap())
+        .collect();
+    assert_eq!(direct, vec!["S!A1:S!A100", "S!B1:S!B1"]);
+
+    let all: Vec<String> = graph
+        .all_precedents(&cell("S!C1", None))
+        .iter()
+        .map(|r| r.to_str_address().unwrap())
+        .collect();
+    assert_eq!(
+        all,
+        vec!["S!A1:S!A100", "S!B1:S!B1", "S!D1:S!D1", "Other!A1:Other!A1"]
+    );
+}
+
+#[test]
+fn test_graph_dependents() {
+    let mut graph = graph(&[
+        ("S!C1", "=SUM(A:A)"),
+        ("S!C2", "=C1+1"),
+        ("S!C3", "=C2+A7"),
+        ("T!C1", "=S!A7"),
+    ]);
+
+    assert_eq!(
+        addresses(graph.direct_dependents(&cell("S!A7", None))),
+        vec!["S!C1", "S!C3", "T!C1"]
+    );
+    assert_eq!(
+        addresses(graph.all_dependents(&cell("S!A1", None))),
+        vec!["S!C1", "S!C2", "S!C3"]
+    );
+    // Same address on another sheet is not read by anyone
+    assert!(graph.direct_dependents(&cell("T!A7", None)).is_empty());
+
+    assert!(graph.remove(&cell("S!C3", None)));
+    assert!(!graph.remove(&cell("S!C3", None)));
+    assert_eq!(
+        addresses(graph.direct_dependents(&cell("S!A7", None))),
+        vec!["S!C1", "T!C1"]
+    );
+}
+
+#[test]
+fn test_graph_order() {
+    let graph = graph(&[
+        ("S!A1", "=B1+C1"),
+        ("S!B1", "=C1*2"),
+        ("S!C1", "=D1"),
+        ("S!E1", "=A1#"),
+    ]);
+
+    assert_eq!(
+        addresses(graph.order().unwrap()),
+        vec!["S!C1", "S!B1", "S!A1", "S!E1"]
+    );
+    assert!(graph.cycles().is_empty());
+
+    // The kept order follows formulas being set and removed
+    let mut graph = graph;
+    graph.set_formula(&cell("S!D1", None), &FunctionBuilder::new("=E1").unwrap());
+    assert_matches!(graph.order(), Err(WebExcelError::CircularReferenceError));
+    graph.remove(&cell("S!E1", None));
+    assert_eq!(
+        addresses(graph.order().unwrap()),
+        vec!["S!D1", "S!C1", "S!B1", "S!A1"]
+    );
+
+    // Only the cells asked for, e.g. B1 and its dependents
+    let pending = [cell("S!A1", None), cell("S!B1", None), cell("S!Z1", None)].into();
+    assert_eq!(
+        graph
+            .components_of(&pending)
+            .into_iter()
+            .map(addresses)
+            .collect::<Vec<_>>(),
+        vec![vec!["S!B1"], vec!["S!A1"]]
+    );
+}
+
+#[test]
+fn test_graph_cycles() {
+    let graph = graph(&[
+        ("S!A1", "=B1+1"),
+        ("S!B1", "=SUM(C1:C5)"),
+        ("S!C3", "=A1"),
+        ("S!D1", "=D1"),
+        ("S!E1", "=A1+D1"),
+    ]);
+
+    assert_matches!(graph.order(), Err(WebExcelError::CircularReferenceError));
+    let cycles: Vec<Vec<String>> = graph.cycles().into_iter().map(addresses).collect();
+    assert_eq!(cycles, vec![vec!["S!A1", "S!B1", "S!C3"], vec!["S!D1"]]);
+
+    // Cycles are calculated before the formulas reading them
+    let components = graph.components();
+    assert_eq!(addresses(components.last().unwrap().clone()), vec!["S!E1"]);
+}
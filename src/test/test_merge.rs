@@ -0,0 +1,60 @@
+use crate::cell::*;
+use crate::merge::*;
+use crate::range::*;
+use crate::test::common;
+
+fn cell(address: &str) -> Cell {
+    common::cell(address, Some("Sheet1"))
+}
+
+fn area(address: &str) -> Range {
+    common::area(address, Some("Sheet1"))
+}
+
+#[test]
+fn test_merge_registry() {
+    let mut merged = MergedCells::new();
+    merged.merge(&area("B2:C3")).unwrap();
+    merged.merge(&area("D2:F2")).unwrap();
+    merged.merge(&area("A9")).unwrap();
+    assert_eq!(merged.len(), 2);
+
+    // Overlapping merges are rejected, other sheets are apart
+    assert!(merged.merge(&area("C3:D4")).is_err());
+    assert!(merged.merge(&area("A1:B2")).is_err());
+    let other = Range::new(
+        &Cell::from_str_address("B2", Some("Other".to_owned())).unwrap(),
+        &Cell::from_str_address("C3", Some("Other".to_owned())).unwrap(),
+    )
+    .unwrap();
+    merged.merge(&other).unwrap();
+    assert_eq!(merged.len(), 3);
+
+    assert!(merged.is_merged(&cell("C3")));
+    assert!(!merged.is_merged(&cell("A9")));
+    assert_eq!(merged.merge_of(&cell("E2")), Some(area("D2:F2")));
+    assert_eq!(merged.anchor(&cell("C3")), cell("B2"));
+    assert_eq!(merged.anchor(&cell("G7")), cell("G7"));
+
+    assert_eq!(merged.unmerge(&area("A1:D2")), 2);
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged.anchor(&cell("C3")), cell("C3"));
+}
+
+#[test]
+fn test_merge_expand() {
+    let mut merged = MergedCells::new();
+    merged.merge(&area("B2:C3")).unwrap();
+    merged.merge(&area("D3:D6")).unwrap();
+    merged.merge(&area("A8:B9")).unwrap();
+
+    // Selecting one cell of a merge selects all of it
+    assert_eq!(merged.expand(&area("C2")), area("B2:C3"));
+
+    // Growing over one merge reaches the next
+    assert_eq!(merged.expand(&area("C3:D3")), area("B2:D6"));
+
+    assert_eq!(merged.expand(&area("F1:G4")), area("F1:G4"));
+    assert_eq!(merged.expand(&area("A7:A8")), area("A7:B9"));
+    assert_eq!(merged.expand(&area("A1:A8")), area("A1:C9"));
+}
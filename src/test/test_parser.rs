@@ -0,0 +1,149 @@
+use crate::error;
+use crate::math::parser::*;
+use matches::assert_matches;
+
+#[test]
+fn test_parse_round_trip() {
+    let formulas = vec![
+        ("=1+2*3", "1+2*3"),
+        ("=(1+2)*3", "(1+2)*3"),
+        ("=SUM(A1:B2, 3)", "SUM(A1:B2,3)"),
+        ("=-2^2", "-2^2"),
+        ("=-(2^2)", "-(2^2)"),
+        ("=1-(2-3)", "1-(2-3)"),
+        ("=A1&\"say \"\"hi\"\"\"", "A1&\"say \"\"hi\"\"\""),
+        ("=$A$1+A$2+$B3", "$A$1+A$2+$B3"),
+        ("=IF(A1,,#N/A)", "IF(A1,,#N/A)"),
+        ("={1,2;3,4}", "{1,2;3,4}"),
+        ("=50%*A:B", "50%*A:B"),
+        ("=SUM(2:3)", "SUM(2:3)"),
+        ("=LAMBDA(x,x+1)(2)", "LAMBDA(x,x+1)(2)"),
+    ];
+
+    for (formula, expected) in formulas {
+        let expr = parse_formula(formula).unwrap();
+        assert_eq!(expr.to_formula().unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_parse_sheet_reference() {
+    let expr = parse_formula("='My Sheet'!A1:B2+[Book2.xlsx]Data!C3+Sheet1!D4:Sheet1!E5").unwrap();
+    let references = expr.references();
+
+    assert_eq!(references.len(), 3);
+    assert_eq!(references[0].sheet(), Some("My Sheet"));
+    assert_eq!(references[0].workbook, None);
+    assert_eq!(references[1].sheet(), Some("Data"));
+    assert_eq!(references[1].workbook.as_deref(), Some("Book2.xlsx"));
+    assert_eq!(references[2].sheet(), Some("Sheet1"));
+    assert_eq!(
+        references[2].range().unwrap().to_str_address().unwrap(),
+        "Sheet1!D4:Sheet1!E5"
+    );
+
+    assert_eq!(
+        expr.to_formula().unwrap(),
+        "'My Sheet'!A1:B2+[Book2.xlsx]Data!C3+Sheet1!D4:E5"
+    );
+}
+
+#[test]
+fn test_parse_function_names() {
+    let expr = parse_formula("=sum(LOG10(A1), _xlfn.STDEV.S(B1:B3))").unwrap();
+    assert_eq!(
+        expr.to_formula().unwrap(),
+        "SUM(LOG10(A1),_XLFN.STDEV.S(B1:B3))"
+    );
+}
+
+#[test]
+fn test_parse_error() {
+    let broken = vec![
+        "=",
+        "=SUM(A1",
+        "=1+",
+        "=\"open",
+        "={1,2;3}",
+        "=Sheet1!A1:Sheet2!B2",
+    ];
+
+    for formula in broken {
+        assert!(parse_formula(formula).is_err(), "{}", formula);
+    }
+
+    assert_matches!(
+        parse_formula("=Sheet1!A1:Sheet2!B2"),
+        Err(error::WebExcelError::RangeDiffSheetError)
+    );
+    // The sheet repeated at the end of a range is matched case-insensitively
+    assert_eq!(
+        parse_formula("=Sheet1!A1:sheet1!B2")
+            .unwrap()
+            .to_formula()
+            .unwrap(),
+        "Sheet1!A1:B2"
+    );
+}
+
+#[test]
+fn test_parse_limits() {
+    // Deep nesting fails instead of overflowing the stack
+    let nested = |depth: usize| format!("={}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(parse_formula(&nested(100)).is_ok());
+    assert_matches!(
+        parse_formula(&nested(4000)),
+        Err(error::WebExcelError::FormulaNestingError)
+    );
+    assert_matches!(
+        parse_formula(&format!("={}1", "-".repeat(4000))),
+        Err(error::WebExcelError::FormulaNestingError)
+    );
+    assert_matches!(
+        parse_formula(&format!("={}", "SUM(".repeat(1000))),
+        Err(error::WebExcelError::FormulaNestingError)
+    );
+
+    assert_matches!(
+        parse_formula(&format!("=1{}", "+1".repeat(5000))),
+        Err(error::WebExcelError::FormulaLengthError)
+    );
+}
+
+#[test]
+fn test_quote_sheet() {
+    assert_eq!(quote_sheet(None, "Sheet1"), "Sheet1");
+    assert_eq!(quote_sheet(None, "My Sheet"), "'My Sheet'");
+    assert_eq!(quote_sheet(None, "Bob's"), "'Bob''s'");
+    assert_eq!(quote_sheet(None, "A1"), "'A1'");
+    assert_eq!(
+        quote_sheet(Some("Book 2.xlsx"), "Data"),
+        "'[Book 2.xlsx]Data'"
+    );
+}
+
+#[test]
+fn test_relocate() {
+    let moved = |formula: &str, rows: i64, columns: i64| {
+        parse_formula(formula)
+            .unwrap()
+            .relocate(rows, columns)
+            .map(|expr| expr.to_formula().unwrap())
+    };
+
+    assert_eq!(moved("=A1+$B$1", 1, 2).unwrap(), "C2+$B$1");
+    assert_eq!(moved("=SUM($A1:B$2)", 2, 1).unwrap(), "SUM($A3:C$2)");
+    assert_eq!(moved("=Sheet2!C3*2", -1, -1).unwrap(), "Sheet2!B2*2");
+    assert_eq!(moved("=SUM(A:A,1:1)", 3, 3).unwrap(), "SUM(D:D,4:4)");
+    assert_eq!(moved("=IF(A1,{1,2},B1)", 0, 1).unwrap(), "IF(B1,{1,2},C1)");
+
+    assert_matches!(
+        moved("=A1", -1, 0),
+        Err(error::WebExcelError::RelocateError)
+    );
+    assert_matches!(
+        moved("=XFD1", 0, 1),
+        Err(error::WebExcelError::RelocateError)
+    );
+    assert_eq!(moved("=$A$1", -1, -1).unwrap(), "$A$1");
+}
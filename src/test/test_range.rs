@@ -1,5 +1,9 @@
 use crate::cell::*;
 use crate::range::*;
+use crate::range_chunk::*;
+use crate::range_slice::*;
+use crate::test::common::area;
+use wasm_bindgen::prelude::*;
 use wasm_bindgen_test::*;
 
 #[wasm_bindgen_test]
@@ -88,6 +92,19 @@ fn test_range_has() {
     assert!(!range1.has(&cell8));
     assert!(!range1.has(&cell9));
     assert!(!range1.has(&cell0));
+
+    // Sheet names are case insensitive
+    let sheet = |name: &str| Some(name.to_owned());
+    let range2 = Range::new(
+        &Cell::from_str_address("A1", sheet("Data")).unwrap(),
+        &Cell::from_str_address("B2", sheet("DATA")).unwrap(),
+    )
+    .unwrap();
+    let lower = Cell::from_str_address("B2", sheet("data")).unwrap();
+    assert!(range2.has(&lower));
+    assert!(!range2.has(&cell1));
+    assert!(range2.same_sheet(&Range::new(&lower, &lower).unwrap()));
+    assert_eq!(lower, Cell::from_str_address("B2", sheet("Data")).unwrap());
 }
 
 #[wasm_bindgen_test]
@@ -196,6 +213,416 @@ fn test_range_intersects() {
     let range1 = Range::new(&cell11, &cell12).unwrap();
     let range2 = Range::new(&cell21, &cell22).unwrap();
 
-    assert!(range1.intersects(&range2).unwrap());
-    assert!(range2.intersects(&range1).unwrap());
+    assert!(range1.intersects(&range2));
+    assert!(range2.intersects(&range1));
+}
+
+fn addresses(ranges: Vec<Range>) -> Vec<String> {
+    ranges
+        .iter()
+        .map(|range| range.to_str_address().unwrap())
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn test_range_intersection() {
+    let range = area("B2:D5", None);
+    assert_eq!(
+        range.intersection(&area("C4:F9", None)),
+        Some(area("C4:D5", None))
+    );
+    assert_eq!(
+        range.intersection(&area("A1:Z99", None)),
+        Some(range.clone())
+    );
+    assert_eq!(range.intersection(&area("E1:F9", None)), None);
+    assert_eq!(range.intersection(&area("B2:D5", Some("Other"))), None);
+
+    // Keeps the sheet
+    let range = area("A1:B2", Some("Data"));
+    let common = range.intersection(&area("B2:C3", Some("Data"))).unwrap();
+    assert_eq!(common.to_str_address().unwrap(), "Data!B2:Data!B2");
+}
+
+#[wasm_bindgen_test]
+fn test_range_union() {
+    let range = area("B2:C3", None);
+    assert_eq!(range.union(&area("E5", None)).unwrap(), area("B2:E5", None));
+    assert_eq!(
+        range.union(&area("A4:B9", None)).unwrap(),
+        area("A2:C9", None)
+    );
+    assert!(range.union(&area("E5", Some("Other"))).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_range_difference() {
+    let range = area("A1:E5", None);
+
+    // A hole in the middle leaves four parts
+    assert_eq!(
+        addresses(range.difference(&area("B2:C3", None))),
+        vec!["A1:E1", "A4:E5", "A2:A3", "D2:E3"]
+    );
+
+    // Overlapping a corner
+    assert_eq!(
+        addresses(range.difference(&area("D4:H9", None))),
+        vec!["A1:E3", "A4:C5"]
+    );
+
+    // Cutting off whole columns
+    assert_eq!(
+        addresses(range.difference(&area("A1:B9", None))),
+        vec!["C1:E5"]
+    );
+
+    assert!(range.difference(&area("A1:Z99", None)).is_empty());
+    assert_eq!(range.difference(&area("G1:H2", None)), vec![range.clone()]);
+    assert_eq!(
+        range.difference(&area("A1:E5", Some("Other"))),
+        vec![range.clone()]
+    );
+
+    // The parts are disjoint and cover exactly the remaining cells
+    let hole = area("B3:D4", None);
+    let parts = range.difference(&hole);
+    let covered: u64 = parts.iter().map(|part| part.cells).sum();
+    assert_eq!(covered, range.cells - hole.cells);
+    for (i, a) in parts.iter().enumerate() {
+        assert!(parts[i + 1..].iter().all(|b| a.intersection(b).is_none()));
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_range_intersects_cross() {
+    // A wide short range crossing a tall narrow one shares no corner
+    let wide = area("A3:E3", None);
+    let tall = area("C1:C5", None);
+    assert!(wide.intersects(&tall));
+    assert!(tall.intersects(&wide));
+    assert_eq!(wide.intersection(&tall), Some(area("C3", None)));
+
+    assert!(!area("A1:B2", None).intersects(&area("C1:D2", None)));
+    assert!(!area("A1:B2", Some("Sheet1")).intersects(&area("A1:B2", Some("Sheet2"))));
+}
+
+#[wasm_bindgen_test]
+fn test_range_sheet_aware() {
+    let range = area("A1:B2", Some("Sheet1"));
+    let cell =
+        |sheet: Option<&str>| Cell::from_str_address("A1", sheet.map(str::to_owned)).unwrap();
+
+    assert!(range.has(&cell(Some("Sheet1"))));
+    assert!(!range.has(&cell(Some("Sheet2"))));
+    assert!(!range.has(&cell(None)));
+
+    assert!(range.includes(&area("B2", Some("Sheet1"))));
+    assert!(!range.includes(&area("B2", Some("Sheet2"))));
+}
+
+#[wasm_bindgen_test]
+fn test_range_adjacent() {
+    let range = area("B2:C3", None);
+
+    // Sharing a border
+    assert!(range.is_adjacent(&area("D3:E9", None)));
+    assert!(range.is_adjacent(&area("A1:A2", None)));
+    assert!(range.is_adjacent(&area("B4", None)));
+    assert!(range.is_adjacent(&area("A1:D1", None)));
+
+    // Corners, gaps, overlaps and other sheets
+    assert!(!range.is_adjacent(&area("D4", None)));
+    assert!(!range.is_adjacent(&area("E2", None)));
+    assert!(!range.is_adjacent(&area("C3:D4", None)));
+    assert!(!range.is_adjacent(&area("D2", Some("Other"))));
+
+    assert!(range.touches(&area("D4", None)));
+    assert!(range.touches(&area("A1", None)));
+    assert!(range.touches(&area("C3:D4", None)));
+    assert!(range.touches(&area("D2", None)));
+    assert!(!range.touches(&area("E2", None)));
+    assert!(!range.touches(&area("B5", None)));
+    assert!(!range.touches(&area("D4", Some("Other"))));
+}
+
+#[wasm_bindgen_test]
+fn test_range_offset_resize() {
+    let range = area("B2:C3", Some("Data"));
+    assert_eq!(range.offset(2, 1).unwrap(), area("C4:D5", Some("Data")));
+    assert_eq!(range.offset(-1, -1).unwrap(), area("A1:B2", Some("Data")));
+    assert!(range.offset(-2, 0).is_err());
+    assert!(area("XFD1", None).offset(0, 1).is_err());
+
+    assert_eq!(range.resize(2, -1).unwrap(), area("B2:B5", Some("Data")));
+    assert_eq!(range.resize(-1, 0).unwrap(), area("B2:C2", Some("Data")));
+    assert!(range.resize(-2, 0).is_err());
+    assert!(area("A1048576", None).resize(1, 0).is_err());
+
+    let cell = Cell::from_str_address("E1", Some("Data".to_owned())).unwrap();
+    assert_eq!(range.expand_to(&cell).unwrap(), area("B1:E3", Some("Data")));
+    let other = Cell::from_str_address("E1", None).unwrap();
+    assert!(range.expand_to(&other).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_range_rows_columns() {
+    let range = area("B2:D5", None);
+    assert_eq!(range.entire_row(), area("A2:XFD5", None));
+    assert_eq!(range.entire_column(), area("B1:D1048576", None));
+    assert_eq!(range.entire_column().cells, 3 * 1_048_576);
+    assert_eq!(range.first_row(), area("B2:D2", None));
+    assert_eq!(range.last_row(), area("B5:D5", None));
+    assert_eq!(range.first_column(), area("B2:B5", None));
+    assert_eq!(range.last_column(), area("D2:D5", None));
+
+    // A whole sheet does not overflow the cell count
+    let sheet = area("A1:XFD1048576", None);
+    assert_eq!(sheet.cells, 1_048_576 * 16_384);
+}
+
+#[wasm_bindgen_test]
+fn test_range_inset_outset() {
+    let range = area("C3:G9", None);
+    assert_eq!(range.inset(1).unwrap(), area("D4:F8", None));
+    assert_eq!(range.inset(2).unwrap(), area("E5:E7", None));
+    assert!(range.inset(3).is_err());
+
+    assert_eq!(range.outset(2).unwrap(), area("A1:I11", None));
+    assert!(range.outset(3).is_err());
+    assert!(area("XFC1048575", None).outset(2).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_range_cells() {
+    let range = area("B2:C4", Some("Data"));
+    let names = |cells: Vec<Cell>| -> Vec<String> {
+        cells
+            .iter()
+            .map(|cell| cell.to_str_address().unwrap())
+            .collect()
+    };
+
+    assert_eq!(
+        names(range.iter_cells().collect()),
+        vec!["Data!B2", "Data!C2", "Data!B3", "Data!C3", "Data!B4", "Data!C4"]
+    );
+    assert_eq!(
+        names(range.iter_cells_by_column().collect()),
+        vec!["Data!B2", "Data!B3", "Data!B4", "Data!C2", "Data!C3", "Data!C4"]
+    );
+    assert_eq!(
+        names(range.iter_cells_by_column().rev().take(2).collect()),
+        vec!["Data!C4", "Data!C3"]
+    );
+
+    let mut cells = range.iter_cells();
+    assert_eq!(cells.remaining(), 6);
+    assert_eq!(cells.nth(4).unwrap().to_str_address().unwrap(), "Data!B4");
+    assert_eq!(cells.remaining(), 1);
+    assert!(cells.nth(3).is_none());
+
+    // Walking a whole sheet lazily
+    let sheet = area("A1:XFD1048576", None);
+    assert_eq!(sheet.iter_cells().remaining(), 1_048_576 * 16_384);
+    let last = sheet.iter_cells().next_back().unwrap();
+    assert_eq!(last.to_str_address().unwrap(), "XFD1048576");
+}
+
+#[wasm_bindgen_test]
+fn test_range_lines() {
+    let range = area("B2:C4", None);
+    let names = |ranges: Vec<Range>| -> Vec<String> {
+        ranges
+            .iter()
+            .map(|range| range.to_str_address().unwrap())
+            .collect()
+    };
+
+    assert_eq!(
+        names(range.row_ranges().collect()),
+        vec!["B2:C2", "B3:C3", "B4:C4"]
+    );
+    assert_eq!(
+        names(range.column_ranges().collect()),
+        vec!["B2:B4", "C2:C4"]
+    );
+    assert_eq!(
+        names(range.row_ranges().rev().collect()),
+        vec!["B4:C4", "B3:C3", "B2:C2"]
+    );
+    assert_eq!(range.column_ranges().len(), 2);
+    assert_eq!(range.row_ranges().next().unwrap().cells, 2);
+}
+
+#[wasm_bindgen_test]
+fn test_range_iterator_js() {
+    let field = |result: &js_sys::Object, name: &str| {
+        js_sys::Reflect::get(result, &JsValue::from_str(name)).unwrap()
+    };
+
+    let mut cells = area("B2:C3", None).cell_iterator(true);
+    assert_eq!(cells.remaining(), 4.0);
+    let first = cells.next();
+    assert_eq!(field(&first, "done"), JsValue::FALSE);
+    assert!(field(&first, "value").is_object());
+    for _ in 0..3 {
+        assert_eq!(field(&cells.next(), "done"), JsValue::FALSE);
+    }
+    let end = cells.next();
+    assert_eq!(field(&end, "done"), JsValue::TRUE);
+    assert!(field(&end, "value").is_undefined());
+    assert_eq!(cells.remaining(), 0.0);
+
+    let mut rows = area("B2:C3", None).row_iterator();
+    assert_eq!(rows.remaining(), 2.0);
+    assert!(field(&rows.next(), "value").is_object());
+    assert_eq!(rows.remaining(), 1.0);
+    assert_eq!(area("B2:C3", None).column_iterator().remaining(), 2.0);
+}
+
+#[wasm_bindgen_test]
+fn test_range_chunks_js() {
+    let chunks = area("B2:E11", None).chunks_js(&ChunkLimits::new().with_max_cells(16));
+    assert_eq!(chunks.length(), 3);
+    assert!(chunks.get(2).is_object());
+}
+
+#[wasm_bindgen_test]
+fn test_range_select_bounds() {
+    let range = area("B2:D9", None);
+
+    // Used to underflow on an end of 0
+    assert!(range.select_column(0, 0).is_err());
+    assert!(range.select_row(0, 0).is_err());
+    assert!(range.select_column(2, 1).is_err());
+    assert!(range.select_column(0, 4).is_err());
+    assert!(range.select_row(5, 9).is_err());
+    assert_eq!(range.select_column(1, 3).unwrap(), area("C2:D9", None));
+
+    // Negative indexes count from the end
+    assert_eq!(
+        range.select_rows(Some(1), Some(-1)).unwrap(),
+        area("B3:D8", None)
+    );
+    assert_eq!(
+        range.select_rows(Some(-2), None).unwrap(),
+        area("B8:D9", None)
+    );
+    assert_eq!(
+        range.select_columns(None, Some(-2)).unwrap(),
+        area("B2:B9", None)
+    );
+    assert!(range.select_columns(Some(-4), None).is_err());
+    assert!(range.select_rows(Some(-1), Some(-1)).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_range_slice() {
+    let range = area("A1:D6", Some("Data"));
+    let sliced = |rows: Slice, columns: Slice| {
+        range
+            .slice(&rows, &columns)
+            .unwrap()
+            .to_str_address()
+            .unwrap()
+    };
+
+    assert_eq!(sliced(Slice::all(), Slice::all()), "Data!A1:D6");
+    assert_eq!(
+        sliced(
+            Slice::between(Some(1), Some(-1)),
+            Slice::between(None, Some(2))
+        ),
+        "Data!A2:B5"
+    );
+
+    // Every other row, every third column
+    assert_eq!(
+        sliced(
+            Slice::new(None, None, Some(2)),
+            Slice::between(Some(1), Some(3))
+        ),
+        "Data!B1:C1,Data!B3:C3,Data!B5:C5"
+    );
+    assert_eq!(
+        sliced(
+            Slice::new(Some(-2), None, Some(5)),
+            Slice::new(None, None, Some(3))
+        ),
+        "Data!A5,Data!D5"
+    );
+
+    assert!(range
+        .slice(&Slice::new(None, None, Some(0)), &Slice::all())
+        .is_err());
+    assert!(range
+        .slice(&Slice::between(Some(2), Some(7)), &Slice::all())
+        .is_err());
+    assert!(range
+        .slice(&Slice::all(), &Slice::between(Some(3), Some(3)))
+        .is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_range_chunks() {
+    let range = area("B2:E11", Some("Data"));
+    let chunked = |limits: ChunkLimits| -> Vec<(String, u32, u32)> {
+        range
+            .chunks(&limits)
+            .into_iter()
+            .map(|chunk| {
+                let address = chunk.range.to_str_address().unwrap();
+                (
+                    address.replace("Data!", ""),
+                    chunk.row_offset,
+                    chunk.column_offset,
+                )
+            })
+            .collect()
+    };
+    let owned = |chunks: &[(&str, u32, u32)]| -> Vec<(String, u32, u32)> {
+        chunks
+            .iter()
+            .map(|(address, row, column)| (address.to_string(), *row, *column))
+            .collect()
+    };
+
+    // Unlimited gives the range itself
+    assert_eq!(chunked(ChunkLimits::new()), owned(&[("B2:E11", 0, 0)]));
+
+    // Full rows as long as they fit, the last chunk shorter
+    assert_eq!(
+        chunked(ChunkLimits::new().with_max_cells(16)),
+        owned(&[("B2:E5", 0, 0), ("B6:E9", 4, 0), ("B10:E11", 8, 0)])
+    );
+
+    // Rows wider than the limit are split
+    assert_eq!(
+        chunked(ChunkLimits::new().with_max_cells(3).with_max_rows(1))[..3],
+        owned(&[("B2:D2", 0, 0), ("E2:E2", 0, 3), ("B3:D3", 1, 0)])[..]
+    );
+    assert_eq!(
+        chunked(ChunkLimits::new().with_max_rows(5).with_max_columns(2)),
+        owned(&[
+            ("B2:C6", 0, 0),
+            ("D2:E6", 0, 2),
+            ("B7:C11", 5, 0),
+            ("D7:E11", 5, 2)
+        ])
+    );
+
+    // Weighted by bytes: 100 bytes per cell in 1000 bytes is 10 cells, 2 rows of 4
+    let limits = ChunkLimits::new().with_bytes(100.0, 1000.0);
+    assert_eq!(limits.cell_limit(), 10);
+    let chunks = range.chunks(&limits);
+    assert_eq!(chunks.len(), 5);
+    assert!(chunks.iter().all(|chunk| chunk.range.cells == 8));
+
+    // Every cell lands in exactly one chunk
+    let chunks = range.chunks(&ChunkLimits::new().with_max_cells(7));
+    let total: u64 = chunks.iter().map(|chunk| chunk.range.cells).sum();
+    assert_eq!(total, range.cells);
+    assert!(chunks.iter().all(|chunk| chunk.range.cells <= 7));
+    assert_eq!(ChunkLimits::new().with_max_cells(0).cell_limit(), 1);
 }
@@ -0,0 +1,104 @@
+use crate::cell::*;
+use crate::range::*;
+use crate::range_areas::*;
+
+fn areas(address: &str) -> RangeAreas {
+    RangeAreas::from_str_address(address, Some("Sheet1".to_owned())).unwrap()
+}
+
+#[test]
+fn test_range_areas_address() {
+    let list = areas("A1:B2, Data!D4,'My Sheet'!C:D,3:4");
+    assert_eq!(list.area_count(), 4);
+    assert_eq!(
+        list.to_str_address().unwrap(),
+        "Sheet1!A1:B2,Data!D4,'My Sheet'!C:D,Sheet1!3:4"
+    );
+
+    let quoted = areas("'a,b'!A1,B2");
+    assert_eq!(quoted.area_count(), 2);
+    assert_eq!(
+        quoted.iter().next().unwrap().cell_start.sheet.as_deref(),
+        Some("a,b")
+    );
+
+    let local = RangeAreas::from_str_address("A1,B2:C3", None).unwrap();
+    assert_eq!(local.to_str_address().unwrap(), "A1,B2:C3");
+
+    assert!(RangeAreas::from_str_address("A1,", None).is_err());
+    assert!(RangeAreas::from_str_address("A1,1+2", None).is_err());
+    assert!(RangeAreas::from_str_address("A1#", None).is_err());
+    assert!(RangeAreas::from_str_address("[Book.xlsx]Sheet1!A1", None).is_err());
+}
+
+#[test]
+fn test_range_areas_normalize() {
+    // Side by side and stacked areas become one
+    let mut list = areas("A1:B2,C1:C2,A3:C4");
+    list.normalize();
+    assert_eq!(list.to_str_address().unwrap(), "Sheet1!A1:C4");
+
+    // Overlapping areas that do not form a rectangle are cut apart
+    let mut list = areas("A1:B2,B2:C3");
+    assert_eq!(list.cell_count(), 7);
+    list.normalize();
+    assert_eq!(list.cell_count(), 7);
+    assert_eq!(
+        list.to_str_address().unwrap(),
+        "Sheet1!A1:B2,Sheet1!C2,Sheet1!B3:C3"
+    );
+
+    // Contained and repeated areas disappear, other sheets stay apart
+    let mut list = areas("A1:D4,B2,A1:D4,Other!A1:D4");
+    assert_eq!(list.cell_count(), 32);
+    list.normalize();
+    assert_eq!(list.to_str_address().unwrap(), "Other!A1:D4,Sheet1!A1:D4");
+
+    // Whole sheets are counted without overflowing
+    let list = areas("A:XFD,Other!A:XFD");
+    assert_eq!(list.cell_count(), 2 * 1_048_576 * 16_384);
+}
+
+#[test]
+fn test_range_areas_contains() {
+    let list = areas("A1:B2,C1:C2,E5");
+    let cell = |address: &str, sheet: &str| {
+        Cell::from_str_address(address, Some(sheet.to_owned())).unwrap()
+    };
+    let range =
+        |start: &str, end: &str| Range::new(&cell(start, "Sheet1"), &cell(end, "Sheet1")).unwrap();
+
+    assert!(list.has(&cell("B2", "Sheet1")));
+    assert!(list.has(&cell("E5", "Sheet1")));
+    assert!(!list.has(&cell("D1", "Sheet1")));
+    assert!(!list.has(&cell("A1", "Other")));
+
+    // Covered by several areas together
+    assert!(list.includes(&range("A1", "C2")));
+    assert!(!list.includes(&range("A1", "D2")));
+    assert!(list.intersects(&range("D4", "F9")));
+    assert!(!list.intersects(&range("D3", "D9")));
+}
+
+#[test]
+fn test_range_areas_iter() {
+    let list = areas("A1:B2,D1");
+    let cells: Vec<String> = list
+        .iter_cells()
+        .map(|cell| cell.to_str_address().unwrap())
+        .collect();
+    assert_eq!(
+        cells,
+        vec![
+            "Sheet1!A1",
+            "Sheet1!B1",
+            "Sheet1!A2",
+            "Sheet1!B2",
+            "Sheet1!D1"
+        ]
+    );
+
+    let collected: RangeAreas = list.iter().cloned().collect();
+    assert_eq!(collected, list);
+    assert_eq!((&list).into_iter().count(), 2);
+}
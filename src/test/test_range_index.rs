@@ -0,0 +1,95 @@
+use crate::cell::*;
+use crate::range::*;
+use crate::range_index::*;
+use crate::test::common::area;
+
+#[test]
+fn test_range_index_lookup() {
+    let mut index = RangeIndex::new();
+    let table = index.insert(&area("A1:D20", Some("Data")));
+    let header = index.insert(&area("A1:D1", Some("Data")));
+    let total = index.insert(&area("D21", Some("Data")));
+    let other = index.insert(&area("A1:D20", Some("Other")));
+    assert_eq!(index.len(), 4);
+
+    let cell = Cell::from_str_address("B1", Some("Data".to_owned())).unwrap();
+    assert_eq!(index.containing(&cell), vec![table, header]);
+    assert_eq!(
+        index.intersecting(&area("C20:E30", Some("Data"))),
+        vec![table, total]
+    );
+    assert_eq!(index.intersecting(&area("B2", Some("Other"))), vec![other]);
+    assert!(index.intersecting(&area("B2", None)).is_empty());
+    assert_eq!(index.intersecting(&area("B2", Some("OTHER"))), vec![other]);
+    // Folded one character at a time like cells, so a final sigma matches
+    let greek = index.insert(&area("A1", Some("ΣΑΣ")));
+    assert_eq!(index.intersecting(&area("A1", Some("σασ"))), vec![greek]);
+    assert!(index.remove(greek));
+
+    assert!(index.remove(table));
+    assert!(!index.remove(table));
+    assert_eq!(index.containing(&cell), vec![header]);
+    assert_eq!(index.get(total), Some(area("D21", Some("Data"))));
+    assert_eq!(index.get(table), None);
+}
+
+#[test]
+fn test_range_index_many() {
+    // Compare against checking every range, with enough ranges to split nodes many times
+    let mut seed: u64 = 42;
+    let mut next = |limit: u32| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((seed >> 33) % limit as u64) as u32
+    };
+
+    let mut index = RangeIndex::new();
+    let mut ranges = vec![];
+    for _ in 0..2000 {
+        let (row, column) = (next(500), next(100));
+        let start = Cell::new(row, column, None).unwrap();
+        let end = Cell::new(row + next(30), column + next(10), None).unwrap();
+        let range = Range::new(&start, &end).unwrap();
+        ranges.push((index.insert(&range), range));
+    }
+
+    let check = |index: &RangeIndex, ranges: &[(u32, Range)], next: &mut dyn FnMut(u32) -> u32| {
+        for _ in 0..200 {
+            let cell = Cell::new(next(530), next(110), None).unwrap();
+            let expected: Vec<u32> = ranges
+                .iter()
+                .filter(|(_, range)| range.has(&cell))
+                .map(|(id, _)| *id)
+                .collect();
+            assert_eq!(index.containing(&cell), expected);
+
+            let window = Range::new(
+                &cell,
+                &Cell::new(cell.row + 5, cell.column + 5, None).unwrap(),
+            )
+            .unwrap();
+            let expected: Vec<u32> = ranges
+                .iter()
+                .filter(|(_, range)| range.intersects(&window))
+                .map(|(id, _)| *id)
+                .collect();
+            assert_eq!(index.intersecting(&window), expected);
+        }
+    };
+    check(&index, &ranges, &mut next);
+
+    // Remove two out of three, then everything
+    let (removed, kept): (Vec<_>, Vec<_>) = ranges.into_iter().partition(|(id, _)| id % 3 != 0);
+    for (id, _) in &removed {
+        assert!(index.remove(*id));
+    }
+    assert_eq!(index.len(), kept.len());
+    check(&index, &kept, &mut next);
+
+    for (id, _) in &kept {
+        assert!(index.remove(*id));
+    }
+    assert!(index.is_empty());
+    assert!(index.intersecting(&area("A1:Z999", None)).is_empty());
+}
@@ -0,0 +1,129 @@
+use crate::cell::{Cell, CellAnchorStyle};
+use crate::error::WebExcelError;
+use crate::range::Range;
+use crate::test::common::{area, cell};
+use matches::assert_matches;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+fn fixed(address: &str, sheet: Option<&str>) -> Cell {
+    let mut cell = cell(address, sheet);
+    cell.anchor(CellAnchorStyle::All);
+    cell
+}
+
+fn json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Settings {
+    anchor: Cell,
+    area: Range,
+    #[serde(with = "crate::structured")]
+    origin: Cell,
+    #[serde(with = "crate::structured")]
+    selection: Range,
+}
+
+#[test]
+fn test_serialize_address() {
+    assert_eq!(json(&cell("B3", Some("Data"))), "\"Data!B3\"");
+    assert_eq!(json(&fixed("A1", Some("My Sheet"))), "\"'My Sheet'!$A$1\"");
+    assert_eq!(json(&cell("C4", None)), "\"C4\"");
+    let range = area("A1:C4", Some("Data"));
+    assert_eq!(json(&range), "\"Data!A1:C4\"");
+
+    let read: Cell = serde_json::from_str("\"'My Sheet'!$A$1\"").unwrap();
+    assert_eq!(read, cell("A1", Some("My Sheet")));
+    assert!(read.fixed_row && read.fixed_column);
+    let read: Range = serde_json::from_str("\"Data!C4:A1\"").unwrap();
+    assert_eq!(read, range);
+    assert_eq!((read.rows, read.columns, read.cells), (4, 3, 12));
+    let single: Range = serde_json::from_str("\"B2\"").unwrap();
+    assert_eq!(single.cells, 1);
+
+    for broken in [
+        "\"A1:B2\"",
+        "\"1+2\"",
+        "\"A1#\"",
+        "\"[Book.xlsx]Data!A1\"",
+        "3",
+    ] {
+        assert!(serde_json::from_str::<Cell>(broken).is_err(), "{}", broken);
+    }
+    assert!(serde_json::from_str::<Range>("\"SUM(A1)\"").is_err());
+}
+
+#[test]
+fn test_serialize_structured() {
+    let read: Cell = serde_json::from_str(r#"{"row": 2, "column": 1, "sheet": "Data"}"#).unwrap();
+    assert_eq!(read, cell("B3", Some("Data")));
+    let read: Range = serde_json::from_str(
+        r#"{"cell_start": {"row": 3, "column": 2}, "cell_end": {"row": 0, "column": 0}}"#,
+    )
+    .unwrap();
+    assert_eq!(read.to_str_address().unwrap(), "A1:C4");
+    assert!(serde_json::from_str::<Cell>(r#"{"row": 1048576, "column": 0}"#).is_err());
+    assert!(serde_json::from_str::<Cell>(r#"{"column": 0}"#).is_err());
+
+    let settings = Settings {
+        anchor: cell("A1", Some("Data")),
+        area: area("A1:B2", None),
+        origin: fixed("C5", Some("Data")),
+        selection: area("D1:D9", Some("Q&A")),
+    };
+    let text = serde_json::to_string(&settings).unwrap();
+    assert_eq!(
+        text,
+        concat!(
+            r#"{"anchor":"Data!A1","area":"A1:B2","#,
+            r#""origin":{"row":4,"column":2,"sheet":"Data","fixed_row":true,"fixed_column":true},"#,
+            r#""selection":{"cell_start":{"row":0,"column":3,"sheet":"Q&A"},"#,
+            r#""cell_end":{"row":8,"column":3,"sheet":"Q&A"}}}"#
+        )
+    );
+    let read: Settings = serde_json::from_str(&text).unwrap();
+    assert_eq!(read, settings);
+    assert!(read.origin.fixed_row);
+}
+
+#[wasm_bindgen_test]
+fn test_serialize_object_js() {
+    let field = |object: &JsValue, key: &str| js_sys::Reflect::get(object, &key.into()).unwrap();
+
+    let origin = fixed("C5", Some("Data"));
+    let object = origin.to_object().unwrap();
+    assert_eq!(field(&object, "row").as_f64(), Some(4.0));
+    assert_eq!(field(&object, "sheet").as_string(), Some("Data".to_owned()));
+    assert_eq!(field(&object, "fixed_row").as_bool(), Some(true));
+    let read = Cell::from_object(object).unwrap();
+    assert_eq!(read, origin);
+    assert!(read.fixed_row && read.fixed_column);
+    assert_eq!(
+        Cell::from_object(JsValue::from_str("Data!B1")).unwrap(),
+        cell("B1", Some("Data"))
+    );
+
+    let range = area("A1:C4", Some("Q&A"));
+    let object = range.to_object().unwrap();
+    assert_eq!(
+        field(&field(&object, "cell_end"), "column").as_f64(),
+        Some(2.0)
+    );
+    assert_eq!(Range::from_object(object).unwrap(), range);
+    assert_eq!(
+        Range::from_object(JsValue::from_str("'Q&A'!A1:C4")).unwrap(),
+        range
+    );
+
+    assert_matches!(
+        Cell::from_object(JsValue::from_f64(3.0)),
+        Err(WebExcelError::ParseError)
+    );
+    assert_matches!(
+        Range::from_object(js_sys::Object::new().into()),
+        Err(WebExcelError::ParseError)
+    );
+}
@@ -0,0 +1,215 @@
+use crate::cell::*;
+use crate::error::WebExcelError;
+use crate::math::eval::CellValue;
+use crate::range::*;
+use crate::test::common;
+use crate::validation::*;
+use crate::workbook::*;
+use matches::assert_matches;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+fn cell(address: &str) -> Cell {
+    common::cell(address, Some("Sheet1"))
+}
+
+fn area(address: &str) -> Range {
+    common::area(address, Some("Sheet1"))
+}
+
+fn text(s: &str) -> CellValue {
+    CellValue::Text(s.to_owned())
+}
+
+#[test]
+fn test_validation_bounded() {
+    let rule = DataValidation::whole_number(
+        &area("B2:B9"),
+        ValidationOperator::Between,
+        "1",
+        Some("10".to_owned()),
+    )
+    .unwrap()
+    .with_error(AlertStyle::Stop, "Out of range", "Enter 1 to 10")
+    .unwrap()
+    .with_prompt("Quantity", "Whole number from 1 to 10")
+    .unwrap();
+    assert_eq!(
+        rule.to_json().unwrap(),
+        concat!(
+            r#"{"range":"Sheet1!B2:B9","rule":{"wholeNumber":{"formula1":"=1","#,
+            r#""operator":"Between","formula2":"=10"}},"ignoreBlanks":true,"#,
+            r#""errorAlert":{"showAlert":true,"style":"Stop","title":"Out of range","#,
+            r#""message":"Enter 1 to 10"},"prompt":{"showPrompt":true,"title":"Quantity","#,
+            r#""message":"Whole number from 1 to 10"}}"#
+        )
+    );
+
+    assert!(rule.check(&CellValue::Number(1.0)).unwrap());
+    assert!(rule.check(&text("10")).unwrap());
+    assert!(!rule.check(&CellValue::Number(11.0)).unwrap());
+    assert!(!rule.check(&CellValue::Number(2.5)).unwrap());
+    assert!(!rule.check(&text("many")).unwrap());
+    assert!(rule.check(&CellValue::Empty).unwrap());
+    assert!(!rule
+        .clone()
+        .with_ignore_blanks(false)
+        .check(&CellValue::Empty)
+        .unwrap());
+
+    let decimal =
+        DataValidation::decimal(&area("C1"), ValidationOperator::LessThan, "0.5", None).unwrap();
+    assert!(decimal.check(&CellValue::Number(0.25)).unwrap());
+    assert!(!decimal.check(&CellValue::Number(0.5)).unwrap());
+
+    let time = DataValidation::time(
+        &area("D1"),
+        ValidationOperator::NotBetween,
+        "0.75",
+        Some("0.375".to_owned()),
+    )
+    .unwrap();
+    assert!(time.check(&CellValue::Number(0.25)).unwrap());
+    assert!(!time.check(&CellValue::Number(0.5)).unwrap());
+    assert!(!time.check(&CellValue::Number(-0.1)).unwrap());
+
+    let length = DataValidation::text_length(
+        &area("E1"),
+        ValidationOperator::LessThanOrEqualTo,
+        "3",
+        None,
+    )
+    .unwrap();
+    assert!(length.check(&text("abc")).unwrap());
+    assert!(length.check(&CellValue::Number(123.0)).unwrap());
+    assert!(!length.check(&CellValue::Bool(true)).unwrap());
+
+    assert!(DataValidation::date(&area("F1"), ValidationOperator::Between, "1", None).is_err());
+    assert_matches!(
+        DataValidation::decimal(&area("F1"), ValidationOperator::EqualTo, "=(", None),
+        Err(WebExcelError::FormulaParseError)
+    );
+    assert_matches!(
+        rule.with_prompt(&"t".repeat(33), ""),
+        Err(WebExcelError::MessageLengthError)
+    );
+}
+
+#[test]
+fn test_validation_list() {
+    let rule = DataValidation::list(&area("A1:A5"), "Yes, No,Maybe")
+        .unwrap()
+        .with_dropdown(false)
+        .unwrap();
+    assert_eq!(
+        rule.to_json().unwrap(),
+        concat!(
+            r#"{"range":"Sheet1!A1:A5","rule":{"list":{"inCellDropDown":false,"#,
+            r#""source":"Yes,No,Maybe"}},"ignoreBlanks":true}"#
+        )
+    );
+    assert!(rule.check(&text("no")).unwrap());
+    assert!(!rule.check(&text("Perhaps")).unwrap());
+    assert!(DataValidation::list(&area("A1"), " , ").is_err());
+    assert!(DataValidation::list(&area("A1"), &"x,".repeat(200)).is_err());
+
+    let mut workbook = Workbook::new();
+    workbook.add_sheet(Some("Lists".to_owned())).unwrap();
+    let lists = |address: &str| Cell::from_str_address(address, Some("Lists".to_owned())).unwrap();
+    workbook.set_value(&lists("A1"), text("Red")).unwrap();
+    workbook
+        .set_value(&lists("A2"), CellValue::Number(7.0))
+        .unwrap();
+
+    let source = Range::new(&lists("A1"), &lists("A3")).unwrap();
+    let rule = DataValidation::list_from_range(&area("B1:B5"), &source).unwrap();
+    assert_eq!(
+        rule.to_json().unwrap(),
+        concat!(
+            r#"{"range":"Sheet1!B1:B5","rule":{"list":{"inCellDropDown":true,"#,
+            r#""source":"=Lists!$A$1:$A$3"}},"ignoreBlanks":true}"#
+        )
+    );
+    assert!(rule.check_at(&text("red"), &cell("B2"), &workbook).unwrap());
+    assert!(rule.check_at(&text("7"), &cell("B2"), &workbook).unwrap());
+    assert!(!rule
+        .check_at(&text("Blue"), &cell("B2"), &workbook)
+        .unwrap());
+    assert_matches!(
+        rule.check_at(&text("Red"), &cell("C1"), &workbook),
+        Err(WebExcelError::OutOfBoundError)
+    );
+    assert_matches!(
+        DataValidation::list_from_range(&area("B1"), &area("A1:B2")),
+        Err(WebExcelError::DimensionError)
+    );
+}
+
+#[test]
+fn test_validation_custom() {
+    let mut workbook = Workbook::new();
+    workbook.set_value(&cell("A1"), text("x")).unwrap();
+    workbook.set_value(&cell("A2"), text("y")).unwrap();
+    workbook
+        .set_value(&cell("C1"), CellValue::Number(5.0))
+        .unwrap();
+
+    // Unique values in A1:A5, the checked value taking the place of the cell
+    let unique = DataValidation::custom(&area("A1:A5"), "=COUNTIF($A$1:$A$5,A1)=1").unwrap();
+    assert_eq!(
+        unique.to_json().unwrap(),
+        concat!(
+            r#"{"range":"Sheet1!A1:A5","rule":{"custom":"#,
+            r#"{"formula":"=COUNTIF($A$1:$A$5,A1)=1"}},"ignoreBlanks":true}"#
+        )
+    );
+    assert!(unique.check_at(&text("z"), &cell("A3"), &workbook).unwrap());
+    assert!(!unique.check_at(&text("y"), &cell("A3"), &workbook).unwrap());
+    assert!(unique.check_at(&text("y"), &cell("A2"), &workbook).unwrap());
+
+    // Relative references move with the checked cell, bounds may read cells
+    let below = DataValidation::decimal(&area("B1:B3"), ValidationOperator::LessThan, "=$C1", None)
+        .unwrap();
+    assert!(below
+        .check_at(&CellValue::Number(4.0), &cell("B1"), &workbook)
+        .unwrap());
+    assert!(!below
+        .check_at(&CellValue::Number(4.0), &cell("B2"), &workbook)
+        .unwrap());
+
+    let written_for_a1 = DataValidation::custom(&area("B2:B3"), "=A1>0")
+        .unwrap()
+        .relative_to(&cell("A1"))
+        .unwrap();
+    assert!(written_for_a1
+        .to_json()
+        .unwrap()
+        .contains(r#""formula":"=B2>0""#));
+    assert!(!written_for_a1.check(&CellValue::Number(-1.0)).unwrap());
+    assert!(written_for_a1.check(&CellValue::Number(1.0)).unwrap());
+    assert!(DataValidation::custom(&area("A1"), "=[Book2.xlsx]Sheet1!A1").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_validation_js() {
+    let rule = DataValidation::whole_number(
+        &area("B2:B9"),
+        ValidationOperator::Between,
+        "1",
+        Some("10".to_owned()),
+    )
+    .unwrap();
+    assert!(rule.check_js(JsValue::from_f64(5.0)).unwrap());
+    assert!(rule.check_js(JsValue::from_str("10")).unwrap());
+    assert!(!rule.check_js(JsValue::from_f64(11.0)).unwrap());
+    assert!(!rule.check_js(JsValue::from_str("many")).unwrap());
+    assert!(rule.check_js(JsValue::NULL).unwrap());
+
+    let object = rule.to_js().unwrap();
+    let range = js_sys::Reflect::get(&object, &JsValue::from_str("range")).unwrap();
+    assert_eq!(range.as_string(), Some("Sheet1!B2:B9".to_owned()));
+    let whole = js_sys::Reflect::get(&object, &JsValue::from_str("rule"))
+        .and_then(|rule| js_sys::Reflect::get(&rule, &JsValue::from_str("wholeNumber")))
+        .unwrap();
+    assert!(whole.is_object());
+}
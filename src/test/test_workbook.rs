@@ -0,0 +1,211 @@
+use crate::error::WebExcelError;
+use crate::math::eval::{CellValue, Evaluator, Value};
+use crate::math::parser::ErrorValue;
+use crate::test::common::{area, cell};
+use crate::workbook::*;
+use matches::assert_matches;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+#[test]
+fn test_worksheet_cells() {
+    let mut sheet = Worksheet::new("Data").unwrap();
+    assert!(Worksheet::new("a/b").is_err());
+    assert!(Worksheet::new("'quoted'").is_err());
+    assert!(Worksheet::new(&"x".repeat(32)).is_err());
+
+    sheet.set_value(&cell("B2", None), CellValue::Number(5.0));
+    sheet.set_formula(&cell("C2", None), "B2*2").unwrap();
+    sheet.set_number_format(&cell("D4", None), "0.00%");
+    assert_eq!(sheet.cell_count(), 3);
+    assert_eq!(sheet.formula(&cell("C2", None)), Some("=B2*2".to_owned()));
+    assert_eq!(sheet.number_format(&cell("B2", None)), GENERAL_FORMAT);
+    assert_eq!(sheet.number_format(&cell("D4", None)), "0.00%");
+    assert!(sheet.set_formula(&cell("C3", None), "=SUM(").is_err());
+
+    // A value replaces the formula, clearing everything drops the cell
+    sheet.set_value(&cell("C2", None), CellValue::Bool(true));
+    assert_eq!(sheet.formula(&cell("C2", None)), None);
+    sheet.set_value(&cell("C2", None), CellValue::Empty);
+    sheet.set_number_format(&cell("D4", None), "General");
+    assert_eq!(sheet.cell_count(), 1);
+
+    let cells: Vec<String> = sheet
+        .iter()
+        .map(|(cell, _)| cell.to_str_address().unwrap())
+        .collect();
+    assert_eq!(cells, vec!["Data!B2"]);
+}
+
+#[test]
+fn test_worksheet_values() {
+    let mut sheet = Worksheet::new("Sheet1").unwrap();
+    let block = area("A1:B2", None);
+    sheet
+        .set_values(
+            &block,
+            vec![
+                vec![CellValue::Number(1.0), CellValue::Text("a".to_owned())],
+                vec![CellValue::Empty, CellValue::Bool(false)],
+            ],
+        )
+        .unwrap();
+    assert_eq!(sheet.cell_count(), 3);
+    assert_eq!(
+        sheet.values(&area("B1:C1", None)),
+        vec![vec![CellValue::Text("a".to_owned()), CellValue::Empty]]
+    );
+
+    // A single value fills the range, other sizes are rejected
+    sheet
+        .set_values(&area("D1:E3", None), vec![vec![CellValue::Number(0.0)]])
+        .unwrap();
+    assert_eq!(sheet.cell_count(), 9);
+    assert!(matches!(
+        sheet.set_values(&block, vec![vec![CellValue::Empty; 2]]),
+        Err(WebExcelError::DimensionError)
+    ));
+
+    sheet.clear_range(&area("A1:D3", None));
+    assert_eq!(sheet.cell_count(), 3);
+}
+
+#[test]
+fn test_worksheet_used_range() {
+    let mut sheet = Worksheet::new("Sheet1").unwrap();
+    assert_eq!(sheet.used_range(false), None);
+
+    sheet.set_value(&cell("C3", None), CellValue::Number(1.0));
+    sheet.set_value(&cell("B5", None), CellValue::Number(2.0));
+    sheet.set_number_format(&cell("F1", None), "0.0");
+    assert_eq!(sheet.used_range(false), Some(area("B1:F5", Some("Sheet1"))));
+    assert_eq!(sheet.used_range(true), Some(area("B3:C5", Some("Sheet1"))));
+
+    sheet.clear(&cell("B5", None));
+    assert_eq!(sheet.used_range(true), Some(area("C3", Some("Sheet1"))));
+}
+
+#[test]
+fn test_workbook_sheets() {
+    let mut book = Workbook::new();
+    assert_eq!(book.sheet_count(), 1);
+    assert_eq!(book.add_sheet(None).unwrap(), "Sheet2");
+    assert_eq!(book.add_sheet(Some("Data".to_owned())).unwrap(), "Data");
+    assert!(book.add_sheet(Some("data".to_owned())).is_err());
+    assert!(book.add_sheet(Some("History".to_owned())).is_err());
+
+    let names = |book: &Workbook| -> Vec<String> {
+        book.sheets().iter().map(|sheet| sheet.name()).collect()
+    };
+    book.move_sheet("data", 0).unwrap();
+    assert_eq!(names(&book), vec!["Data", "Sheet1", "Sheet2"]);
+    assert_eq!(book.active_sheet(), "Sheet1");
+
+    book.rename_sheet("Sheet2", "Summary").unwrap();
+    book.rename_sheet("Data", "DATA").unwrap();
+    assert!(book.rename_sheet("DATA", "Summary").is_err());
+    assert!(matches!(
+        book.rename_sheet("Missing", "Other"),
+        Err(WebExcelError::SheetNotFoundError)
+    ));
+    book.move_sheet("DATA", 10).unwrap();
+    assert_eq!(names(&book), vec!["Sheet1", "Summary", "DATA"]);
+
+    book.activate("summary").unwrap();
+    book.delete_sheet("Summary").unwrap();
+    assert_eq!(book.active_sheet(), "DATA");
+    book.delete_sheet("DATA").unwrap();
+    assert_eq!(book.active_sheet(), "Sheet1");
+    assert!(matches!(
+        book.delete_sheet("Sheet1"),
+        Err(WebExcelError::LastSheetError)
+    ));
+}
+
+#[test]
+fn test_workbook_cells() {
+    let mut book = Workbook::new();
+    book.add_sheet(Some("Rates".to_owned())).unwrap();
+
+    book.set_value(&cell("A1", None), CellValue::Number(100.0))
+        .unwrap();
+    book.set_value(&cell("A1", Some("rates")), CellValue::Number(0.5))
+        .unwrap();
+    book.set_formula(&cell("B1", None), "=A1*Rates!A1").unwrap();
+    assert!(book
+        .set_value(&cell("A1", Some("Missing")), CellValue::Empty)
+        .is_err());
+
+    assert_eq!(
+        book.value(&cell("A1", Some("Sheet1"))),
+        CellValue::Number(100.0)
+    );
+    assert_eq!(
+        book.formula(&cell("B1", Some("Sheet1"))).unwrap().unwrap(),
+        "=A1*Rates!A1"
+    );
+    assert_eq!(
+        book.values(&area("A1:A2", Some("Rates"))).unwrap(),
+        vec![vec![CellValue::Number(0.5)], vec![CellValue::Empty]]
+    );
+
+    // Formulas can be evaluated against the workbook
+    let formula = book.formula(&cell("B1", None)).unwrap().unwrap();
+    let result = Evaluator::new(&book)
+        .on_sheet(Some("Sheet1".to_owned()))
+        .evaluate_formula(&formula)
+        .unwrap();
+    assert!(matches!(result, Value::Scalar(CellValue::Number(n)) if n == 50.0));
+
+    // A missing sheet reads as #REF!
+    assert_eq!(
+        book.value(&cell("A1", Some("Nope"))),
+        CellValue::Error(ErrorValue::Ref)
+    );
+    let result = Evaluator::new(&book)
+        .evaluate_formula("=SUM(Nope!A1:B2)+1")
+        .unwrap();
+    assert!(matches!(
+        result,
+        Value::Scalar(CellValue::Error(ErrorValue::Ref))
+    ));
+}
+
+#[wasm_bindgen_test]
+fn test_workbook_values_js() {
+    let mut book = Workbook::new();
+    let block = area("A1:B2", None);
+    let values: js_sys::Array = [
+        js_sys::Array::of2(&JsValue::from_f64(1.0), &JsValue::from_str("a")),
+        js_sys::Array::of2(&JsValue::TRUE, &JsValue::from_str("#N/A")),
+    ]
+    .iter()
+    .collect();
+    book.set_values_at(&block, &values).unwrap();
+    assert_eq!(book.value(&cell("A2", None)), CellValue::Bool(true));
+    assert_eq!(
+        book.value(&cell("B2", None)),
+        CellValue::Error(ErrorValue::NA)
+    );
+
+    let read = book.values_at(&block).unwrap();
+    assert_eq!(read.length(), 2);
+    let first = js_sys::Array::from(&read.get(0));
+    assert_eq!(first.get(0).as_f64(), Some(1.0));
+    assert_eq!(first.get(1).as_string(), Some("a".to_owned()));
+    let second = js_sys::Array::from(&read.get(1));
+    assert_eq!(second.get(1).as_string(), Some("#N/A".to_owned()));
+
+    // A single value fills the range, `null` clears it
+    let blank = js_sys::Array::of1(&js_sys::Array::of1(&JsValue::NULL));
+    book.set_values_at(&block, &blank).unwrap();
+    assert_eq!(book.value(&cell("A1", None)), CellValue::Empty);
+    assert!(js_sys::Array::from(&book.values_at(&block).unwrap().get(1))
+        .get(1)
+        .is_null());
+
+    assert_matches!(
+        book.set_values_at(&area("A1:B2", Some("Nope")), &values),
+        Err(WebExcelError::SheetNotFoundError)
+    );
+}
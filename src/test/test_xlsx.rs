@@ -0,0 +1,679 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::eval::{CellValue, Evaluator};
+use crate::math::parser::ErrorValue;
+use crate::range::Range;
+use crate::test::common;
+use crate::util::deflate::deflate;
+use crate::util::inflate::inflate;
+use crate::util::xml::parse_xml;
+use crate::util::zip::{crc32, ZipArchive, ZipWriter};
+use crate::workbook::{CellData, Workbook, Worksheet};
+use crate::xlsx::*;
+use matches::assert_matches;
+
+/// Zip archive storing `files` uncompressed.
+fn package(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut data = vec![];
+    let mut directory = vec![];
+    for (name, content) in files {
+        let offset = data.len() as u32;
+        let header = |signature: u32, central: bool| {
+            let mut header = signature.to_le_bytes().to_vec();
+            if central {
+                header.extend(20u16.to_le_bytes());
+            }
+            header.extend([20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            header.extend(crc32(content.as_bytes()).to_le_bytes());
+            header.extend((content.len() as u32).to_le_bytes());
+            header.extend((content.len() as u32).to_le_bytes());
+            header.extend((name.len() as u16).to_le_bytes());
+            header.extend(0u16.to_le_bytes());
+            if central {
+                header.extend([0; 10]);
+                header.extend(offset.to_le_bytes());
+            }
+            header.extend(name.as_bytes());
+            header
+        };
+        data.extend(header(0x0403_4b50, false));
+        data.extend(content.as_bytes());
+        directory.extend(header(0x0201_4b50, true));
+    }
+
+    let start = data.len() as u32;
+    let count = files.len() as u16;
+    data.extend(&directory);
+    data.extend(0x0605_4b50u32.to_le_bytes());
+    data.extend([0; 4]);
+    data.extend(count.to_le_bytes());
+    data.extend(count.to_le_bytes());
+    data.extend((directory.len() as u32).to_le_bytes());
+    data.extend(start.to_le_bytes());
+    data.extend([0; 2]);
+    data
+}
+
+const RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>
+</Relationships>"#;
+
+const WORKBOOK: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+  <bookViews><workbookView activeTab="1"/></bookViews>
+  <sheets>
+    <sheet name="Data" sheetId="1" r:id="rId1"/>
+    <sheet name="Q&amp;A" sheetId="2" r:id="rId2"/>
+    <sheet name="Chart" sheetId="3" r:id="rId5"/>
+  </sheets>
+  <definedNames>
+    <definedName name="_xlnm._FilterDatabase" localSheetId="0" hidden="1">Data!$A$1:$C$4</definedName>
+    <definedName name="Rate">Data!$B$2</definedName>
+    <definedName name="Local" localSheetId="1">'Q&amp;A'!$A$1</definedName>
+  </definedNames>
+</workbook>"#;
+
+const WORKBOOK_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>
+  <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="/xl/worksheets/sheet2.xml"/>
+  <Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/sharedStrings" Target="sharedStrings.xml"/>
+  <Relationship Id="rId4" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+  <Relationship Id="rId5" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/chartsheet" Target="chartsheets/sheet1.xml"/>
+</Relationships>"#;
+
+const SHARED_STRINGS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<sst xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" count="4" uniqueCount="4">
+  <si><t>Item</t></si>
+  <si><t>Price</t></si>
+  <si><r><rPr><b/></rPr><t>Bold</t></r><r><t xml:space="preserve"> tail</t></r><rPh><t>x</t></rPh></si>
+  <si><t xml:space="preserve">  spaced &lt;tag&gt;</t></si>
+</sst>"#;
+
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+  <numFmts count="1"><numFmt numFmtId="164" formatCode="&quot;$&quot;#,##0.00"/></numFmts>
+  <cellXfs count="4">
+    <xf numFmtId="0" fontId="0"/>
+    <xf numFmtId="164" fontId="0" applyNumberFormat="1"/>
+    <xf numFmtId="14" fontId="0" applyNumberFormat="1"/>
+    <xf numFmtId="10" fontId="0" applyNumberFormat="1"/>
+  </cellXfs>
+</styleSheet>"#;
+
+const SHEET1: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+  <sheetData>
+    <row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c><c r="C1" t="inlineStr"><is><t>Total</t></is></c></row>
+    <row r="2"><c r="A2" t="s"><v>2</v></c><c r="B2" s="1"><v>2.5</v></c><c r="C2" s="1"><f t="shared" ref="C2:C4" si="0">B2*2</f><v>5</v></c></row>
+    <row r="3"><c r="A3" t="s"><v>3</v></c><c r="B3" s="1"><v>4</v></c><c r="C3" s="1"><f t="shared" si="0"/><v>8</v></c></row>
+    <row><c t="b"><v>1</v></c><c s="2"><v>45356</v></c><c r="C4"><f t="shared" si="0"/><v>90712</v></c><c r="D4" t="e"><v>#DIV/0!</v></c><c r="E4" t="str"><f>_xlfn.CONCAT(A1,B1)</f><v>ItemPrice</v></c><c r="F4"><f>SUM(_xlfn.ANCHORARRAY(B2))</f><v>2.5</v></c></row>
+    <row r="6"><c r="A6" s="3"/><c r="B6" t="d"><v>2024-03-05T12:00:00</v></c></row>
+  </sheetData>
+  <mergeCells count="1"><mergeCell ref="A6:B7"/></mergeCells>
+  <tableParts count="1"><tablePart r:id="rId1"/></tableParts>
+</worksheet>"#;
+
+const SHEET1_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/table" Target="../tables/table1.xml"/>
+</Relationships>"#;
+
+const TABLE1: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<table xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" id="1" name="Table1" displayName="Prices" ref="A1:C4" totalsRowCount="1">
+  <autoFilter ref="A1:C3"/>
+  <tableColumns count="3"><tableColumn id="1" name="Item"/><tableColumn id="2" name="Price"/><tableColumn id="3" name="Total"/></tableColumns>
+</table>"#;
+
+const SHEET2: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+  <sheetData><row r="1"><c r="A1"><f>Rate*2</f><v>5</v></c></row></sheetData>
+</worksheet>"#;
+
+fn sample() -> Vec<u8> {
+    package(&[
+        ("_rels/.rels", RELS),
+        ("xl/workbook.xml", WORKBOOK),
+        ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS),
+        ("xl/sharedStrings.xml", SHARED_STRINGS),
+        ("xl/styles.xml", STYLES),
+        ("xl/worksheets/sheet1.xml", SHEET1),
+        ("xl/worksheets/_rels/sheet1.xml.rels", SHEET1_RELS),
+        ("xl/worksheets/sheet2.xml", SHEET2),
+        ("xl/tables/table1.xml", TABLE1),
+        ("xl/chartsheets/sheet1.xml", "<chartsheet/>"),
+    ])
+}
+
+fn cell(address: &str) -> Cell {
+    common::cell(address, Some("Data"))
+}
+
+fn area(address: &str) -> Range {
+    common::area(address, Some("Data"))
+}
+
+#[test]
+fn test_inflate() {
+    // Fixed Huffman codes
+    let fixed = [203, 72, 205, 201, 201, 215, 81, 200, 64, 162, 20, 1];
+    assert_eq!(inflate(&fixed, 20).unwrap(), b"hello, hello, hello!");
+    // Output past the limit fails before it is written
+    assert_matches!(inflate(&fixed, 19), Err(WebExcelError::FileFormatError));
+
+    // Dynamic Huffman codes with long back references
+    let dynamic = [
+        77, 208, 193, 17, 196, 32, 8, 5, 208, 134, 60, 4, 65, 192, 161, 154, 108, 255, 69, 44, 65,
+        69, 46, 81, 39, 79, 62, 248, 52, 131, 246, 254, 140, 252, 227, 203, 140, 197, 55, 192, 123,
+        231, 251, 62, 154, 33, 135, 90, 255, 141, 233, 56, 133, 235, 176, 89, 167, 96, 178, 152,
+        244, 195, 122, 81, 208, 140, 191, 179, 205, 177, 24, 226, 97, 242, 92, 7, 189, 217, 192,
+        112, 171, 23, 163, 76, 157, 37, 149, 60, 118, 70, 236, 88, 183, 77, 15, 227, 81, 134, 144,
+        102, 170, 193, 246, 172, 61, 187, 211, 121, 29, 123, 123, 248, 221, 51, 128, 93, 142, 179,
+        94, 121, 20, 82, 119, 49, 6, 232, 126, 148, 236, 14, 47, 83, 127, 59, 249, 202, 155, 236,
+        105, 229, 78, 91, 156, 164, 211, 237, 178, 138, 149, 84, 200, 84, 58, 169, 25, 171, 197,
+        253, 1,
+    ];
+    let expected: String = (0..60)
+        .map(|i| format!("{},{};", i * i % 97, "ab".repeat(i % 5)))
+        .collect();
+    assert_eq!(
+        inflate(&dynamic, expected.len()).unwrap(),
+        expected.as_bytes()
+    );
+
+    // Stored block
+    assert_eq!(
+        inflate(&[1, 3, 0, 252, 255, b'a', b'b', b'c'], 3).unwrap(),
+        b"abc"
+    );
+    assert_matches!(
+        inflate(&[1, 3, 0, 0, 0, b'a'], 3),
+        Err(WebExcelError::FileFormatError)
+    );
+    assert_matches!(
+        inflate(&fixed[..6], 20),
+        Err(WebExcelError::FileFormatError)
+    );
+    assert_matches!(inflate(&[7], 20), Err(WebExcelError::FileFormatError));
+}
+
+#[test]
+fn test_deflate() {
+    let mut seed = 7u32;
+    let noise: Vec<u8> = (0..5000)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as u8
+        })
+        .collect();
+    let rows: String = (0..2000)
+        .map(|i| {
+            format!(
+                "<row r=\"{}\"><c r=\"A{}\"><v>{}</v></c></row>",
+                i,
+                i,
+                i % 7
+            )
+        })
+        .collect();
+    let runs = [b"a".repeat(1000), b"ab".repeat(40_000)].concat();
+
+    for data in [
+        &b""[..],
+        b"x",
+        b"hello, hello, hello!",
+        &noise,
+        rows.as_bytes(),
+        &runs,
+    ] {
+        assert_eq!(inflate(&deflate(data), data.len()).unwrap(), data);
+    }
+    // A long run inflated with a small limit stops early
+    let bomb = deflate(&vec![0; 1 << 20]);
+    assert!(bomb.len() < (1 << 20) / 100);
+    assert_matches!(inflate(&bomb, 1 << 10), Err(WebExcelError::FileFormatError));
+    assert!(deflate(rows.as_bytes()).len() < rows.len() / 4);
+}
+
+#[test]
+fn test_zip_archive() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+    let bytes = package(&[("a.txt", "alpha"), ("Dir/B.xml", "<b/>")]);
+    let archive = ZipArchive::new(&bytes).unwrap();
+    assert_eq!(archive.entries().len(), 2);
+    assert_eq!(archive.read("a.txt").unwrap().unwrap(), b"alpha");
+    assert_eq!(archive.read("/dir/b.XML").unwrap().unwrap(), b"<b/>");
+    assert!(archive.read("missing").is_none());
+
+    let mut corrupted = bytes.clone();
+    corrupted[30 + 5] = b'A';
+    let archive = ZipArchive::new(&corrupted).unwrap();
+    assert_matches!(
+        archive.read("a.txt").unwrap(),
+        Err(WebExcelError::FileFormatError)
+    );
+    assert_matches!(
+        ZipArchive::new(b"not a zip"),
+        Err(WebExcelError::FileFormatError)
+    );
+
+    let mut writer = ZipWriter::new();
+    writer.add("short.txt", b"ab").unwrap();
+    writer
+        .add("xl/long.xml", "<c/>".repeat(500).as_bytes())
+        .unwrap();
+    let bytes = writer.finish();
+    let archive = ZipArchive::new(&bytes).unwrap();
+    assert_eq!(archive.entries().len(), 2);
+    assert_eq!(archive.read("short.txt").unwrap().unwrap(), b"ab");
+    assert_eq!(
+        archive.read("xl/long.xml").unwrap().unwrap(),
+        "<c/>".repeat(500).as_bytes()
+    );
+    assert!(bytes.len() < 500);
+
+    // The entry count of the central directory stops at 65535
+    let mut full = ZipWriter::new();
+    for n in 0..u16::MAX {
+        full.add(&n.to_string(), b"").unwrap();
+    }
+    assert_matches!(
+        full.add("one more", b""),
+        Err(WebExcelError::FileFormatError)
+    );
+}
+
+#[test]
+fn test_xml() {
+    let root = parse_xml(
+        "<?xml version=\"1.0\"?><!-- note --><a:root xmlns:a=\"urn:a\" k='v &amp; w'>\n  <a:item n=\"1\">x &lt; y &#65;&#x42;</a:item>\n  <item/><![CDATA[<raw>]]></a:root>",
+    )
+    .unwrap();
+    assert_eq!(root.name, "a:root");
+    assert_eq!(root.local_name(), "root");
+    assert_eq!(root.attribute("k"), Some("v & w"));
+    assert_eq!(root.children_named("item").count(), 2);
+    let item = root.child("item").unwrap();
+    assert_eq!(item.attribute("n"), Some("1"));
+    assert_eq!(item.text(), "x < y AB");
+    assert_eq!(root.text(), "<raw>");
+
+    for broken in ["<a><b></a>", "<a>", "<a x=1/>", "text", "<a>&bogus;</a>"] {
+        assert_matches!(parse_xml(broken), Err(WebExcelError::FileFormatError));
+    }
+}
+
+#[test]
+fn test_xlsx_read_cells() {
+    let file = XlsxFile::read(&sample()).unwrap();
+    let workbook = file.workbook();
+    let names: Vec<String> = workbook.sheets().iter().map(|s| s.name()).collect();
+    assert_eq!(names, vec!["Data", "Q&A"]);
+    assert_eq!(workbook.active_sheet(), "Q&A");
+
+    let value = |address: &str| workbook.value(&cell(address));
+    assert_eq!(value("A1"), CellValue::Text("Item".to_owned()));
+    assert_eq!(value("C1"), CellValue::Text("Total".to_owned()));
+    assert_eq!(value("A2"), CellValue::Text("Bold tail".to_owned()));
+    assert_eq!(value("A3"), CellValue::Text("  spaced <tag>".to_owned()));
+    assert_eq!(value("B2"), CellValue::Number(2.5));
+    assert_eq!(value("A4"), CellValue::Bool(true));
+    assert_eq!(value("B4"), CellValue::Number(45356.0));
+    assert_eq!(value("D4"), CellValue::Error(ErrorValue::Div0));
+    assert_eq!(value("A6"), CellValue::Empty);
+    assert_eq!(value("B6"), CellValue::Number(45356.5));
+
+    let formula = |address: &str| workbook.formula(&cell(address)).unwrap();
+    assert_eq!(formula("C2"), Some("=B2*2".to_owned()));
+    assert_eq!(formula("C3"), Some("=B3*2".to_owned()));
+    assert_eq!(formula("C4"), Some("=B4*2".to_owned()));
+    assert_eq!(formula("E4"), Some("=CONCAT(A1,B1)".to_owned()));
+    assert_eq!(formula("F4"), Some("=SUM(B2#)".to_owned()));
+    assert_eq!(formula("B2"), None);
+    assert_eq!(value("C4"), CellValue::Number(90712.0));
+    assert_eq!(value("E4"), CellValue::Text("ItemPrice".to_owned()));
+
+    let format = |address: &str| workbook.number_format(&cell(address)).unwrap();
+    assert_eq!(format("B2"), "\"$\"#,##0.00");
+    assert_eq!(format("B4"), "mm-dd-yy");
+    assert_eq!(format("A6"), "0.00%");
+    assert_eq!(format("A1"), "General");
+    assert_eq!(workbook.text(&cell("B3")).unwrap(), "$4.00");
+}
+
+#[test]
+fn test_xlsx_read_date1904() {
+    let workbook = WORKBOOK.replace("<bookViews>", "<workbookPr date1904=\"1\"/><bookViews>");
+    let file = XlsxFile::read(&package(&[
+        ("_rels/.rels", RELS),
+        ("xl/workbook.xml", &workbook),
+        ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS),
+        ("xl/sharedStrings.xml", SHARED_STRINGS),
+        ("xl/styles.xml", STYLES),
+        ("xl/worksheets/sheet1.xml", SHEET1),
+        ("xl/worksheets/_rels/sheet1.xml.rels", SHEET1_RELS),
+        ("xl/worksheets/sheet2.xml", SHEET2),
+        ("xl/tables/table1.xml", TABLE1),
+    ]))
+    .unwrap();
+
+    // Only numbers shown as dates move to the 1900 date system
+    let value = |address: &str| file.workbook().value(&cell(address));
+    assert_eq!(value("B4"), CellValue::Number(45356.0 + 1462.0));
+    assert_eq!(value("B2"), CellValue::Number(2.5));
+    assert_eq!(value("B6"), CellValue::Number(45356.5));
+}
+
+#[test]
+fn test_xlsx_read_names_merges_tables() {
+    let file = XlsxFile::read(&sample()).unwrap();
+
+    assert_eq!(
+        file.names(),
+        &[
+            XlsxName {
+                name: "_xlnm._FilterDatabase".to_owned(),
+                formula: "=Data!$A$1:$C$4".to_owned(),
+                sheet: Some("Data".to_owned()),
+                hidden: true,
+            },
+            XlsxName {
+                name: "Rate".to_owned(),
+                formula: "=Data!$B$2".to_owned(),
+                sheet: None,
+                hidden: false,
+            },
+            XlsxName {
+                name: "Local".to_owned(),
+                formula: "='Q&A'!$A$1".to_owned(),
+                sheet: Some("Q&A".to_owned()),
+                hidden: false,
+            },
+        ]
+    );
+    let names = file.defined_names();
+    assert!(names.has("Rate") && !names.has("Local"));
+    let result = Evaluator::new(file.workbook())
+        .with_names(&names)
+        .evaluate_formula("=Rate*2")
+        .unwrap()
+        .into_scalar();
+    assert_eq!(result, CellValue::Number(5.0));
+
+    assert!(file.merges().is_merged(&cell("B7")));
+    assert_eq!(file.merges().anchor(&cell("B7")), cell("A6"));
+
+    let table = file.table("prices").unwrap();
+    assert_eq!(table.name, "Prices");
+    assert_eq!(table.range.to_str_address().unwrap(), "Data!A1:Data!C4");
+    assert_eq!(table.columns(), &["Item", "Price", "Total"]);
+    assert!(table.header_row && table.totals_row);
+    assert_eq!(
+        table.data_range().unwrap().to_str_address().unwrap(),
+        "Data!A2:Data!C3"
+    );
+    assert_eq!(file.tables().len(), 1);
+}
+
+#[test]
+fn test_xlsx_read_errors() {
+    assert_matches!(XlsxFile::read(b"PK"), Err(WebExcelError::FileFormatError));
+    assert_matches!(
+        XlsxFile::read(&package(&[("_rels/.rels", RELS)])),
+        Err(WebExcelError::FileFormatError)
+    );
+    let broken = package(&[
+        ("xl/workbook.xml", WORKBOOK),
+        ("xl/_rels/workbook.xml.rels", WORKBOOK_RELS),
+        ("xl/worksheets/sheet1.xml", "<worksheet><sheetData>"),
+    ]);
+    assert_matches!(XlsxFile::read(&broken), Err(WebExcelError::FileFormatError));
+
+    // A part declaring an extracted size of 1GB fails before anything is extracted
+    let mut bomb = sample();
+    let name = b"xl/worksheets/sheet1.xml";
+    let header = (0..bomb.len() - 46 - name.len())
+        .find(|&at| bomb[at..at + 4] == [0x50, 0x4b, 1, 2] && bomb[at + 46..].starts_with(name))
+        .unwrap();
+    bomb[header + 24..header + 28].copy_from_slice(&(1u32 << 30).to_le_bytes());
+    assert_matches!(XlsxFile::read(&bomb), Err(WebExcelError::FileFormatError));
+}
+
+/// Two sheets with values, formulas and number formats, `Report` active.
+fn report() -> XlsxFile {
+    let mut data = Worksheet::new("Data").unwrap();
+    let set = |sheet: &mut Worksheet, address: &str, value: CellValue, formula: Option<&str>| {
+        let data = CellData {
+            value,
+            formula: formula.map(str::to_owned),
+            number_format: None,
+        };
+        sheet.set_cell(&common::cell(address, None), data);
+    };
+    set(
+        &mut data,
+        "A1",
+        CellValue::Text("Item <&>".to_owned()),
+        None,
+    );
+    set(&mut data, "B1", CellValue::Text("  Price".to_owned()), None);
+    set(
+        &mut data,
+        "A2",
+        CellValue::Text("Item <&>".to_owned()),
+        None,
+    );
+    set(&mut data, "B2", CellValue::Number(1234.5), None);
+    set(&mut data, "B3", CellValue::Number(-0.125), None);
+    set(&mut data, "C2", CellValue::Number(2469.0), Some("=B2*2"));
+    set(
+        &mut data,
+        "C3",
+        CellValue::Text("low".to_owned()),
+        Some("=IF(B3<0,\"low\",\"ok\")"),
+    );
+    set(&mut data, "D2", CellValue::Bool(false), None);
+    set(&mut data, "D3", CellValue::Error(ErrorValue::NA), None);
+    set(&mut data, "E2", CellValue::Empty, Some("=SUM(B2:B3)"));
+    data.set_number_format(&common::cell("B2", None), "\"$\"#,##0.00");
+    data.set_number_format(&common::cell("B3", None), "0.00%");
+    data.set_number_format(&common::cell("F9", None), "\"$\"#,##0.00");
+
+    let mut report = Worksheet::new("Report & Summary").unwrap();
+    set(
+        &mut report,
+        "A1",
+        CellValue::Text("Item <&>".to_owned()),
+        None,
+    );
+    let mut workbook = Workbook::from_sheets(vec![data, report]).unwrap();
+    workbook.activate("Report & Summary").unwrap();
+    XlsxFile::from_workbook(workbook)
+}
+
+#[test]
+fn test_xlsx_write() {
+    let mut file = report();
+    file.set_column_width(&area("A1:B1"), 24.5).unwrap();
+    file.set_column_width(&area("D1:D1"), 8.0).unwrap();
+    file.merge(&area("A5:C6")).unwrap();
+    file.freeze_panes(&cell("B2")).unwrap();
+    file.freeze_panes(&common::cell("A3", None)).unwrap();
+    file.add_name("Rate", "=Data!$B$2", None).unwrap();
+    file.add_name("Local", "Data!$A$1", Some("data".to_owned()))
+        .unwrap();
+
+    let bytes = file.write().unwrap();
+    let archive = ZipArchive::new(&bytes).unwrap();
+    let part = |name: &str| String::from_utf8(archive.read(name).unwrap().unwrap()).unwrap();
+    let sheet = part("xl/worksheets/sheet1.xml");
+    assert!(sheet.contains("<dimension ref=\"A1:F9\"/>"));
+    assert!(sheet.contains(
+        "<pane xSplit=\"1\" ySplit=\"1\" topLeftCell=\"B2\" activePane=\"bottomRight\" state=\"frozen\"/>"
+    ));
+    assert!(sheet.contains("<col min=\"1\" max=\"2\" width=\"24.5\" customWidth=\"1\"/>"));
+    assert!(sheet.contains("<c r=\"C2\"><f>B2*2</f><v>2469</v></c>"));
+    assert!(sheet.contains("<c r=\"E2\"><f>SUM(B2:B3)</f></c>"));
+    assert!(sheet.contains("<c r=\"F9\" s=\"1\"/>"));
+    assert!(sheet.contains("<mergeCell ref=\"A5:C6\"/>"));
+    assert!(part("xl/sharedStrings.xml").contains("uniqueCount=\"2\""));
+    assert!(part("xl/styles.xml").contains("<numFmt numFmtId=\"164\""));
+
+    let read = XlsxFile::read(&bytes).unwrap();
+    let workbook = read.workbook();
+    assert_eq!(workbook.active_sheet(), "Report & Summary");
+    for (address, value) in [
+        ("A1", CellValue::Text("Item <&>".to_owned())),
+        ("B1", CellValue::Text("  Price".to_owned())),
+        ("B2", CellValue::Number(1234.5)),
+        ("B3", CellValue::Number(-0.125)),
+        ("C2", CellValue::Number(2469.0)),
+        ("C3", CellValue::Text("low".to_owned())),
+        ("D2", CellValue::Bool(false)),
+        ("D3", CellValue::Error(ErrorValue::NA)),
+        ("E2", CellValue::Empty),
+    ] {
+        assert_eq!(workbook.value(&cell(address)), value, "{}", address);
+    }
+    let formula = |address: &str| workbook.formula(&cell(address)).unwrap();
+    assert_eq!(formula("C3"), Some("=IF(B3<0,\"low\",\"ok\")".to_owned()));
+    assert_eq!(formula("E2"), Some("=SUM(B2:B3)".to_owned()));
+    assert_eq!(workbook.text(&cell("B2")).unwrap(), "$1,234.50");
+    assert_eq!(workbook.number_format(&cell("B3")).unwrap(), "0.00%");
+    assert_eq!(
+        workbook.number_format(&cell("F9")).unwrap(),
+        "\"$\"#,##0.00"
+    );
+    assert_eq!(
+        workbook.value(&common::cell("Report & Summary!A1", None)),
+        CellValue::Text("Item <&>".to_owned())
+    );
+
+    assert_eq!(read.column_width(&cell("B7")), Some(24.5));
+    assert_eq!(read.column_width(&cell("D1")), Some(8.0));
+    assert_eq!(read.column_width(&cell("C1")), None);
+    assert_eq!(read.frozen_cell(Some("Data".to_owned())), Some(cell("B2")));
+    assert_eq!(
+        read.frozen_cell(None).unwrap().to_str_address().unwrap(),
+        "Report & Summary!A3"
+    );
+    assert_eq!(read.merges().anchor(&cell("C6")), cell("A5"));
+    assert_eq!(read.names(), file.names());
+    assert_eq!(read.names()[1].sheet, Some("Data".to_owned()));
+    assert!(read.defined_names().has("Rate"));
+}
+
+#[test]
+fn test_xlsx_future_functions() {
+    let mut sheet = Worksheet::new("Data").unwrap();
+    for (address, n) in [("B1", 1.0), ("C1", 3.0), ("C2", 1.0), ("C3", 3.0)] {
+        sheet.set_value(&common::cell(address, None), CellValue::Number(n));
+    }
+    for (address, formula) in [
+        ("A1", "=LET(x,B1*2,FILTER(C1:C3,C1:C3>x))"),
+        ("E1", "=LAMBDA(n,n+Rate)(3)"),
+        ("F1", "=SORT(UNIQUE(C1:C3))+IFERROR(B1,0)"),
+        ("G1", "=SUM(D1#)*2"),
+        ("H1", "=SEQUENCE(2,3)"),
+    ] {
+        let data = CellData {
+            value: CellValue::Empty,
+            formula: Some(formula.to_owned()),
+            number_format: None,
+        };
+        sheet.set_cell(&common::cell(address, None), data);
+    }
+    let mut file = XlsxFile::from_workbook(Workbook::from_sheets(vec![sheet]).unwrap());
+    file.add_name("Top", "=XLOOKUP(1,C1:C3,B1:B3)", None)
+        .unwrap();
+
+    let bytes = file.write().unwrap();
+    let archive = ZipArchive::new(&bytes).unwrap();
+    let part = |name: &str| String::from_utf8(archive.read(name).unwrap().unwrap()).unwrap();
+    let xml = part("xl/worksheets/sheet1.xml");
+    // Dynamic arrays are array formulas over their spill range, marked by the cell metadata
+    assert!(xml.contains(
+        "<c r=\"A1\" cm=\"1\"><f t=\"array\" ref=\"A1:A2\">\
+         _xlfn.LET(_xlpm.x,B1*2,_xlfn._xlws.FILTER(C1:C3,C1:C3&gt;_xlpm.x))</f></c>"
+    ));
+    assert!(xml.contains("<c r=\"E1\"><f>_xlfn.LAMBDA(_xlpm.n,_xlpm.n+Rate)(3)</f></c>"));
+    assert!(xml.contains(
+        "<c r=\"F1\" cm=\"1\"><f t=\"array\" ref=\"F1:F2\">\
+         _xlfn._xlws.SORT(_xlfn.UNIQUE(C1:C3))+IFERROR(B1,0)</f></c>"
+    ));
+    assert!(xml.contains(
+        "<c r=\"G1\" cm=\"1\"><f t=\"array\" ref=\"G1\">SUM(_xlfn.ANCHORARRAY(D1))*2</f></c>"
+    ));
+    assert!(xml
+        .contains("<c r=\"H1\" cm=\"1\"><f t=\"array\" ref=\"H1:J2\">_xlfn.SEQUENCE(2,3)</f></c>"));
+    assert!(part("xl/workbook.xml").contains(">_xlfn.XLOOKUP(1,C1:C3,B1:B3)</definedName>"));
+    assert!(part("xl/metadata.xml").contains("<xda:dynamicArrayProperties fDynamic=\"1\""));
+    assert!(part("xl/_rels/workbook.xml.rels").contains("/sheetMetadata\" Target=\"metadata.xml\""));
+    assert!(part("[Content_Types].xml").contains("PartName=\"/xl/metadata.xml\""));
+
+    let read = XlsxFile::read(&bytes).unwrap();
+    let formula = |address: &str| read.workbook().formula(&cell(address)).unwrap();
+    assert_eq!(
+        formula("A1"),
+        Some("=LET(x,B1*2,FILTER(C1:C3,C1:C3>x))".to_owned())
+    );
+    assert_eq!(formula("E1"), Some("=LAMBDA(n,n+Rate)(3)".to_owned()));
+    assert_eq!(
+        formula("F1"),
+        Some("=SORT(UNIQUE(C1:C3))+IFERROR(B1,0)".to_owned())
+    );
+    assert_eq!(formula("G1"), Some("=SUM(D1#)*2".to_owned()));
+    assert_eq!(formula("H1"), Some("=SEQUENCE(2,3)".to_owned()));
+    assert_eq!(read.names(), file.names());
+
+    // Without dynamic arrays there is no metadata part
+    let bytes = report().write().unwrap();
+    assert!(ZipArchive::new(&bytes)
+        .unwrap()
+        .read("xl/metadata.xml")
+        .is_none());
+}
+
+#[test]
+fn test_xlsx_write_errors() {
+    let mut file = report();
+    let range = area("A1:B2");
+    assert_matches!(
+        file.set_column_width(&range, 256.0),
+        Err(WebExcelError::OutOfBoundError)
+    );
+    assert_matches!(
+        file.set_column_width(&range, f64::NAN),
+        Err(WebExcelError::OutOfBoundError)
+    );
+    let elsewhere = common::cell("Missing!B2", None);
+    assert_matches!(
+        file.freeze_panes(&elsewhere),
+        Err(WebExcelError::SheetNotFoundError)
+    );
+
+    file.merge(&range).unwrap();
+    assert_matches!(file.merge(&range), Err(WebExcelError::MergeOverlapError));
+
+    file.add_name("Rate", "=1", None).unwrap();
+    assert_matches!(
+        file.add_name("rate", "=2", None),
+        Err(WebExcelError::NameError)
+    );
+    assert_matches!(
+        file.add_name("A1", "=2", None),
+        Err(WebExcelError::NameError)
+    );
+    assert_matches!(
+        file.add_name("Bad", "=1+", None),
+        Err(WebExcelError::FormulaParseError)
+    );
+    file.add_name("Rate", "=2", Some("Data".to_owned()))
+        .unwrap();
+
+    file.freeze_panes(&cell("C3")).unwrap();
+    file.freeze_panes(&cell("A1")).unwrap();
+    assert_eq!(file.frozen_cell(Some("Data".to_owned())), None);
+}
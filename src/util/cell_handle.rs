@@ -1,5 +1,10 @@
 use crate::{cell, error};
 
+/// Last row index (0-based) of an Excel worksheet. Excel Row 1048576.
+pub const MAX_ROW: u32 = 1_048_575;
+/// Last column index (0-based) of an Excel worksheet. Excel Column XFD.
+pub const MAX_COLUMN: u32 = 16_383;
+
 /// Converts an Excel-like cell address (e.g., "A1", "BC23") to its corresponding row and column indices.
 ///
 /// The function expects the address to be in the format of one or more uppercase letters followed by one or more digits.
@@ -88,7 +93,7 @@ pub fn r1c1_to_address(
     col_lock: bool,
 ) -> Result<String, error::WebExcelError> {
     // Input check
-    if col > 16383 || row > 1048575 {
+    if col > MAX_COLUMN || row > MAX_ROW {
         return Err(error::WebExcelError::OutOfBoundError);
     }
 
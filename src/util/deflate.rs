@@ -0,0 +1,136 @@
+use crate::util::inflate::{DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+/// Farthest back a match may start.
+const WINDOW: usize = 32_768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Candidates compared before settling for the longest match found so far.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+/// Bits written least significant first.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which goes most significant bit first.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+
+    /// Literal or length symbol with the fixed Huffman code.
+    fn literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn match_of(&mut self, length: usize, distance: usize) {
+        let index = LENGTH_BASE
+            .iter()
+            .rposition(|&b| b as usize <= length)
+            .unwrap();
+        self.literal(257 + index as u32);
+        self.write(
+            (length - LENGTH_BASE[index] as usize) as u32,
+            LENGTH_EXTRA[index] as u32,
+        );
+
+        let index = DISTANCE_BASE
+            .iter()
+            .rposition(|&b| b as usize <= distance)
+            .unwrap();
+        self.write_code(index as u32, 5);
+        self.write(
+            (distance - DISTANCE_BASE[index] as usize) as u32,
+            DISTANCE_EXTRA[index] as u32,
+        );
+    }
+}
+
+fn hash(data: &[u8], at: usize) -> usize {
+    let key = (data[at] as u32) << 16 | (data[at + 1] as u32) << 8 | data[at + 2] as u32;
+    (key.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Chain position `at` in front of the earlier positions with the same hash.
+fn insert(data: &[u8], head: &mut [usize], previous: &mut [usize], at: usize) {
+    if at + MIN_MATCH <= data.len() {
+        let h = hash(data, at);
+        previous[at] = head[h];
+        head[h] = at;
+    }
+}
+
+/// Compress `data` into a raw DEFLATE stream of a single block with the fixed Huffman
+/// codes, finding repeats with hash chains. Plenty for the repetitive XML of a package.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // Last block, fixed codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+    let mut at = 0;
+    while at < data.len() {
+        let (mut length, mut distance) = (0, 0);
+        if at + MIN_MATCH <= data.len() {
+            let limit = MAX_MATCH.min(data.len() - at);
+            let mut candidate = head[hash(data, at)];
+            let mut chain = 0;
+            while candidate != usize::MAX && at - candidate <= WINDOW && chain < MAX_CHAIN {
+                let found = (0..limit)
+                    .take_while(|&k| data[candidate + k] == data[at + k])
+                    .count();
+                if found > length {
+                    (length, distance) = (found, at - candidate);
+                    if found == limit {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        if length >= MIN_MATCH {
+            bits.match_of(length, distance);
+            for k in at..at + length {
+                insert(data, &mut head, &mut previous, k);
+            }
+            at += length;
+        } else {
+            bits.literal(data[at] as u32);
+            insert(data, &mut head, &mut previous, at);
+            at += 1;
+        }
+    }
+
+    bits.literal(256);
+    bits.finish()
+}
@@ -0,0 +1,307 @@
+/// Most entries of a node before it is split.
+const MAX_ENTRIES: usize = 8;
+/// Fewest entries of a node other than the root. Emptier nodes are dissolved and reinserted.
+const MIN_ENTRIES: usize = 3;
+
+/// Rectangle of grid positions, bounds included.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub top: u32,
+    pub left: u32,
+    pub bottom: u32,
+    pub right: u32,
+}
+
+impl Rect {
+    pub fn new(top: u32, left: u32, bottom: u32, right: u32) -> Rect {
+        Rect {
+            top,
+            left,
+            bottom,
+            right,
+        }
+    }
+
+    pub fn point(row: u32, column: u32) -> Rect {
+        Rect::new(row, column, row, column)
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.top <= other.bottom
+            && other.top <= self.bottom
+            && self.left <= other.right
+            && other.left <= self.right
+    }
+
+    fn union(&self, other: &Rect) -> Rect {
+        Rect::new(
+            self.top.min(other.top),
+            self.left.min(other.left),
+            self.bottom.max(other.bottom),
+            self.right.max(other.right),
+        )
+    }
+
+    fn area(&self) -> u64 {
+        (self.bottom - self.top + 1) as u64 * (self.right - self.left + 1) as u64
+    }
+
+    /// Growth of the area needed to cover `other` as well.
+    fn enlargement(&self, other: &Rect) -> u64 {
+        self.union(other).area() - self.area()
+    }
+}
+
+/// Entries of a node: items in a leaf, child nodes in a branch.
+type Entries<E> = Vec<(Rect, E)>;
+
+#[derive(Clone, Debug)]
+enum Node<T> {
+    Leaf(Entries<T>),
+    Branch(Entries<Node<T>>),
+}
+
+impl<T> Node<T> {
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(entries) => entries.len(),
+            Node::Branch(children) => children.len(),
+        }
+    }
+
+    fn bounds(&self) -> Rect {
+        let rects: Vec<Rect> = match self {
+            Node::Leaf(entries) => entries.iter().map(|(rect, _)| *rect).collect(),
+            Node::Branch(children) => children.iter().map(|(rect, _)| *rect).collect(),
+        };
+        rects[1..]
+            .iter()
+            .fold(rects[0], |bounds, rect| bounds.union(rect))
+    }
+
+    /// Move every item below this node into `items`.
+    fn drain_into(self, items: &mut Vec<(Rect, T)>) {
+        match self {
+            Node::Leaf(entries) => items.extend(entries),
+            Node::Branch(children) => {
+                for (_, child) in children {
+                    child.drain_into(items);
+                }
+            }
+        }
+    }
+}
+
+/// R-tree of items placed on rectangles, answering which items overlap a rectangle.
+/// Nodes are split with Guttman's quadratic split.
+#[derive(Clone, Debug)]
+pub struct RTree<T> {
+    root: Node<T>,
+    len: usize,
+}
+
+impl<T> Default for RTree<T> {
+    fn default() -> Self {
+        RTree {
+            root: Node::Leaf(vec![]),
+            len: 0,
+        }
+    }
+}
+
+impl<T: PartialEq> RTree<T> {
+    pub fn new() -> RTree<T> {
+        RTree::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, rect: Rect, item: T) {
+        self.len += 1;
+        if let Some(sibling) = insert(&mut self.root, rect, item) {
+            let root = std::mem::replace(&mut self.root, Node::Leaf(vec![]));
+            let bounds = root.bounds();
+            self.root = Node::Branch(vec![(bounds, root), sibling]);
+        }
+    }
+
+    /// Remove `item` placed on `rect`. Returns whether it was found.
+    pub fn remove(&mut self, rect: &Rect, item: &T) -> bool {
+        let mut orphans = vec![];
+        if !remove(&mut self.root, rect, item, &mut orphans) {
+            return false;
+        }
+        self.len -= 1;
+
+        // A root left with a single child hands over to it
+        while let Node::Branch(children) = &mut self.root {
+            if children.len() != 1 {
+                break;
+            }
+            self.root = children.pop().unwrap().1;
+        }
+        if let Node::Branch(children) = &self.root {
+            if children.is_empty() {
+                self.root = Node::Leaf(vec![]);
+            }
+        }
+
+        for (rect, item) in orphans {
+            self.len -= 1;
+            self.insert(rect, item);
+        }
+        true
+    }
+
+    /// Items whose rectangle overlaps `window`.
+    pub fn search(&self, window: &Rect) -> Vec<&T> {
+        let mut found = vec![];
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            match node {
+                Node::Leaf(entries) => found.extend(
+                    entries
+                        .iter()
+                        .filter(|(rect, _)| rect.intersects(window))
+                        .map(|(_, item)| item),
+                ),
+                Node::Branch(children) => stack.extend(
+                    children
+                        .iter()
+                        .filter(|(rect, _)| rect.intersects(window))
+                        .map(|(_, child)| child),
+                ),
+            }
+        }
+        found
+    }
+}
+
+/// Insert below `node`, returning the new sibling when `node` had to be split.
+fn insert<T>(node: &mut Node<T>, rect: Rect, item: T) -> Option<(Rect, Node<T>)> {
+    match node {
+        Node::Leaf(entries) => {
+            entries.push((rect, item));
+            if entries.len() <= MAX_ENTRIES {
+                return None;
+            }
+            let (kept, moved) = split(std::mem::take(entries));
+            *entries = kept;
+            let sibling = Node::Leaf(moved);
+            Some((sibling.bounds(), sibling))
+        }
+        Node::Branch(children) => {
+            // Child needing the least enlargement, then the smallest one
+            let best = (0..children.len())
+                .min_by_key(|&i| {
+                    let bounds = &children[i].0;
+                    (bounds.enlargement(&rect), bounds.area())
+                })
+                .unwrap();
+
+            let (bounds, child) = &mut children[best];
+            let sibling = insert(child, rect, item);
+            *bounds = child.bounds();
+            children.extend(sibling);
+            if children.len() <= MAX_ENTRIES {
+                return None;
+            }
+
+            let (kept, moved) = split(std::mem::take(children));
+            *children = kept;
+            let sibling = Node::Branch(moved);
+            Some((sibling.bounds(), sibling))
+        }
+    }
+}
+
+/// Remove `item` from below `node`, moving the items of nodes left too empty into `orphans`.
+fn remove<T: PartialEq>(
+    node: &mut Node<T>,
+    rect: &Rect,
+    item: &T,
+    orphans: &mut Vec<(Rect, T)>,
+) -> bool {
+    match node {
+        Node::Leaf(entries) => match entries.iter().position(|(r, i)| r == rect && i == item) {
+            Some(index) => {
+                entries.remove(index);
+                true
+            }
+            None => false,
+        },
+        Node::Branch(children) => {
+            for index in 0..children.len() {
+                if !children[index].0.intersects(rect)
+                    || !remove(&mut children[index].1, rect, item, orphans)
+                {
+                    continue;
+                }
+
+                if children[index].1.len() < MIN_ENTRIES {
+                    children.remove(index).1.drain_into(orphans);
+                } else {
+                    children[index].0 = children[index].1.bounds();
+                }
+                return true;
+            }
+            false
+        }
+    }
+}
+
+/// Quadratic split: start from the two entries wasting the most area together,
+/// then hand out the others to the group they enlarge the least.
+fn split<E>(entries: Entries<E>) -> (Entries<E>, Entries<E>) {
+    let mut seeds = (0, 1);
+    let mut worst = i128::MIN;
+    for i in 0..entries.len() {
+        for j in i + 1..entries.len() {
+            let (a, b) = (&entries[i].0, &entries[j].0);
+            let waste = a.union(b).area() as i128 - a.area() as i128 - b.area() as i128;
+            if waste > worst {
+                worst = waste;
+                seeds = (i, j);
+            }
+        }
+    }
+
+    let mut rest: Vec<Option<(Rect, E)>> = entries.into_iter().map(Some).collect();
+    let second = rest[seeds.1].take().unwrap();
+    let first = rest[seeds.0].take().unwrap();
+    let (mut bounds_a, mut bounds_b) = (first.0, second.0);
+    let (mut group_a, mut group_b) = (vec![first], vec![second]);
+
+    let mut remaining = rest.len() - 2;
+    for entry in rest.into_iter().flatten() {
+        // Make sure both groups end up with enough entries
+        let to_a = if group_a.len() + remaining == MIN_ENTRIES {
+            true
+        } else if group_b.len() + remaining == MIN_ENTRIES {
+            false
+        } else {
+            let (grow_a, grow_b) = (
+                bounds_a.enlargement(&entry.0),
+                bounds_b.enlargement(&entry.0),
+            );
+            (grow_a, bounds_a.area(), group_a.len()) <= (grow_b, bounds_b.area(), group_b.len())
+        };
+
+        if to_a {
+            bounds_a = bounds_a.union(&entry.0);
+            group_a.push(entry);
+        } else {
+            bounds_b = bounds_b.union(&entry.0);
+            group_b.push(entry);
+        }
+        remaining -= 1;
+    }
+
+    (group_a, group_b)
+}
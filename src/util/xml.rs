@@ -0,0 +1,243 @@
+use crate::error::WebExcelError;
+
+/// Content of an element: a child element or text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+}
+
+/// Element of a parsed XML document. Names keep their namespace prefix, e.g. `r:id`,
+/// while lookups by name compare the local part only.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+}
+
+/// Part of a name after its namespace prefix.
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+impl XmlElement {
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    /// Value of the attribute `name`. A prefixed name such as `r:id` must match exactly,
+    /// a plain one matches attributes without a prefix.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Child elements with the local name `name`.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.elements().filter(move |e| e.local_name() == name)
+    }
+
+    /// First child element with the local name `name`.
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.elements().find(|e| e.local_name() == name)
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|node| match node {
+            XmlNode::Element(element) => Some(element),
+            XmlNode::Text(_) => None,
+        })
+    }
+
+    /// Text directly inside the element.
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| match node {
+                XmlNode::Text(text) => Some(text.as_str()),
+                XmlNode::Element(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Escape text for element content and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Replace entity and character references, e.g. `&amp;` and `&#x41;`.
+fn unescape(text: &str) -> Result<String, WebExcelError> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(';')
+            .ok_or(WebExcelError::FileFormatError)?
+            + start;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => entity
+                        .strip_prefix('#')
+                        .ok_or(WebExcelError::FileFormatError)?
+                        .parse(),
+                };
+                code.ok()
+                    .and_then(char::from_u32)
+                    .ok_or(WebExcelError::FileFormatError)?
+            }
+        };
+        result.push(c);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Parse an XML document into its root element. Declarations, comments, processing
+/// instructions and doctypes are skipped, CDATA sections become text.
+/// Whitespace between elements is dropped unless it is all the text of an element.
+pub fn parse_xml(text: &str) -> Result<XmlElement, WebExcelError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut stack: Vec<XmlElement> = vec![XmlElement::default()];
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        if let Some(tag) = rest.strip_prefix('<') {
+            if let Some(after) = tag.strip_prefix("!--") {
+                rest = skip_past(after, "-->")?;
+            } else if let Some(after) = tag.strip_prefix("![CDATA[") {
+                let end = after.find("]]>").ok_or(WebExcelError::FileFormatError)?;
+                push_text(stack.last_mut().unwrap(), after[..end].to_owned());
+                rest = &after[end + 3..];
+            } else if tag.starts_with('?') || tag.starts_with('!') {
+                rest = skip_past(tag, ">")?;
+            } else if let Some(after) = tag.strip_prefix('/') {
+                let end = after.find('>').ok_or(WebExcelError::FileFormatError)?;
+                let element = stack.pop().ok_or(WebExcelError::FileFormatError)?;
+                let parent = stack.last_mut().ok_or(WebExcelError::FileFormatError)?;
+                if after[..end].trim() != element.name {
+                    return Err(WebExcelError::FileFormatError);
+                }
+                parent.children.push(XmlNode::Element(trimmed(element)));
+                rest = &after[end + 1..];
+            } else {
+                let (element, closed, after) = start_tag(tag)?;
+                if closed {
+                    let parent = stack.last_mut().unwrap();
+                    parent.children.push(XmlNode::Element(element));
+                } else {
+                    stack.push(element);
+                }
+                rest = after;
+            }
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            push_text(stack.last_mut().unwrap(), unescape(&rest[..end])?);
+            rest = &rest[end..];
+        }
+    }
+
+    if stack.len() != 1 {
+        return Err(WebExcelError::FileFormatError);
+    }
+    let document = stack.pop().unwrap();
+    document
+        .children
+        .into_iter()
+        .find_map(|node| match node {
+            XmlNode::Element(element) => Some(element),
+            XmlNode::Text(_) => None,
+        })
+        .ok_or(WebExcelError::FileFormatError)
+}
+
+fn skip_past<'a>(text: &'a str, end: &str) -> Result<&'a str, WebExcelError> {
+    let at = text.find(end).ok_or(WebExcelError::FileFormatError)?;
+    Ok(&text[at + end.len()..])
+}
+
+fn push_text(element: &mut XmlElement, text: String) {
+    if text.is_empty() {
+        return;
+    }
+    match element.children.last_mut() {
+        Some(XmlNode::Text(previous)) => previous.push_str(&text),
+        _ => element.children.push(XmlNode::Text(text)),
+    }
+}
+
+/// Drop whitespace between child elements, keeping text of elements holding only text.
+fn trimmed(mut element: XmlElement) -> XmlElement {
+    if element
+        .children
+        .iter()
+        .any(|n| matches!(n, XmlNode::Element(_)))
+    {
+        element
+            .children
+            .retain(|node| !matches!(node, XmlNode::Text(text) if text.trim().is_empty()));
+    }
+    element
+}
+
+/// Read a start tag after its `<`: the element, whether it is self-closing, and the rest.
+fn start_tag(tag: &str) -> Result<(XmlElement, bool, &str), WebExcelError> {
+    let name_end = tag
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .ok_or(WebExcelError::FileFormatError)?;
+    let mut element = XmlElement {
+        name: tag[..name_end].to_owned(),
+        ..Default::default()
+    };
+    if element.name.is_empty() {
+        return Err(WebExcelError::FileFormatError);
+    }
+
+    let mut rest = tag[name_end..].trim_start();
+    loop {
+        if let Some(after) = rest.strip_prefix("/>") {
+            return Ok((element, true, after));
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return Ok((element, false, after));
+        }
+
+        let equals = rest.find('=').ok_or(WebExcelError::FileFormatError)?;
+        let key = rest[..equals].trim().to_owned();
+        let value = rest[equals + 1..].trim_start();
+        let quote = value.chars().next().ok_or(WebExcelError::FileFormatError)?;
+        if quote != '"' && quote != '\'' {
+            return Err(WebExcelError::FileFormatError);
+        }
+        let end = value[1..]
+            .find(quote)
+            .ok_or(WebExcelError::FileFormatError)?
+            + 1;
+        element.attributes.push((key, unescape(&value[1..end])?));
+        rest = value[end + 1..].trim_start();
+    }
+}
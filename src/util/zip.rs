@@ -0,0 +1,235 @@
+use crate::error::WebExcelError;
+use crate::util::deflate::deflate;
+use crate::util::inflate::inflate;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+/// Size of the end of central directory record without its comment.
+const END_OF_DIRECTORY_SIZE: usize = 22;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+/// Version 2.0, enough for deflate.
+const VERSION: u16 = 20;
+/// DOS date of 1980-01-01, the earliest there is.
+const DOS_DATE: u16 = 0x0021;
+
+/// CRC-32 checksum used by zip archives.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// `at + length`, failing rather than overflowing on offsets of a damaged archive.
+fn after(at: usize, length: usize) -> Result<usize, WebExcelError> {
+    at.checked_add(length).ok_or(WebExcelError::FileFormatError)
+}
+
+fn bytes_at(data: &[u8], at: usize, length: usize) -> Result<&[u8], WebExcelError> {
+    data.get(at..after(at, length)?)
+        .ok_or(WebExcelError::FileFormatError)
+}
+
+fn u16_at(data: &[u8], at: usize) -> Result<u16, WebExcelError> {
+    bytes_at(data, at, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Result<u32, WebExcelError> {
+    bytes_at(data, at, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// File of an archive, as listed by the central directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZipEntry {
+    pub name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    size: usize,
+    offset: usize,
+}
+
+impl ZipEntry {
+    /// Size of the content once extracted, as the archive declares it.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Zip archive read from bytes, such as an Office Open XML package.
+/// Only stored and deflated files are supported, without encryption or ZIP64.
+#[derive(Clone, Debug)]
+pub struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<ZipEntry>,
+}
+
+impl<'a> ZipArchive<'a> {
+    pub fn new(data: &'a [u8]) -> Result<ZipArchive<'a>, WebExcelError> {
+        // The end record closes the archive, followed by a comment of up to 65535 bytes
+        let end = (0..=data.len().saturating_sub(END_OF_DIRECTORY_SIZE))
+            .rev()
+            .take(u16::MAX as usize + 1)
+            .find(|&at| matches!(u32_at(data, at), Ok(END_OF_DIRECTORY)))
+            .ok_or(WebExcelError::FileFormatError)?;
+        let count = u16_at(data, end + 10)? as usize;
+        let mut at = u32_at(data, end + 16)? as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            if u32_at(data, at)? != CENTRAL_HEADER {
+                return Err(WebExcelError::FileFormatError);
+            }
+            // The fixed part of the header is 46 bytes, read all at once
+            let header = bytes_at(data, at, 46)?;
+            let name_length = u16_at(header, 28)? as usize;
+            let extra_length = u16_at(header, 30)? as usize;
+            let comment_length = u16_at(header, 32)? as usize;
+            let name = bytes_at(data, after(at, 46)?, name_length)?;
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: u16_at(header, 10)?,
+                crc: u32_at(header, 16)?,
+                compressed_size: u32_at(header, 20)? as usize,
+                size: u32_at(header, 24)? as usize,
+                offset: u32_at(header, 42)? as usize,
+            });
+            at = after(at, 46 + name_length + extra_length + comment_length)?;
+        }
+
+        Ok(ZipArchive { data, entries })
+    }
+
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Entry named `name`, ignoring case like package part names.
+    pub fn entry(&self, name: &str) -> Option<&ZipEntry> {
+        let name = name.trim_start_matches('/');
+        self.entries
+            .iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Content of the file named `name`, `None` if there is no such file.
+    /// Fails on an unsupported compression method or a damaged file.
+    pub fn read(&self, name: &str) -> Option<Result<Vec<u8>, WebExcelError>> {
+        self.entry(name).map(|entry| self.extract(entry))
+    }
+
+    pub fn extract(&self, entry: &ZipEntry) -> Result<Vec<u8>, WebExcelError> {
+        let header = bytes_at(self.data, entry.offset, 30)?;
+        if u32_at(header, 0)? != LOCAL_HEADER {
+            return Err(WebExcelError::FileFormatError);
+        }
+        let length = 30 + u16_at(header, 26)? as usize + u16_at(header, 28)? as usize;
+        let start = after(entry.offset, length)?;
+        let compressed = bytes_at(self.data, start, entry.compressed_size)?;
+
+        let content = match entry.method {
+            STORED => compressed.to_vec(),
+            DEFLATED => inflate(compressed, entry.size)?,
+            _ => return Err(WebExcelError::FileFormatError),
+        };
+        if content.len() != entry.size || crc32(&content) != entry.crc {
+            return Err(WebExcelError::FileFormatError);
+        }
+        Ok(content)
+    }
+}
+
+/// Zip archive built in memory, one file after the other. Files are deflated unless
+/// that does not make them smaller.
+#[derive(Clone, Debug, Default)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    count: u16,
+}
+
+impl ZipWriter {
+    pub fn new() -> ZipWriter {
+        ZipWriter::default()
+    }
+
+    /// Append a file. Fails with `FileFormatError` past 65535 files or once a name,
+    /// a file or the archive grows beyond what the zip headers can record.
+    pub fn add(&mut self, name: &str, content: &[u8]) -> Result<(), WebExcelError> {
+        let count = self
+            .count
+            .checked_add(1)
+            .ok_or(WebExcelError::FileFormatError)?;
+        // Empty files cannot get any smaller
+        let compressed = match content.is_empty() {
+            true => vec![],
+            false => deflate(content),
+        };
+        let (method, stored) = match compressed.len() < content.len() {
+            true => (DEFLATED, compressed.as_slice()),
+            false => (STORED, content),
+        };
+        // Names are recorded in 16 bits, sizes and offsets in 32
+        if name.len() > u16::MAX as usize
+            || content.len() > u32::MAX as usize
+            || self.data.len() > u32::MAX as usize
+        {
+            return Err(WebExcelError::FileFormatError);
+        }
+
+        // Fields shared by the local header and the central directory
+        let mut fields = vec![];
+        fields.extend(VERSION.to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+        fields.extend(method.to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+        fields.extend(DOS_DATE.to_le_bytes());
+        fields.extend(crc32(content).to_le_bytes());
+        fields.extend((stored.len() as u32).to_le_bytes());
+        fields.extend((content.len() as u32).to_le_bytes());
+        fields.extend((name.len() as u16).to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+
+        self.directory.extend(CENTRAL_HEADER.to_le_bytes());
+        self.directory.extend(VERSION.to_le_bytes());
+        self.directory.extend(&fields);
+        // Comment length, disk number, internal and external attributes
+        self.directory.extend([0; 10]);
+        self.directory
+            .extend((self.data.len() as u32).to_le_bytes());
+        self.directory.extend(name.as_bytes());
+
+        self.data.extend(LOCAL_HEADER.to_le_bytes());
+        self.data.extend(&fields);
+        self.data.extend(name.as_bytes());
+        self.data.extend(stored);
+        self.count = count;
+        Ok(())
+    }
+
+    /// Bytes of the archive, closed by its central directory.
+    pub fn finish(mut self) -> Vec<u8> {
+        let start = self.data.len() as u32;
+        self.data.extend(&self.directory);
+        self.data.extend(END_OF_DIRECTORY.to_le_bytes());
+        self.data.extend([0; 4]);
+        self.data.extend(self.count.to_le_bytes());
+        self.data.extend(self.count.to_le_bytes());
+        self.data
+            .extend((self.directory.len() as u32).to_le_bytes());
+        self.data.extend(start.to_le_bytes());
+        self.data.extend([0; 2]);
+        self.data
+    }
+}
@@ -0,0 +1,640 @@
+use crate::cell::{same_sheet, Cell};
+use crate::error::WebExcelError;
+use crate::math::eval::{compare_values, parse_number, CellSource, CellValue, Evaluator};
+use crate::math::func::{checked_formula, formula_text};
+use crate::math::parser::{Expr, Reference};
+use crate::range::Range;
+use crate::range_areas::RangeAreas;
+use crate::util::json::Json;
+use crate::workbook::Workbook;
+use std::cmp::Ordering;
+use wasm_bindgen::prelude::*;
+
+/// Length of an error alert or input prompt title allowed by Excel.
+pub const MAX_TITLE_LENGTH: usize = 32;
+/// Length of an error alert or input prompt message allowed by Excel.
+pub const MAX_MESSAGE_LENGTH: usize = 255;
+/// Length of an inline list source, separators included, allowed by Excel.
+pub const MAX_LIST_LENGTH: usize = 255;
+
+/// Comparison of a validation rule, named after Office JS `DataValidationOperator`.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ValidationOperator {
+    Between = "Between",
+    NotBetween = "NotBetween",
+    EqualTo = "EqualTo",
+    NotEqualTo = "NotEqualTo",
+    GreaterThan = "GreaterThan",
+    LessThan = "LessThan",
+    GreaterThanOrEqualTo = "GreaterThanOrEqualTo",
+    LessThanOrEqualTo = "LessThanOrEqualTo",
+}
+
+impl ValidationOperator {
+    fn as_str(&self) -> Result<&'static str, WebExcelError> {
+        match self {
+            ValidationOperator::Between => Ok("Between"),
+            ValidationOperator::NotBetween => Ok("NotBetween"),
+            ValidationOperator::EqualTo => Ok("EqualTo"),
+            ValidationOperator::NotEqualTo => Ok("NotEqualTo"),
+            ValidationOperator::GreaterThan => Ok("GreaterThan"),
+            ValidationOperator::LessThan => Ok("LessThan"),
+            ValidationOperator::GreaterThanOrEqualTo => Ok("GreaterThanOrEqualTo"),
+            ValidationOperator::LessThanOrEqualTo => Ok("LessThanOrEqualTo"),
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+
+    fn takes_two(&self) -> bool {
+        matches!(
+            self,
+            ValidationOperator::Between | ValidationOperator::NotBetween
+        )
+    }
+
+    /// Whether `n` satisfies the operator against `first` and, for the between operators, `second`.
+    fn holds(&self, n: f64, first: f64, second: Option<f64>) -> bool {
+        let (low, high) = match second {
+            Some(second) => (first.min(second), first.max(second)),
+            None => (first, first),
+        };
+        match self {
+            ValidationOperator::Between => low <= n && n <= high,
+            ValidationOperator::NotBetween => n < low || high < n,
+            ValidationOperator::EqualTo => n == first,
+            ValidationOperator::NotEqualTo => n != first,
+            ValidationOperator::GreaterThan => n > first,
+            ValidationOperator::LessThan => n < first,
+            ValidationOperator::GreaterThanOrEqualTo => n >= first,
+            ValidationOperator::LessThanOrEqualTo => n <= first,
+            _ => false,
+        }
+    }
+}
+
+/// Style of the alert shown on invalid input, named after Office JS `DataValidationAlertStyle`.
+/// Only `Stop` prevents the input, the others let the user keep it.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlertStyle {
+    Stop = "Stop",
+    Warning = "Warning",
+    Information = "Information",
+}
+
+impl AlertStyle {
+    fn as_str(&self) -> Result<&'static str, WebExcelError> {
+        match self {
+            AlertStyle::Stop => Ok("Stop"),
+            AlertStyle::Warning => Ok("Warning"),
+            AlertStyle::Information => Ok("Information"),
+            _ => Err(WebExcelError::FormulaBuildError),
+        }
+    }
+}
+
+/// Rules comparing the input with one or two bounds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Bounded {
+    WholeNumber,
+    Decimal,
+    Date,
+    Time,
+    TextLength,
+}
+
+impl Bounded {
+    fn property(&self) -> &'static str {
+        match self {
+            Bounded::WholeNumber => "wholeNumber",
+            Bounded::Decimal => "decimal",
+            Bounded::Date => "date",
+            Bounded::Time => "time",
+            Bounded::TextLength => "textLength",
+        }
+    }
+
+    /// Number compared with the bounds, `None` when the input does not fit the rule at all.
+    fn measure(&self, value: &CellValue) -> Option<f64> {
+        if let Bounded::TextLength = self {
+            return value.as_text().ok().map(|s| s.chars().count() as f64);
+        }
+
+        let n = match value {
+            CellValue::Number(n) => *n,
+            CellValue::Text(s) => parse_number(s)?,
+            _ => return None,
+        };
+        match self {
+            Bounded::WholeNumber if n.fract() != 0.0 => None,
+            Bounded::Date | Bounded::Time if n < 0.0 => None,
+            _ => Some(n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Rule {
+    Bounded {
+        kind: Bounded,
+        operator: ValidationOperator,
+        formula1: Expr,
+        formula2: Option<Expr>,
+    },
+    List {
+        items: Vec<String>,
+        dropdown: bool,
+    },
+    ListRange {
+        source: Range,
+        dropdown: bool,
+    },
+    Custom(Expr),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Message {
+    title: String,
+    message: String,
+}
+
+impl Message {
+    fn new(title: &str, message: &str) -> Result<Message, WebExcelError> {
+        if title.chars().count() > MAX_TITLE_LENGTH || message.chars().count() > MAX_MESSAGE_LENGTH
+        {
+            return Err(WebExcelError::MessageLengthError);
+        }
+        Ok(Message {
+            title: title.to_owned(),
+            message: message.to_owned(),
+        })
+    }
+}
+
+/// Data validation of a range, built here, applied with Office JS and checked natively:
+///
+/// ```js
+/// const { range, ...validation } = rule.to_js();
+/// sheet.getRange(range).dataValidation.set(validation);
+/// ```
+///
+/// Like conditional formats, formulas are written for the top-left cell of the range and
+/// their relative references move for the other cells. Dates and times are serial numbers,
+/// e.g. `45292` for 2024-01-01 and `0.5` for noon.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataValidation {
+    range: Range,
+    rule: Rule,
+    ignore_blanks: bool,
+    alert: Option<(AlertStyle, Message)>,
+    prompt: Option<Message>,
+}
+
+#[wasm_bindgen]
+impl DataValidation {
+    /// Whole numbers compared with one bound, or two for `Between` and `NotBetween`.
+    pub fn whole_number(
+        range: &Range,
+        operator: ValidationOperator,
+        formula1: &str,
+        formula2: Option<String>,
+    ) -> Result<DataValidation, WebExcelError> {
+        DataValidation::bounded(range, Bounded::WholeNumber, operator, formula1, formula2)
+    }
+
+    pub fn decimal(
+        range: &Range,
+        operator: ValidationOperator,
+        formula1: &str,
+        formula2: Option<String>,
+    ) -> Result<DataValidation, WebExcelError> {
+        DataValidation::bounded(range, Bounded::Decimal, operator, formula1, formula2)
+    }
+
+    /// Dates as serial numbers, e.g. `date(range, GreaterThan, "=TODAY()", None)`.
+    pub fn date(
+        range: &Range,
+        operator: ValidationOperator,
+        formula1: &str,
+        formula2: Option<String>,
+    ) -> Result<DataValidation, WebExcelError> {
+        DataValidation::bounded(range, Bounded::Date, operator, formula1, formula2)
+    }
+
+    /// Times as fractions of a day, e.g. `time(range, Between, "0.375", Some("0.75"))`
+    /// for 9:00 to 18:00.
+    pub fn time(
+        range: &Range,
+        operator: ValidationOperator,
+        formula1: &str,
+        formula2: Option<String>,
+    ) -> Result<DataValidation, WebExcelError> {
+        DataValidation::bounded(range, Bounded::Time, operator, formula1, formula2)
+    }
+
+    /// Number of characters of the input, numbers counted as displayed by `&`.
+    pub fn text_length(
+        range: &Range,
+        operator: ValidationOperator,
+        formula1: &str,
+        formula2: Option<String>,
+    ) -> Result<DataValidation, WebExcelError> {
+        DataValidation::bounded(range, Bounded::TextLength, operator, formula1, formula2)
+    }
+
+    /// Inline list of choices separated by commas, e.g. `Yes,No,Maybe`.
+    /// Choices are compared without regard to case, like Excel.
+    pub fn list(range: &Range, items: &str) -> Result<DataValidation, WebExcelError> {
+        if items.chars().count() > MAX_LIST_LENGTH {
+            return Err(WebExcelError::FormulaLengthError);
+        }
+        let items: Vec<String> = items
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect();
+        if items.is_empty() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+
+        let rule = Rule::List {
+            items,
+            dropdown: true,
+        };
+        Ok(DataValidation::with_rule(range, rule))
+    }
+
+    /// Choices read from a single row or column. A source without a sheet is taken on the
+    /// sheet of `range`.
+    pub fn list_from_range(range: &Range, source: &Range) -> Result<DataValidation, WebExcelError> {
+        if source.rows != 1 && source.columns != 1 {
+            return Err(WebExcelError::DimensionError);
+        }
+
+        let mut source = source.clone();
+        if source.cell_start.sheet.is_none() {
+            source.cell_start.sheet = range.cell_start.sheet.clone();
+            source.cell_end.sheet = range.cell_start.sheet.clone();
+        }
+        let rule = Rule::ListRange {
+            source,
+            dropdown: true,
+        };
+        Ok(DataValidation::with_rule(range, rule))
+    }
+
+    /// Accept the input when a formula is true, e.g. `=COUNTIF($A:$A,A1)=1` on `A1:A100`.
+    pub fn custom(range: &Range, formula: &str) -> Result<DataValidation, WebExcelError> {
+        let rule = Rule::Custom(checked_formula(formula)?);
+        Ok(DataValidation::with_rule(range, rule))
+    }
+
+    /// Show the choices of a list in a dropdown, on by default.
+    pub fn with_dropdown(mut self, show: bool) -> Result<DataValidation, WebExcelError> {
+        match &mut self.rule {
+            Rule::List { dropdown, .. } | Rule::ListRange { dropdown, .. } => *dropdown = show,
+            _ => return Err(WebExcelError::FormulaBuildError),
+        }
+        Ok(self)
+    }
+
+    /// Accept blank input whatever the rule, on by default.
+    pub fn with_ignore_blanks(mut self, ignore: bool) -> DataValidation {
+        self.ignore_blanks = ignore;
+        self
+    }
+
+    /// Alert shown on invalid input. Titles are limited to 32 characters, messages to 255.
+    pub fn with_error(
+        mut self,
+        style: AlertStyle,
+        title: &str,
+        message: &str,
+    ) -> Result<DataValidation, WebExcelError> {
+        style.as_str()?;
+        self.alert = Some((style, Message::new(title, message)?));
+        Ok(self)
+    }
+
+    /// Prompt shown when a cell of the range is selected.
+    pub fn with_prompt(
+        mut self,
+        title: &str,
+        message: &str,
+    ) -> Result<DataValidation, WebExcelError> {
+        self.prompt = Some(Message::new(title, message)?);
+        Ok(self)
+    }
+
+    /// Take the formulas as written for `origin` instead of the top-left cell of the range.
+    /// See `ConditionalFormat::relative_to`.
+    pub fn relative_to(self, origin: &Cell) -> Result<DataValidation, WebExcelError> {
+        let top_left = &self.range.cell_start;
+        let rows = top_left.row as i64 - origin.row as i64;
+        let columns = top_left.column as i64 - origin.column as i64;
+        self.relocate(rows, columns)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn range(&self) -> Range {
+        self.range.clone()
+    }
+
+    /// Check a value given by JavaScript. See `DataValidation::check`.
+    #[wasm_bindgen(js_name = check)]
+    pub fn check_js(&self, value: JsValue) -> Result<bool, WebExcelError> {
+        self.check(&CellValue::from_js(&value))
+    }
+
+    /// Check a value given by JavaScript about to be written into `cell` of `workbook`.
+    /// See `DataValidation::check_at`.
+    #[wasm_bindgen(js_name = check_at)]
+    pub fn check_at_js(
+        &self,
+        value: JsValue,
+        cell: &Cell,
+        workbook: &Workbook,
+    ) -> Result<bool, WebExcelError> {
+        self.check_at(&CellValue::from_js(&value), cell, workbook)
+    }
+
+    /// JSON text of `to_js`.
+    pub fn to_json(&self) -> Result<String, WebExcelError> {
+        Ok(self.to_json_tree()?.to_string())
+    }
+
+    /// Object with the `range` address and the properties for `DataValidation.set`:
+    /// `rule`, `ignoreBlanks` and, when set, `errorAlert` and `prompt`.
+    pub fn to_js(&self) -> Result<JsValue, WebExcelError> {
+        Ok(self.to_json_tree()?.to_js())
+    }
+}
+
+impl DataValidation {
+    fn with_rule(range: &Range, rule: Rule) -> DataValidation {
+        DataValidation {
+            range: range.clone(),
+            rule,
+            ignore_blanks: true,
+            alert: None,
+            prompt: None,
+        }
+    }
+
+    fn bounded(
+        range: &Range,
+        kind: Bounded,
+        operator: ValidationOperator,
+        formula1: &str,
+        formula2: Option<String>,
+    ) -> Result<DataValidation, WebExcelError> {
+        operator.as_str()?;
+        if operator.takes_two() != formula2.is_some() {
+            return Err(WebExcelError::FormulaBuildError);
+        }
+
+        let rule = Rule::Bounded {
+            kind,
+            operator,
+            formula1: checked_formula(formula1)?,
+            formula2: formula2.as_deref().map(checked_formula).transpose()?,
+        };
+        Ok(DataValidation::with_rule(range, rule))
+    }
+
+    /// Check `value` as input of the top-left cell, with every other cell blank.
+    /// Enough for rules that do not read cells, see `check_at` for the others.
+    pub fn check(&self, value: &CellValue) -> Result<bool, WebExcelError> {
+        self.check_at(value, &self.range.cell_start, &Blank)
+    }
+
+    /// Check `value` as input of `cell`, reading formulas and list sources from `source`
+    /// as if `cell` already held `value`. A cell without a sheet is taken on the sheet of
+    /// the range. Fails when `cell` is outside the range.
+    pub fn check_at(
+        &self,
+        value: &CellValue,
+        cell: &Cell,
+        source: &dyn CellSource,
+    ) -> Result<bool, WebExcelError> {
+        let mut cell = cell.clone();
+        if cell.sheet.is_none() {
+            cell.sheet = self.range.cell_start.sheet.clone();
+        }
+        if !self.range.has(&cell) {
+            return Err(WebExcelError::OutOfBoundError);
+        }
+        if value.is_empty() && self.ignore_blanks {
+            return Ok(true);
+        }
+
+        let top_left = &self.range.cell_start;
+        let moved = self.clone().relocate(
+            cell.row as i64 - top_left.row as i64,
+            cell.column as i64 - top_left.column as i64,
+        )?;
+        let typed = Typed {
+            source,
+            cell,
+            value,
+        };
+        let evaluator = Evaluator::new(&typed).on_sheet(top_left.sheet.clone());
+        let number = |formula: &Expr| evaluator.evaluate(formula).into_scalar().as_number().ok();
+
+        Ok(match &moved.rule {
+            Rule::Bounded {
+                kind,
+                operator,
+                formula1,
+                formula2,
+            } => {
+                let second = formula2.as_ref().map(number);
+                match (kind.measure(value), number(formula1), second) {
+                    (Some(n), Some(first), None) => operator.holds(n, first, None),
+                    (Some(n), Some(first), Some(Some(second))) => {
+                        operator.holds(n, first, Some(second))
+                    }
+                    _ => false,
+                }
+            }
+            Rule::List { items, .. } => match value.as_text() {
+                Ok(text) => items
+                    .iter()
+                    .any(|item| item.to_lowercase() == text.to_lowercase()),
+                Err(_) => false,
+            },
+            Rule::ListRange {
+                source: choices, ..
+            } => choices
+                .iter_cells()
+                .any(|choice| same_choice(&typed.value(&choice), value)),
+            Rule::Custom(formula) => evaluator
+                .evaluate(formula)
+                .into_scalar()
+                .as_bool()
+                .unwrap_or(false),
+        })
+    }
+
+    fn relocate(mut self, rows: i64, columns: i64) -> Result<DataValidation, WebExcelError> {
+        self.rule = match &self.rule {
+            Rule::Bounded {
+                kind,
+                operator,
+                formula1,
+                formula2,
+            } => Rule::Bounded {
+                kind: *kind,
+                operator: *operator,
+                formula1: formula1.relocate(rows, columns)?,
+                formula2: formula2
+                    .as_ref()
+                    .map(|f| f.relocate(rows, columns))
+                    .transpose()?,
+            },
+            Rule::Custom(formula) => Rule::Custom(formula.relocate(rows, columns)?),
+            other => other.clone(),
+        };
+        Ok(self)
+    }
+
+    fn to_json_tree(&self) -> Result<Json, WebExcelError> {
+        let (property, rule) = match &self.rule {
+            Rule::Bounded {
+                kind,
+                operator,
+                formula1,
+                formula2,
+            } => {
+                let mut rule = Json::object([
+                    ("formula1", formula_text(formula1)?.into()),
+                    ("operator", operator.as_str()?.into()),
+                ]);
+                if let Some(formula2) = formula2 {
+                    rule.set("formula2", formula_text(formula2)?.into());
+                }
+                (kind.property(), rule)
+            }
+            Rule::List { items, dropdown } => (
+                "list",
+                Json::object([
+                    ("inCellDropDown", (*dropdown).into()),
+                    ("source", items.join(",").into()),
+                ]),
+            ),
+            Rule::ListRange { source, dropdown } => {
+                // Absolute, or Excel would move the source along with each cell
+                let (mut start, mut end) = (source.cell_start.clone(), source.cell_end.clone());
+                for cell in [&mut start, &mut end] {
+                    cell.fixed_row = true;
+                    cell.fixed_column = true;
+                }
+                let single = start == end;
+                let reference = Expr::Reference(Reference {
+                    workbook: None,
+                    start,
+                    end: (!single).then_some(end),
+                    spill: false,
+                });
+                (
+                    "list",
+                    Json::object([
+                        ("inCellDropDown", (*dropdown).into()),
+                        ("source", formula_text(&reference)?.into()),
+                    ]),
+                )
+            }
+            Rule::Custom(formula) => (
+                "custom",
+                Json::object([("formula", formula_text(formula)?.into())]),
+            ),
+        };
+
+        let mut json = Json::object([
+            (
+                "range",
+                RangeAreas::from(self.range.clone())
+                    .to_str_address()?
+                    .into(),
+            ),
+            ("rule", Json::object([(property, rule)])),
+            ("ignoreBlanks", self.ignore_blanks.into()),
+        ]);
+        if let Some((style, alert)) = &self.alert {
+            json.set(
+                "errorAlert",
+                Json::object([
+                    ("showAlert", true.into()),
+                    ("style", style.as_str()?.into()),
+                    ("title", alert.title.as_str().into()),
+                    ("message", alert.message.as_str().into()),
+                ]),
+            );
+        }
+        if let Some(prompt) = &self.prompt {
+            json.set(
+                "prompt",
+                Json::object([
+                    ("showPrompt", true.into()),
+                    ("title", prompt.title.as_str().into()),
+                    ("message", prompt.message.as_str().into()),
+                ]),
+            );
+        }
+        Ok(json)
+    }
+}
+
+/// Cells of `source`, except `cell` which holds the value being checked.
+struct Typed<'a> {
+    source: &'a dyn CellSource,
+    cell: Cell,
+    value: &'a CellValue,
+}
+
+impl CellSource for Typed<'_> {
+    fn value(&self, cell: &Cell) -> CellValue {
+        if *cell == self.cell {
+            self.value.clone()
+        } else {
+            self.source.value(cell)
+        }
+    }
+
+    fn used_bounds(&self, sheet: Option<&str>) -> Option<(u32, u32)> {
+        let bounds = self.source.used_bounds(sheet);
+        if !same_sheet(&self.cell.sheet, &sheet.map(str::to_owned)) {
+            return bounds;
+        }
+        let (row, column) = bounds.unwrap_or((self.cell.row, self.cell.column));
+        Some((row.max(self.cell.row), column.max(self.cell.column)))
+    }
+}
+
+/// Source where every cell is blank.
+struct Blank;
+
+impl CellSource for Blank {
+    fn value(&self, _cell: &Cell) -> CellValue {
+        CellValue::Empty
+    }
+}
+
+/// Whether an input matches a choice of a list read from cells. Numbers typed as text
+/// match the number, as Excel converts the input before checking it.
+fn same_choice(choice: &CellValue, value: &CellValue) -> bool {
+    if choice.is_empty() {
+        return false;
+    }
+    let value = match (choice, value) {
+        (CellValue::Number(_), CellValue::Text(s)) => match parse_number(s) {
+            Some(n) => CellValue::Number(n),
+            None => value.clone(),
+        },
+        _ => value.clone(),
+    };
+    compare_values(choice, &value) == Ordering::Equal
+}
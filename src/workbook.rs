@@ -0,0 +1,664 @@
+use crate::cell::Cell;
+use crate::error::WebExcelError;
+use crate::math::eval::{CellSource, CellValue};
+use crate::math::format::NumberFormat;
+use crate::math::parser::{parse_formula, ErrorValue};
+use crate::range::Range;
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+/// Longest sheet name Excel accepts.
+pub const MAX_SHEET_NAME_LENGTH: usize = 31;
+
+/// Number format of cells without one.
+pub const GENERAL_FORMAT: &str = "General";
+
+/// Check a sheet name against Excel's rules. It must be 1 to 31 characters long,
+/// may not contain `: \ / ? * [ ]`, start or end with `'`, or be the reserved `History`.
+pub fn is_valid_sheet_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().count() <= MAX_SHEET_NAME_LENGTH
+        && !name.contains([':', '\\', '/', '?', '*', '[', ']'])
+        && !name.starts_with('\'')
+        && !name.ends_with('\'')
+        && !name.eq_ignore_ascii_case("History")
+}
+
+/// Content of a single cell. A formula keeps its last calculated value in `value`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CellData {
+    pub value: CellValue,
+    /// Formula text, starting with `=`.
+    pub formula: Option<String>,
+    /// Number format code such as `0.00%`, `None` for `General`.
+    pub number_format: Option<String>,
+}
+
+impl CellData {
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty() && self.formula.is_none() && self.number_format.is_none()
+    }
+}
+
+/// A named sheet holding only the cells in use, keyed by row then column.
+/// The sheet of the `Cell`s and `Range`s given to it is ignored.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Worksheet {
+    name: String,
+    cells: BTreeMap<(u32, u32), CellData>,
+}
+
+#[wasm_bindgen]
+impl Worksheet {
+    #[wasm_bindgen(constructor)]
+    pub fn new(name: &str) -> Result<Worksheet, WebExcelError> {
+        if !is_valid_sheet_name(name) {
+            return Err(WebExcelError::NameError);
+        }
+
+        Ok(Worksheet {
+            name: name.to_owned(),
+            cells: BTreeMap::new(),
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Number of cells holding a value, formula or number format.
+    #[wasm_bindgen(getter)]
+    pub fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Value of `cell` as a number, string, boolean, error string such as `"#DIV/0!"`,
+    /// or `null` when empty.
+    pub fn value_at(&self, cell: &Cell) -> JsValue {
+        self.value(cell).to_js()
+    }
+
+    /// Put a value into `cell`, removing its formula. `null` clears the value.
+    pub fn set_value_at(&mut self, cell: &Cell, value: &JsValue) {
+        self.set_value(cell, CellValue::from_js(value));
+    }
+
+    pub fn formula(&self, cell: &Cell) -> Option<String> {
+        self.cell(cell).and_then(|data| data.formula.clone())
+    }
+
+    /// Put a formula into `cell`, e.g. `=SUM(A1:A10)`. Its value is empty until calculated.
+    pub fn set_formula(&mut self, cell: &Cell, formula: &str) -> Result<(), WebExcelError> {
+        parse_formula(formula)?;
+        let formula = formula.trim_start();
+        let formula = formula.strip_prefix('=').unwrap_or(formula);
+
+        self.update(cell, |data| {
+            data.value = CellValue::Empty;
+            data.formula = Some(format!("={}", formula));
+        });
+        Ok(())
+    }
+
+    /// Number format of `cell`, `General` when it has none.
+    pub fn number_format(&self, cell: &Cell) -> String {
+        self.cell(cell)
+            .and_then(|data| data.number_format.clone())
+            .unwrap_or_else(|| GENERAL_FORMAT.to_owned())
+    }
+
+    /// Text displayed in `cell`: its value rendered with its number format, like Office JS
+    /// `Range.text`. A number format that cannot be parsed displays as `General`.
+    pub fn text(&self, cell: &Cell) -> String {
+        NumberFormat::new(&self.number_format(cell))
+            .unwrap_or_default()
+            .display(&self.value(cell))
+    }
+
+    pub fn set_number_format(&mut self, cell: &Cell, format: &str) {
+        let format = (!format.is_empty() && !format.eq_ignore_ascii_case(GENERAL_FORMAT))
+            .then(|| format.to_owned());
+        self.update(cell, |data| data.number_format = format);
+    }
+
+    /// Remove the value, formula and number format of `cell`.
+    pub fn clear(&mut self, cell: &Cell) {
+        self.cells.remove(&(cell.row, cell.column));
+    }
+
+    /// Remove the values, formulas and number formats of every cell in `range`.
+    pub fn clear_range(&mut self, range: &Range) {
+        let (start, end) = (&range.cell_start, &range.cell_end);
+        self.cells.retain(|(row, column), _| {
+            !(start.row..=end.row).contains(row) || !(start.column..=end.column).contains(column)
+        });
+    }
+
+    /// Values of `range` as an array of rows, like `Excel.Range.values`.
+    pub fn values_at(&self, range: &Range) -> js_sys::Array {
+        to_js_grid(self.values(range), |value| value.to_js())
+    }
+
+    /// Set the values of `range` from an array of rows, like `Excel.Range.values`.
+    /// A single value is put into every cell.
+    pub fn set_values_at(
+        &mut self,
+        range: &Range,
+        values: &js_sys::Array,
+    ) -> Result<(), WebExcelError> {
+        self.set_values(range, from_js_grid(values, CellValue::from_js))
+    }
+
+    /// Formulas of `range` as an array of rows, like `Excel.Range.formulas`.
+    /// Cells without a formula give their value.
+    pub fn formulas_at(&self, range: &Range) -> js_sys::Array {
+        let grid = grid_of(range, |row, column| match self.cells.get(&(row, column)) {
+            Some(CellData {
+                formula: Some(formula),
+                ..
+            }) => JsValue::from_str(formula),
+            Some(data) => data.value.to_js(),
+            None => JsValue::NULL,
+        });
+        to_js_grid(grid, |value| value.clone())
+    }
+
+    /// Set the formulas of `range` from an array of rows, like `Excel.Range.formulas`.
+    /// Text starting with `=` is a formula, anything else a value.
+    pub fn set_formulas_at(
+        &mut self,
+        range: &Range,
+        formulas: &js_sys::Array,
+    ) -> Result<(), WebExcelError> {
+        let grid = fit(range, from_js_grid(formulas, |value| value.clone()))?;
+        for (cell, value) in range.iter_cells().zip(grid.into_iter().flatten()) {
+            match value.as_string() {
+                Some(formula) if formula.starts_with('=') => self.set_formula(&cell, &formula)?,
+                _ => self.set_value(&cell, CellValue::from_js(&value)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Number formats of `range` as an array of rows, like `Excel.Range.numberFormat`.
+    pub fn number_formats_at(&self, range: &Range) -> js_sys::Array {
+        let grid = grid_of(range, |row, column| {
+            self.number_format(&position(row, column))
+        });
+        to_js_grid(grid, |format| JsValue::from_str(format))
+    }
+
+    /// Set the number formats of `range` from an array of rows. A single format is used for every cell.
+    pub fn set_number_formats_at(
+        &mut self,
+        range: &Range,
+        formats: &js_sys::Array,
+    ) -> Result<(), WebExcelError> {
+        let grid = fit(
+            range,
+            from_js_grid(formats, |value| value.as_string().unwrap_or_default()),
+        )?;
+        for (cell, format) in range.iter_cells().zip(grid.into_iter().flatten()) {
+            self.set_number_format(&cell, &format);
+        }
+        Ok(())
+    }
+
+    /// Smallest range covering every cell in use, or only the cells holding a value or formula
+    /// with `values_only`. `None` for an empty sheet.
+    pub fn used_range(&self, values_only: bool) -> Option<Range> {
+        let mut used = self
+            .cells
+            .iter()
+            .filter(|(_, data)| !values_only || !data.value.is_empty() || data.formula.is_some())
+            .map(|(key, _)| *key);
+
+        let first = used.next()?;
+        let (top, bottom, left, right) = used.fold(
+            (first.0, first.0, first.1, first.1),
+            |(top, bottom, left, right), (row, column)| {
+                (top, bottom.max(row), left.min(column), right.max(column))
+            },
+        );
+
+        let sheet = Some(self.name.clone());
+        Range::new(
+            &Cell::new(top, left, sheet.clone()).ok()?,
+            &Cell::new(bottom, right, sheet).ok()?,
+        )
+        .ok()
+    }
+}
+
+impl Worksheet {
+    pub fn cell(&self, cell: &Cell) -> Option<&CellData> {
+        self.cells.get(&(cell.row, cell.column))
+    }
+
+    /// Replace the content of `cell`.
+    pub fn set_cell(&mut self, cell: &Cell, data: CellData) {
+        self.update(cell, |current| *current = data);
+    }
+
+    pub fn value(&self, cell: &Cell) -> CellValue {
+        self.cell(cell)
+            .map(|data| data.value.clone())
+            .unwrap_or_default()
+    }
+
+    /// Put a value into `cell`, removing its formula.
+    pub fn set_value(&mut self, cell: &Cell, value: CellValue) {
+        self.update(cell, |data| {
+            data.value = value;
+            data.formula = None;
+        });
+    }
+
+    /// Values of `range`, row by row.
+    pub fn values(&self, range: &Range) -> Vec<Vec<CellValue>> {
+        grid_of(range, |row, column| self.value(&position(row, column)))
+    }
+
+    /// Set the values of `range` from rows matching its size, or from a single value
+    /// put into every cell.
+    pub fn set_values(
+        &mut self,
+        range: &Range,
+        values: Vec<Vec<CellValue>>,
+    ) -> Result<(), WebExcelError> {
+        let grid = fit(range, values)?;
+        for (cell, value) in range.iter_cells().zip(grid.into_iter().flatten()) {
+            self.set_value(&cell, value);
+        }
+        Ok(())
+    }
+
+    /// Cells in use row by row, qualified with the sheet name.
+    pub fn iter(&self) -> impl Iterator<Item = (Cell, &CellData)> + '_ {
+        self.cells.iter().map(|((row, column), data)| {
+            let mut cell = position(*row, *column);
+            cell.sheet = Some(self.name.clone());
+            (cell, data)
+        })
+    }
+
+    /// Change the content of `cell`, dropping it once nothing is left.
+    fn update(&mut self, cell: &Cell, change: impl FnOnce(&mut CellData)) {
+        let key = (cell.row, cell.column);
+        let data = self.cells.entry(key).or_default();
+        change(data);
+        if data.is_empty() {
+            self.cells.remove(&key);
+        }
+    }
+}
+
+impl CellSource for Worksheet {
+    fn value(&self, cell: &Cell) -> CellValue {
+        Worksheet::value(self, cell)
+    }
+
+    fn used_bounds(&self, _sheet: Option<&str>) -> Option<(u32, u32)> {
+        let range = self.used_range(true)?;
+        Some((range.cell_end.row, range.cell_end.column))
+    }
+}
+
+/// Ordered sheets of a workbook, one of them active.
+/// Cells and ranges without a sheet refer to the active sheet, sheet names are case insensitive.
+/// Formulas are kept as text and are not rewritten when sheets are renamed or deleted.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workbook {
+    sheets: Vec<Worksheet>,
+    active: usize,
+}
+
+impl Default for Workbook {
+    fn default() -> Self {
+        Workbook {
+            sheets: vec![Worksheet::new("Sheet1").unwrap()],
+            active: 0,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Workbook {
+    /// A workbook with a single `Sheet1`.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Workbook {
+        Workbook::default()
+    }
+
+    /// Sheet names in order.
+    pub fn sheet_names(&self) -> js_sys::Array {
+        self.sheets
+            .iter()
+            .map(|sheet| JsValue::from_str(&sheet.name))
+            .collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sheet_count(&self) -> usize {
+        self.sheets.len()
+    }
+
+    pub fn has_sheet(&self, name: &str) -> bool {
+        self.index_of(name).is_some()
+    }
+
+    /// Add a sheet after the last one and return its name.
+    /// Without a name, the first free one of `Sheet1`, `Sheet2`, ... is used.
+    pub fn add_sheet(&mut self, name: Option<String>) -> Result<String, WebExcelError> {
+        let name = match name {
+            Some(name) => name,
+            None => (1..)
+                .map(|n| format!("Sheet{}", n))
+                .find(|name| !self.has_sheet(name))
+                .unwrap(),
+        };
+        if self.has_sheet(&name) {
+            return Err(WebExcelError::NameError);
+        }
+
+        self.sheets.push(Worksheet::new(&name)?);
+        Ok(name)
+    }
+
+    /// Rename a sheet. Changing only the case of a name is allowed.
+    pub fn rename_sheet(&mut self, name: &str, new_name: &str) -> Result<(), WebExcelError> {
+        let index = self.find(name)?;
+        if !is_valid_sheet_name(new_name) {
+            return Err(WebExcelError::NameError);
+        }
+        if self.index_of(new_name).is_some_and(|other| other != index) {
+            return Err(WebExcelError::NameError);
+        }
+
+        self.sheets[index].name = new_name.to_owned();
+        Ok(())
+    }
+
+    /// Delete a sheet. The only sheet of the workbook cannot be deleted.
+    /// When the active sheet goes, the next one becomes active.
+    pub fn delete_sheet(&mut self, name: &str) -> Result<(), WebExcelError> {
+        let index = self.find(name)?;
+        if self.sheets.len() == 1 {
+            return Err(WebExcelError::LastSheetError);
+        }
+
+        self.sheets.remove(index);
+        if self.active > index || self.active == self.sheets.len() {
+            self.active -= 1;
+        }
+        Ok(())
+    }
+
+    /// Move a sheet to `position`, counted from 0. Positions past the end move it last.
+    pub fn move_sheet(&mut self, name: &str, position: usize) -> Result<(), WebExcelError> {
+        let index = self.find(name)?;
+        let position = position.min(self.sheets.len() - 1);
+        let active = self.sheets[self.active].name.clone();
+
+        let sheet = self.sheets.remove(index);
+        self.sheets.insert(position, sheet);
+        self.active = self.find(&active)?;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn active_sheet(&self) -> String {
+        self.sheets[self.active].name.clone()
+    }
+
+    pub fn activate(&mut self, name: &str) -> Result<(), WebExcelError> {
+        self.active = self.find(name)?;
+        Ok(())
+    }
+
+    /// Value of `cell` as a number, string, boolean, error string or `null`.
+    pub fn value_at(&self, cell: &Cell) -> Result<JsValue, WebExcelError> {
+        Ok(self.sheet_of(cell.sheet.as_deref())?.value_at(cell))
+    }
+
+    pub fn set_value_at(&mut self, cell: &Cell, value: &JsValue) -> Result<(), WebExcelError> {
+        self.sheet_of_mut(cell.sheet.as_deref())?
+            .set_value_at(cell, value);
+        Ok(())
+    }
+
+    pub fn formula(&self, cell: &Cell) -> Result<Option<String>, WebExcelError> {
+        Ok(self.sheet_of(cell.sheet.as_deref())?.formula(cell))
+    }
+
+    pub fn set_formula(&mut self, cell: &Cell, formula: &str) -> Result<(), WebExcelError> {
+        self.sheet_of_mut(cell.sheet.as_deref())?
+            .set_formula(cell, formula)
+    }
+
+    pub fn number_format(&self, cell: &Cell) -> Result<String, WebExcelError> {
+        Ok(self.sheet_of(cell.sheet.as_deref())?.number_format(cell))
+    }
+
+    pub fn text(&self, cell: &Cell) -> Result<String, WebExcelError> {
+        Ok(self.sheet_of(cell.sheet.as_deref())?.text(cell))
+    }
+
+    pub fn set_number_format(&mut self, cell: &Cell, format: &str) -> Result<(), WebExcelError> {
+        self.sheet_of_mut(cell.sheet.as_deref())?
+            .set_number_format(cell, format);
+        Ok(())
+    }
+
+    pub fn clear(&mut self, cell: &Cell) -> Result<(), WebExcelError> {
+        self.sheet_of_mut(cell.sheet.as_deref())?.clear(cell);
+        Ok(())
+    }
+
+    pub fn clear_range(&mut self, range: &Range) -> Result<(), WebExcelError> {
+        self.range_sheet_mut(range)?.clear_range(range);
+        Ok(())
+    }
+
+    pub fn values_at(&self, range: &Range) -> Result<js_sys::Array, WebExcelError> {
+        Ok(self.range_sheet(range)?.values_at(range))
+    }
+
+    pub fn set_values_at(
+        &mut self,
+        range: &Range,
+        values: &js_sys::Array,
+    ) -> Result<(), WebExcelError> {
+        self.range_sheet_mut(range)?.set_values_at(range, values)
+    }
+
+    pub fn formulas_at(&self, range: &Range) -> Result<js_sys::Array, WebExcelError> {
+        Ok(self.range_sheet(range)?.formulas_at(range))
+    }
+
+    pub fn set_formulas_at(
+        &mut self,
+        range: &Range,
+        formulas: &js_sys::Array,
+    ) -> Result<(), WebExcelError> {
+        self.range_sheet_mut(range)?
+            .set_formulas_at(range, formulas)
+    }
+
+    pub fn number_formats_at(&self, range: &Range) -> Result<js_sys::Array, WebExcelError> {
+        Ok(self.range_sheet(range)?.number_formats_at(range))
+    }
+
+    pub fn set_number_formats_at(
+        &mut self,
+        range: &Range,
+        formats: &js_sys::Array,
+    ) -> Result<(), WebExcelError> {
+        self.range_sheet_mut(range)?
+            .set_number_formats_at(range, formats)
+    }
+
+    /// Used range of a sheet, see `Worksheet::used_range`.
+    pub fn used_range(
+        &self,
+        sheet: &str,
+        values_only: bool,
+    ) -> Result<Option<Range>, WebExcelError> {
+        Ok(self.sheets[self.find(sheet)?].used_range(values_only))
+    }
+}
+
+impl Workbook {
+    /// A workbook of the given sheets, the first one active.
+    /// Fails if there are none, or if two share a name.
+    pub fn from_sheets(sheets: Vec<Worksheet>) -> Result<Workbook, WebExcelError> {
+        let mut workbook = Workbook {
+            sheets: vec![],
+            active: 0,
+        };
+        for sheet in sheets {
+            if workbook.has_sheet(&sheet.name) {
+                return Err(WebExcelError::NameError);
+            }
+            workbook.sheets.push(sheet);
+        }
+
+        if workbook.sheets.is_empty() {
+            return Err(WebExcelError::LastSheetError);
+        }
+        Ok(workbook)
+    }
+
+    pub fn sheets(&self) -> &[Worksheet] {
+        &self.sheets
+    }
+
+    pub fn sheet(&self, name: &str) -> Option<&Worksheet> {
+        self.index_of(name).map(|index| &self.sheets[index])
+    }
+
+    pub fn sheet_mut(&mut self, name: &str) -> Option<&mut Worksheet> {
+        self.index_of(name).map(|index| &mut self.sheets[index])
+    }
+
+    /// The named sheet, or the active one for `None`.
+    pub fn sheet_of(&self, sheet: Option<&str>) -> Result<&Worksheet, WebExcelError> {
+        match sheet {
+            Some(name) => self.sheet(name).ok_or(WebExcelError::SheetNotFoundError),
+            None => Ok(&self.sheets[self.active]),
+        }
+    }
+
+    pub fn sheet_of_mut(&mut self, sheet: Option<&str>) -> Result<&mut Worksheet, WebExcelError> {
+        match sheet {
+            Some(name) => self
+                .sheet_mut(name)
+                .ok_or(WebExcelError::SheetNotFoundError),
+            None => Ok(&mut self.sheets[self.active]),
+        }
+    }
+
+    /// Value of `cell`, `#REF!` on a missing sheet.
+    pub fn value(&self, cell: &Cell) -> CellValue {
+        self.sheet_of(cell.sheet.as_deref())
+            .map(|sheet| sheet.value(cell))
+            .unwrap_or(CellValue::Error(ErrorValue::Ref))
+    }
+
+    pub fn set_value(&mut self, cell: &Cell, value: CellValue) -> Result<(), WebExcelError> {
+        self.sheet_of_mut(cell.sheet.as_deref())?
+            .set_value(cell, value);
+        Ok(())
+    }
+
+    pub fn values(&self, range: &Range) -> Result<Vec<Vec<CellValue>>, WebExcelError> {
+        Ok(self.range_sheet(range)?.values(range))
+    }
+
+    pub fn set_values(
+        &mut self,
+        range: &Range,
+        values: Vec<Vec<CellValue>>,
+    ) -> Result<(), WebExcelError> {
+        self.range_sheet_mut(range)?.set_values(range, values)
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.sheets
+            .iter()
+            .position(|sheet| sheet.name.to_lowercase() == name.to_lowercase())
+    }
+
+    fn find(&self, name: &str) -> Result<usize, WebExcelError> {
+        self.index_of(name).ok_or(WebExcelError::SheetNotFoundError)
+    }
+
+    fn range_sheet(&self, range: &Range) -> Result<&Worksheet, WebExcelError> {
+        self.sheet_of(range.cell_start.sheet.as_deref())
+    }
+
+    fn range_sheet_mut(&mut self, range: &Range) -> Result<&mut Worksheet, WebExcelError> {
+        self.sheet_of_mut(range.cell_start.sheet.as_deref())
+    }
+}
+
+impl CellSource for Workbook {
+    fn value(&self, cell: &Cell) -> CellValue {
+        Workbook::value(self, cell)
+    }
+
+    fn used_bounds(&self, sheet: Option<&str>) -> Option<(u32, u32)> {
+        self.sheet_of(sheet).ok()?.used_bounds(sheet)
+    }
+}
+
+/// Unqualified cell at `row`, `column`.
+fn position(row: u32, column: u32) -> Cell {
+    Cell {
+        row,
+        column,
+        ..Default::default()
+    }
+}
+
+fn grid_of<T>(range: &Range, mut f: impl FnMut(u32, u32) -> T) -> Vec<Vec<T>> {
+    (range.cell_start.row..=range.cell_end.row)
+        .map(|row| {
+            (range.cell_start.column..=range.cell_end.column)
+                .map(|column| f(row, column))
+                .collect()
+        })
+        .collect()
+}
+
+/// Check `grid` against the size of `range`, repeating a single value over the whole range.
+fn fit<T: Clone>(range: &Range, grid: Vec<Vec<T>>) -> Result<Vec<Vec<T>>, WebExcelError> {
+    let (rows, columns) = (range.rows as usize, range.columns as usize);
+    if grid.len() == 1 && grid[0].len() == 1 {
+        return Ok(vec![vec![grid[0][0].clone(); columns]; rows]);
+    }
+    if grid.len() != rows || grid.iter().any(|row| row.len() != columns) {
+        return Err(WebExcelError::DimensionError);
+    }
+    Ok(grid)
+}
+
+pub(crate) fn to_js_grid<T>(grid: Vec<Vec<T>>, f: impl Fn(&T) -> JsValue) -> js_sys::Array {
+    grid.iter()
+        .map(|row| row.iter().map(&f).collect::<js_sys::Array>())
+        .collect()
+}
+
+/// Read an array of rows. A value that is not an array is a row of its own.
+pub(crate) fn from_js_grid<T>(values: &js_sys::Array, f: impl Fn(&JsValue) -> T) -> Vec<Vec<T>> {
+    values
+        .iter()
+        .map(|row| match row.dyn_into::<js_sys::Array>() {
+            Ok(row) => row.iter().map(|value| f(&value)).collect(),
+            Err(value) => vec![f(&value)],
+        })
+        .collect()
+}
@@ -0,0 +1,1520 @@
+use crate::cell::Cell;
+use crate::csv::parse_date_time;
+use crate::error::WebExcelError;
+use crate::math::array::spill_range;
+use crate::math::eval::{CellValue, Evaluator};
+use crate::math::format::NumberFormat;
+use crate::math::func::FunctionBuilder;
+use crate::math::names::is_valid_name;
+use crate::math::names::DefinedNames;
+use crate::math::parser::{parse_formula, ErrorValue, Expr, Reference};
+use crate::merge::MergedCells;
+use crate::range::Range;
+use crate::util::cell_handle::{r1c1_to_address, MAX_COLUMN};
+use crate::util::xml::{escape, parse_xml, XmlElement};
+use crate::util::zip::{ZipArchive, ZipWriter};
+use crate::workbook::{CellData, Workbook, Worksheet, GENERAL_FORMAT};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use wasm_bindgen::prelude::*;
+
+/// Codes of the number formats built into Excel, by id. Ids missing here are
+/// locale dependent and read as `General`.
+pub const BUILTIN_NUMBER_FORMATS: [(u32, &str); 28] = [
+    (0, GENERAL_FORMAT),
+    (1, "0"),
+    (2, "0.00"),
+    (3, "#,##0"),
+    (4, "#,##0.00"),
+    (9, "0%"),
+    (10, "0.00%"),
+    (11, "0.00E+00"),
+    (12, "# ?/?"),
+    (13, "# ??/??"),
+    (14, "mm-dd-yy"),
+    (15, "d-mmm-yy"),
+    (16, "d-mmm"),
+    (17, "mmm-yy"),
+    (18, "h:mm AM/PM"),
+    (19, "h:mm:ss AM/PM"),
+    (20, "h:mm"),
+    (21, "h:mm:ss"),
+    (22, "m/d/yy h:mm"),
+    (37, "#,##0 ;(#,##0)"),
+    (38, "#,##0 ;[Red](#,##0)"),
+    (39, "#,##0.00;(#,##0.00)"),
+    (40, "#,##0.00;[Red](#,##0.00)"),
+    (45, "mm:ss"),
+    (46, "[h]:mm:ss"),
+    (47, "mmss.0"),
+    (48, "##0.0E+0"),
+    (49, "@"),
+];
+
+/// Defined name of a workbook, visible in a single sheet when `sheet` is set.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XlsxName {
+    #[wasm_bindgen(getter_with_clone)]
+    pub name: String,
+    /// Formula text, starting with `=`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub formula: String,
+    #[wasm_bindgen(getter_with_clone)]
+    pub sheet: Option<String>,
+    pub hidden: bool,
+}
+
+/// Table of a sheet, a `ListObject` in Office JS.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct XlsxTable {
+    /// Name used by structured references, e.g. `Sales` in `Sales[Amount]`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub name: String,
+    /// Whole table, with its header and totals rows.
+    #[wasm_bindgen(getter_with_clone)]
+    pub range: Range,
+    columns: Vec<String>,
+    pub header_row: bool,
+    pub totals_row: bool,
+}
+
+#[wasm_bindgen]
+impl XlsxTable {
+    /// Column names in order.
+    #[wasm_bindgen(js_name = columns)]
+    pub fn columns_js(&self) -> js_sys::Array {
+        self.columns.iter().map(|c| JsValue::from_str(c)).collect()
+    }
+
+    /// Rows between the header and totals rows, `None` if there are none.
+    pub fn data_range(&self) -> Option<Range> {
+        let top = self.range.cell_start.row + self.header_row as u32;
+        let bottom = self
+            .range
+            .cell_end
+            .row
+            .checked_sub(self.totals_row as u32)?;
+        (top <= bottom).then(|| {
+            self.range.area(
+                top,
+                self.range.cell_start.column,
+                bottom,
+                self.range.cell_end.column,
+            )
+        })
+    }
+}
+
+impl XlsxTable {
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+}
+
+/// Widest column Excel allows, in characters.
+const MAX_COLUMN_WIDTH: f64 = 255.0;
+/// Largest part read from a package once extracted, in bytes.
+const MAX_PART_SIZE: usize = 256 << 20;
+/// Most bytes extracted from the parts of a package in all.
+const MAX_PACKAGE_SIZE: usize = 512 << 20;
+/// Days from 1900-01-00 to 1904-01-01, the start of the 1904 date system.
+const DATE_1904_OFFSET: f64 = 1462.0;
+
+/// Content of an `.xlsx` package: sheets with their values, formulas and number formats,
+/// defined names, merged cells, tables, column widths and frozen panes. Formulas keep their
+/// last calculated value. The `_xlfn.` prefixes Excel stores on functions added after
+/// Excel 2007 are dropped when reading and put back when writing.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct XlsxFile {
+    workbook: Workbook,
+    names: Vec<XlsxName>,
+    merges: MergedCells,
+    tables: Vec<XlsxTable>,
+    /// Custom widths by sheet name and column.
+    widths: BTreeMap<(String, u32), f64>,
+    /// Top-left scrolling cell of each sheet with frozen panes, by sheet name.
+    frozen: BTreeMap<String, Cell>,
+}
+
+#[wasm_bindgen]
+impl XlsxFile {
+    /// A package of `workbook` to write, with nothing else set yet.
+    #[wasm_bindgen(constructor)]
+    pub fn new(workbook: &Workbook) -> XlsxFile {
+        XlsxFile::from_workbook(workbook.clone())
+    }
+
+    /// Read the bytes of an `.xlsx` file, e.g. a `Uint8Array` of a `File`.
+    /// Fails with `FileFormatError` on anything that is not a readable package.
+    pub fn read(bytes: &[u8]) -> Result<XlsxFile, WebExcelError> {
+        Package::new(bytes)?.read()
+    }
+
+    #[wasm_bindgen(getter, js_name = workbook)]
+    pub fn workbook_js(&self) -> Workbook {
+        self.workbook.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = merges)]
+    pub fn merges_js(&self) -> MergedCells {
+        self.merges.clone()
+    }
+
+    /// Defined names in the order of the file, as an array of `XlsxName`.
+    #[wasm_bindgen(js_name = names)]
+    pub fn names_js(&self) -> js_sys::Array {
+        self.names.iter().cloned().map(JsValue::from).collect()
+    }
+
+    /// Tables of every sheet, as an array of `XlsxTable`.
+    #[wasm_bindgen(js_name = tables)]
+    pub fn tables_js(&self) -> js_sys::Array {
+        self.tables.iter().cloned().map(JsValue::from).collect()
+    }
+
+    /// Table named `name`, ignoring case.
+    pub fn table(&self, name: &str) -> Option<XlsxTable> {
+        self.tables
+            .iter()
+            .find(|table| table.name.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Workbook level names the evaluator understands. Sheet level names, and names whose
+    /// formula cannot be parsed, are left out.
+    pub fn defined_names(&self) -> DefinedNames {
+        let mut names = DefinedNames::new();
+        for name in self.names.iter().filter(|name| name.sheet.is_none()) {
+            if let Ok(formula) = FunctionBuilder::new(&name.formula) {
+                let _ = names.add(&name.name, &formula);
+            }
+        }
+        names
+    }
+
+    /// Set the width of the columns of `range`, in characters, from 0 to 255.
+    /// A range without a sheet refers to the active one.
+    pub fn set_column_width(&mut self, range: &Range, width: f64) -> Result<(), WebExcelError> {
+        if !(0.0..=MAX_COLUMN_WIDTH).contains(&width) {
+            return Err(WebExcelError::OutOfBoundError);
+        }
+        let sheet = self.sheet_name(range.cell_start.sheet.as_deref())?;
+        for column in range.cell_start.column..=range.cell_end.column {
+            self.widths.insert((sheet.clone(), column), width);
+        }
+        Ok(())
+    }
+
+    /// Custom width of the column of `cell`, `None` for the default width.
+    pub fn column_width(&self, cell: &Cell) -> Option<f64> {
+        let sheet = self.sheet_name(cell.sheet.as_deref()).ok()?;
+        self.widths.get(&(sheet, cell.column)).copied()
+    }
+
+    /// Freeze the rows above and the columns left of `cell`, which becomes the top-left
+    /// scrolling cell of its sheet. `A1` unfreezes the panes.
+    pub fn freeze_panes(&mut self, cell: &Cell) -> Result<(), WebExcelError> {
+        let sheet = self.sheet_name(cell.sheet.as_deref())?;
+        if cell.row == 0 && cell.column == 0 {
+            self.frozen.remove(&sheet);
+        } else {
+            let frozen = Cell::new(cell.row, cell.column, Some(sheet.clone()))?;
+            self.frozen.insert(sheet, frozen);
+        }
+        Ok(())
+    }
+
+    /// Top-left scrolling cell of a sheet with frozen panes, the active one for `None`.
+    pub fn frozen_cell(&self, sheet: Option<String>) -> Option<Cell> {
+        let sheet = self.sheet_name(sheet.as_deref()).ok()?;
+        self.frozen.get(&sheet).cloned()
+    }
+
+    /// Merge `range`, on the active sheet if it has none.
+    /// Fails if it overlaps an existing merge.
+    pub fn merge(&mut self, range: &Range) -> Result<(), WebExcelError> {
+        let sheet = self.sheet_name(range.cell_start.sheet.as_deref())?;
+        let mut range = range.clone();
+        range.cell_start.sheet = Some(sheet.clone());
+        range.cell_end.sheet = Some(sheet);
+        self.merges.merge(&range)
+    }
+
+    /// Define `name` as `formula`, for the whole workbook or only `sheet`.
+    /// Fails with `NameError` on an invalid name or one already defined in that scope.
+    pub fn add_name(
+        &mut self,
+        name: &str,
+        formula: &str,
+        sheet: Option<String>,
+    ) -> Result<(), WebExcelError> {
+        let sheet = match sheet {
+            Some(sheet) => Some(self.sheet_name(Some(&sheet))?),
+            None => None,
+        };
+        let taken = self
+            .names
+            .iter()
+            .any(|n| n.sheet == sheet && n.name.eq_ignore_ascii_case(name));
+        if !is_valid_name(name) || taken {
+            return Err(WebExcelError::NameError);
+        }
+
+        let formula = formula.strip_prefix('=').unwrap_or(formula);
+        parse_formula(formula)?;
+        self.names.push(XlsxName {
+            name: name.to_owned(),
+            formula: format!("={}", formula),
+            sheet,
+            hidden: false,
+        });
+        Ok(())
+    }
+
+    /// Bytes of an `.xlsx` file of the package, e.g. to download as a `Blob`.
+    /// Tables are not written. Formulas keep the value of their cell as cached result,
+    /// and are calculated again when Excel opens the file.
+    pub fn write(&self) -> Result<Vec<u8>, WebExcelError> {
+        PackageWriter::new(self).write()
+    }
+}
+
+impl XlsxFile {
+    /// A package of `workbook` to write, with nothing else set yet.
+    pub fn from_workbook(workbook: Workbook) -> XlsxFile {
+        XlsxFile {
+            workbook,
+            names: vec![],
+            merges: MergedCells::new(),
+            tables: vec![],
+            widths: BTreeMap::new(),
+            frozen: BTreeMap::new(),
+        }
+    }
+
+    /// Name of the sheet `sheet` as the workbook spells it, the active one for `None`.
+    fn sheet_name(&self, sheet: Option<&str>) -> Result<String, WebExcelError> {
+        Ok(self.workbook.sheet_of(sheet)?.name())
+    }
+
+    pub fn workbook(&self) -> &Workbook {
+        &self.workbook
+    }
+
+    pub fn into_workbook(self) -> Workbook {
+        self.workbook
+    }
+
+    pub fn names(&self) -> &[XlsxName] {
+        &self.names
+    }
+
+    pub fn merges(&self) -> &MergedCells {
+        &self.merges
+    }
+
+    pub fn tables(&self) -> &[XlsxTable] {
+        &self.tables
+    }
+}
+
+/// Relationship of a package part to another one.
+struct Relationship {
+    id: String,
+    kind: String,
+    /// Path of the target inside the package.
+    target: String,
+}
+
+impl Relationship {
+    fn is(&self, kind: &str) -> bool {
+        self.kind.rsplit('/').next() == Some(kind)
+    }
+}
+
+/// Value of the relationship id attribute `r:id`, whatever its namespace prefix.
+fn relationship_id(element: &XmlElement) -> Option<&str> {
+    element
+        .attributes
+        .iter()
+        .find(|(key, _)| key.ends_with(":id"))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Path of `target` relative to the folder of the part `source`.
+fn resolve(source: &str, target: &str) -> String {
+    let mut segments: Vec<&str> = match target.strip_prefix('/') {
+        Some(_) => vec![],
+        None => source.split('/').collect(),
+    };
+    segments.pop();
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+struct Package<'a> {
+    archive: ZipArchive<'a>,
+    /// Bytes extracted so far, kept under `MAX_PACKAGE_SIZE`.
+    extracted: std::cell::Cell<usize>,
+}
+
+impl<'a> Package<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Package<'a>, WebExcelError> {
+        Ok(Package {
+            archive: ZipArchive::new(bytes)?,
+            extracted: std::cell::Cell::new(0),
+        })
+    }
+
+    /// Parsed part at `path`. Parts declaring more than `MAX_PART_SIZE` bytes, or taking
+    /// the package past `MAX_PACKAGE_SIZE`, fail before they are extracted.
+    fn xml(&self, path: &str) -> Result<Option<XmlElement>, WebExcelError> {
+        let entry = match self.archive.entry(path) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let extracted = self.extracted.get() + entry.size();
+        if entry.size() > MAX_PART_SIZE || extracted > MAX_PACKAGE_SIZE {
+            return Err(WebExcelError::FileFormatError);
+        }
+        self.extracted.set(extracted);
+        let bytes = self.archive.extract(entry)?;
+        let text = String::from_utf8(bytes).map_err(|_| WebExcelError::FileFormatError)?;
+        parse_xml(&text).map(Some)
+    }
+
+    /// Internal relationships of the part at `path`, `""` for the package itself.
+    fn relationships(&self, path: &str) -> Result<Vec<Relationship>, WebExcelError> {
+        let (folder, file) = path.rsplit_once('/').unwrap_or(("", path));
+        let rels = match folder {
+            "" => format!("_rels/{}.rels", file),
+            _ => format!("{}/_rels/{}.rels", folder, file),
+        };
+        let root = match self.xml(&rels)? {
+            Some(root) => root,
+            None => return Ok(vec![]),
+        };
+
+        Ok(root
+            .children_named("Relationship")
+            .filter(|r| r.attribute("TargetMode") != Some("External"))
+            .filter_map(|r| {
+                Some(Relationship {
+                    id: r.attribute("Id")?.to_owned(),
+                    kind: r.attribute("Type")?.to_owned(),
+                    target: resolve(path, r.attribute("Target")?),
+                })
+            })
+            .collect())
+    }
+
+    fn read(&self) -> Result<XlsxFile, WebExcelError> {
+        let workbook_path = self
+            .relationships("")?
+            .into_iter()
+            .find(|r| r.is("officeDocument"))
+            .map_or_else(|| "xl/workbook.xml".to_owned(), |r| r.target);
+        let root = self
+            .xml(&workbook_path)?
+            .ok_or(WebExcelError::FileFormatError)?;
+        let relationships = self.relationships(&workbook_path)?;
+        let part = |kind: &str| relationships.iter().find(|r| r.is(kind));
+
+        let strings = match part("sharedStrings") {
+            Some(r) => self.shared_strings(&r.target)?,
+            None => vec![],
+        };
+        let formats = match part("styles") {
+            Some(r) => self.cell_formats(&r.target)?,
+            None => vec![],
+        };
+        let dates: Vec<bool> = formats
+            .iter()
+            .map(|format| {
+                format
+                    .as_deref()
+                    .is_some_and(|code| NumberFormat::new(code).is_ok_and(|f| f.is_date()))
+            })
+            .collect();
+
+        let date1904 = root
+            .child("workbookPr")
+            .and_then(|pr| pr.attribute("date1904"))
+            .is_some_and(|flag| matches!(flag, "1" | "true"));
+
+        let listed: Vec<&XmlElement> = root
+            .child("sheets")
+            .map(|sheets| sheets.children_named("sheet").collect())
+            .unwrap_or_default();
+        let mut file = XlsxFile::from_workbook(Workbook::new());
+        let mut sheets = vec![];
+        for sheet in &listed {
+            let name = sheet
+                .attribute("name")
+                .ok_or(WebExcelError::FileFormatError)?;
+            // Chart and dialog sheets hold no cells
+            let target = relationship_id(sheet)
+                .and_then(|id| relationships.iter().find(|r| r.id == id))
+                .filter(|r| r.is("worksheet"));
+            if let Some(target) = target {
+                let reader = SheetReader {
+                    name,
+                    strings: &strings,
+                    formats: &formats,
+                    dates: &dates,
+                    date1904,
+                };
+                sheets.push(self.worksheet(&target.target, &reader, &mut file)?);
+            }
+        }
+        file.workbook = Workbook::from_sheets(sheets)?;
+
+        let active = root
+            .child("bookViews")
+            .and_then(|views| views.child("workbookView"))
+            .and_then(|view| view.attribute("activeTab"))
+            .and_then(|tab| tab.parse::<usize>().ok())
+            .and_then(|tab| listed.get(tab))
+            .and_then(|sheet| sheet.attribute("name"));
+        if let Some(active) = active {
+            let _ = file.workbook.activate(active);
+        }
+
+        for name in root
+            .child("definedNames")
+            .map(|names| names.children_named("definedName").collect::<Vec<_>>())
+            .unwrap_or_default()
+        {
+            let sheet = name
+                .attribute("localSheetId")
+                .and_then(|id| id.parse::<usize>().ok())
+                .and_then(|id| listed.get(id))
+                .and_then(|sheet| sheet.attribute("name"));
+            file.names.push(XlsxName {
+                name: name
+                    .attribute("name")
+                    .ok_or(WebExcelError::FileFormatError)?
+                    .to_owned(),
+                formula: format!("={}", plain_formula(&name.text())),
+                sheet: sheet.map(str::to_owned),
+                hidden: matches!(name.attribute("hidden"), Some("1" | "true")),
+            });
+        }
+        Ok(file)
+    }
+
+    /// Strings of the shared string table. Rich text is flattened, phonetic runs dropped.
+    fn shared_strings(&self, path: &str) -> Result<Vec<String>, WebExcelError> {
+        let root = self.xml(path)?.ok_or(WebExcelError::FileFormatError)?;
+        Ok(root.children_named("si").map(rich_text).collect())
+    }
+
+    /// Number format of each cell style, `None` for `General`.
+    fn cell_formats(&self, path: &str) -> Result<Vec<Option<String>>, WebExcelError> {
+        let root = self.xml(path)?.ok_or(WebExcelError::FileFormatError)?;
+        let custom: HashMap<u32, String> = root
+            .child("numFmts")
+            .map(|formats| {
+                formats
+                    .children_named("numFmt")
+                    .filter_map(|f| {
+                        let id = f.attribute("numFmtId")?.parse().ok()?;
+                        Some((id, f.attribute("formatCode")?.to_owned()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let styles = match root.child("cellXfs") {
+            Some(styles) => styles,
+            None => return Ok(vec![]),
+        };
+        Ok(styles
+            .children_named("xf")
+            .map(|xf| {
+                let id: u32 = xf.attribute("numFmtId")?.parse().ok()?;
+                let code = match custom.get(&id) {
+                    Some(code) => code.as_str(),
+                    None => {
+                        BUILTIN_NUMBER_FORMATS
+                            .iter()
+                            .find(|(builtin, _)| *builtin == id)?
+                            .1
+                    }
+                };
+                (!code.eq_ignore_ascii_case(GENERAL_FORMAT)).then(|| code.to_owned())
+            })
+            .collect())
+    }
+
+    fn worksheet(
+        &self,
+        path: &str,
+        reader: &SheetReader,
+        file: &mut XlsxFile,
+    ) -> Result<Worksheet, WebExcelError> {
+        let root = self.xml(path)?.ok_or(WebExcelError::FileFormatError)?;
+        let mut sheet = Worksheet::new(reader.name)?;
+        if let Some(data) = root.child("sheetData") {
+            reader.cells(data, &mut sheet)?;
+        }
+
+        for column in root
+            .child("cols")
+            .map(|cols| cols.children_named("col").collect::<Vec<_>>())
+            .unwrap_or_default()
+        {
+            let number = |key: &str| column.attribute(key).and_then(|n| n.parse::<u32>().ok());
+            let width = column
+                .attribute("width")
+                .and_then(|w| w.parse::<f64>().ok());
+            if let (Some(min), Some(max), Some(width)) = (number("min"), number("max"), width) {
+                for column in min.max(1)..=max.min(MAX_COLUMN + 1) {
+                    file.widths
+                        .insert((reader.name.to_owned(), column - 1), width);
+                }
+            }
+        }
+
+        let pane = root
+            .child("sheetViews")
+            .and_then(|views| views.child("sheetView"))
+            .and_then(|view| view.child("pane"))
+            .filter(|pane| {
+                pane.attribute("state")
+                    .is_some_and(|s| s.starts_with("frozen"))
+            });
+        if let Some(pane) = pane {
+            let split = |key: &str| {
+                pane.attribute(key)
+                    .and_then(|n| n.parse::<f64>().ok())
+                    .map_or(0, |n| n as u32)
+            };
+            let (rows, columns) = (split("ySplit"), split("xSplit"));
+            if rows > 0 || columns > 0 {
+                let cell = Cell::new(rows, columns, Some(reader.name.to_owned()))?;
+                file.frozen.insert(reader.name.to_owned(), cell);
+            }
+        }
+
+        if let Some(merges) = root.child("mergeCells") {
+            for merge in merges.children_named("mergeCell") {
+                let address = merge
+                    .attribute("ref")
+                    .ok_or(WebExcelError::FileFormatError)?;
+                file.merges.merge(&reader.range(address)?)?;
+            }
+        }
+
+        let parts = root
+            .child("tableParts")
+            .map(|parts| parts.children_named("tablePart").collect::<Vec<_>>())
+            .unwrap_or_default();
+        if !parts.is_empty() {
+            let relationships = self.relationships(path)?;
+            for part in parts {
+                let target = relationship_id(part)
+                    .and_then(|id| relationships.iter().find(|r| r.id == id))
+                    .ok_or(WebExcelError::FileFormatError)?;
+                let table = self
+                    .xml(&target.target)?
+                    .ok_or(WebExcelError::FileFormatError)?;
+                file.tables.push(reader.table(&table)?);
+            }
+        }
+        Ok(sheet)
+    }
+}
+
+/// Text of a string item: its `t`, or the `t` of each of its runs.
+fn rich_text(item: &XmlElement) -> String {
+    item.elements()
+        .filter_map(|e| match e.local_name() {
+            "t" => Some(e.text()),
+            "r" => e.child("t").map(XmlElement::text),
+            _ => None,
+        })
+        .collect()
+}
+
+/// What a sheet needs from the rest of the package.
+struct SheetReader<'a> {
+    name: &'a str,
+    strings: &'a [String],
+    formats: &'a [Option<String>],
+    /// Whether the number format of each cell style shows dates.
+    dates: &'a [bool],
+    /// Dates count days from 1904-01-01 rather than 1900-01-00.
+    date1904: bool,
+}
+
+impl SheetReader<'_> {
+    /// Range of an address such as `A1:C4` or `B2` on this sheet.
+    fn range(&self, address: &str) -> Result<Range, WebExcelError> {
+        let (start, end) = address.split_once(':').unwrap_or((address, address));
+        let sheet = Some(self.name.to_owned());
+        Range::new(
+            &Cell::from_str_address(start, sheet.clone())?,
+            &Cell::from_str_address(end, sheet)?,
+        )
+    }
+
+    fn cells(&self, data: &XmlElement, sheet: &mut Worksheet) -> Result<(), WebExcelError> {
+        // Master formula of each shared formula group, by group index
+        let mut shared: HashMap<String, (Cell, Expr)> = HashMap::new();
+        let mut next_row = 0;
+        for row in data.children_named("row") {
+            let row_index = match row.attribute("r").map(str::parse::<u32>) {
+                Some(Ok(r)) if r > 0 => r - 1,
+                Some(_) => return Err(WebExcelError::FileFormatError),
+                None => next_row,
+            };
+            next_row = row_index + 1;
+
+            let mut next_column = 0;
+            for c in row.children_named("c") {
+                let cell = match c.attribute("r") {
+                    Some(address) => Cell::from_str_address(address, None)?,
+                    None => Cell::new(row_index, next_column, None)?,
+                };
+                next_column = cell.column + 1;
+
+                let formula = c
+                    .child("f")
+                    .and_then(|f| self.formula(f, &cell, &mut shared));
+                let style = c.attribute("s").and_then(|s| s.parse::<usize>().ok());
+                let number_format = style.and_then(|s| self.formats.get(s).cloned().flatten());
+                let mut value = self.value(c)?;
+                if let CellValue::Number(n) = &mut value {
+                    if self.date1904 && style.is_some_and(|s| self.dates.get(s) == Some(&true)) {
+                        *n += DATE_1904_OFFSET;
+                    }
+                }
+                let data = CellData {
+                    value,
+                    formula,
+                    number_format,
+                };
+                sheet.set_cell(&cell, data);
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&self, c: &XmlElement) -> Result<CellValue, WebExcelError> {
+        let text = match c.child("v") {
+            Some(v) => v.text(),
+            None if c.attribute("t") == Some("inlineStr") => {
+                return Ok(CellValue::Text(
+                    c.child("is").map(rich_text).unwrap_or_default(),
+                ))
+            }
+            None => return Ok(CellValue::Empty),
+        };
+
+        Ok(match c.attribute("t").unwrap_or("n") {
+            "s" => {
+                let index: usize = text.parse().map_err(|_| WebExcelError::FileFormatError)?;
+                let string = self
+                    .strings
+                    .get(index)
+                    .ok_or(WebExcelError::FileFormatError)?;
+                CellValue::Text(string.clone())
+            }
+            "str" | "inlineStr" => CellValue::Text(text),
+            "b" => CellValue::Bool(text.trim() == "1"),
+            "e" => match ErrorValue::ALL.into_iter().find(|e| e.as_str() == text) {
+                Some(e) => CellValue::Error(e),
+                None => CellValue::Text(text),
+            },
+            "d" => match parse_date_time(text.trim_end_matches('Z')) {
+                Some((value, _)) => value,
+                None => CellValue::Text(text),
+            },
+            _ => CellValue::Number(
+                text.trim()
+                    .parse()
+                    .map_err(|_| WebExcelError::FileFormatError)?,
+            ),
+        })
+    }
+
+    /// Formula of `cell`, starting with `=`. Cells sharing a formula only hold its group
+    /// index, and get the formula of the group moved from its first cell.
+    fn formula(
+        &self,
+        f: &XmlElement,
+        cell: &Cell,
+        shared: &mut HashMap<String, (Cell, Expr)>,
+    ) -> Option<String> {
+        let text = plain_formula(&f.text());
+        let group = f
+            .attribute("si")
+            .filter(|_| f.attribute("t") == Some("shared"));
+        match group {
+            Some(group) if text.is_empty() => {
+                let (master, expr) = shared.get(group)?;
+                let moved = expr
+                    .relocate(
+                        cell.row as i64 - master.row as i64,
+                        cell.column as i64 - master.column as i64,
+                    )
+                    .ok()?;
+                Some(format!("={}", moved.to_formula().ok()?))
+            }
+            Some(group) => {
+                if let Ok(expr) = parse_formula(&text) {
+                    shared.insert(group.to_owned(), (cell.clone(), expr));
+                }
+                Some(format!("={}", text))
+            }
+            None if text.is_empty() => None,
+            None => Some(format!("={}", text)),
+        }
+    }
+
+    fn table(&self, table: &XmlElement) -> Result<XlsxTable, WebExcelError> {
+        let name = table
+            .attribute("displayName")
+            .or_else(|| table.attribute("name"))
+            .ok_or(WebExcelError::FileFormatError)?;
+        let address = table
+            .attribute("ref")
+            .ok_or(WebExcelError::FileFormatError)?;
+        let count = |key: &str, default: u32| {
+            table
+                .attribute(key)
+                .and_then(|n| n.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+
+        Ok(XlsxTable {
+            name: name.to_owned(),
+            range: self.range(address)?,
+            columns: table
+                .child("tableColumns")
+                .map(|columns| {
+                    columns
+                        .children_named("tableColumn")
+                        .filter_map(|c| c.attribute("name").map(str::to_owned))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            header_row: count("headerRowCount", 1) > 0,
+            totals_row: count("totalsRowCount", 0) > 0,
+        })
+    }
+}
+
+const MAIN_NAMESPACE: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const RELATIONSHIPS_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n";
+/// Id of the first number format that is not built in.
+const FIRST_CUSTOM_FORMAT: u32 = 164;
+
+/// Functions added after Excel 2007, stored with an `_xlfn.` prefix.
+const FUTURE_FUNCTIONS: [&str; 39] = [
+    "ANCHORARRAY",
+    "BYCOL",
+    "BYROW",
+    "CHOOSECOLS",
+    "CHOOSEROWS",
+    "CONCAT",
+    "DROP",
+    "EXPAND",
+    "FILTER",
+    "HSTACK",
+    "IFNA",
+    "IFS",
+    "ISOMITTED",
+    "LAMBDA",
+    "LET",
+    "MAKEARRAY",
+    "MAP",
+    "MAXIFS",
+    "MINIFS",
+    "RANDARRAY",
+    "REDUCE",
+    "SCAN",
+    "SEQUENCE",
+    "SORT",
+    "SORTBY",
+    "SWITCH",
+    "TAKE",
+    "TEXTAFTER",
+    "TEXTBEFORE",
+    "TEXTJOIN",
+    "TEXTSPLIT",
+    "TOCOL",
+    "TOROW",
+    "UNIQUE",
+    "VSTACK",
+    "WRAPCOLS",
+    "WRAPROWS",
+    "XLOOKUP",
+    "XMATCH",
+];
+/// Functions returning arrays, whose formulas are stored as dynamic arrays so that they
+/// spill when opened rather than being read as `=@FILTER(...)`.
+const DYNAMIC_ARRAY_FUNCTIONS: [&str; 23] = [
+    "BYCOL",
+    "BYROW",
+    "CHOOSECOLS",
+    "CHOOSEROWS",
+    "DROP",
+    "EXPAND",
+    "FILTER",
+    "HSTACK",
+    "MAKEARRAY",
+    "MAP",
+    "RANDARRAY",
+    "SCAN",
+    "SEQUENCE",
+    "SORT",
+    "SORTBY",
+    "TAKE",
+    "TEXTSPLIT",
+    "TOCOL",
+    "TOROW",
+    "UNIQUE",
+    "VSTACK",
+    "WRAPCOLS",
+    "WRAPROWS",
+];
+/// Cell metadata marking formulas as dynamic arrays, referred to by `cm="1"`.
+const DYNAMIC_ARRAY_METADATA: &str = "<metadataTypes count=\"1\"><metadataType name=\"XLDAPR\" \
+     minSupportedVersion=\"120000\" copy=\"1\" pasteAll=\"1\" pasteValues=\"1\" merge=\"1\" \
+     splitFirst=\"1\" rowColShift=\"1\" clearFormats=\"1\" clearComments=\"1\" assign=\"1\" \
+     coerce=\"1\" cellMeta=\"1\"/></metadataTypes><futureMetadata name=\"XLDAPR\" count=\"1\">\
+     <bk><extLst><ext uri=\"{bdbb8cdc-fa1e-496e-a857-3c3f30c029c3}\">\
+     <xda:dynamicArrayProperties fDynamic=\"1\" fCollapsed=\"0\"/></ext></extLst></bk>\
+     </futureMetadata><cellMetadata count=\"1\"><bk><rc t=\"1\" v=\"0\"/></bk></cellMetadata>";
+/// Future functions stored with an `_xlfn._xlws.` prefix instead.
+const WORKSHEET_FUNCTIONS: [&str; 2] = ["FILTER", "SORT"];
+/// Prefix of `LET` and `LAMBDA` parameter names as stored.
+const PARAMETER_PREFIX: &str = "_xlpm.";
+
+/// Formula text as Excel stores it, with the prefixes of future functions and of
+/// `LET` and `LAMBDA` parameters. Formulas that do not parse are kept as they are.
+fn stored_formula(formula: &str) -> String {
+    match parse_formula(formula).and_then(|expr| prefixed(&expr, &[]).to_formula()) {
+        Ok(text) => text,
+        Err(_) => formula.to_owned(),
+    }
+}
+
+/// Formula text as stored without the prefixes added by `stored_formula`.
+fn plain_formula(text: &str) -> String {
+    if !text.to_ascii_lowercase().contains("_xl") {
+        return text.to_owned();
+    }
+    match parse_formula(text).and_then(|expr| unprefixed(&expr).to_formula()) {
+        Ok(plain) => plain,
+        Err(_) => text.to_owned(),
+    }
+}
+
+/// `expr` with its future functions prefixed, and the names among `params` taken as
+/// parameters of an enclosing `LET` or `LAMBDA`. Spill references such as `A1#` are
+/// stored as `ANCHORARRAY(A1)`.
+fn prefixed(expr: &Expr, params: &[String]) -> Expr {
+    let all = |exprs: &[Expr]| exprs.iter().map(|e| prefixed(e, params)).collect();
+    match expr {
+        Expr::Name(name) if params.iter().any(|p| p.eq_ignore_ascii_case(name)) => {
+            Expr::Name(format!("{}{}", PARAMETER_PREFIX, name))
+        }
+        Expr::Reference(r) if r.spill => {
+            let anchor = Expr::Reference(Reference {
+                spill: false,
+                ..r.clone()
+            });
+            prefixed(
+                &Expr::Function("ANCHORARRAY".to_owned(), vec![anchor]),
+                params,
+            )
+        }
+        Expr::Array(rows) => Expr::Array(rows.iter().map(|row| all(row)).collect()),
+        Expr::Unary(op, e) => Expr::Unary(*op, Box::new(prefixed(e, params))),
+        Expr::Binary(op, l, r) => Expr::Binary(
+            *op,
+            Box::new(prefixed(l, params)),
+            Box::new(prefixed(r, params)),
+        ),
+        Expr::Function(name, args) => {
+            let stored = if WORKSHEET_FUNCTIONS.contains(&name.as_str()) {
+                format!("_xlfn._xlws.{}", name)
+            } else if FUTURE_FUNCTIONS.contains(&name.as_str()) {
+                format!("_xlfn.{}", name)
+            } else {
+                name.clone()
+            };
+            // Parameters come before the last argument, every other one for LET
+            let step = match name.as_str() {
+                "LET" => 2,
+                "LAMBDA" => 1,
+                _ => return Expr::Function(stored, all(args)),
+            };
+            let mut scope = params.to_vec();
+            let mut stored_args = vec![];
+            for (i, arg) in args.iter().enumerate() {
+                match arg {
+                    Expr::Name(param) if i % step == 0 && i + 1 < args.len() => {
+                        scope.push(param.clone());
+                        stored_args.push(Expr::Name(format!("{}{}", PARAMETER_PREFIX, param)));
+                    }
+                    _ => stored_args.push(prefixed(arg, &scope)),
+                }
+            }
+            Expr::Function(stored, stored_args)
+        }
+        Expr::Call(callee, args) => Expr::Call(Box::new(prefixed(callee, params)), all(args)),
+        other => other.clone(),
+    }
+}
+
+/// `expr` without the prefixes of future functions and parameters, with `ANCHORARRAY(A1)`
+/// read back as the spill reference `A1#`.
+fn unprefixed(expr: &Expr) -> Expr {
+    let all = |exprs: &[Expr]| exprs.iter().map(unprefixed).collect();
+    match expr {
+        Expr::Name(name) => {
+            let prefix = name.get(..PARAMETER_PREFIX.len());
+            match prefix.filter(|p| p.eq_ignore_ascii_case(PARAMETER_PREFIX)) {
+                Some(_) => Expr::Name(name[PARAMETER_PREFIX.len()..].to_owned()),
+                None => expr.clone(),
+            }
+        }
+        Expr::Array(rows) => Expr::Array(rows.iter().map(|row| all(row)).collect()),
+        Expr::Unary(op, e) => Expr::Unary(*op, Box::new(unprefixed(e))),
+        Expr::Binary(op, l, r) => {
+            Expr::Binary(*op, Box::new(unprefixed(l)), Box::new(unprefixed(r)))
+        }
+        Expr::Function(name, args) => {
+            let plain = name
+                .trim_start_matches("_XLFN.")
+                .trim_start_matches("_XLWS.");
+            match (plain, args.as_slice()) {
+                ("ANCHORARRAY", [Expr::Reference(r)]) if r.end.is_none() && !r.spill => {
+                    Expr::Reference(Reference {
+                        spill: true,
+                        ..r.clone()
+                    })
+                }
+                _ => Expr::Function(plain.to_owned(), all(args)),
+            }
+        }
+        Expr::Call(callee, args) => Expr::Call(Box::new(unprefixed(callee)), all(args)),
+        other => other.clone(),
+    }
+}
+
+/// Whether a formula can return an array, calling a function that does or reading a spill.
+fn is_dynamic_array(expr: &Expr) -> bool {
+    let mut dynamic = false;
+    expr.walk(&mut |e| match e {
+        Expr::Function(name, _) => dynamic |= DYNAMIC_ARRAY_FUNCTIONS.contains(&name.as_str()),
+        Expr::Reference(r) => dynamic |= r.spill,
+        _ => {}
+    });
+    dynamic
+}
+
+/// Address of a cell without its sheet, e.g. `B2`.
+fn address(row: u32, column: u32) -> Result<String, WebExcelError> {
+    r1c1_to_address(row, column, false, false)
+}
+
+/// Writes the parts of a package, with the strings and formats shared by its sheets.
+struct PackageWriter<'a> {
+    file: &'a XlsxFile,
+    /// Shared strings, and their index.
+    strings: Vec<&'a str>,
+    string_index: HashMap<&'a str, usize>,
+    /// Number formats other than `General`. Cell style `k + 1` uses format `k`.
+    formats: Vec<&'a str>,
+    format_index: HashMap<&'a str, usize>,
+    /// Names the dynamic array formulas are evaluated with, to find the range they spill over.
+    names: DefinedNames,
+    /// Whether any formula is a dynamic array, needing the metadata part.
+    dynamic_arrays: bool,
+}
+
+impl<'a> PackageWriter<'a> {
+    fn new(file: &'a XlsxFile) -> PackageWriter<'a> {
+        let mut writer = PackageWriter {
+            file,
+            strings: vec![],
+            string_index: HashMap::new(),
+            formats: vec![],
+            format_index: HashMap::new(),
+            names: file.defined_names(),
+            dynamic_arrays: false,
+        };
+        for sheet in file.workbook.sheets() {
+            for (_, data) in sheet.iter() {
+                if let Some(formula) = &data.formula {
+                    writer.dynamic_arrays |=
+                        parse_formula(formula).is_ok_and(|e| is_dynamic_array(&e));
+                }
+                if let (CellValue::Text(text), None) = (&data.value, &data.formula) {
+                    if !writer.string_index.contains_key(text.as_str()) {
+                        writer.string_index.insert(text, writer.strings.len());
+                        writer.strings.push(text);
+                    }
+                }
+                if let Some(format) = data.number_format.as_deref() {
+                    if !format.eq_ignore_ascii_case(GENERAL_FORMAT)
+                        && !writer.format_index.contains_key(format)
+                    {
+                        writer.format_index.insert(format, writer.formats.len());
+                        writer.formats.push(format);
+                    }
+                }
+            }
+        }
+        writer
+    }
+
+    fn write(&self) -> Result<Vec<u8>, WebExcelError> {
+        let sheets = self.file.workbook.sheets();
+        let mut zip = ZipWriter::new();
+        zip.add("[Content_Types].xml", self.content_types().as_bytes())?;
+        zip.add(
+            "_rels/.rels",
+            relationships(&[("officeDocument", "xl/workbook.xml")]).as_bytes(),
+        )?;
+        zip.add("xl/workbook.xml", self.workbook().as_bytes())?;
+
+        let mut parts: Vec<(&str, String)> = (1..=sheets.len())
+            .map(|n| ("worksheet", format!("worksheets/sheet{}.xml", n)))
+            .collect();
+        parts.push(("styles", "styles.xml".to_owned()));
+        parts.push(("sharedStrings", "sharedStrings.xml".to_owned()));
+        if self.dynamic_arrays {
+            parts.push(("sheetMetadata", "metadata.xml".to_owned()));
+        }
+        let parts: Vec<(&str, &str)> = parts.iter().map(|(k, t)| (*k, t.as_str())).collect();
+        zip.add(
+            "xl/_rels/workbook.xml.rels",
+            relationships(&parts).as_bytes(),
+        )?;
+
+        zip.add("xl/styles.xml", self.styles().as_bytes())?;
+        zip.add("xl/sharedStrings.xml", self.shared_strings().as_bytes())?;
+        if self.dynamic_arrays {
+            let metadata = format!(
+                "{}<metadata xmlns=\"{}\" xmlns:xda=\"http://schemas.microsoft.com/office/\
+                 spreadsheetml/2017/dynamicarray\">{}</metadata>",
+                XML_DECLARATION, MAIN_NAMESPACE, DYNAMIC_ARRAY_METADATA
+            );
+            zip.add("xl/metadata.xml", metadata.as_bytes())?;
+        }
+        for (n, sheet) in sheets.iter().enumerate() {
+            let path = format!("xl/worksheets/sheet{}.xml", n + 1);
+            zip.add(&path, self.worksheet(sheet)?.as_bytes())?;
+        }
+        Ok(zip.finish())
+    }
+
+    fn content_types(&self) -> String {
+        let kind = "application/vnd.openxmlformats-officedocument.spreadsheetml";
+        let mut xml = format!(
+            "{}<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+             <Default Extension=\"rels\" \
+             ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+             <Default Extension=\"xml\" ContentType=\"application/xml\"/>",
+            XML_DECLARATION
+        );
+        let mut part = |name: &str, content: &str| {
+            let _ = write!(
+                xml,
+                "<Override PartName=\"/xl/{}\" ContentType=\"{}.{}+xml\"/>",
+                name, kind, content
+            );
+        };
+        part("workbook.xml", "sheet.main");
+        for n in 1..=self.file.workbook.sheets().len() {
+            part(&format!("worksheets/sheet{}.xml", n), "worksheet");
+        }
+        part("styles.xml", "styles");
+        part("sharedStrings.xml", "sharedStrings");
+        if self.dynamic_arrays {
+            part("metadata.xml", "sheetMetadata");
+        }
+        xml.push_str("</Types>");
+        xml
+    }
+
+    fn workbook(&self) -> String {
+        let workbook = &self.file.workbook;
+        let sheets = workbook.sheets();
+        let active = workbook.active_sheet();
+        let mut xml = format!(
+            "{}<workbook xmlns=\"{}\" xmlns:r=\"{}\"><bookViews><workbookView activeTab=\"{}\"/>\
+             </bookViews><sheets>",
+            XML_DECLARATION,
+            MAIN_NAMESPACE,
+            RELATIONSHIPS_NAMESPACE,
+            sheets.iter().position(|s| s.name() == active).unwrap_or(0)
+        );
+        for (n, sheet) in sheets.iter().enumerate() {
+            let _ = write!(
+                xml,
+                "<sheet name=\"{}\" sheetId=\"{}\" r:id=\"rId{}\"/>",
+                escape(&sheet.name()),
+                n + 1,
+                n + 1
+            );
+        }
+        xml.push_str("</sheets>");
+
+        if !self.file.names.is_empty() {
+            xml.push_str("<definedNames>");
+            for name in &self.file.names {
+                let _ = write!(xml, "<definedName name=\"{}\"", escape(&name.name));
+                let scope = name
+                    .sheet
+                    .as_deref()
+                    .and_then(|sheet| sheets.iter().position(|s| s.name() == sheet));
+                if let Some(scope) = scope {
+                    let _ = write!(xml, " localSheetId=\"{}\"", scope);
+                }
+                if name.hidden {
+                    xml.push_str(" hidden=\"1\"");
+                }
+                let formula = name.formula.strip_prefix('=').unwrap_or(&name.formula);
+                let _ = write!(xml, ">{}</definedName>", escape(&stored_formula(formula)));
+            }
+            xml.push_str("</definedNames>");
+        }
+        xml.push_str("<calcPr calcId=\"0\" fullCalcOnLoad=\"1\"/></workbook>");
+        xml
+    }
+
+    fn styles(&self) -> String {
+        let mut xml = format!(
+            "{}<styleSheet xmlns=\"{}\">",
+            XML_DECLARATION, MAIN_NAMESPACE
+        );
+        let ids: Vec<u32> = self
+            .formats
+            .iter()
+            .scan(FIRST_CUSTOM_FORMAT, |custom, format| {
+                let builtin = BUILTIN_NUMBER_FORMATS
+                    .iter()
+                    .find(|(_, code)| code == format)
+                    .map(|(id, _)| *id);
+                Some(builtin.unwrap_or_else(|| {
+                    *custom += 1;
+                    *custom - 1
+                }))
+            })
+            .collect();
+
+        let custom: Vec<(u32, &str)> = ids
+            .iter()
+            .zip(&self.formats)
+            .filter(|(id, _)| **id >= FIRST_CUSTOM_FORMAT)
+            .map(|(id, format)| (*id, *format))
+            .collect();
+        if !custom.is_empty() {
+            let _ = write!(xml, "<numFmts count=\"{}\">", custom.len());
+            for (id, format) in custom {
+                let _ = write!(
+                    xml,
+                    "<numFmt numFmtId=\"{}\" formatCode=\"{}\"/>",
+                    id,
+                    escape(format)
+                );
+            }
+            xml.push_str("</numFmts>");
+        }
+
+        xml.push_str(
+            "<fonts count=\"1\"><font><sz val=\"11\"/><name val=\"Calibri\"/></font></fonts>\
+             <fills count=\"2\"><fill><patternFill patternType=\"none\"/></fill>\
+             <fill><patternFill patternType=\"gray125\"/></fill></fills>\
+             <borders count=\"1\"><border><left/><right/><top/><bottom/><diagonal/></border>\
+             </borders><cellStyleXfs count=\"1\">\
+             <xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\"/></cellStyleXfs>",
+        );
+        let _ = write!(
+            xml,
+            "<cellXfs count=\"{}\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" \
+             xfId=\"0\"/>",
+            ids.len() + 1
+        );
+        for id in ids {
+            let _ = write!(
+                xml,
+                "<xf numFmtId=\"{}\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\" \
+                 applyNumberFormat=\"1\"/>",
+                id
+            );
+        }
+        xml.push_str(
+            "</cellXfs><cellStyles count=\"1\">\
+             <cellStyle name=\"Normal\" xfId=\"0\" builtinId=\"0\"/></cellStyles></styleSheet>",
+        );
+        xml
+    }
+
+    fn shared_strings(&self) -> String {
+        let mut xml = format!(
+            "{}<sst xmlns=\"{}\" uniqueCount=\"{}\">",
+            XML_DECLARATION,
+            MAIN_NAMESPACE,
+            self.strings.len()
+        );
+        for string in &self.strings {
+            let _ = write!(
+                xml,
+                "<si><t xml:space=\"preserve\">{}</t></si>",
+                escape(string)
+            );
+        }
+        xml.push_str("</sst>");
+        xml
+    }
+
+    fn worksheet(&self, sheet: &Worksheet) -> Result<String, WebExcelError> {
+        let name = sheet.name();
+        let dimension = match sheet.used_range(false) {
+            Some(range) => format!(
+                "{}:{}",
+                address(range.cell_start.row, range.cell_start.column)?,
+                address(range.cell_end.row, range.cell_end.column)?
+            ),
+            None => "A1".to_owned(),
+        };
+        let mut xml = format!(
+            "{}<worksheet xmlns=\"{}\" xmlns:r=\"{}\"><dimension ref=\"{}\"/>",
+            XML_DECLARATION, MAIN_NAMESPACE, RELATIONSHIPS_NAMESPACE, dimension
+        );
+
+        let selected = name == self.file.workbook.active_sheet();
+        let _ = write!(
+            xml,
+            "<sheetViews><sheetView{} workbookViewId=\"0\">",
+            if selected { " tabSelected=\"1\"" } else { "" }
+        );
+        if let Some(cell) = self.file.frozen.get(&name) {
+            if cell.column > 0 {
+                let _ = write!(xml, "<pane xSplit=\"{}\"", cell.column);
+            } else {
+                xml.push_str("<pane");
+            }
+            if cell.row > 0 {
+                let _ = write!(xml, " ySplit=\"{}\"", cell.row);
+            }
+            let pane = match (cell.row > 0, cell.column > 0) {
+                (true, true) => "bottomRight",
+                (true, false) => "bottomLeft",
+                _ => "topRight",
+            };
+            let _ = write!(
+                xml,
+                " topLeftCell=\"{}\" activePane=\"{}\" state=\"frozen\"/>",
+                address(cell.row, cell.column)?,
+                pane
+            );
+        }
+        xml.push_str("</sheetView></sheetViews><sheetFormatPr defaultRowHeight=\"15\"/>");
+
+        // Consecutive columns of the same width share one element
+        let mut columns: Vec<(u32, u32, f64)> = vec![];
+        for ((_, column), width) in self
+            .file
+            .widths
+            .range((name.clone(), 0)..=(name.clone(), MAX_COLUMN))
+        {
+            match columns.last_mut() {
+                Some((_, last, w)) if *last + 1 == *column && *w == *width => *last = *column,
+                _ => columns.push((*column, *column, *width)),
+            }
+        }
+        if !columns.is_empty() {
+            xml.push_str("<cols>");
+            for (min, max, width) in columns {
+                let _ = write!(
+                    xml,
+                    "<col min=\"{}\" max=\"{}\" width=\"{}\" customWidth=\"1\"/>",
+                    min + 1,
+                    max + 1,
+                    width
+                );
+            }
+            xml.push_str("</cols>");
+        }
+
+        xml.push_str("<sheetData>");
+        let mut row = None;
+        for (cell, data) in sheet.iter() {
+            if row != Some(cell.row) {
+                if row.is_some() {
+                    xml.push_str("</row>");
+                }
+                let _ = write!(xml, "<row r=\"{}\">", cell.row + 1);
+                row = Some(cell.row);
+            }
+            self.cell(&mut xml, &name, &cell, data)?;
+        }
+        if row.is_some() {
+            xml.push_str("</row>");
+        }
+        xml.push_str("</sheetData>");
+
+        let merges: Vec<&Range> = self
+            .file
+            .merges
+            .iter()
+            .filter(|range| range.cell_start.sheet.as_deref() == Some(name.as_str()))
+            .collect();
+        if !merges.is_empty() {
+            let _ = write!(xml, "<mergeCells count=\"{}\">", merges.len());
+            for range in merges {
+                let _ = write!(
+                    xml,
+                    "<mergeCell ref=\"{}:{}\"/>",
+                    address(range.cell_start.row, range.cell_start.column)?,
+                    address(range.cell_end.row, range.cell_end.column)?
+                );
+            }
+            xml.push_str("</mergeCells>");
+        }
+        xml.push_str("</worksheet>");
+        Ok(xml)
+    }
+
+    /// A `c` element, with the formula and its cached result, or the value of the cell.
+    fn cell(
+        &self,
+        xml: &mut String,
+        sheet: &str,
+        cell: &Cell,
+        data: &CellData,
+    ) -> Result<(), WebExcelError> {
+        let _ = write!(xml, "<c r=\"{}\"", address(cell.row, cell.column)?);
+        let style = data
+            .number_format
+            .as_deref()
+            .and_then(|format| self.format_index.get(format));
+        if let Some(style) = style {
+            let _ = write!(xml, " s=\"{}\"", style + 1);
+        }
+
+        let formula = data
+            .formula
+            .as_deref()
+            .map(|f| stored_formula(f.strip_prefix('=').unwrap_or(f)));
+        let (kind, value) = match &data.value {
+            CellValue::Empty => (None, None),
+            CellValue::Number(n) if n.is_finite() => (None, Some(n.to_string())),
+            CellValue::Number(_) => (Some("e"), Some(ErrorValue::Num.as_str().to_owned())),
+            CellValue::Text(text) => match formula {
+                Some(_) => (Some("str"), Some(escape(text))),
+                None => (
+                    Some("s"),
+                    Some(self.string_index[text.as_str()].to_string()),
+                ),
+            },
+            CellValue::Bool(b) => (Some("b"), Some((*b as u8).to_string())),
+            CellValue::Error(e) => (Some("e"), Some(escape(e.as_str()))),
+        };
+        if let Some(kind) = kind {
+            let _ = write!(xml, " t=\"{}\"", kind);
+        }
+        let spill = match &data.formula {
+            Some(formula) => self.spill(sheet, cell, formula)?,
+            None => None,
+        };
+        if spill.is_some() {
+            xml.push_str(" cm=\"1\"");
+        }
+
+        if formula.is_none() && value.is_none() {
+            xml.push_str("/>");
+            return Ok(());
+        }
+        xml.push('>');
+        match (formula, spill) {
+            (Some(formula), Some(spill)) => {
+                let _ = write!(
+                    xml,
+                    "<f t=\"array\" ref=\"{}\">{}</f>",
+                    spill,
+                    escape(&formula)
+                );
+            }
+            (Some(formula), None) => {
+                let _ = write!(xml, "<f>{}</f>", escape(&formula));
+            }
+            _ => {}
+        }
+        if let Some(value) = value {
+            let _ = write!(xml, "<v>{}</v>", value);
+        }
+        xml.push_str("</c>");
+        Ok(())
+    }
+
+    /// Address of the range a dynamic array formula at `cell` spills over, e.g. `B2:B9`,
+    /// `None` for other formulas. The formula is evaluated against the workbook to size it.
+    fn spill(
+        &self,
+        sheet: &str,
+        cell: &Cell,
+        formula: &str,
+    ) -> Result<Option<String>, WebExcelError> {
+        match parse_formula(formula) {
+            Ok(expr) if is_dynamic_array(&expr) => {}
+            _ => return Ok(None),
+        }
+        let value = Evaluator::new(&self.file.workbook)
+            .with_names(&self.names)
+            .on_sheet(Some(sheet.to_owned()))
+            .evaluate_formula(formula)?;
+        let anchor = address(cell.row, cell.column)?;
+        // An array running off the sheet is a #SPILL! error, covering only its anchor
+        match spill_range(cell, &value) {
+            Ok(range) if range.cells > 1 => Ok(Some(format!(
+                "{}:{}",
+                anchor,
+                address(range.cell_end.row, range.cell_end.column)?
+            ))),
+            _ => Ok(Some(anchor)),
+        }
+    }
+}
+
+/// Relationships part pointing to each `(kind, target)`, with ids `rId1` onwards.
+fn relationships(parts: &[(&str, &str)]) -> String {
+    let mut xml = format!(
+        "{}<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">",
+        XML_DECLARATION
+    );
+    for (n, (kind, target)) in parts.iter().enumerate() {
+        let _ = write!(
+            xml,
+            "<Relationship Id=\"rId{}\" Type=\"{}/{}\" Target=\"{}\"/>",
+            n + 1,
+            RELATIONSHIPS_NAMESPACE,
+            kind,
+            target
+        );
+    }
+    xml.push_str("</Relationships>");
+    xml
+}